@@ -7,8 +7,8 @@ use clap::{Parser, Subcommand};
 use colored::Colorize;
 use std::path::PathBuf;
 use zephyr_proof::{
-    generate_proof, new_prover, new_prover_with_params, prove_transaction,
-    ProofOutput, ProverConfig,
+    Hardfork, ProofOutput, ProverConfig, generate_proof, new_prover, new_prover_with_params,
+    prove_transaction,
 };
 
 #[derive(Parser)]
@@ -31,6 +31,10 @@ struct Cli {
     /// Number of threads for parallel processing
     #[arg(short = 't', long, global = true)]
     num_threads: Option<usize>,
+
+    /// Hardfork whose gas schedule the trace follows (petersburg..cancun)
+    #[arg(long, global = true, default_value = "cancun")]
+    hardfork: Hardfork,
 }
 
 #[derive(Subcommand)]
@@ -90,6 +94,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         parallel: !cli.no_parallel,
         num_threads: cli.num_threads,
         rpc_url: None,
+        hardfork: cli.hardfork,
     };
 
     match cli.command {
@@ -126,7 +131,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             println!("Proof saved to: {}", output.display());
         }
 
-        Commands::Verify { proof_file, verbose } => {
+        Commands::Verify {
+            proof_file,
+            verbose,
+        } => {
             println!("Reading proof from: {}", proof_file.display());
 
             // Read proof file
@@ -135,11 +143,17 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
             println!(
                 "{}",
-                format!("Verifying proof (k={}, num_steps={})...", proof.k, proof.num_steps).cyan()
+                format!(
+                    "Verifying proof (k={}, num_steps={})...",
+                    proof.k, proof.num_steps
+                )
+                .cyan()
             );
 
             // Verify proof with verbosity option
-            let valid = zephyr_proof::prover::verifier::verify_with_verbosity(&proof, &config, verbose).await?;
+            let valid =
+                zephyr_proof::prover::verifier::verify_with_verbosity(&proof, &config, verbose)
+                    .await?;
 
             if valid {
                 println!("{}", "Proof is VALID!".green().bold());
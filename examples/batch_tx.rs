@@ -5,7 +5,7 @@
 
 use colored::Colorize;
 use tokio::task::JoinSet;
-use zephyr_proof::{generate_proof, verify_proof, ProverConfig};
+use zephyr_proof::{Hardfork, ProverConfig, generate_proof, verify_proof};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -18,7 +18,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             "ADD Operation (5 + 3)",
             r#"{
                 "opcodes": [96, 96, 1],
-                "stack_states": [[5, 0, 0], [3, 5, 0], [8, 0, 0]],
+                "stack_states": [[0, 0, 0], [5, 0, 0], [3, 5, 0]],
                 "pcs": [0, 2, 4],
                 "gas_values": [1000, 997, 994],
                 "memory_ops": null,
@@ -32,7 +32,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             "MUL Operation (7 * 6)",
             r#"{
                 "opcodes": [96, 96, 2],
-                "stack_states": [[7, 0, 0], [6, 7, 0], [42, 0, 0]],
+                "stack_states": [[0, 0, 0], [7, 0, 0], [6, 7, 0]],
                 "pcs": [0, 2, 4],
                 "gas_values": [1000, 997, 992],
                 "memory_ops": null,
@@ -46,7 +46,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             "SUB Operation (20 - 8)",
             r#"{
                 "opcodes": [96, 96, 3],
-                "stack_states": [[20, 0, 0], [8, 20, 0], [12, 0, 0]],
+                "stack_states": [[0, 0, 0], [20, 0, 0], [8, 20, 0]],
                 "pcs": [0, 2, 4],
                 "gas_values": [1000, 997, 994],
                 "memory_ops": null,
@@ -61,11 +61,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             r#"{
                 "opcodes": [96, 96, 1, 96, 2],
                 "stack_states": [
+                    [0, 0, 0],
                     [10, 0, 0],
                     [5, 10, 0],
                     [15, 0, 0],
-                    [2, 15, 0],
-                    [30, 0, 0]
+                    [2, 15, 0]
                 ],
                 "pcs": [0, 2, 4, 6, 8],
                 "gas_values": [1000, 997, 994, 991, 986],
@@ -86,6 +86,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         parallel: true,
         num_threads: Some(4),
         rpc_url: None,
+        hardfork: Hardfork::Cancun,
     };
 
     println!("{}", "Prover Configuration:".cyan());
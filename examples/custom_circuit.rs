@@ -7,16 +7,16 @@
 //! 3. Use individual chips (AddChip, EvmChip)
 //! 4. Verify constraints with MockProver
 
+use alloy_primitives::U256;
 use colored::Colorize;
 use halo2_proofs::{
-    arithmetic::Field,
-    circuit::{Layouter, SimpleFloorPlanner, Value},
+    circuit::{Layouter, SimpleFloorPlanner},
     dev::MockProver,
     pasta::Fp,
     plonk::{Circuit, ConstraintSystem, Error},
 };
 use zephyr_proof::{
-    chips::{AddChip, AddChipConfig, EvmChip, EvmChipConfig},
+    chips::{AddChip, AddChipConfig, EvmChip, EvmChipConfig, Hardfork},
     circuits::main_circuit::{EvmCircuit, ExecutionStep},
 };
 
@@ -122,7 +122,7 @@ fn example_1_add_chip() -> Result<(), Box<dyn std::error::Error>> {
 fn example_2_evm_chip() -> Result<(), Box<dyn std::error::Error>> {
     #[derive(Default, Clone, Debug)]
     struct TestEvmCircuit {
-        operations: Vec<(u8, Fp, Fp, u64, u64)>, // (opcode, stack_0, stack_1, pc, gas)
+        steps: Vec<ExecutionStep>,
     }
 
     impl Circuit<Fp> for TestEvmCircuit {
@@ -143,35 +143,46 @@ fn example_2_evm_chip() -> Result<(), Box<dyn std::error::Error>> {
             mut layouter: impl Layouter<Fp>,
         ) -> Result<(), Error> {
             let chip = EvmChip::construct(config);
+            chip.load_tables(&mut layouter, Hardfork::Cancun)?;
 
-            for (i, (opcode, stack_0, stack_1, pc, gas)) in self.operations.iter().enumerate() {
-                chip.execute_opcode(
-                    layouter.namespace(|| format!("op_{}", i)),
-                    *opcode,
-                    *stack_0,
-                    *stack_1,
-                    *pc,
-                    *gas,
-                )?;
-            }
+            // All steps share one region so pc/gas/depth transitions are checked
+            chip.assign_steps(layouter.namespace(|| "ops"), &self.steps, Hardfork::Cancun)?;
 
             Ok(())
         }
     }
 
-    let operations = vec![
-        (0x01, Fp::from(10), Fp::from(5), 0, 1000), // ADD
-        (0x02, Fp::from(7), Fp::from(6), 2, 997),   // MUL
-        (0x03, Fp::from(20), Fp::from(8), 4, 994),  // SUB
+    // (opcode, stack_0, stack_1, pc, gas, stack_depth)
+    let operations = [
+        (0x60, U256::from(10), U256::ZERO, 0, 1000, 0), // PUSH1 10
+        (0x60, U256::from(5), U256::from(10), 2, 997, 1), // PUSH1 5
+        (0x01, U256::from(5), U256::from(10), 4, 994, 2), // ADD
+        (0x60, U256::from(6), U256::from(15), 5, 991, 1), // PUSH1 6
+        (0x02, U256::from(6), U256::from(15), 7, 988, 2), // MUL
+        (0x60, U256::from(8), U256::from(90), 8, 983, 1), // PUSH1 8
+        (0x03, U256::from(8), U256::from(90), 10, 980, 2), // SUB
     ];
+    let steps = operations
+        .iter()
+        .map(
+            |&(opcode, stack_0, stack_1, pc, gas, stack_depth)| ExecutionStep {
+                opcode,
+                stack: [stack_0, stack_1, U256::ZERO],
+                pc,
+                gas,
+                stack_depth,
+                memory_size: 0,
+            },
+        )
+        .collect();
 
     println!("  Testing EvmChip operations:");
-    println!("    [0] ADD: 10 + 5 = 15");
-    println!("    [1] MUL: 7 * 6 = 42");
-    println!("    [2] SUB: 20 - 8 = 12");
+    println!("    [0] ADD: 5 + 10 = 15");
+    println!("    [1] MUL: 6 * 15 = 90");
+    println!("    [2] SUB: 8 - 90 (mod 2^256)");
 
-    let circuit = TestEvmCircuit { operations };
-    let k = 6;
+    let circuit = TestEvmCircuit { steps };
+    let k = 9;
 
     let prover = MockProver::run(k, &circuit, vec![])?;
     prover
@@ -189,33 +200,43 @@ fn example_3_custom_circuit() -> Result<(), Box<dyn std::error::Error>> {
     let steps = vec![
         ExecutionStep {
             opcode: 0x60, // PUSH1
-            stack: [Fp::from(100), Fp::ZERO, Fp::ZERO],
+            stack: [U256::ZERO, U256::ZERO, U256::ZERO],
             pc: 0,
             gas: 10000,
+            stack_depth: 0,
+            memory_size: 0,
         },
         ExecutionStep {
             opcode: 0x60, // PUSH1
-            stack: [Fp::from(50), Fp::from(100), Fp::ZERO],
+            stack: [U256::from(100), U256::ZERO, U256::ZERO],
             pc: 2,
             gas: 9997,
+            stack_depth: 1,
+            memory_size: 0,
         },
         ExecutionStep {
             opcode: 0x01, // ADD
-            stack: [Fp::from(150), Fp::ZERO, Fp::ZERO],
+            stack: [U256::from(50), U256::from(100), U256::ZERO],
             pc: 4,
             gas: 9994,
+            stack_depth: 2,
+            memory_size: 0,
         },
         ExecutionStep {
             opcode: 0x60, // PUSH1
-            stack: [Fp::from(2), Fp::from(150), Fp::ZERO],
-            pc: 6,
+            stack: [U256::from(150), U256::ZERO, U256::ZERO],
+            pc: 5,
             gas: 9991,
+            stack_depth: 1,
+            memory_size: 0,
         },
         ExecutionStep {
             opcode: 0x02, // MUL
-            stack: [Fp::from(300), Fp::ZERO, Fp::ZERO],
-            pc: 8,
+            stack: [U256::from(2), U256::from(150), U256::ZERO],
+            pc: 7,
             gas: 9988,
+            stack_depth: 2,
+            memory_size: 0,
         },
     ];
 
@@ -227,14 +248,11 @@ fn example_3_custom_circuit() -> Result<(), Box<dyn std::error::Error>> {
     println!("    MUL        -> stack: [300]");
     println!("    Final result: 300");
 
-    // Create trace commitment
-    let trace_commitment = Fp::from(12345);
-
-    // Build circuit
-    let circuit = EvmCircuit::new(steps, trace_commitment);
+    // Build circuit; the trace commitment is the Poseidon hash chain over the steps
+    let circuit = EvmCircuit::from_steps(steps);
 
     let k = 10;
-    let public_inputs = vec![trace_commitment];
+    let public_inputs = vec![circuit.trace_commitment];
 
     println!("\n  Circuit configuration:");
     println!("    Execution steps: {}", circuit.steps.len());
@@ -259,86 +277,100 @@ fn example_4_complex_flow() -> Result<(), Box<dyn std::error::Error>> {
     let mut steps = Vec::new();
     let mut pc = 0u64;
     let mut gas = 100000u64;
-    let mut stack_values = vec![0u64; 3];
+    let mut stack_values = [0u64; 3];
+    let mut stack_depth = 0u64;
+
+    // Every step shows the stack before it executes
 
     // PUSH1 10
-    stack_values[0] = 10;
     steps.push(ExecutionStep {
         opcode: 0x60,
         stack: [
-            Fp::from(stack_values[0]),
-            Fp::from(stack_values[1]),
-            Fp::from(stack_values[2]),
+            U256::from(stack_values[0]),
+            U256::from(stack_values[1]),
+            U256::from(stack_values[2]),
         ],
         pc,
         gas,
+        stack_depth,
+        memory_size: 0,
     });
-    gas -= OpCode::Push1.gas_cost();
+    gas -= OpCode::Push1.gas_cost(Hardfork::Cancun);
     pc += 2;
+    stack_depth += 1;
+    stack_values[0] = 10;
 
     // PUSH1 20
-    stack_values[1] = stack_values[0];
-    stack_values[0] = 20;
     steps.push(ExecutionStep {
         opcode: 0x60,
         stack: [
-            Fp::from(stack_values[0]),
-            Fp::from(stack_values[1]),
-            Fp::from(stack_values[2]),
+            U256::from(stack_values[0]),
+            U256::from(stack_values[1]),
+            U256::from(stack_values[2]),
         ],
         pc,
         gas,
+        stack_depth,
+        memory_size: 0,
     });
-    gas -= OpCode::Push1.gas_cost();
+    gas -= OpCode::Push1.gas_cost(Hardfork::Cancun);
     pc += 2;
+    stack_depth += 1;
+    stack_values[1] = stack_values[0];
+    stack_values[0] = 20;
 
     // ADD (10 + 20 = 30)
-    stack_values[0] = stack_values[0] + stack_values[1];
-    stack_values[1] = 0;
     steps.push(ExecutionStep {
         opcode: 0x01,
         stack: [
-            Fp::from(stack_values[0]),
-            Fp::from(stack_values[1]),
-            Fp::from(stack_values[2]),
+            U256::from(stack_values[0]),
+            U256::from(stack_values[1]),
+            U256::from(stack_values[2]),
         ],
         pc,
         gas,
+        stack_depth,
+        memory_size: 0,
     });
-    gas -= OpCode::Add.gas_cost();
+    gas -= OpCode::Add.gas_cost(Hardfork::Cancun);
     pc += 1;
+    stack_depth -= 1;
+    stack_values[0] += stack_values[1];
+    stack_values[1] = 0;
 
     // PUSH1 5
-    stack_values[1] = stack_values[0];
-    stack_values[0] = 5;
     steps.push(ExecutionStep {
         opcode: 0x60,
         stack: [
-            Fp::from(stack_values[0]),
-            Fp::from(stack_values[1]),
-            Fp::from(stack_values[2]),
+            U256::from(stack_values[0]),
+            U256::from(stack_values[1]),
+            U256::from(stack_values[2]),
         ],
         pc,
         gas,
+        stack_depth,
+        memory_size: 0,
     });
-    gas -= OpCode::Push1.gas_cost();
+    gas -= OpCode::Push1.gas_cost(Hardfork::Cancun);
     pc += 2;
+    stack_depth += 1;
+    stack_values[1] = stack_values[0];
+    stack_values[0] = 5;
 
     // MUL (30 * 5 = 150)
-    stack_values[0] = stack_values[0] * stack_values[1];
-    stack_values[1] = 0;
     steps.push(ExecutionStep {
         opcode: 0x02,
         stack: [
-            Fp::from(stack_values[0]),
-            Fp::from(stack_values[1]),
-            Fp::from(stack_values[2]),
+            U256::from(stack_values[0]),
+            U256::from(stack_values[1]),
+            U256::from(stack_values[2]),
         ],
         pc,
         gas,
+        stack_depth,
+        memory_size: 0,
     });
-    gas -= OpCode::Mul.gas_cost();
-    pc += 1;
+    gas -= OpCode::Mul.gas_cost(Hardfork::Cancun);
 
     println!("  Complex execution:");
     println!("    Initial gas: 100000");
@@ -352,11 +384,10 @@ fn example_4_complex_flow() -> Result<(), Box<dyn std::error::Error>> {
     println!("    Gas consumed: {}", 100000 - gas);
     println!("    Result: 150");
 
-    let trace_commitment = Fp::from(54321);
-    let circuit = EvmCircuit::new(steps, trace_commitment);
+    let circuit = EvmCircuit::from_steps(steps);
 
     let k = 10;
-    let public_inputs = vec![trace_commitment];
+    let public_inputs = vec![circuit.trace_commitment];
 
     let prover = MockProver::run(k, &circuit, vec![public_inputs])?;
     prover
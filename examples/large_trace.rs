@@ -6,10 +6,9 @@
 
 use colored::Colorize;
 use zephyr_proof::{
-    generate_proof,
-    prover::parallel_prover::generate_proof_chunked,
-    utils::evm_parser::{parse_evm_data, EvmTrace},
-    verify_proof, ProverConfig,
+    Hardfork, ProverConfig, generate_proof,
+    utils::evm_parser::{EvmTrace, U256},
+    verify_proof,
 };
 
 #[tokio::main]
@@ -48,6 +47,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             parallel: true,
             num_threads: Some(4),
             rpc_url: None,
+            hardfork: Hardfork::Cancun,
         };
 
         // Determine if chunking will be used
@@ -56,7 +56,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
         if needs_chunking {
             println!("\n  Will use chunking (trace size > {} rows)", max_rows);
-            let num_chunks = (trace.opcodes.len() + max_rows - 1) / max_rows;
+            let num_chunks = trace.opcodes.len().div_ceil(max_rows);
             println!("    Estimated chunks: {}", num_chunks);
         } else {
             println!(
@@ -134,6 +134,18 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+/// Top three words of a stack kept top last, as a trace step shows them
+fn top_words(stack: &[U256]) -> Vec<U256> {
+    let mut top: Vec<U256> = stack.iter().rev().take(3).copied().collect();
+    top.resize(3, U256::ZERO);
+    top
+}
+
+/// Pop a word, reading the items the trace starts with as zero
+fn pop(stack: &mut Vec<U256>) -> U256 {
+    stack.pop().unwrap_or_default()
+}
+
 /// Generate a large trace with repetitive ADD operations
 fn generate_large_trace(size: usize) -> EvmTrace {
     let mut opcodes = Vec::with_capacity(size);
@@ -144,21 +156,23 @@ fn generate_large_trace(size: usize) -> EvmTrace {
     let initial_gas = 1_000_000u64;
     let mut current_gas = initial_gas;
     let mut pc = 0u64;
-    let mut accumulator = 0u64;
+    let mut stack = Vec::new();
 
     for i in 0..size {
+        // Steps show the stack before they execute
+        stack_states.push(top_words(&stack));
+
         // Alternate between PUSH1 and ADD operations
         if i % 2 == 0 {
             // PUSH1: Push a value onto stack
             opcodes.push(0x60);
-            accumulator += 1;
-            stack_states.push(vec![accumulator, 0, 0]);
+            stack.push(U256::from(i % 256));
             current_gas = current_gas.saturating_sub(3);
         } else {
             // ADD: Add top two stack values
             opcodes.push(0x01);
-            accumulator *= 2; // Simplified: just double the value
-            stack_states.push(vec![accumulator, 0, 0]);
+            let sum = pop(&mut stack).wrapping_add(pop(&mut stack));
+            stack.push(sum);
             current_gas = current_gas.saturating_sub(3);
         }
 
@@ -172,7 +186,15 @@ fn generate_large_trace(size: usize) -> EvmTrace {
         stack_states,
         pcs,
         gas_values,
+        stack_sizes: None,
+        memory_sizes: None,
+        accesses: None,
+        prewarmed: None,
+        sstores: None,
+        refunds: None,
+        intrinsic_gas: None,
         memory_ops: None,
+        stack_ops: None,
         storage_ops: None,
         tx_hash: Some(format!("0xlarge_trace_{}", size)),
         block_number: Some(12345),
@@ -191,44 +213,47 @@ fn generate_complex_trace(size: usize) -> EvmTrace {
     let initial_gas = 1_000_000u64;
     let mut current_gas = initial_gas;
     let mut pc = 0u64;
-    let mut stack_top = 0u64;
+    let mut stack = Vec::new();
 
     for i in 0..size {
         let op_type = i % 5;
 
+        // Steps show the stack before they execute
+        stack_states.push(top_words(&stack));
+
         match op_type {
             0 => {
                 // PUSH1
                 opcodes.push(0x60);
-                stack_top = (i % 256) as u64;
-                stack_states.push(vec![stack_top, 0, 0]);
+                stack.push(U256::from(i % 256));
                 current_gas = current_gas.saturating_sub(3);
             }
             1 => {
                 // ADD
                 opcodes.push(0x01);
-                stack_top = stack_top.wrapping_add(1);
-                stack_states.push(vec![stack_top, 0, 0]);
+                let sum = pop(&mut stack).wrapping_add(pop(&mut stack));
+                stack.push(sum);
                 current_gas = current_gas.saturating_sub(3);
             }
             2 => {
                 // MUL
                 opcodes.push(0x02);
-                stack_top = stack_top.wrapping_mul(2);
-                stack_states.push(vec![stack_top, 0, 0]);
+                let product = pop(&mut stack).wrapping_mul(pop(&mut stack));
+                stack.push(product);
                 current_gas = current_gas.saturating_sub(5);
             }
             3 => {
                 // SUB
                 opcodes.push(0x03);
-                stack_top = stack_top.wrapping_sub(1);
-                stack_states.push(vec![stack_top, 0, 0]);
+                let difference = pop(&mut stack).wrapping_sub(pop(&mut stack));
+                stack.push(difference);
                 current_gas = current_gas.saturating_sub(3);
             }
             _ => {
                 // DUP1
                 opcodes.push(0x80);
-                stack_states.push(vec![stack_top, stack_top, 0]);
+                let top = pop(&mut stack);
+                stack.extend([top, top]);
                 current_gas = current_gas.saturating_sub(3);
             }
         }
@@ -243,7 +268,15 @@ fn generate_complex_trace(size: usize) -> EvmTrace {
         stack_states,
         pcs,
         gas_values,
+        stack_sizes: None,
+        memory_sizes: None,
+        accesses: None,
+        prewarmed: None,
+        sstores: None,
+        refunds: None,
+        intrinsic_gas: None,
         memory_ops: None,
+        stack_ops: None,
         storage_ops: None,
         tx_hash: Some(format!("0xcomplex_trace_{}", size)),
         block_number: Some(12345),
@@ -11,9 +11,9 @@
 
 use colored::Colorize;
 use zephyr_proof::{
-    prove_transaction,
+    Hardfork, ProverConfig, prove_transaction,
     utils::evm_parser::{fetch_and_execute_tx, trace_to_witness},
-    verify_proof, ProverConfig,
+    verify_proof,
 };
 
 #[tokio::main]
@@ -57,6 +57,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         parallel: true,
         num_threads: None, // Auto-detect
         rpc_url: Some(rpc_url.clone()),
+        hardfork: Hardfork::Cancun,
     };
 
     println!("{}", "Prover Configuration:".cyan());
@@ -64,7 +65,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("  Parallel: {}", config.parallel);
     println!(
         "  Threads: {}\n",
-        config.num_threads.unwrap_or_else(|| num_cpus::get())
+        config.num_threads.unwrap_or_else(num_cpus::get)
     );
 
     // Fetch and prove transaction
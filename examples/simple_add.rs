@@ -4,7 +4,7 @@
 //! This example creates a trace with PUSH1, PUSH1, ADD opcodes and generates a proof.
 
 use colored::Colorize;
-use zephyr_proof::{generate_proof, verify_proof, ProverConfig};
+use zephyr_proof::{Hardfork, ProverConfig, generate_proof, verify_proof};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -16,9 +16,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let trace_json = r#"{
         "opcodes": [96, 96, 1],
         "stack_states": [
+            [0, 0, 0],
             [5, 0, 0],
-            [3, 5, 0],
-            [8, 0, 0]
+            [3, 5, 0]
         ],
         "pcs": [0, 2, 4],
         "gas_values": [1000, 997, 994],
@@ -41,6 +41,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         parallel: true,
         num_threads: Some(2),
         rpc_url: None,
+        hardfork: Hardfork::Cancun,
     };
 
     println!("{}", "Prover Configuration:".cyan());
@@ -10,10 +10,16 @@
 use colored::Colorize;
 use halo2_proofs::{dev::MockProver, pasta::Fp};
 use zephyr_proof::{
-    circuits::main_circuit::{EvmCircuit, ExecutionStep},
-    utils::evm_parser::{trace_to_witness, CircuitWitness, EvmTrace},
+    chips::evm_chip::OpCode,
+    circuits::main_circuit::EvmCircuit,
+    utils::evm_parser::{EvmTrace, U256, trace_to_witness},
 };
 
+/// Lift small stack literals into 256-bit words
+fn words(values: &[u64]) -> Vec<U256> {
+    values.iter().map(|&v| U256::from(v)).collect()
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("{}", "Witness Generation Example".cyan().bold());
     println!("==============================\n");
@@ -51,10 +57,18 @@ fn example_1_simple_add() -> Result<(), Box<dyn std::error::Error>> {
     // Create trace: PUSH1 5, PUSH1 3, ADD
     let trace = EvmTrace {
         opcodes: vec![0x60, 0x60, 0x01],
-        stack_states: vec![vec![5, 0, 0], vec![3, 5, 0], vec![8, 0, 0]],
+        stack_states: vec![words(&[0, 0, 0]), words(&[5, 0, 0]), words(&[3, 5, 0])],
         pcs: vec![0, 2, 4],
         gas_values: vec![1000, 997, 994],
+        stack_sizes: None,
+        memory_sizes: None,
+        accesses: None,
+        prewarmed: None,
+        sstores: None,
+        refunds: None,
+        intrinsic_gas: None,
         memory_ops: None,
+        stack_ops: None,
         storage_ops: None,
         tx_hash: Some("0xadd_example".to_string()),
         block_number: Some(1),
@@ -73,16 +87,13 @@ fn example_1_simple_add() -> Result<(), Box<dyn std::error::Error>> {
     println!("    Opcode cells: {:?}", witness.opcode_cells);
     println!("    Stack cells: {:?}", witness.stack_cells);
     println!("    Gas cells: {:?}", witness.gas_cells);
-    println!(
-        "    Public inputs (first 2): {:?}",
-        &witness.public_inputs[..2]
-    );
+    println!("    Trace commitment: {:?}", witness.public_inputs[0]);
 
     // Validate witness
     assert_eq!(witness.opcode_cells.len(), 3);
     assert_eq!(witness.stack_cells.len(), 9); // 3 steps * 3 stack values
     assert_eq!(witness.gas_cells.len(), 3);
-    assert_eq!(witness.public_inputs.len(), 4); // SHA256 hash split into 4 u64s
+    assert_eq!(witness.public_inputs.len(), 1); // Poseidon trace commitment
 
     println!("\n  {}", "Witness validated successfully!".green());
 
@@ -96,15 +107,23 @@ fn example_2_multiple_ops() -> Result<(), Box<dyn std::error::Error>> {
     let trace = EvmTrace {
         opcodes: vec![0x60, 0x60, 0x01, 0x60, 0x02],
         stack_states: vec![
-            vec![10, 0, 0],
-            vec![5, 10, 0],
-            vec![15, 0, 0],
-            vec![2, 15, 0],
-            vec![30, 0, 0],
+            words(&[0, 0, 0]),
+            words(&[10, 0, 0]),
+            words(&[5, 10, 0]),
+            words(&[15, 0, 0]),
+            words(&[2, 15, 0]),
         ],
-        pcs: vec![0, 2, 4, 6, 8],
+        pcs: vec![0, 2, 4, 5, 7],
         gas_values: vec![1000, 997, 994, 991, 986],
+        stack_sizes: None,
+        memory_sizes: None,
+        accesses: None,
+        prewarmed: None,
+        sstores: None,
+        refunds: None,
+        intrinsic_gas: None,
         memory_ops: None,
+        stack_ops: None,
         storage_ops: None,
         tx_hash: Some("0xmulti_ops".to_string()),
         block_number: Some(1),
@@ -133,12 +152,10 @@ fn example_2_multiple_ops() -> Result<(), Box<dyn std::error::Error>> {
     // Show opcode breakdown
     println!("\n  Opcode breakdown:");
     for (i, opcode) in witness.opcode_cells.iter().enumerate() {
-        let opcode_name = match *opcode {
-            0x60 => "PUSH1",
-            0x01 => "ADD",
-            0x02 => "MUL",
-            _ => "UNKNOWN",
-        };
+        let opcode_name = u8::try_from(*opcode)
+            .ok()
+            .and_then(OpCode::from_u8)
+            .map_or("UNKNOWN", |op| op.name());
         println!("    [{}] 0x{:02x} ({})", i, opcode, opcode_name);
     }
 
@@ -149,21 +166,28 @@ fn example_2_multiple_ops() -> Result<(), Box<dyn std::error::Error>> {
 
 /// Example 3: Create custom trace with storage operations
 fn example_3_custom_trace() -> Result<(), Box<dyn std::error::Error>> {
-    use alloy_primitives::U256;
     use zephyr_proof::utils::evm_parser::StorageOp;
 
     // Create trace with storage operations
     let trace = EvmTrace {
         opcodes: vec![0x54, 0x60, 0x01, 0x55],
         stack_states: vec![
-            vec![100, 0, 0], // SLOAD result
-            vec![5, 100, 0], // PUSH1 5
-            vec![105, 0, 0], // ADD result
-            vec![0, 0, 0],   // SSTORE (no return)
+            words(&[0, 0, 0]),   // SLOAD slot 0
+            words(&[100, 0, 0]), // SLOAD result
+            words(&[5, 100, 0]), // PUSH1 5
+            words(&[105, 0, 0]), // ADD result, for SSTORE
         ],
-        pcs: vec![0, 1, 3, 5],
+        pcs: vec![0, 1, 3, 4],
         gas_values: vec![10000, 9800, 9797, 9594],
+        stack_sizes: None,
+        memory_sizes: None,
+        accesses: None,
+        prewarmed: None,
+        sstores: None,
+        refunds: None,
+        intrinsic_gas: None,
         memory_ops: None,
+        stack_ops: None,
         storage_ops: Some(vec![
             StorageOp {
                 key: U256::from(0),
@@ -211,10 +235,18 @@ fn example_4_witness_to_circuit() -> Result<(), Box<dyn std::error::Error>> {
     // Create a simple trace
     let trace = EvmTrace {
         opcodes: vec![0x60, 0x60, 0x01],
-        stack_states: vec![vec![1, 0, 0], vec![2, 1, 0], vec![3, 0, 0]],
+        stack_states: vec![words(&[0, 0, 0]), words(&[1, 0, 0]), words(&[2, 1, 0])],
         pcs: vec![0, 2, 4],
         gas_values: vec![1000, 997, 994],
+        stack_sizes: None,
+        memory_sizes: None,
+        accesses: None,
+        prewarmed: None,
+        sstores: None,
+        refunds: None,
+        intrinsic_gas: None,
         memory_ops: None,
+        stack_ops: None,
         storage_ops: None,
         tx_hash: Some("0xtest_add".to_string()),
         block_number: Some(1),
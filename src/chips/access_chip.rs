@@ -0,0 +1,468 @@
+//! EIP-2929 access set chip
+//!
+//! From Berlin on, SLOAD, SSTORE, BALANCE, EXTCODE* and the CALL family cost
+//! more the first time a transaction touches an account or storage slot. The
+//! chip proves, for every access, whether its key was already in the access
+//! set: the keys warm before the first step (sender, recipient, precompiles,
+//! access list) followed by the keys of every earlier access.
+//!
+//! Keys are compared through their Poseidon hash. Access `i` is warm exactly
+//! when the product of `h_i - h_j` over every earlier key `j` is zero, so a
+//! cold access carries the inverse of that product. This takes one row per
+//! pair of keys, quadratic in the number of accesses. The keys are also chained
+//! into the access commitment, which seeds the trace commitment and so binds
+//! the slot owners and pre-warmed keys the stack does not determine.
+
+use alloy_primitives::U256;
+use halo2_gadgets::poseidon::primitives::{P128Pow5T3, Spec};
+use halo2_proofs::{
+    arithmetic::Field,
+    circuit::{AssignedCell, Layouter, Value},
+    pasta::group::ff::PrimeField,
+    plonk::{Advice, Column, ConstraintSystem, Error, Expression, Selector},
+    poly::Rotation,
+};
+use std::marker::PhantomData;
+
+use crate::chips::evm_chip::{AssignedStep, StateAccessKind, u64_to_field};
+use crate::chips::hash_chip::{HashChip, POSEIDON_RATE, POSEIDON_WIDTH};
+use crate::chips::word_chip::{
+    WordChip, WordChipConfig, assign_value, limb_shift, limb_value, word_limbs,
+};
+use crate::utils::evm_parser::{AccessKey, Address, StateAccess};
+
+/// Elements hashed per key: kind (1 for storage), address and the `(lo, hi)`
+/// limbs of the slot
+pub const ACCESS_KEY_INPUTS: usize = 4;
+
+/// Bytes of the address bits in the high limb of a stack word
+const ADDRESS_HI_BYTES: usize = 4;
+
+/// Bytes of the high limb above the address
+const ADDRESS_REST_BYTES: usize = 12;
+
+/// An address as a field element
+pub fn address_to_field<F: Field>(address: Address) -> F {
+    let [lo, hi] = word_limbs::<F>(U256::from_be_slice(address.as_slice()));
+    lo + hi * limb_shift::<F>()
+}
+
+/// The elements an access key is hashed from
+pub fn access_key_fields<F: Field>(key: &AccessKey) -> [F; ACCESS_KEY_INPUTS] {
+    let [slot_lo, slot_hi] = word_limbs::<F>(key.slot.unwrap_or_default());
+    let kind = if key.slot.is_some() { F::ONE } else { F::ZERO };
+    [kind, address_to_field(key.address), slot_lo, slot_hi]
+}
+
+/// Configuration for the AccessChip
+#[derive(Clone, Debug)]
+pub struct AccessChipConfig {
+    /// Advice columns; each gate documents its own row layout
+    pub cells: [Column<Advice>; 5],
+    /// Address in the low 160 bits of a stack word
+    pub s_address: Selector,
+    /// First row of a warmth product
+    pub s_product_first: Selector,
+    /// Every later row of a warmth product
+    pub s_product_next: Selector,
+    /// Warm flag from the final product
+    pub s_warmth: Selector,
+    /// Dynamic gas of the accessing step from its warm flag
+    pub s_cold_gas: Selector,
+    /// Range checks for the address bytes
+    pub word_config: WordChipConfig,
+}
+
+/// An accessing step with the cells the chip constrains
+#[derive(Clone, Debug)]
+pub struct AccessStep<'a, F: Field> {
+    /// Cells of the step
+    pub step: &'a AssignedStep<F>,
+    /// Access set entry the opcode touches
+    pub kind: StateAccessKind,
+    /// Recorded access (the key owner of storage and created accounts)
+    pub access: StateAccess,
+    /// Cold surcharge the step's dynamic gas is priced by, when that is its
+    /// whole dynamic gas
+    pub cold_cost: Option<u64>,
+}
+
+/// Cells `AccessChip::assign` hands on
+#[derive(Clone, Debug)]
+pub struct AssignedAccesses<F: Field> {
+    /// Access commitment (zero without keys)
+    pub commitment: AssignedCell<F, F>,
+    /// Warm flag of every access, when warmth is proven
+    pub is_warm: Vec<Option<AssignedCell<F, F>>>,
+}
+
+/// Chip proving the warmth of state accesses
+pub struct AccessChip<F: PrimeField> {
+    config: AccessChipConfig,
+    _marker: PhantomData<F>,
+}
+
+impl<F> AccessChip<F>
+where
+    F: PrimeField,
+    P128Pow5T3: Spec<F, POSEIDON_WIDTH, POSEIDON_RATE>,
+{
+    /// Construct a new AccessChip
+    pub fn construct(config: AccessChipConfig) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Configure the key derivation and warmth gates
+    ///
+    /// # Arguments
+    ///
+    /// * `cells` - Advice columns, which may be shared with other word gadgets
+    /// * `word_config` - Range checks for the address bytes
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        cells: [Column<Advice>; 5],
+        word_config: WordChipConfig,
+    ) -> AccessChipConfig {
+        for column in cells {
+            meta.enable_equality(column);
+        }
+
+        let s_address = meta.selector();
+        let s_product_first = meta.selector();
+        let s_product_next = meta.selector();
+        let s_warmth = meta.selector();
+        let s_cold_gas = meta.selector();
+
+        let one = Expression::Constant(F::ONE);
+
+        // Gate: address of a stack word. Row: [lo, hi, low, rest, address]
+        // with hi = low + 2^32 * rest and address = lo + 2^128 * low, where
+        // low fits in 4 bytes and rest in 12.
+        meta.create_gate("access_address", |meta| {
+            let s = meta.query_selector(s_address);
+            let mut q = |column: usize| meta.query_advice(cells[column], Rotation::cur());
+            let (lo, hi, low, rest, address) = (q(0), q(1), q(2), q(3), q(4));
+            let low_shift = Expression::Constant(u64_to_field::<F>(1 << 32));
+            let limb = Expression::Constant(limb_shift::<F>());
+
+            vec![
+                s.clone() * (hi - low.clone() - rest * low_shift),
+                s * (address - lo - low * limb),
+            ]
+        });
+
+        // Gate: first factor of a warmth product. Row: [key, earlier, product, -, -]
+        meta.create_gate("access_product_first", |meta| {
+            let s = meta.query_selector(s_product_first);
+            let key = meta.query_advice(cells[0], Rotation::cur());
+            let earlier = meta.query_advice(cells[1], Rotation::cur());
+            let product = meta.query_advice(cells[2], Rotation::cur());
+
+            vec![s * (product - (key - earlier))]
+        });
+
+        // Gate: every later factor, with the key repeated from the row above
+        meta.create_gate("access_product_next", |meta| {
+            let s = meta.query_selector(s_product_next);
+            let key_prev = meta.query_advice(cells[0], Rotation::prev());
+            let product_prev = meta.query_advice(cells[2], Rotation::prev());
+            let key = meta.query_advice(cells[0], Rotation::cur());
+            let earlier = meta.query_advice(cells[1], Rotation::cur());
+            let product = meta.query_advice(cells[2], Rotation::cur());
+
+            vec![
+                s.clone() * (key.clone() - key_prev),
+                s * (product - product_prev * (key - earlier)),
+            ]
+        });
+
+        // Gate: warm exactly when the product vanishes. Last product row:
+        // [key, earlier, product, is_warm, inverse]
+        meta.create_gate("access_warmth", |meta| {
+            let s = meta.query_selector(s_warmth);
+            let product = meta.query_advice(cells[2], Rotation::cur());
+            let is_warm = meta.query_advice(cells[3], Rotation::cur());
+            let inverse = meta.query_advice(cells[4], Rotation::cur());
+
+            vec![
+                s.clone() * is_warm.clone() * (one.clone() - is_warm.clone()),
+                s.clone() * is_warm.clone() * product.clone(),
+                s * (one.clone() - is_warm) * (product * inverse - one.clone()),
+            ]
+        });
+
+        // Gate: a cold access pays the surcharge, a warm one nothing.
+        // Row: [is_warm, dynamic_gas, cold_cost, -, -]
+        meta.create_gate("access_cold_gas", |meta| {
+            let s = meta.query_selector(s_cold_gas);
+            let is_warm = meta.query_advice(cells[0], Rotation::cur());
+            let dynamic_gas = meta.query_advice(cells[1], Rotation::cur());
+            let cold_cost = meta.query_advice(cells[2], Rotation::cur());
+
+            vec![s * (dynamic_gas - (one.clone() - is_warm) * cold_cost)]
+        });
+
+        AccessChipConfig {
+            cells,
+            s_address,
+            s_product_first,
+            s_product_next,
+            s_warmth,
+            s_cold_gas,
+            word_config,
+        }
+    }
+
+    /// Hash every key, prove the warmth of every access and chain the keys
+    ///
+    /// # Arguments
+    ///
+    /// * `hash_chip` - Poseidon chip for the key hashes and the chain
+    /// * `prewarmed` - Keys warm before the first step
+    /// * `accesses` - Accessing steps in trace order
+    /// * `prove_warmth` - Whether access warmth is priced (Berlin on)
+    ///
+    /// # Returns
+    ///
+    /// The access commitment and the warm flag of every access
+    pub fn assign(
+        &self,
+        mut layouter: impl Layouter<F>,
+        hash_chip: &HashChip<F>,
+        prewarmed: &[AccessKey],
+        accesses: &[AccessStep<'_, F>],
+        prove_warmth: bool,
+    ) -> Result<AssignedAccesses<F>, Error> {
+        let mut keys = Vec::with_capacity(prewarmed.len() + accesses.len());
+        let mut warmth = Vec::with_capacity(accesses.len());
+        for (i, key) in prewarmed.iter().enumerate() {
+            let fields =
+                self.assign_prewarmed(layouter.namespace(|| format!("warm {}", i)), key)?;
+            keys.push(hash_chip.hash(layouter.namespace(|| format!("warm {} hash", i)), fields)?);
+        }
+
+        for (i, access) in accesses.iter().enumerate() {
+            let mut layouter = layouter.namespace(|| format!("access {}", i));
+            let fields = self.assign_key(layouter.namespace(|| "key"), access)?;
+            let key = hash_chip.hash(layouter.namespace(|| "key hash"), fields)?;
+
+            let is_warm = if prove_warmth {
+                let is_warm = self.assign_warmth(layouter.namespace(|| "warmth"), &key, &keys)?;
+                if let Some(cold_cost) = access.cold_cost {
+                    self.constrain_cold_gas(
+                        layouter.namespace(|| "cold gas"),
+                        &is_warm,
+                        &access.step.dynamic_gas,
+                        cold_cost,
+                    )?;
+                }
+                Some(is_warm)
+            } else {
+                None
+            };
+            warmth.push(is_warm);
+            keys.push(key);
+        }
+
+        let mut link = hash_chip.zero(layouter.namespace(|| "seed"))?;
+        for (i, key) in keys.into_iter().enumerate() {
+            link = hash_chip.hash(layouter.namespace(|| format!("link {}", i)), [link, key])?;
+        }
+        Ok(AssignedAccesses {
+            commitment: link,
+            is_warm: warmth,
+        })
+    }
+
+    /// Assign the hash inputs of a pre-warmed key
+    fn assign_prewarmed(
+        &self,
+        mut layouter: impl Layouter<F>,
+        key: &AccessKey,
+    ) -> Result<[AssignedCell<F, F>; ACCESS_KEY_INPUTS], Error> {
+        let fields = access_key_fields::<F>(key);
+        layouter.assign_region(
+            || "prewarmed key",
+            |mut region| {
+                let mut cells = Vec::with_capacity(ACCESS_KEY_INPUTS);
+                for (column, value) in self.config.cells.into_iter().zip(fields) {
+                    cells.push(assign_value(&mut region, column, 0, Value::known(value))?);
+                }
+                cells.try_into().map_err(|_| Error::Synthesis)
+            },
+        )
+    }
+
+    /// Assign the hash inputs of a step's key
+    ///
+    /// Storage slots are the word on top of the stack and accounts the low 160
+    /// bits of their stack operand. Slot owners and created addresses are taken
+    /// from the access record.
+    fn assign_key(
+        &self,
+        mut layouter: impl Layouter<F>,
+        access: &AccessStep<'_, F>,
+    ) -> Result<[AssignedCell<F, F>; ACCESS_KEY_INPUTS], Error> {
+        let cells = self.config.cells;
+        let recorded = Value::known(address_to_field::<F>(access.access.key.address));
+
+        match access.kind {
+            StateAccessKind::Storage => {
+                let [kind, address] = layouter.assign_region(
+                    || "storage key",
+                    |mut region| {
+                        let kind =
+                            region.assign_advice_from_constant(|| "kind", cells[0], 0, F::ONE)?;
+                        let address = assign_value(&mut region, cells[1], 0, recorded)?;
+                        Ok([kind, address])
+                    },
+                )?;
+                let slot = &access.step.stack[0];
+                Ok([kind, address, slot.lo.clone(), slot.hi.clone()])
+            }
+            StateAccessKind::Account(operand) => {
+                let word = &access.step.stack[operand];
+                let (low, rest, address) = layouter.assign_region(
+                    || "account address",
+                    |mut region| {
+                        self.config.s_address.enable(&mut region, 0)?;
+
+                        let lo = word.lo.copy_advice(|| "lo", &mut region, cells[0], 0)?;
+                        let hi = word.hi.copy_advice(|| "hi", &mut region, cells[1], 0)?;
+                        let hi_value = hi.value().map(|v| limb_value(*v));
+                        let low_value =
+                            hi_value.map(|hi| u64_to_field::<F>(hi as u64 & 0xffff_ffff));
+                        let rest_value = hi_value.map(|hi| u128_to_field::<F>(hi >> 32));
+
+                        let low = assign_value(&mut region, cells[2], 0, low_value)?;
+                        let rest = assign_value(&mut region, cells[3], 0, rest_value)?;
+                        let address_value = lo
+                            .value()
+                            .zip(low.value())
+                            .map(|(lo, low)| *lo + *low * limb_shift::<F>());
+                        let address = assign_value(&mut region, cells[4], 0, address_value)?;
+                        Ok((low, rest, address))
+                    },
+                )?;
+
+                let word_chip = WordChip::construct(self.config.word_config.clone());
+                word_chip.range_check(layouter.namespace(|| "low"), &low, ADDRESS_HI_BYTES)?;
+                word_chip.range_check(layouter.namespace(|| "rest"), &rest, ADDRESS_REST_BYTES)?;
+
+                let [kind, slot_lo, slot_hi] = self.assign_zeros(layouter.namespace(|| "slot"))?;
+                Ok([kind, address, slot_lo, slot_hi])
+            }
+            StateAccessKind::Created => {
+                let address = layouter.assign_region(
+                    || "created address",
+                    |mut region| assign_value(&mut region, cells[0], 0, recorded),
+                )?;
+                let [kind, slot_lo, slot_hi] = self.assign_zeros(layouter.namespace(|| "slot"))?;
+                Ok([kind, address, slot_lo, slot_hi])
+            }
+        }
+    }
+
+    /// Zero kind and slot limbs of an account key
+    fn assign_zeros(
+        &self,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<[AssignedCell<F, F>; 3], Error> {
+        layouter.assign_region(
+            || "account kind",
+            |mut region| {
+                let mut zero = |column: usize| {
+                    region.assign_advice_from_constant(
+                        || "zero",
+                        self.config.cells[column],
+                        0,
+                        F::ZERO,
+                    )
+                };
+                Ok([zero(0)?, zero(1)?, zero(2)?])
+            },
+        )
+    }
+
+    /// Prove whether `key` equals one of the `earlier` keys
+    ///
+    /// # Returns
+    ///
+    /// Cell holding 1 for a warm access and 0 for a cold one
+    fn assign_warmth(
+        &self,
+        mut layouter: impl Layouter<F>,
+        key: &AssignedCell<F, F>,
+        earlier: &[AssignedCell<F, F>],
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let cells = self.config.cells;
+        layouter.assign_region(
+            || "access warmth",
+            |mut region| {
+                // Nothing precedes the very first key, which is always cold
+                if earlier.is_empty() {
+                    return region.assign_advice_from_constant(|| "is_warm", cells[3], 0, F::ZERO);
+                }
+
+                let mut product = Value::known(F::ONE);
+                for (row, earlier) in earlier.iter().enumerate() {
+                    if row == 0 {
+                        self.config.s_product_first.enable(&mut region, row)?;
+                    } else {
+                        self.config.s_product_next.enable(&mut region, row)?;
+                    }
+
+                    let key = if row == 0 {
+                        key.copy_advice(|| "key", &mut region, cells[0], row)?
+                    } else {
+                        assign_value(&mut region, cells[0], row, key.value().copied())?
+                    };
+                    let earlier = earlier.copy_advice(|| "earlier", &mut region, cells[1], row)?;
+                    product = product * key.value().zip(earlier.value()).map(|(k, e)| *k - *e);
+                    assign_value(&mut region, cells[2], row, product)?;
+                }
+
+                let last = earlier.len() - 1;
+                self.config.s_warmth.enable(&mut region, last)?;
+                let is_warm = product.map(|p| if p.is_zero_vartime() { F::ONE } else { F::ZERO });
+                let inverse = product.map(|p| p.invert().unwrap_or(F::ZERO));
+                assign_value(&mut region, cells[4], last, inverse)?;
+                assign_value(&mut region, cells[3], last, is_warm)
+            },
+        )
+    }
+
+    /// Pin a step's dynamic gas to its cold surcharge, or zero when warm
+    fn constrain_cold_gas(
+        &self,
+        mut layouter: impl Layouter<F>,
+        is_warm: &AssignedCell<F, F>,
+        dynamic_gas: &AssignedCell<F, F>,
+        cold_cost: u64,
+    ) -> Result<(), Error> {
+        let cells = self.config.cells;
+        layouter.assign_region(
+            || "cold gas",
+            |mut region| {
+                self.config.s_cold_gas.enable(&mut region, 0)?;
+                is_warm.copy_advice(|| "is_warm", &mut region, cells[0], 0)?;
+                dynamic_gas.copy_advice(|| "dynamic_gas", &mut region, cells[1], 0)?;
+                region.assign_advice_from_constant(
+                    || "cold_cost",
+                    cells[2],
+                    0,
+                    u64_to_field::<F>(cold_cost),
+                )?;
+                Ok(())
+            },
+        )
+    }
+}
+
+/// Field element of a 128-bit value
+pub(crate) fn u128_to_field<F: Field>(value: u128) -> F {
+    word_limbs::<F>(U256::from(value))[0]
+}
@@ -0,0 +1,476 @@
+//! Bitwise chip for AND, OR, XOR and NOT
+//!
+//! Binary operations decompose both operand limbs and the result limb into
+//! `CHUNK_BITS`-bit chunks with running sums, and look up every
+//! `(a, b, a op b)` chunk triple in the fixed bitwise table.
+//! NOT needs no lookup: for a 128-bit limb, `!a = 2^128 - 1 - a`.
+
+use halo2_proofs::{
+    circuit::{AssignedCell, Layouter, Value},
+    pasta::group::ff::PrimeField,
+    plonk::{Advice, Column, ConstraintSystem, Error, Expression, Selector},
+    poly::Rotation,
+};
+use std::marker::PhantomData;
+
+use crate::chips::evm_chip::{OpCode, u64_to_field};
+use crate::chips::tables::BitwiseTableConfig;
+use crate::chips::word_chip::{AssignedWord, limb_shift, limb_value};
+
+/// Bits per looked-up chunk
+///
+/// 4-bit chunks keep the table at `1 + 3 * 256` rows so it fits the small
+/// circuits used for short traces; 8-bit chunks would need `3 * 2^16` rows.
+pub const CHUNK_BITS: usize = 4;
+
+/// Chunks per 128-bit limb
+pub const CHUNKS_PER_LIMB: usize = 128 / CHUNK_BITS;
+
+/// Binary bitwise operations supported by the lookup table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitwiseOp {
+    /// Bitwise AND
+    And,
+    /// Bitwise OR
+    Or,
+    /// Bitwise XOR
+    Xor,
+}
+
+impl BitwiseOp {
+    /// Every operation, in table order
+    pub const ALL: [BitwiseOp; 3] = [BitwiseOp::And, BitwiseOp::Or, BitwiseOp::Xor];
+
+    /// Table tag of the operation (0 is reserved for disabled rows)
+    pub fn tag(&self) -> u64 {
+        match self {
+            BitwiseOp::And => 1,
+            BitwiseOp::Or => 2,
+            BitwiseOp::Xor => 3,
+        }
+    }
+
+    /// Apply the operation natively
+    pub fn apply(&self, a: u64, b: u64) -> u64 {
+        match self {
+            BitwiseOp::And => a & b,
+            BitwiseOp::Or => a | b,
+            BitwiseOp::Xor => a ^ b,
+        }
+    }
+
+    /// The binary bitwise operation performed by an opcode, if any
+    pub fn from_opcode(op: OpCode) -> Option<Self> {
+        match op {
+            OpCode::And => Some(BitwiseOp::And),
+            OpCode::Or => Some(BitwiseOp::Or),
+            OpCode::Xor => Some(BitwiseOp::Xor),
+            _ => None,
+        }
+    }
+}
+
+/// Configuration for the BitwiseChip
+#[derive(Clone, Debug)]
+pub struct BitwiseChipConfig {
+    /// Left operand chunk (most significant first)
+    pub a: Column<Advice>,
+    /// Right operand chunk
+    pub b: Column<Advice>,
+    /// Result chunk
+    pub c: Column<Advice>,
+    /// Running sum of the left operand chunks
+    pub a_acc: Column<Advice>,
+    /// Running sum of the right operand chunks
+    pub b_acc: Column<Advice>,
+    /// Running sum of the result chunks
+    pub c_acc: Column<Advice>,
+    /// Enabled on every chunk row of an AND
+    pub s_and: Selector,
+    /// Enabled on every chunk row of an OR
+    pub s_or: Selector,
+    /// Enabled on every chunk row of a XOR
+    pub s_xor: Selector,
+    /// Enabled on the first chunk row of a limb
+    pub s_first: Selector,
+    /// Enabled on every chunk row followed by another chunk of the same limb
+    pub s_acc: Selector,
+    /// Enabled on rows constraining `c_acc = 2^128 - 1 - a_acc`
+    pub s_not: Selector,
+    /// Chunk-wise AND/OR/XOR table
+    pub table: BitwiseTableConfig,
+}
+
+/// Chip constraining bitwise opcodes over word limbs
+pub struct BitwiseChip<F: PrimeField> {
+    config: BitwiseChipConfig,
+    _marker: PhantomData<F>,
+}
+
+impl<F: PrimeField> BitwiseChip<F> {
+    /// Construct a new BitwiseChip
+    pub fn construct(config: BitwiseChipConfig) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Configure the chunk decomposition, table lookup and NOT gates
+    pub fn configure(meta: &mut ConstraintSystem<F>) -> BitwiseChipConfig {
+        let a = meta.advice_column();
+        let b = meta.advice_column();
+        let c = meta.advice_column();
+        let a_acc = meta.advice_column();
+        let b_acc = meta.advice_column();
+        let c_acc = meta.advice_column();
+        for column in [a_acc, b_acc, c_acc] {
+            meta.enable_equality(column);
+        }
+
+        let s_and = meta.complex_selector();
+        let s_or = meta.complex_selector();
+        let s_xor = meta.complex_selector();
+        let s_first = meta.selector();
+        let s_acc = meta.selector();
+        let s_not = meta.selector();
+
+        let table = BitwiseTableConfig::configure(meta);
+
+        // Lookup: (tag, a, b, c) must be a row of the bitwise table.
+        // Selectors are exclusive, so the tag is recovered from whichever is enabled.
+        meta.lookup(|meta| {
+            let and = meta.query_selector(s_and);
+            let or = meta.query_selector(s_or);
+            let xor = meta.query_selector(s_xor);
+            let enabled = and.clone() + or.clone() + xor.clone();
+            let tag = and * Expression::Constant(u64_to_field::<F>(BitwiseOp::And.tag()))
+                + or * Expression::Constant(u64_to_field::<F>(BitwiseOp::Or.tag()))
+                + xor * Expression::Constant(u64_to_field::<F>(BitwiseOp::Xor.tag()));
+
+            vec![
+                (tag, table.tag),
+                (
+                    enabled.clone() * meta.query_advice(a, Rotation::cur()),
+                    table.a,
+                ),
+                (
+                    enabled.clone() * meta.query_advice(b, Rotation::cur()),
+                    table.b,
+                ),
+                (
+                    enabled * meta.query_advice(c, Rotation::cur()),
+                    table.result,
+                ),
+            ]
+        });
+
+        // Gate: running sums start at the first chunk
+        meta.create_gate("bitwise_first_chunk", |meta| {
+            let s = meta.query_selector(s_first);
+            [(a, a_acc), (b, b_acc), (c, c_acc)]
+                .into_iter()
+                .map(|(chunk, acc)| {
+                    s.clone()
+                        * (meta.query_advice(acc, Rotation::cur())
+                            - meta.query_advice(chunk, Rotation::cur()))
+                })
+                .collect::<Vec<_>>()
+        });
+
+        // Gate: running sums shift in the next chunk
+        meta.create_gate("bitwise_running_sum", |meta| {
+            let s = meta.query_selector(s_acc);
+            let radix = Expression::Constant(u64_to_field::<F>(1 << CHUNK_BITS));
+            [(a, a_acc), (b, b_acc), (c, c_acc)]
+                .into_iter()
+                .map(|(chunk, acc)| {
+                    s.clone()
+                        * (meta.query_advice(acc, Rotation::next())
+                            - meta.query_advice(acc, Rotation::cur()) * radix.clone()
+                            - meta.query_advice(chunk, Rotation::next()))
+                })
+                .collect::<Vec<_>>()
+        });
+
+        // Gate: NOT flips every bit of a range-checked 128-bit limb
+        meta.create_gate("bitwise_not", |meta| {
+            let s = meta.query_selector(s_not);
+            let a = meta.query_advice(a_acc, Rotation::cur());
+            let c = meta.query_advice(c_acc, Rotation::cur());
+            let max_limb = Expression::Constant(limb_shift::<F>() - F::ONE);
+
+            vec![s * (c - (max_limb - a))]
+        });
+
+        BitwiseChipConfig {
+            a,
+            b,
+            c,
+            a_acc,
+            b_acc,
+            c_acc,
+            s_and,
+            s_or,
+            s_xor,
+            s_first,
+            s_acc,
+            s_not,
+            table,
+        }
+    }
+
+    /// Load the bitwise lookup table
+    pub fn load_table(&self, layouter: &mut impl Layouter<F>) -> Result<(), Error> {
+        self.config.table.load(layouter)
+    }
+
+    /// Constrain `result = a op b` limb by limb
+    ///
+    /// # Arguments
+    ///
+    /// * `op` - Operation to apply
+    /// * `a` - Left operand word
+    /// * `b` - Right operand word
+    /// * `result` - Word claimed to equal `a op b`
+    pub fn assign_binary(
+        &self,
+        mut layouter: impl Layouter<F>,
+        op: BitwiseOp,
+        a: &AssignedWord<F>,
+        b: &AssignedWord<F>,
+        result: &AssignedWord<F>,
+    ) -> Result<(), Error> {
+        let limbs = a.limbs().into_iter().zip(b.limbs()).zip(result.limbs());
+        for (i, ((a, b), result)) in limbs.enumerate() {
+            self.assign_binary_limb(
+                layouter.namespace(|| format!("limb {}", i)),
+                op,
+                a,
+                b,
+                result,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Constrain `result = NOT a` as `2^128 - 1 - a` on both limbs
+    ///
+    /// Relies on both words being range checked to 128-bit limbs.
+    pub fn assign_not(
+        &self,
+        mut layouter: impl Layouter<F>,
+        a: &AssignedWord<F>,
+        result: &AssignedWord<F>,
+    ) -> Result<(), Error> {
+        layouter.assign_region(
+            || "not",
+            |mut region| {
+                for (row, (a, result)) in a.limbs().into_iter().zip(result.limbs()).enumerate() {
+                    self.config.s_not.enable(&mut region, row)?;
+                    a.copy_advice(|| "a", &mut region, self.config.a_acc, row)?;
+                    result.copy_advice(|| "not a", &mut region, self.config.c_acc, row)?;
+                }
+                Ok(())
+            },
+        )
+    }
+
+    fn assign_binary_limb(
+        &self,
+        mut layouter: impl Layouter<F>,
+        op: BitwiseOp,
+        a: &AssignedCell<F, F>,
+        b: &AssignedCell<F, F>,
+        result: &AssignedCell<F, F>,
+    ) -> Result<(), Error> {
+        let selector = match op {
+            BitwiseOp::And => self.config.s_and,
+            BitwiseOp::Or => self.config.s_or,
+            BitwiseOp::Xor => self.config.s_xor,
+        };
+
+        layouter.assign_region(
+            || "bitwise limb",
+            |mut region| {
+                let a_chunks = a.value().map(|v| limb_chunks(*v));
+                let b_chunks = b.value().map(|v| limb_chunks(*v));
+                let c_chunks = a_chunks.clone().zip(b_chunks.clone()).map(|(a, b)| {
+                    a.iter()
+                        .zip(b.iter())
+                        .map(|(&a, &b)| op.apply(a, b))
+                        .collect::<Vec<_>>()
+                });
+
+                let radix = Value::known(u64_to_field::<F>(1 << CHUNK_BITS));
+                let mut accs = [Value::known(F::ZERO); 3];
+                let mut last = None;
+                for row in 0..CHUNKS_PER_LIMB {
+                    selector.enable(&mut region, row)?;
+                    if row == 0 {
+                        self.config.s_first.enable(&mut region, row)?;
+                    }
+                    if row + 1 < CHUNKS_PER_LIMB {
+                        self.config.s_acc.enable(&mut region, row)?;
+                    }
+
+                    let columns = [
+                        (self.config.a, self.config.a_acc, &a_chunks),
+                        (self.config.b, self.config.b_acc, &b_chunks),
+                        (self.config.c, self.config.c_acc, &c_chunks),
+                    ];
+                    let mut acc_cells = Vec::with_capacity(3);
+                    for (acc, (chunk_column, acc_column, chunks)) in accs.iter_mut().zip(columns) {
+                        let chunk = chunks.as_ref().map(|c| u64_to_field::<F>(c[row]));
+                        region.assign_advice(|| "chunk", chunk_column, row, || chunk)?;
+
+                        *acc = *acc * radix + chunk;
+                        acc_cells.push(region.assign_advice(|| "acc", acc_column, row, || *acc)?);
+                    }
+                    last = Some(acc_cells);
+                }
+
+                let last = last.ok_or(Error::Synthesis)?;
+                region.constrain_equal(last[0].cell(), a.cell())?;
+                region.constrain_equal(last[1].cell(), b.cell())?;
+                region.constrain_equal(last[2].cell(), result.cell())
+            },
+        )
+    }
+}
+
+/// Big-endian `CHUNK_BITS`-bit chunks of a limb
+///
+/// Only the low 128 bits are decomposed; a value that does not fit leaves the
+/// running sum short of the limb and fails the final equality.
+fn limb_chunks<F: PrimeField>(value: F) -> Vec<u64> {
+    let limb = limb_value(value);
+    let mask = (1u128 << CHUNK_BITS) - 1;
+    (0..CHUNKS_PER_LIMB)
+        .rev()
+        .map(|i| ((limb >> (i * CHUNK_BITS)) & mask) as u64)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chips::word_chip::word_limbs;
+    use alloy_primitives::U256;
+    use halo2_proofs::{
+        circuit::SimpleFloorPlanner,
+        dev::MockProver,
+        pasta::Fp,
+        plonk::{Circuit, ConstraintSystem},
+    };
+
+    /// Test circuit checking `result = op(a, b)`, or `result = NOT a` when `op` is `None`
+    #[derive(Default)]
+    struct BitwiseCircuit {
+        op: Option<BitwiseOp>,
+        a: U256,
+        b: U256,
+        result: U256,
+    }
+
+    impl Circuit<Fp> for BitwiseCircuit {
+        type Config = (BitwiseChipConfig, Column<Advice>);
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self {
+                op: self.op,
+                ..Self::default()
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            let words = meta.advice_column();
+            meta.enable_equality(words);
+            (BitwiseChip::configure(meta), words)
+        }
+
+        fn synthesize(
+            &self,
+            (config, column): Self::Config,
+            mut layouter: impl Layouter<Fp>,
+        ) -> Result<(), Error> {
+            let chip = BitwiseChip::construct(config);
+            chip.load_table(&mut layouter)?;
+
+            let [a, b, result] = layouter.assign_region(
+                || "words",
+                |mut region| {
+                    let mut words = Vec::with_capacity(3);
+                    for (i, word) in [self.a, self.b, self.result].into_iter().enumerate() {
+                        let [lo, hi] = word_limbs::<Fp>(word);
+                        words.push(AssignedWord {
+                            lo: region.assign_advice(
+                                || "lo",
+                                column,
+                                2 * i,
+                                || Value::known(lo),
+                            )?,
+                            hi: region.assign_advice(
+                                || "hi",
+                                column,
+                                2 * i + 1,
+                                || Value::known(hi),
+                            )?,
+                        });
+                    }
+                    Ok([words[0].clone(), words[1].clone(), words[2].clone()])
+                },
+            )?;
+
+            match self.op {
+                Some(op) => chip.assign_binary(layouter.namespace(|| "op"), op, &a, &b, &result),
+                None => chip.assign_not(layouter.namespace(|| "not"), &a, &result),
+            }
+        }
+    }
+
+    fn operands() -> (U256, U256) {
+        let a = (U256::from(0xdead_beef_u64) << 160) | U256::from(u128::MAX - 0xf0f0);
+        let b = U256::MAX >> 4;
+        (a, b)
+    }
+
+    fn run(op: Option<BitwiseOp>, a: U256, b: U256, result: U256) -> MockProver<Fp> {
+        let circuit = BitwiseCircuit { op, a, b, result };
+        MockProver::run(10, &circuit, vec![]).unwrap()
+    }
+
+    #[test]
+    fn test_limb_chunks() {
+        let chunks = limb_chunks(Fp::from(0xabcu64));
+        assert_eq!(chunks.len(), CHUNKS_PER_LIMB);
+        assert_eq!(&chunks[CHUNKS_PER_LIMB - 3..], &[0xa, 0xb, 0xc]);
+        assert!(chunks[..CHUNKS_PER_LIMB - 3].iter().all(|&c| c == 0));
+    }
+
+    #[test]
+    fn test_bitwise_ops() {
+        let (a, b) = operands();
+        run(Some(BitwiseOp::And), a, b, a & b).assert_satisfied();
+        run(Some(BitwiseOp::Or), a, b, a | b).assert_satisfied();
+        run(Some(BitwiseOp::Xor), a, b, a ^ b).assert_satisfied();
+        run(None, a, U256::ZERO, !a).assert_satisfied();
+    }
+
+    #[test]
+    fn test_bitwise_wrong_result_fails() {
+        let (a, b) = operands();
+        assert!(run(Some(BitwiseOp::And), a, b, a | b).verify().is_err());
+        assert!(
+            run(Some(BitwiseOp::Xor), a, b, a.wrapping_add(b))
+                .verify()
+                .is_err()
+        );
+        assert!(
+            run(None, a, U256::ZERO, U256::MAX - a + U256::from(1u64))
+                .verify()
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,538 @@
+//! Comparator chip for LT, GT, SLT, SGT, EQ and ISZERO
+//!
+//! Unsigned comparisons subtract the operands limb by limb: the borrow out of
+//! the high limb is `a < b`, and both difference limbs are range checked so the
+//! borrows cannot be forged. Signed comparisons flip the sign bit of both
+//! operands first, which maps two's complement order onto unsigned order.
+//! Equality uses an IsZero gadget (inverse witness) on each limb difference.
+
+use halo2_proofs::{
+    circuit::{AssignedCell, Layouter, Value},
+    pasta::group::ff::PrimeField,
+    plonk::{Advice, Column, ConstraintSystem, Error, Expression, Selector},
+    poly::Rotation,
+};
+use std::marker::PhantomData;
+
+use crate::chips::evm_chip::OpCode;
+use crate::chips::word_chip::{
+    AssignedWord, LIMB_BYTES, WordChip, WordChipConfig, assign_value, limb_shift, limb_value,
+};
+
+/// Comparison performed by an opcode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    /// Unsigned `a < b`
+    Lt,
+    /// Unsigned `a > b`
+    Gt,
+    /// Signed `a < b`
+    SLt,
+    /// Signed `a > b`
+    SGt,
+    /// `a == b`
+    Eq,
+    /// `a == 0`
+    IsZero,
+}
+
+impl CompareOp {
+    /// The comparison performed by an opcode, if any
+    pub fn from_opcode(op: OpCode) -> Option<Self> {
+        match op {
+            OpCode::Lt => Some(CompareOp::Lt),
+            OpCode::Gt => Some(CompareOp::Gt),
+            OpCode::SLt => Some(CompareOp::SLt),
+            OpCode::SGt => Some(CompareOp::SGt),
+            OpCode::Eq => Some(CompareOp::Eq),
+            OpCode::IsZero => Some(CompareOp::IsZero),
+            _ => None,
+        }
+    }
+}
+
+/// Sign bit of a high limb and the limb with that bit flipped
+type SignSplit<F> = (AssignedCell<F, F>, AssignedCell<F, F>);
+
+/// Configuration for the ComparatorChip
+#[derive(Clone, Debug)]
+pub struct ComparatorChipConfig {
+    /// Shared advice columns; each gate documents its own row layout
+    pub cells: [Column<Advice>; 5],
+    /// Subtraction with borrows over two rows
+    pub s_lt: Selector,
+    /// Sign bit extraction and flip of a high limb
+    pub s_sign: Selector,
+    /// Limb-wise IsZero of `a - b` over two rows
+    pub s_is_zero: Selector,
+    /// Forces the `b` operand of an IsZero row to zero (ISZERO)
+    pub s_unary: Selector,
+    /// Range checks for difference limbs and sign remainders
+    pub word_config: WordChipConfig,
+}
+
+/// Chip constraining comparison opcodes over word limbs
+pub struct ComparatorChip<F: PrimeField> {
+    config: ComparatorChipConfig,
+    _marker: PhantomData<F>,
+}
+
+impl<F: PrimeField> ComparatorChip<F> {
+    /// Construct a new ComparatorChip
+    pub fn construct(config: ComparatorChipConfig) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Configure the comparison gates
+    ///
+    /// # Arguments
+    ///
+    /// * `cells` - Advice columns, which may be shared with other word gadgets
+    /// * `word_config` - Range check configuration shared with the stack words
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        cells: [Column<Advice>; 5],
+        word_config: WordChipConfig,
+    ) -> ComparatorChipConfig {
+        for column in cells {
+            meta.enable_equality(column);
+        }
+
+        let s_lt = meta.selector();
+        let s_sign = meta.selector();
+        let s_is_zero = meta.selector();
+        let s_unary = meta.selector();
+
+        let bool_check = |x: Expression<F>| x.clone() * (Expression::Constant(F::ONE) - x);
+
+        // Gate: a - b with borrows. Rows:
+        //   [a.lo, a.hi, b.lo, b.hi, borrow_lo]
+        //   [d.lo, d.hi, lt,   zero, -        ]
+        // With d range checked to 128-bit limbs, `lt` is the borrow out of the word.
+        meta.create_gate("compare_less_than", |meta| {
+            let s = meta.query_selector(s_lt);
+            let mut q =
+                |column: usize, rotation: Rotation| meta.query_advice(cells[column], rotation);
+            let (a_lo, a_hi) = (q(0, Rotation::cur()), q(1, Rotation::cur()));
+            let (b_lo, b_hi) = (q(2, Rotation::cur()), q(3, Rotation::cur()));
+            let borrow_lo = q(4, Rotation::cur());
+            let (d_lo, d_hi) = (q(0, Rotation::next()), q(1, Rotation::next()));
+            let lt = q(2, Rotation::next());
+            let zero = q(3, Rotation::next());
+            let shift = Expression::Constant(limb_shift::<F>());
+
+            vec![
+                s.clone() * bool_check(borrow_lo.clone()),
+                s.clone() * bool_check(lt.clone()),
+                s.clone() * zero,
+                s.clone() * (a_lo - b_lo + borrow_lo.clone() * shift.clone() - d_lo),
+                s * (a_hi - b_hi - borrow_lo + lt * shift - d_hi),
+            ]
+        });
+
+        // Gate: sign bit of a high limb. Row:
+        //   [hi, sign, 2 * hi - sign * 2^128, hi + 2^127 - sign * 2^128, -]
+        // The third cell is range checked to 128 bits, which pins `sign` to the top bit.
+        meta.create_gate("compare_sign", |meta| {
+            let s = meta.query_selector(s_sign);
+            let mut q = |column: usize| meta.query_advice(cells[column], Rotation::cur());
+            let (hi, sign, rest, flipped) = (q(0), q(1), q(2), q(3));
+            let shift = Expression::Constant(limb_shift::<F>());
+            let half_shift = Expression::Constant(limb_shift::<F>() * F::TWO_INV);
+
+            vec![
+                s.clone() * bool_check(sign.clone()),
+                s.clone()
+                    * (rest - (hi.clone() * Expression::Constant(F::from(2)))
+                        + sign.clone() * shift.clone()),
+                s * (flipped - hi - half_shift + sign * shift),
+            ]
+        });
+
+        // Gate: IsZero of a - b on both limbs. Rows:
+        //   [a.lo,   a.hi, b.lo, b.hi, inv_lo]
+        //   [inv_hi, eq,   zero, -,    -     ]
+        meta.create_gate("compare_is_zero", |meta| {
+            let s = meta.query_selector(s_is_zero);
+            let mut q =
+                |column: usize, rotation: Rotation| meta.query_advice(cells[column], rotation);
+            let diff_lo = q(0, Rotation::cur()) - q(2, Rotation::cur());
+            let diff_hi = q(1, Rotation::cur()) - q(3, Rotation::cur());
+            let inv_lo = q(4, Rotation::cur());
+            let inv_hi = q(0, Rotation::next());
+            let eq = q(1, Rotation::next());
+            let zero = q(2, Rotation::next());
+
+            let one = Expression::Constant(F::ONE);
+            let is_zero_lo = one.clone() - diff_lo.clone() * inv_lo;
+            let is_zero_hi = one - diff_hi.clone() * inv_hi;
+
+            vec![
+                s.clone() * diff_lo * is_zero_lo.clone(),
+                s.clone() * diff_hi * is_zero_hi.clone(),
+                s.clone() * (eq - is_zero_lo * is_zero_hi),
+                s * zero,
+            ]
+        });
+
+        // Gate: ISZERO compares against a zero word
+        meta.create_gate("compare_unary", |meta| {
+            let s = meta.query_selector(s_unary);
+            let b_lo = meta.query_advice(cells[2], Rotation::cur());
+            let b_hi = meta.query_advice(cells[3], Rotation::cur());
+
+            vec![s.clone() * b_lo, s * b_hi]
+        });
+
+        ComparatorChipConfig {
+            cells,
+            s_lt,
+            s_sign,
+            s_is_zero,
+            s_unary,
+            word_config,
+        }
+    }
+
+    /// Constrain `result = op(a, b)` as a 0/1 word
+    ///
+    /// # Arguments
+    ///
+    /// * `op` - Comparison to apply
+    /// * `a` - Top stack operand
+    /// * `b` - Second stack operand (ignored for ISZERO)
+    /// * `result` - Word claimed to hold the comparison result
+    pub fn assign(
+        &self,
+        mut layouter: impl Layouter<F>,
+        op: CompareOp,
+        a: &AssignedWord<F>,
+        b: &AssignedWord<F>,
+        result: &AssignedWord<F>,
+    ) -> Result<(), Error> {
+        let computed = match op {
+            CompareOp::Lt => self.less_than(layouter.namespace(|| "lt"), false, a, b)?,
+            CompareOp::Gt => self.less_than(layouter.namespace(|| "gt"), false, b, a)?,
+            CompareOp::SLt => self.less_than(layouter.namespace(|| "slt"), true, a, b)?,
+            CompareOp::SGt => self.less_than(layouter.namespace(|| "sgt"), true, b, a)?,
+            CompareOp::Eq => self.is_equal(layouter.namespace(|| "eq"), a, Some(b))?,
+            CompareOp::IsZero => self.is_equal(layouter.namespace(|| "iszero"), a, None)?,
+        };
+
+        layouter.assign_region(
+            || "comparison result",
+            |mut region| {
+                region.constrain_equal(computed.lo.cell(), result.lo.cell())?;
+                region.constrain_equal(computed.hi.cell(), result.hi.cell())
+            },
+        )
+    }
+
+    /// Compute `a < b`, signed or unsigned, as a 0/1 word
+    pub fn less_than(
+        &self,
+        mut layouter: impl Layouter<F>,
+        signed: bool,
+        a: &AssignedWord<F>,
+        b: &AssignedWord<F>,
+    ) -> Result<AssignedWord<F>, Error> {
+        let (a_hi, b_hi) = if signed {
+            (
+                self.flip_sign(layouter.namespace(|| "sign a"), &a.hi)?.1,
+                self.flip_sign(layouter.namespace(|| "sign b"), &b.hi)?.1,
+            )
+        } else {
+            (a.hi.clone(), b.hi.clone())
+        };
+
+        let cells = self.config.cells;
+        let (lt, differences) = layouter.assign_region(
+            || "less than",
+            |mut region| {
+                self.config.s_lt.enable(&mut region, 0)?;
+
+                let a_lo = a.lo.copy_advice(|| "a lo", &mut region, cells[0], 0)?;
+                let a_hi = a_hi.copy_advice(|| "a hi", &mut region, cells[1], 0)?;
+                let b_lo = b.lo.copy_advice(|| "b lo", &mut region, cells[2], 0)?;
+                let b_hi = b_hi.copy_advice(|| "b hi", &mut region, cells[3], 0)?;
+
+                let limbs =
+                    [&a_lo, &a_hi, &b_lo, &b_hi].map(|cell| cell.value().map(|v| limb_value(*v)));
+                let [a_lo, a_hi, b_lo, b_hi] = limbs;
+                let sub = a_lo
+                    .zip(a_hi)
+                    .zip(b_lo.zip(b_hi))
+                    .map(|((a_lo, a_hi), (b_lo, b_hi))| {
+                        let (d_lo, borrow_lo) = a_lo.overflowing_sub(b_lo);
+                        let (t, borrow_a) = a_hi.overflowing_sub(b_hi);
+                        let (d_hi, borrow_b) = t.overflowing_sub(borrow_lo as u128);
+                        (d_lo, d_hi, borrow_lo, borrow_a || borrow_b)
+                    });
+
+                assign_value(&mut region, cells[4], 0, sub.map(|s| F::from(s.2 as u64)))?;
+                let d_lo = assign_value(&mut region, cells[0], 1, sub.map(|s| F::from_u128(s.0)))?;
+                let d_hi = assign_value(&mut region, cells[1], 1, sub.map(|s| F::from_u128(s.1)))?;
+                let lt = assign_value(&mut region, cells[2], 1, sub.map(|s| F::from(s.3 as u64)))?;
+                let zero = assign_value(&mut region, cells[3], 1, Value::known(F::ZERO))?;
+
+                Ok((AssignedWord { lo: lt, hi: zero }, [d_lo, d_hi]))
+            },
+        )?;
+
+        let word_chip = WordChip::construct(self.config.word_config.clone());
+        for (i, difference) in differences.iter().enumerate() {
+            word_chip.range_check(
+                layouter.namespace(|| format!("difference {}", i)),
+                difference,
+                LIMB_BYTES,
+            )?;
+        }
+        Ok(lt)
+    }
+
+    /// Sign bit of a word (1 when negative in two's complement)
+    pub fn sign_bit(
+        &self,
+        layouter: impl Layouter<F>,
+        word: &AssignedWord<F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        Ok(self.flip_sign(layouter, &word.hi)?.0)
+    }
+
+    /// Split the sign bit off a high limb, returning it with the flipped limb
+    fn flip_sign(
+        &self,
+        mut layouter: impl Layouter<F>,
+        hi: &AssignedCell<F, F>,
+    ) -> Result<SignSplit<F>, Error> {
+        let cells = self.config.cells;
+        let (sign, rest, flipped) = layouter.assign_region(
+            || "sign",
+            |mut region| {
+                self.config.s_sign.enable(&mut region, 0)?;
+
+                let hi = hi.copy_advice(|| "hi", &mut region, cells[0], 0)?;
+                let hi = hi.value().map(|v| limb_value(*v));
+                let sign = hi.map(|hi| hi >> 127);
+
+                let sign = assign_value(&mut region, cells[1], 0, sign.map(|s| F::from(s as u64)))?;
+                let rest =
+                    assign_value(&mut region, cells[2], 0, hi.map(|hi| F::from_u128(hi << 1)))?;
+                let flipped = assign_value(
+                    &mut region,
+                    cells[3],
+                    0,
+                    hi.map(|hi| F::from_u128(hi ^ (1 << 127))),
+                )?;
+                Ok((sign, rest, flipped))
+            },
+        )?;
+
+        WordChip::construct(self.config.word_config.clone()).range_check(
+            layouter.namespace(|| "sign remainder"),
+            &rest,
+            LIMB_BYTES,
+        )?;
+        Ok((sign, flipped))
+    }
+
+    /// Compute `a == b` as a 0/1 word, with `b = 0` when absent (ISZERO)
+    pub fn is_equal(
+        &self,
+        mut layouter: impl Layouter<F>,
+        a: &AssignedWord<F>,
+        b: Option<&AssignedWord<F>>,
+    ) -> Result<AssignedWord<F>, Error> {
+        let cells = self.config.cells;
+        layouter.assign_region(
+            || "is zero",
+            |mut region| {
+                self.config.s_is_zero.enable(&mut region, 0)?;
+
+                let a_lo = a.lo.copy_advice(|| "a lo", &mut region, cells[0], 0)?;
+                let a_hi = a.hi.copy_advice(|| "a hi", &mut region, cells[1], 0)?;
+                let (b_lo, b_hi) = match b {
+                    Some(b) => (
+                        b.lo.copy_advice(|| "b lo", &mut region, cells[2], 0)?,
+                        b.hi.copy_advice(|| "b hi", &mut region, cells[3], 0)?,
+                    ),
+                    None => {
+                        self.config.s_unary.enable(&mut region, 0)?;
+                        let zero = Value::known(F::ZERO);
+                        (
+                            assign_value(&mut region, cells[2], 0, zero)?,
+                            assign_value(&mut region, cells[3], 0, zero)?,
+                        )
+                    }
+                };
+
+                let diff_lo = a_lo.value().copied() - b_lo.value().copied();
+                let diff_hi = a_hi.value().copied() - b_hi.value().copied();
+                let inverse = |d: Value<F>| d.map(|d| d.invert().unwrap_or(F::ZERO));
+                let eq = diff_lo
+                    .zip(diff_hi)
+                    .map(|(lo, hi)| F::from((lo.is_zero_vartime() && hi.is_zero_vartime()) as u64));
+
+                assign_value(&mut region, cells[4], 0, inverse(diff_lo))?;
+                assign_value(&mut region, cells[0], 1, inverse(diff_hi))?;
+                let eq = assign_value(&mut region, cells[1], 1, eq)?;
+                let zero = assign_value(&mut region, cells[2], 1, Value::known(F::ZERO))?;
+
+                Ok(AssignedWord { lo: eq, hi: zero })
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chips::tables::ByteTableConfig;
+    use crate::chips::word_chip::word_limbs;
+    use alloy_primitives::U256;
+    use halo2_proofs::{circuit::SimpleFloorPlanner, dev::MockProver, pasta::Fp, plonk::Circuit};
+
+    /// Test circuit checking `result = op(a, b)`
+    struct CompareCircuit {
+        op: CompareOp,
+        a: U256,
+        b: U256,
+        result: U256,
+    }
+
+    impl Circuit<Fp> for CompareCircuit {
+        type Config = (ComparatorChipConfig, Column<Advice>);
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self {
+                op: self.op,
+                a: U256::ZERO,
+                b: U256::ZERO,
+                result: U256::ZERO,
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            let byte_table = ByteTableConfig::configure(meta);
+            let word_config = WordChip::configure(meta, byte_table);
+            let words = meta.advice_column();
+            meta.enable_equality(words);
+            let cells = [(); 5].map(|_| meta.advice_column());
+            (ComparatorChip::configure(meta, cells, word_config), words)
+        }
+
+        fn synthesize(
+            &self,
+            (config, column): Self::Config,
+            mut layouter: impl Layouter<Fp>,
+        ) -> Result<(), Error> {
+            config.word_config.byte_table.load(&mut layouter)?;
+
+            let [a, b, result] = layouter.assign_region(
+                || "words",
+                |mut region| {
+                    let mut words = Vec::with_capacity(3);
+                    for (i, word) in [self.a, self.b, self.result].into_iter().enumerate() {
+                        let [lo, hi] = word_limbs::<Fp>(word);
+                        let lo = assign_value(&mut region, column, 2 * i, Value::known(lo))?;
+                        let hi = assign_value(&mut region, column, 2 * i + 1, Value::known(hi))?;
+                        words.push(AssignedWord { lo, hi });
+                    }
+                    Ok([words[0].clone(), words[1].clone(), words[2].clone()])
+                },
+            )?;
+
+            ComparatorChip::construct(config).assign(layouter, self.op, &a, &b, &result)
+        }
+    }
+
+    fn run(op: CompareOp, a: U256, b: U256, result: bool) -> MockProver<Fp> {
+        let circuit = CompareCircuit {
+            op,
+            a,
+            b,
+            result: U256::from(result as u64),
+        };
+        MockProver::run(9, &circuit, vec![]).unwrap()
+    }
+
+    /// Native EVM semantics of each comparison
+    fn expected(op: CompareOp, a: U256, b: U256) -> bool {
+        let signed = |x: U256| alloy_primitives::I256::from_raw(x);
+        match op {
+            CompareOp::Lt => a < b,
+            CompareOp::Gt => a > b,
+            CompareOp::SLt => signed(a) < signed(b),
+            CompareOp::SGt => signed(a) > signed(b),
+            CompareOp::Eq => a == b,
+            CompareOp::IsZero => a.is_zero(),
+        }
+    }
+
+    fn samples() -> Vec<U256> {
+        let one = U256::from(1u64);
+        vec![
+            U256::ZERO,
+            one,
+            U256::from(u128::MAX),
+            one << 128,
+            (one << 255) - one,
+            one << 255,
+            U256::MAX,
+        ]
+    }
+
+    #[test]
+    fn test_comparisons() {
+        let ops = [
+            CompareOp::Lt,
+            CompareOp::Gt,
+            CompareOp::SLt,
+            CompareOp::SGt,
+            CompareOp::Eq,
+            CompareOp::IsZero,
+        ];
+        for op in ops {
+            for a in samples() {
+                for b in samples() {
+                    run(op, a, b, expected(op, a, b)).assert_satisfied();
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_wrong_comparison_fails() {
+        let ops = [
+            CompareOp::Lt,
+            CompareOp::SGt,
+            CompareOp::Eq,
+            CompareOp::IsZero,
+        ];
+        for op in ops {
+            for (a, b) in [
+                (U256::ZERO, U256::MAX),
+                (U256::from(7u64), U256::from(7u64)),
+            ] {
+                let prover = run(op, a, b, !expected(op, a, b));
+                assert!(prover.verify().is_err(), "{:?}({}, {})", op, a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn test_non_boolean_result_fails() {
+        let circuit = CompareCircuit {
+            op: CompareOp::Lt,
+            a: U256::ZERO,
+            b: U256::from(1u64),
+            result: U256::from(2u64),
+        };
+        let prover = MockProver::run(9, &circuit, vec![]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+}
@@ -0,0 +1,586 @@
+//! Division chip for DIV, MOD, SDIV and SMOD
+//!
+//! The quotient `q` and remainder `r` are witnessed and constrained by
+//! `a = q * b + r` (with no overflow past 2^256) and `r < b`. Division by zero
+//! forces `q = 0` and makes the result 0 for both DIV and MOD. Signed variants
+//! divide the absolute values and negate the result according to the operand
+//! signs, which also covers `SDIV(-2^255, -1) = -2^255`.
+
+use alloy_primitives::{U256, U512};
+use halo2_proofs::{
+    circuit::{AssignedCell, Layouter, Value},
+    pasta::group::ff::PrimeField,
+    plonk::{Advice, Column, ConstraintSystem, Error, Expression, Selector},
+    poly::Rotation,
+};
+use std::marker::PhantomData;
+
+use crate::chips::comparator_chip::{ComparatorChip, ComparatorChipConfig};
+use crate::chips::evm_chip::OpCode;
+use crate::chips::mul_add_chip::{MulAddChip, MulAddChipConfig};
+use crate::chips::word_chip::{
+    AssignedWord, WordChip, WordChipConfig, assign_value, limb_shift, word_limbs,
+};
+
+/// Division performed by an opcode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DivOp {
+    /// Unsigned quotient
+    Div,
+    /// Unsigned remainder
+    Mod,
+    /// Signed quotient, rounded towards zero
+    SDiv,
+    /// Signed remainder, with the sign of the dividend
+    SMod,
+}
+
+impl DivOp {
+    /// The division performed by an opcode, if any
+    pub fn from_opcode(op: OpCode) -> Option<Self> {
+        match op {
+            OpCode::Div => Some(DivOp::Div),
+            OpCode::Mod => Some(DivOp::Mod),
+            OpCode::SDiv => Some(DivOp::SDiv),
+            OpCode::SMod => Some(DivOp::SMod),
+            _ => None,
+        }
+    }
+
+    /// Whether the operands are two's complement signed
+    pub fn is_signed(&self) -> bool {
+        matches!(self, DivOp::SDiv | DivOp::SMod)
+    }
+
+    /// Whether the result is the quotient rather than the remainder
+    pub fn is_quotient(&self) -> bool {
+        matches!(self, DivOp::Div | DivOp::SDiv)
+    }
+}
+
+/// Configuration for the DivChip
+#[derive(Clone, Debug)]
+pub struct DivChipConfig {
+    /// Advice columns; each gate documents its own row layout
+    pub cells: [Column<Advice>; 5],
+    /// Selects the quotient as the result
+    pub s_quotient: Selector,
+    /// Selects the remainder as the result
+    pub s_remainder: Selector,
+    /// Conditional two's complement negation of a word
+    pub s_negate: Selector,
+    /// Divisor zero check, remainder bound and operand signs
+    pub comparator_config: ComparatorChipConfig,
+    /// `q * b + r` over 512 bits
+    pub mul_add_config: MulAddChipConfig,
+    /// Range checks for the quotient, remainder and negations
+    pub word_config: WordChipConfig,
+}
+
+/// Chip constraining division opcodes over word limbs
+pub struct DivChip<F: PrimeField> {
+    config: DivChipConfig,
+    _marker: PhantomData<F>,
+}
+
+impl<F: PrimeField> DivChip<F> {
+    /// Construct a new DivChip
+    pub fn construct(config: DivChipConfig) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Configure the division gates
+    ///
+    /// # Arguments
+    ///
+    /// * `cells` - Advice columns, which may be shared with other word gadgets
+    /// * `comparator_config` - Comparator used for `b == 0`, `r < b` and signs
+    /// * `mul_add_config` - Multiply-add used for `q * b + r`
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        cells: [Column<Advice>; 5],
+        comparator_config: ComparatorChipConfig,
+        mul_add_config: MulAddChipConfig,
+    ) -> DivChipConfig {
+        for column in cells {
+            meta.enable_equality(column);
+        }
+
+        let s_quotient = meta.selector();
+        let s_remainder = meta.selector();
+        let s_negate = meta.selector();
+
+        let one = Expression::Constant(F::ONE);
+
+        // Gate: pick the result of an unsigned division. Rows:
+        //   [b_zero, lt,     q.lo,   q.hi, r.lo]
+        //   [r.hi,   out.lo, out.hi, -,    -   ]
+        // `b_zero` is `b == 0` and `lt` is `r < b`. A nonzero divisor needs
+        // `r < b`, which makes q and r unique; a zero divisor forces q = 0.
+        meta.create_gate("div_select", |meta| {
+            let s_quotient = meta.query_selector(s_quotient);
+            let s_remainder = meta.query_selector(s_remainder);
+            let mut q =
+                |column: usize, rotation: Rotation| meta.query_advice(cells[column], rotation);
+            let (b_zero, lt) = (q(0, Rotation::cur()), q(1, Rotation::cur()));
+            let (q_lo, q_hi) = (q(2, Rotation::cur()), q(3, Rotation::cur()));
+            let (r_lo, r_hi) = (q(4, Rotation::cur()), q(0, Rotation::next()));
+            let (out_lo, out_hi) = (q(1, Rotation::next()), q(2, Rotation::next()));
+            let nonzero = one.clone() - b_zero.clone();
+            let picks = [
+                (s_quotient, [q_lo.clone(), q_hi.clone()]),
+                (
+                    s_remainder,
+                    [nonzero.clone() * r_lo, nonzero.clone() * r_hi],
+                ),
+            ];
+
+            picks
+                .into_iter()
+                .flat_map(|(s, [pick_lo, pick_hi])| {
+                    [
+                        s.clone() * nonzero.clone() * (one.clone() - lt.clone()),
+                        s.clone() * b_zero.clone() * q_lo.clone(),
+                        s.clone() * b_zero.clone() * q_hi.clone(),
+                        s.clone() * (out_lo.clone() - pick_lo),
+                        s * (out_hi.clone() - pick_hi),
+                    ]
+                })
+                .collect::<Vec<_>>()
+        });
+
+        // Gate: out = -x mod 2^256 when exactly one of two signs is set. Rows:
+        //   [x.lo,   x.hi,   n.lo,   n.hi,   carry_lo]
+        //   [out.lo, out.hi, sign_a, sign_b, carry_hi]
+        // With n range checked, `x + n = carry_hi * 2^256` pins n to -x mod 2^256.
+        meta.create_gate("div_negate", |meta| {
+            let s = meta.query_selector(s_negate);
+            let mut q =
+                |column: usize, rotation: Rotation| meta.query_advice(cells[column], rotation);
+            let (x_lo, x_hi) = (q(0, Rotation::cur()), q(1, Rotation::cur()));
+            let (n_lo, n_hi) = (q(2, Rotation::cur()), q(3, Rotation::cur()));
+            let (carry_lo, carry_hi) = (q(4, Rotation::cur()), q(4, Rotation::next()));
+            let (out_lo, out_hi) = (q(0, Rotation::next()), q(1, Rotation::next()));
+            let (sign_a, sign_b) = (q(2, Rotation::next()), q(3, Rotation::next()));
+            let shift = Expression::Constant(limb_shift::<F>());
+            let negate = sign_a.clone() + sign_b.clone()
+                - Expression::Constant(F::from(2)) * sign_a * sign_b;
+            let bool_check = |x: Expression<F>| x.clone() * (one.clone() - x);
+
+            vec![
+                s.clone() * bool_check(carry_lo.clone()),
+                s.clone() * bool_check(carry_hi.clone()),
+                s.clone() * (x_lo.clone() + n_lo.clone() - carry_lo.clone() * shift.clone()),
+                s.clone() * (x_hi.clone() + n_hi.clone() + carry_lo - carry_hi * shift),
+                s.clone() * (out_lo - x_lo.clone() - negate.clone() * (n_lo - x_lo)),
+                s * (out_hi - x_hi.clone() - negate * (n_hi - x_hi)),
+            ]
+        });
+
+        DivChipConfig {
+            cells,
+            s_quotient,
+            s_remainder,
+            s_negate,
+            word_config: comparator_config.word_config.clone(),
+            comparator_config,
+            mul_add_config,
+        }
+    }
+
+    /// Constrain `result = op(a, b)` with `a` the dividend and `b` the divisor
+    ///
+    /// # Arguments
+    ///
+    /// * `op` - Division to apply
+    /// * `a` - Top stack operand (dividend)
+    /// * `b` - Second stack operand (divisor)
+    /// * `result` - Word claimed to hold the quotient or remainder
+    pub fn assign(
+        &self,
+        mut layouter: impl Layouter<F>,
+        op: DivOp,
+        a: &AssignedWord<F>,
+        b: &AssignedWord<F>,
+        result: &AssignedWord<F>,
+    ) -> Result<(), Error> {
+        let computed = if op.is_signed() {
+            let comparator = ComparatorChip::construct(self.config.comparator_config.clone());
+            let sign_a = comparator.sign_bit(layouter.namespace(|| "sign a"), a)?;
+            let sign_b = comparator.sign_bit(layouter.namespace(|| "sign b"), b)?;
+            let a_abs = self.negate_if(layouter.namespace(|| "abs a"), a, &sign_a, None)?;
+            let b_abs = self.negate_if(layouter.namespace(|| "abs b"), b, &sign_b, None)?;
+
+            let unsigned =
+                self.divide(layouter.namespace(|| "divide"), op, &a_abs, None, &b_abs)?;
+            // The quotient is negative when the signs differ, the remainder
+            // takes the sign of the dividend
+            let sign_b = op.is_quotient().then_some(&sign_b);
+            self.negate_if(layouter.namespace(|| "sign"), &unsigned, &sign_a, sign_b)?
+        } else {
+            self.divide(layouter.namespace(|| "divide"), op, a, None, b)?
+        };
+
+        layouter.assign_region(
+            || "division result",
+            |mut region| {
+                region.constrain_equal(computed.lo.cell(), result.lo.cell())?;
+                region.constrain_equal(computed.hi.cell(), result.hi.cell())
+            },
+        )
+    }
+
+    /// Unsigned quotient or remainder of `a / b`, 0 when `b = 0`
+    ///
+    /// The dividend is `a_hi * 2^256 + a`, or just `a` when `a_hi` is absent.
+    /// A 512-bit dividend must leave a quotient that fits in 256 bits, which
+    /// holds whenever `a_hi < b`.
+    ///
+    /// # Arguments
+    ///
+    /// * `op` - DIV for the quotient or MOD for the remainder
+    /// * `a` - Low half of the dividend
+    /// * `a_hi` - High half of the dividend, if any
+    /// * `b` - Divisor
+    pub fn divide(
+        &self,
+        mut layouter: impl Layouter<F>,
+        op: DivOp,
+        a: &AssignedWord<F>,
+        a_hi: Option<&AssignedWord<F>>,
+        b: &AssignedWord<F>,
+    ) -> Result<AssignedWord<F>, Error> {
+        let cells = self.config.cells;
+        let a_hi_value = match a_hi {
+            Some(a_hi) => a_hi.value(),
+            None => Value::known(U256::ZERO),
+        };
+        let witness = a
+            .value()
+            .zip(a_hi_value)
+            .zip(b.value())
+            .map(|((a, a_hi), b)| {
+                let a: U512 = (U512::from(a_hi) << 256) | U512::from(a);
+                if b.is_zero() {
+                    (U256::ZERO, a.wrapping_to::<U256>())
+                } else {
+                    let b: U512 = U512::from(b);
+                    ((a / b).wrapping_to::<U256>(), (a % b).wrapping_to::<U256>())
+                }
+            });
+
+        let [quotient, remainder] = layouter.assign_region(
+            || "quotient and remainder",
+            |mut region| {
+                let mut assign_word = |column: usize, word: Value<U256>| {
+                    let [lo, hi] = word.map(word_limbs::<F>).transpose_array();
+                    Ok::<_, Error>(AssignedWord {
+                        lo: assign_value(&mut region, cells[column], 0, lo)?,
+                        hi: assign_value(&mut region, cells[column + 1], 0, hi)?,
+                    })
+                };
+                Ok([
+                    assign_word(0, witness.map(|(q, _)| q))?,
+                    assign_word(2, witness.map(|(_, r)| r))?,
+                ])
+            },
+        )?;
+
+        let word_chip = WordChip::construct(self.config.word_config.clone());
+        word_chip.range_check_word(layouter.namespace(|| "quotient"), &quotient)?;
+        word_chip.range_check_word(layouter.namespace(|| "remainder"), &remainder)?;
+
+        // a = q * b + r over 512 bits, with a zero high half for word dividends
+        let mul_add = MulAddChip::construct(self.config.mul_add_config.clone());
+        let [lo, hi] =
+            mul_add.mul_add(layouter.namespace(|| "q * b + r"), &quotient, b, &remainder)?;
+        layouter.assign_region(
+            || "dividend",
+            |mut region| {
+                region.constrain_equal(lo.lo.cell(), a.lo.cell())?;
+                region.constrain_equal(lo.hi.cell(), a.hi.cell())?;
+                match a_hi {
+                    Some(a_hi) => {
+                        region.constrain_equal(hi.lo.cell(), a_hi.lo.cell())?;
+                        region.constrain_equal(hi.hi.cell(), a_hi.hi.cell())
+                    }
+                    None => {
+                        region.constrain_constant(hi.lo.cell(), F::ZERO)?;
+                        region.constrain_constant(hi.hi.cell(), F::ZERO)
+                    }
+                }
+            },
+        )?;
+
+        let comparator = ComparatorChip::construct(self.config.comparator_config.clone());
+        let b_zero = comparator.is_equal(layouter.namespace(|| "b == 0"), b, None)?;
+        let lt = comparator.less_than(layouter.namespace(|| "r < b"), false, &remainder, b)?;
+
+        layouter.assign_region(
+            || "division select",
+            |mut region| {
+                if op.is_quotient() {
+                    self.config.s_quotient.enable(&mut region, 0)?;
+                } else {
+                    self.config.s_remainder.enable(&mut region, 0)?;
+                }
+
+                let b_zero = b_zero
+                    .lo
+                    .copy_advice(|| "b zero", &mut region, cells[0], 0)?;
+                lt.lo.copy_advice(|| "lt", &mut region, cells[1], 0)?;
+                quotient
+                    .lo
+                    .copy_advice(|| "q lo", &mut region, cells[2], 0)?;
+                quotient
+                    .hi
+                    .copy_advice(|| "q hi", &mut region, cells[3], 0)?;
+                remainder
+                    .lo
+                    .copy_advice(|| "r lo", &mut region, cells[4], 0)?;
+                remainder
+                    .hi
+                    .copy_advice(|| "r hi", &mut region, cells[0], 1)?;
+
+                let nonzero = b_zero.value().map(|z| F::ONE - z);
+                let out = if op.is_quotient() {
+                    [quotient.lo.value().copied(), quotient.hi.value().copied()]
+                } else {
+                    [
+                        nonzero * remainder.lo.value().copied(),
+                        nonzero * remainder.hi.value().copied(),
+                    ]
+                };
+                Ok(AssignedWord {
+                    lo: assign_value(&mut region, cells[1], 1, out[0])?,
+                    hi: assign_value(&mut region, cells[2], 1, out[1])?,
+                })
+            },
+        )
+    }
+
+    /// Two's complement negation of `x` when exactly one of the signs is set
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - Range checked word to negate
+    /// * `sign_a` - Boolean cell
+    /// * `sign_b` - Second boolean cell, taken as 0 when absent
+    pub fn negate_if(
+        &self,
+        mut layouter: impl Layouter<F>,
+        x: &AssignedWord<F>,
+        sign_a: &AssignedCell<F, F>,
+        sign_b: Option<&AssignedCell<F, F>>,
+    ) -> Result<AssignedWord<F>, Error> {
+        let cells = self.config.cells;
+        let negated = layouter.assign_region(
+            || "negate",
+            |mut region| {
+                self.config.s_negate.enable(&mut region, 0)?;
+
+                x.lo.copy_advice(|| "x lo", &mut region, cells[0], 0)?;
+                x.hi.copy_advice(|| "x hi", &mut region, cells[1], 0)?;
+                let sign_a = sign_a.copy_advice(|| "sign a", &mut region, cells[2], 1)?;
+                let sign_b = match sign_b {
+                    Some(sign) => sign.copy_advice(|| "sign b", &mut region, cells[3], 1)?,
+                    None => {
+                        let zero = assign_value(&mut region, cells[3], 1, Value::known(F::ZERO))?;
+                        region.constrain_constant(zero.cell(), F::ZERO)?;
+                        zero
+                    }
+                };
+
+                let value = x.value();
+                let negated = value.map(|x| x.wrapping_neg());
+                let [n_lo, n_hi] = negated.map(word_limbs::<F>).transpose_array();
+                // Carries out of x + n: set whenever the limbs below are nonzero
+                let carry_lo = value.map(|x| (x.as_limbs()[0] | x.as_limbs()[1] != 0) as u64);
+                let carry_hi = value.map(|x| !x.is_zero() as u64);
+
+                let n_lo = assign_value(&mut region, cells[2], 0, n_lo)?;
+                let n_hi = assign_value(&mut region, cells[3], 0, n_hi)?;
+                assign_value(&mut region, cells[4], 0, carry_lo.map(F::from))?;
+                assign_value(&mut region, cells[4], 1, carry_hi.map(F::from))?;
+
+                let negate = sign_a.value().zip(sign_b.value()).map(|(a, b)| *a != *b);
+                let out = value
+                    .zip(negated)
+                    .zip(negate)
+                    .map(|((x, n), negate)| word_limbs::<F>(if negate { n } else { x }));
+                let [out_lo, out_hi] = out.transpose_array();
+                let out = AssignedWord {
+                    lo: assign_value(&mut region, cells[0], 1, out_lo)?,
+                    hi: assign_value(&mut region, cells[1], 1, out_hi)?,
+                };
+                Ok((AssignedWord { lo: n_lo, hi: n_hi }, out))
+            },
+        )?;
+
+        let (negation, out) = negated;
+        WordChip::construct(self.config.word_config.clone())
+            .range_check_word(layouter.namespace(|| "negation"), &negation)?;
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chips::tables::ByteTableConfig;
+    use alloy_primitives::I256;
+    use halo2_proofs::{circuit::SimpleFloorPlanner, dev::MockProver, pasta::Fp, plonk::Circuit};
+
+    /// Test circuit checking `result = op(a, b)`
+    struct DivCircuit {
+        op: DivOp,
+        a: U256,
+        b: U256,
+        result: U256,
+    }
+
+    impl Circuit<Fp> for DivCircuit {
+        type Config = (DivChipConfig, Column<Advice>);
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self {
+                op: self.op,
+                a: U256::ZERO,
+                b: U256::ZERO,
+                result: U256::ZERO,
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            let constants = meta.fixed_column();
+            meta.enable_constant(constants);
+            let byte_table = ByteTableConfig::configure(meta);
+            let word_config = WordChip::configure(meta, byte_table);
+            let words = meta.advice_column();
+            meta.enable_equality(words);
+
+            let cells = [(); 5].map(|_| meta.advice_column());
+            let comparator_config = ComparatorChip::configure(meta, cells, word_config.clone());
+            let mul_add_config = MulAddChip::configure(meta, cells, word_config);
+            let config = DivChip::configure(meta, cells, comparator_config, mul_add_config);
+            (config, words)
+        }
+
+        fn synthesize(
+            &self,
+            (config, column): Self::Config,
+            mut layouter: impl Layouter<Fp>,
+        ) -> Result<(), Error> {
+            config.word_config.byte_table.load(&mut layouter)?;
+
+            let [a, b, result] = layouter.assign_region(
+                || "words",
+                |mut region| {
+                    let mut words = Vec::with_capacity(3);
+                    for (i, word) in [self.a, self.b, self.result].into_iter().enumerate() {
+                        let [lo, hi] = word_limbs::<Fp>(word);
+                        let lo = assign_value(&mut region, column, 2 * i, Value::known(lo))?;
+                        let hi = assign_value(&mut region, column, 2 * i + 1, Value::known(hi))?;
+                        words.push(AssignedWord { lo, hi });
+                    }
+                    Ok([words[0].clone(), words[1].clone(), words[2].clone()])
+                },
+            )?;
+
+            let word_chip = WordChip::construct(config.word_config.clone());
+            for word in [&a, &b, &result] {
+                word_chip.range_check_word(layouter.namespace(|| "word"), word)?;
+            }
+            DivChip::construct(config).assign(layouter, self.op, &a, &b, &result)
+        }
+    }
+
+    fn run(op: DivOp, a: U256, b: U256, result: U256) -> MockProver<Fp> {
+        let circuit = DivCircuit { op, a, b, result };
+        MockProver::run(11, &circuit, vec![]).unwrap()
+    }
+
+    /// Native EVM semantics of each division
+    fn expected(op: DivOp, a: U256, b: U256) -> U256 {
+        if b.is_zero() {
+            return U256::ZERO;
+        }
+        let (a_signed, b_signed) = (I256::from_raw(a), I256::from_raw(b));
+        match op {
+            DivOp::Div => a / b,
+            DivOp::Mod => a % b,
+            DivOp::SDiv => a_signed.wrapping_div(b_signed).into_raw(),
+            DivOp::SMod => a_signed.wrapping_rem(b_signed).into_raw(),
+        }
+    }
+
+    fn samples() -> Vec<U256> {
+        let one = U256::from(1u64);
+        vec![
+            U256::ZERO,
+            one,
+            U256::from(7u64),
+            U256::from(u128::MAX) * U256::from(3u64),
+            one << 255,
+            U256::MAX - U256::from(6u64), // -7
+            U256::MAX,                    // -1
+        ]
+    }
+
+    const OPS: [DivOp; 4] = [DivOp::Div, DivOp::Mod, DivOp::SDiv, DivOp::SMod];
+
+    #[test]
+    fn test_divisions() {
+        for op in OPS {
+            for a in samples() {
+                for b in samples() {
+                    run(op, a, b, expected(op, a, b)).assert_satisfied();
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_signed_overflow() {
+        // -2^255 / -1 does not fit and wraps back to -2^255
+        let min = U256::from(1u64) << 255;
+        assert_eq!(expected(DivOp::SDiv, min, U256::MAX), min);
+        run(DivOp::SDiv, min, U256::MAX, min).assert_satisfied();
+        run(DivOp::SMod, min, U256::MAX, U256::ZERO).assert_satisfied();
+    }
+
+    #[test]
+    fn test_wrong_division_fails() {
+        let a = U256::from(100u64);
+        let b = U256::from(7u64);
+        let cases = [
+            (DivOp::Div, a, b, U256::from(13u64)),
+            (DivOp::Mod, a, b, U256::from(9u64)),
+            // Division by zero returns zero, not the dividend
+            (DivOp::Div, a, U256::ZERO, a),
+            (DivOp::Mod, a, U256::ZERO, a),
+            // -100 / 7 rounds towards zero
+            (
+                DivOp::SDiv,
+                U256::ZERO - a,
+                b,
+                U256::ZERO - U256::from(15u64),
+            ),
+            (DivOp::SMod, U256::ZERO - a, b, U256::from(5u64)),
+        ];
+        for (op, a, b, result) in cases {
+            assert_ne!(expected(op, a, b), result);
+            assert!(
+                run(op, a, b, result).verify().is_err(),
+                "{:?}({}, {})",
+                op,
+                a,
+                b
+            );
+        }
+    }
+}
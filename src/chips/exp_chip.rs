@@ -0,0 +1,586 @@
+//! Exponentiation chip for EXP
+//!
+//! `base^e mod 2^256` is computed by square-and-multiply over the 256 bits of
+//! the exponent, most significant first: `acc' = acc^2 * (bit ? base : 1)`.
+//! Every step is one product over 64-bit sub-limbs with range checked carries,
+//! so the layout does not depend on the exponent. The same pass counts the
+//! significant bytes of the exponent for the dynamic gas charge.
+
+use alloy_primitives::U256;
+use halo2_proofs::{
+    circuit::{AssignedCell, Layouter, Region, Value},
+    pasta::group::ff::PrimeField,
+    plonk::{Advice, Column, ConstraintSystem, Error, Expression, Selector},
+    poly::Rotation,
+};
+use std::marker::PhantomData;
+
+use crate::chips::evm_chip::u64_to_field;
+use crate::chips::word_chip::{
+    AssignedWord, WordChip, WordChipConfig, assign_value, limb_shift, word_limbs,
+};
+
+/// Bits of the exponent, one square-and-multiply step each
+const EXP_BITS: usize = 256;
+/// Rows per step
+const STEP_ROWS: usize = 4;
+/// Bytes of a 64-bit sub-limb
+const SUB_LIMB_BYTES: usize = 8;
+/// Bytes of a step carry (`c_0 < 2^128`, the others stay below 2^132)
+const CARRY_BYTES: [usize; 4] = [16, 17, 17, 17];
+/// Gas per significant byte of the exponent
+pub const EXP_BYTE_GAS: u64 = 50;
+
+/// Configuration for the ExpChip
+#[derive(Clone, Debug)]
+pub struct ExpChipConfig {
+    /// Advice columns; each gate documents its own row layout
+    pub cells: [Column<Advice>; 5],
+    /// Square-and-multiply product of every step
+    pub s_step: Selector,
+    /// First step: starts the exponent accumulator and the seen flag
+    pub s_first: Selector,
+    /// First step of the low exponent limb: restarts the exponent accumulator
+    pub s_limb_start: Selector,
+    /// Every other step: extends the exponent accumulator
+    pub s_next: Selector,
+    /// Last step of the first exponent byte: starts the byte count
+    pub s_first_byte_end: Selector,
+    /// Last step of every later exponent byte: extends the byte count
+    pub s_byte_end: Selector,
+    /// Recombination of four sub-limbs into two word limbs
+    pub s_words: Selector,
+    /// Dynamic gas from the byte count
+    pub s_gas: Selector,
+    /// Range checks for the sub-limbs and carries
+    pub word_config: WordChipConfig,
+}
+
+/// Chip constraining EXP over word limbs
+pub struct ExpChip<F: PrimeField> {
+    config: ExpChipConfig,
+    _marker: PhantomData<F>,
+}
+
+/// Witness of one square-and-multiply step
+#[derive(Clone, Debug)]
+struct ExpStep {
+    /// Accumulator before the step, as 64-bit sub-limbs
+    acc: [u64; 4],
+    /// Exponent bit
+    bit: bool,
+    /// Carries out of each sub-limb of the product
+    carries: [U256; 4],
+    /// Exponent bits of the current limb seen so far
+    exponent: u128,
+    /// Whether a set bit has been seen so far
+    seen: bool,
+    /// Significant exponent bytes among the bytes completed so far
+    count: u64,
+}
+
+impl ExpStep {
+    /// Square-and-multiply steps for `base^e`, followed by the final accumulator
+    fn trace(base: U256, e: U256) -> (Vec<ExpStep>, [u64; 4]) {
+        let mut steps = Vec::with_capacity(EXP_BITS);
+        let mut acc = U256::from(1u64);
+        let (mut exponent, mut seen, mut count) = (0u128, false, 0u64);
+
+        for step in 0..EXP_BITS {
+            let bit = e.bit(EXP_BITS - 1 - step);
+            let factor = if bit { base } else { U256::from(1u64) };
+            let (a, m) = (acc.as_limbs(), factor.as_limbs());
+
+            let mut carries = [U256::ZERO; 4];
+            let mut carry = U256::ZERO;
+            for (t, slot) in carries.iter_mut().enumerate() {
+                let mut sum = carry;
+                for (i, j, k) in triples(t) {
+                    sum += U256::from(a[i]) * U256::from(a[j]) * U256::from(m[k]);
+                }
+                carry = sum >> 64;
+                *slot = carry;
+            }
+
+            exponent = if step == EXP_BITS / 2 {
+                0
+            } else {
+                exponent << 1
+            };
+            exponent |= bit as u128;
+            seen |= bit;
+            if step % 8 == 7 {
+                count += seen as u64;
+            }
+
+            steps.push(ExpStep {
+                acc: *a,
+                bit,
+                carries,
+                exponent,
+                seen,
+                count,
+            });
+            acc = acc.wrapping_mul(acc).wrapping_mul(factor);
+        }
+
+        (steps, *acc.as_limbs())
+    }
+}
+
+/// Sub-limb index triples `(i, j, k)` with `i + j + k = t`
+fn triples(t: usize) -> impl Iterator<Item = (usize, usize, usize)> {
+    (0..=t).flat_map(move |i| (0..=t - i).map(move |j| (i, j, t - i - j)))
+}
+
+/// Field element of a value below 2^254
+fn u256_to_field<F: PrimeField>(value: U256) -> F {
+    let [lo, hi] = word_limbs::<F>(value);
+    lo + hi * limb_shift::<F>()
+}
+
+impl<F: PrimeField> ExpChip<F> {
+    /// Construct a new ExpChip
+    pub fn construct(config: ExpChipConfig) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Configure the square-and-multiply gates
+    ///
+    /// # Arguments
+    ///
+    /// * `cells` - Advice columns, which may be shared with other word gadgets
+    /// * `word_config` - Range checks for the sub-limbs and carries
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        cells: [Column<Advice>; 5],
+        word_config: WordChipConfig,
+    ) -> ExpChipConfig {
+        for column in cells {
+            meta.enable_equality(column);
+        }
+
+        let s_step = meta.selector();
+        let s_first = meta.selector();
+        let s_limb_start = meta.selector();
+        let s_next = meta.selector();
+        let s_first_byte_end = meta.selector();
+        let s_byte_end = meta.selector();
+        let s_words = meta.selector();
+        let s_gas = meta.selector();
+
+        let one = Expression::Constant(F::ONE);
+        let two = Expression::Constant(F::from(2));
+        let sub_shift = Expression::Constant(u64_to_field::<F>(1 << 32).square());
+
+        // Gate: one square-and-multiply step. Rows:
+        //   [a0, a1, a2, a3, bit     ]
+        //   [b0, b1, b2, b3, exponent]
+        //   [c0, c1, c2, c3, seen    ]
+        //   [count, -, -, -, -       ]
+        //   [n0, n1, n2, n3, -       ]  (first row of the next step)
+        // With m = bit ? b : 1 and S_t the sum of a_i * a_j * m_k over
+        // i + j + k = t, the sub-limbs of n = a^2 * m mod 2^256 satisfy
+        // S_t + c_{t-1} = n_t + c_t * 2^64. Every term stays far below the
+        // field modulus once n and c are range checked.
+        meta.create_gate("exp_step", |meta| {
+            let s = meta.query_selector(s_step);
+            let mut q = |column: usize, row: i32| meta.query_advice(cells[column], Rotation(row));
+            let a = [0, 1, 2, 3].map(|i| q(i, 0));
+            let b = [0, 1, 2, 3].map(|i| q(i, 1));
+            let c = [0, 1, 2, 3].map(|i| q(i, 2));
+            let n = [0, 1, 2, 3].map(|i| q(i, STEP_ROWS as i32));
+            let bit = q(4, 0);
+
+            let unit = |k: usize| {
+                if k == 0 {
+                    one.clone()
+                } else {
+                    Expression::Constant(F::ZERO)
+                }
+            };
+            let m: Vec<_> = (0..4)
+                .map(|k| unit(k) + bit.clone() * (b[k].clone() - unit(k)))
+                .collect();
+
+            let mut constraints = vec![s.clone() * bit.clone() * (one.clone() - bit)];
+            for t in 0..4 {
+                let product = triples(t)
+                    .map(|(i, j, k)| a[i].clone() * a[j].clone() * m[k].clone())
+                    .reduce(|acc, term| acc + term)
+                    .expect("at least one triple");
+                let carry_in = match t {
+                    0 => Expression::Constant(F::ZERO),
+                    _ => c[t - 1].clone(),
+                };
+                constraints.push(
+                    s.clone()
+                        * (product + carry_in - n[t].clone() - c[t].clone() * sub_shift.clone()),
+                );
+            }
+            constraints
+        });
+
+        // Gate: the first step starts the exponent accumulator and seen flag
+        meta.create_gate("exp_first_bit", |meta| {
+            let s = meta.query_selector(s_first);
+            let mut q = |column: usize, row: i32| meta.query_advice(cells[column], Rotation(row));
+            let (bit, exponent, seen) = (q(4, 0), q(4, 1), q(4, 2));
+
+            vec![s.clone() * (exponent - bit.clone()), s * (seen - bit)]
+        });
+
+        // Gate: later steps extend the exponent accumulator and seen flag. The
+        // exponent limbs are rebuilt bit by bit, the high limb over the first
+        // 128 steps.
+        meta.create_gate("exp_next_bit", |meta| {
+            let s_limb_start = meta.query_selector(s_limb_start);
+            let s_next = meta.query_selector(s_next);
+            let mut q = |column: usize, row: i32| meta.query_advice(cells[column], Rotation(row));
+            let (bit, exponent, seen) = (q(4, 0), q(4, 1), q(4, 2));
+            let step = STEP_ROWS as i32;
+            let (prev_exponent, prev_seen) = (q(4, 1 - step), q(4, 2 - step));
+            let next_seen = prev_seen.clone() + bit.clone() - prev_seen * bit.clone();
+
+            vec![
+                s_limb_start.clone() * (exponent.clone() - bit.clone()),
+                s_limb_start * (seen.clone() - next_seen.clone()),
+                s_next.clone() * (exponent - two * prev_exponent - bit),
+                s_next * (seen - next_seen),
+            ]
+        });
+
+        // Gate: the byte count adds the seen flag at the last bit of every
+        // byte, counting the significant bytes of the exponent
+        meta.create_gate("exp_byte_count", |meta| {
+            let s_first_byte_end = meta.query_selector(s_first_byte_end);
+            let s_byte_end = meta.query_selector(s_byte_end);
+            let mut q = |column: usize, row: i32| meta.query_advice(cells[column], Rotation(row));
+            let (seen, count) = (q(4, 2), q(0, 3));
+            let prev_count = q(0, 3 - 8 * STEP_ROWS as i32);
+
+            vec![
+                s_first_byte_end * (count.clone() - seen.clone()),
+                s_byte_end * (count - prev_count - seen),
+            ]
+        });
+
+        // Gate: word limbs from 64-bit sub-limbs. Rows:
+        //   [x0, x1, x2, x3, -]
+        //   [lo, hi, -,  -,  -]
+        meta.create_gate("exp_words", |meta| {
+            let s = meta.query_selector(s_words);
+            let mut q = |column: usize, row: i32| meta.query_advice(cells[column], Rotation(row));
+            let x = [0, 1, 2, 3].map(|i| q(i, 0));
+            let (lo, hi) = (q(0, 1), q(1, 1));
+
+            vec![
+                s.clone() * (lo - x[0].clone() - x[1].clone() * sub_shift.clone()),
+                s * (hi - x[2].clone() - x[3].clone() * sub_shift.clone()),
+            ]
+        });
+
+        // Gate: dynamic gas charged for the significant exponent bytes. Row:
+        //   [count, gas, -, -, -]
+        meta.create_gate("exp_gas", |meta| {
+            let s = meta.query_selector(s_gas);
+            let count = meta.query_advice(cells[0], Rotation::cur());
+            let gas = meta.query_advice(cells[1], Rotation::cur());
+            let byte_gas = Expression::Constant(u64_to_field::<F>(EXP_BYTE_GAS));
+
+            vec![s * (gas - byte_gas * count)]
+        });
+
+        ExpChipConfig {
+            cells,
+            s_step,
+            s_first,
+            s_limb_start,
+            s_next,
+            s_first_byte_end,
+            s_byte_end,
+            s_words,
+            s_gas,
+            word_config,
+        }
+    }
+
+    /// Constrain `result = base^e mod 2^256` and the dynamic gas of the step
+    ///
+    /// # Arguments
+    ///
+    /// * `base` - Top stack operand
+    /// * `e` - Second stack operand (exponent)
+    /// * `result` - Word claimed to hold the result
+    /// * `dynamic_gas` - Dynamic gas cell of the EXP step
+    pub fn assign(
+        &self,
+        mut layouter: impl Layouter<F>,
+        base: &AssignedWord<F>,
+        e: &AssignedWord<F>,
+        result: &AssignedWord<F>,
+        dynamic_gas: &AssignedCell<F, F>,
+    ) -> Result<(), Error> {
+        let cells = self.config.cells;
+        let trace = base
+            .value()
+            .zip(e.value())
+            .map(|(b, e)| ExpStep::trace(b, e));
+        let sub_limbs = base.value().map(|b| *b.as_limbs());
+
+        let (sub_limb_cells, carry_cells) = layouter.assign_region(
+            || "exp",
+            |mut region| {
+                let mut sub_limb_cells = Vec::new();
+                let mut carry_cells = Vec::new();
+
+                // Base sub-limbs, recombined into the base limbs
+                self.config.s_words.enable(&mut region, 0)?;
+                let base_sub_limbs = self.assign_sub_limbs(&mut region, 0, sub_limbs)?;
+                base.lo
+                    .copy_advice(|| "base lo", &mut region, cells[0], 1)?;
+                base.hi
+                    .copy_advice(|| "base hi", &mut region, cells[1], 1)?;
+                sub_limb_cells.extend(base_sub_limbs.iter().cloned());
+
+                let start = 2;
+                for i in 0..EXP_BITS {
+                    let offset = start + i * STEP_ROWS;
+                    let step = trace.as_ref().map(|(steps, _)| steps[i].clone());
+
+                    self.config.s_step.enable(&mut region, offset)?;
+                    let selector = match i {
+                        0 => self.config.s_first,
+                        i if i == EXP_BITS / 2 => self.config.s_limb_start,
+                        _ => self.config.s_next,
+                    };
+                    selector.enable(&mut region, offset)?;
+                    match i {
+                        7 => self.config.s_first_byte_end.enable(&mut region, offset)?,
+                        i if i % 8 == 7 => self.config.s_byte_end.enable(&mut region, offset)?,
+                        _ => {}
+                    }
+
+                    // The accumulator starts at 1; later ones are the previous outputs
+                    let acc =
+                        self.assign_sub_limbs(&mut region, offset, step.as_ref().map(|s| s.acc))?;
+                    if i == 0 {
+                        for (k, cell) in acc.iter().enumerate() {
+                            region.constrain_constant(cell.cell(), F::from((k == 0) as u64))?;
+                        }
+                    } else {
+                        sub_limb_cells.extend(acc);
+                    }
+
+                    let value = |f: fn(&ExpStep) -> u64| step.as_ref().map(|s| F::from(f(s)));
+                    assign_value(&mut region, cells[4], offset, value(|s| s.bit as u64))?;
+                    for (k, cell) in base_sub_limbs.iter().enumerate() {
+                        cell.copy_advice(|| "base sub-limb", &mut region, cells[k], offset + 1)?;
+                    }
+                    let exponent = assign_value(
+                        &mut region,
+                        cells[4],
+                        offset + 1,
+                        step.as_ref().map(|s| F::from_u128(s.exponent)),
+                    )?;
+                    for k in 0..4 {
+                        let carry = step.as_ref().map(|s| u256_to_field::<F>(s.carries[k]));
+                        let cell = assign_value(&mut region, cells[k], offset + 2, carry)?;
+                        carry_cells.push((cell, CARRY_BYTES[k]));
+                    }
+                    assign_value(&mut region, cells[4], offset + 2, value(|s| s.seen as u64))?;
+                    assign_value(&mut region, cells[0], offset + 3, value(|s| s.count))?;
+
+                    // Exponent limbs are complete after the last bit of each half
+                    if i == EXP_BITS / 2 - 1 {
+                        region.constrain_equal(exponent.cell(), e.hi.cell())?;
+                    }
+                    if i == EXP_BITS - 1 {
+                        region.constrain_equal(exponent.cell(), e.lo.cell())?;
+                        self.config.s_gas.enable(&mut region, offset + 3)?;
+                        dynamic_gas.copy_advice(
+                            || "dynamic gas",
+                            &mut region,
+                            cells[1],
+                            offset + 3,
+                        )?;
+                    }
+                }
+
+                // Final accumulator, recombined into the result limbs
+                let offset = start + EXP_BITS * STEP_ROWS;
+                self.config.s_words.enable(&mut region, offset)?;
+                let output = trace.as_ref().map(|(_, output)| *output);
+                sub_limb_cells.extend(self.assign_sub_limbs(&mut region, offset, output)?);
+                result
+                    .lo
+                    .copy_advice(|| "result lo", &mut region, cells[0], offset + 1)?;
+                result
+                    .hi
+                    .copy_advice(|| "result hi", &mut region, cells[1], offset + 1)?;
+
+                Ok((sub_limb_cells, carry_cells))
+            },
+        )?;
+
+        let word_chip = WordChip::construct(self.config.word_config.clone());
+        for (i, cell) in sub_limb_cells.iter().enumerate() {
+            word_chip.range_check(
+                layouter.namespace(|| format!("sub-limb {}", i)),
+                cell,
+                SUB_LIMB_BYTES,
+            )?;
+        }
+        for (i, (cell, num_bytes)) in carry_cells.iter().enumerate() {
+            word_chip.range_check(
+                layouter.namespace(|| format!("carry {}", i)),
+                cell,
+                *num_bytes,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Assign four 64-bit sub-limbs on one row
+    fn assign_sub_limbs(
+        &self,
+        region: &mut Region<'_, F>,
+        offset: usize,
+        sub_limbs: Value<[u64; 4]>,
+    ) -> Result<Vec<AssignedCell<F, F>>, Error> {
+        (0..4)
+            .map(|k| {
+                let value = sub_limbs.map(|limbs| F::from(limbs[k]));
+                assign_value(region, self.config.cells[k], offset, value)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chips::tables::ByteTableConfig;
+    use halo2_proofs::{circuit::SimpleFloorPlanner, dev::MockProver, pasta::Fp, plonk::Circuit};
+
+    /// Test circuit checking `result = base^e` and its dynamic gas
+    struct ExpCircuit {
+        base: U256,
+        e: U256,
+        result: U256,
+        gas: u64,
+    }
+
+    impl Circuit<Fp> for ExpCircuit {
+        type Config = (ExpChipConfig, Column<Advice>);
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self {
+                base: U256::ZERO,
+                e: U256::ZERO,
+                result: U256::ZERO,
+                gas: 0,
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            let constants = meta.fixed_column();
+            meta.enable_constant(constants);
+            let byte_table = ByteTableConfig::configure(meta);
+            let word_config = WordChip::configure(meta, byte_table);
+            let words = meta.advice_column();
+            meta.enable_equality(words);
+
+            let cells = [(); 5].map(|_| meta.advice_column());
+            (ExpChip::configure(meta, cells, word_config), words)
+        }
+
+        fn synthesize(
+            &self,
+            (config, column): Self::Config,
+            mut layouter: impl Layouter<Fp>,
+        ) -> Result<(), Error> {
+            config.word_config.byte_table.load(&mut layouter)?;
+
+            let (words, gas) = layouter.assign_region(
+                || "words",
+                |mut region| {
+                    let mut words = Vec::with_capacity(3);
+                    for (i, word) in [self.base, self.e, self.result].into_iter().enumerate() {
+                        let [lo, hi] = word_limbs::<Fp>(word);
+                        let lo = assign_value(&mut region, column, 2 * i, Value::known(lo))?;
+                        let hi = assign_value(&mut region, column, 2 * i + 1, Value::known(hi))?;
+                        words.push(AssignedWord { lo, hi });
+                    }
+                    let gas =
+                        assign_value(&mut region, column, 6, Value::known(Fp::from(self.gas)))?;
+                    Ok((words, gas))
+                },
+            )?;
+
+            let word_chip = WordChip::construct(config.word_config.clone());
+            for word in &words {
+                word_chip.range_check_word(layouter.namespace(|| "word"), word)?;
+            }
+            ExpChip::construct(config).assign(layouter, &words[0], &words[1], &words[2], &gas)
+        }
+    }
+
+    fn run(base: U256, e: U256, result: U256, gas: u64) -> MockProver<Fp> {
+        let circuit = ExpCircuit {
+            base,
+            e,
+            result,
+            gas,
+        };
+        MockProver::run(13, &circuit, vec![]).unwrap()
+    }
+
+    fn gas(e: U256) -> u64 {
+        EXP_BYTE_GAS * e.byte_len() as u64
+    }
+
+    #[test]
+    fn test_exp() {
+        let ten = U256::from(10u64);
+        let cases = [
+            (ten, U256::from(18u64)),
+            (U256::from(2u64), U256::from(255u64)),
+            (U256::from(3u64), U256::MAX),
+            (U256::MAX, (U256::from(1u64) << 128) | U256::from(5u64)),
+            (U256::ZERO, U256::ZERO),
+            (ten, U256::ZERO),
+        ];
+        for (base, e) in cases {
+            run(base, e, base.pow(e), gas(e)).assert_satisfied();
+        }
+    }
+
+    #[test]
+    fn test_wrong_exp_fails() {
+        let (base, e) = (U256::from(10u64), U256::from(18u64));
+        let result = base.pow(e);
+        // 10^18 needs one exponent byte
+        assert_eq!(gas(e), 50);
+
+        assert!(
+            run(base, e, result + U256::from(1u64), 50)
+                .verify()
+                .is_err()
+        );
+        assert!(run(base, e, result, 0).verify().is_err());
+        assert!(run(base, e, result, 100).verify().is_err());
+        // 2^256 wraps to zero
+        let two = U256::from(2u64);
+        run(two, U256::from(256u64), U256::ZERO, 100).assert_satisfied();
+        assert!(
+            run(two, U256::from(256u64), U256::from(1u64), 100)
+                .verify()
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,156 @@
+//! Poseidon hash chain over execution steps
+//!
+//! Binds the public trace commitment to the opcodes, program counters, gas
+//! values, stack depths, memory sizes and stack values witnessed in the
+//! execution region, and to the access set keys and SSTORE slot values
+//! through the seed of the chain.
+
+use halo2_gadgets::poseidon::{
+    Hash, Pow5Chip, Pow5Config,
+    primitives::{ConstantLength, P128Pow5T3, Spec},
+};
+use halo2_proofs::{
+    arithmetic::Field,
+    circuit::{AssignedCell, Layouter},
+    pasta::group::ff::PrimeField,
+    plonk::{Advice, Column, ConstraintSystem, Error, Fixed},
+};
+
+use crate::chips::evm_chip::AssignedStep;
+
+/// Poseidon state width
+pub const POSEIDON_WIDTH: usize = 3;
+
+/// Poseidon sponge rate
+pub const POSEIDON_RATE: usize = 2;
+
+/// Elements absorbed per chain link: previous link, opcode, pc, gas, stack
+/// depth, memory size and the `(lo, hi)` limbs of the three stack words
+pub const STEP_HASH_INPUTS: usize = 12;
+
+/// Configuration for the HashChip
+#[derive(Clone, Debug)]
+pub struct HashChipConfig<F: Field> {
+    /// Poseidon permutation configuration
+    pub poseidon: Pow5Config<F, POSEIDON_WIDTH, POSEIDON_RATE>,
+    /// Column holding the zero that seeds the chain
+    pub seed: Column<Advice>,
+    /// Fixed column used for constants
+    pub constants: Column<Fixed>,
+}
+
+/// Chip computing the trace commitment hash chain
+///
+/// `h_0` is the state commitment (zero without state accesses) and
+/// `h_{i+1} = Poseidon(h_i, opcode_i, pc_i, gas_i, depth_i, memory_i, stack_i[0..3])`,
+/// with each stack word absorbed as its low limb followed by its high limb.
+/// The commitment is the final link `h_n`.
+pub struct HashChip<F: Field> {
+    config: HashChipConfig<F>,
+}
+
+impl<F> HashChip<F>
+where
+    F: PrimeField,
+    P128Pow5T3: Spec<F, POSEIDON_WIDTH, POSEIDON_RATE>,
+{
+    /// Construct a new HashChip
+    pub fn construct(config: HashChipConfig<F>) -> Self {
+        Self { config }
+    }
+
+    /// Configure the Poseidon permutation on dedicated columns
+    pub fn configure(meta: &mut ConstraintSystem<F>) -> HashChipConfig<F> {
+        let state = [
+            meta.advice_column(),
+            meta.advice_column(),
+            meta.advice_column(),
+        ];
+        let partial_sbox = meta.advice_column();
+        let rc_a = [
+            meta.fixed_column(),
+            meta.fixed_column(),
+            meta.fixed_column(),
+        ];
+        let rc_b = [
+            meta.fixed_column(),
+            meta.fixed_column(),
+            meta.fixed_column(),
+        ];
+
+        // The sponge's initial state and the chain seed are loaded from constants
+        let constants = rc_b[0];
+        meta.enable_constant(constants);
+
+        let seed = state[0];
+        let poseidon = Pow5Chip::configure::<P128Pow5T3>(meta, state, partial_sbox, rc_a, rc_b);
+
+        HashChipConfig {
+            poseidon,
+            seed,
+            constants,
+        }
+    }
+
+    /// Assign the zero that starts a hash chain
+    pub fn zero(&self, mut layouter: impl Layouter<F>) -> Result<AssignedCell<F, F>, Error> {
+        layouter.assign_region(
+            || "chain seed",
+            |mut region| region.assign_advice_from_constant(|| "h_0", self.config.seed, 0, F::ZERO),
+        )
+    }
+
+    /// Poseidon hash of a fixed number of cells
+    pub fn hash<const L: usize>(
+        &self,
+        mut layouter: impl Layouter<F>,
+        message: [AssignedCell<F, F>; L],
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let chip = Pow5Chip::construct(self.config.poseidon.clone());
+        let hasher =
+            Hash::<F, _, P128Pow5T3, ConstantLength<L>, POSEIDON_WIDTH, POSEIDON_RATE>::init(
+                chip,
+                layouter.namespace(|| "init"),
+            )?;
+        hasher.hash(layouter.namespace(|| "hash"), message)
+    }
+
+    /// Hash every assigned step into the chain and return the final link
+    ///
+    /// # Arguments
+    ///
+    /// * `seed` - First link of the chain (the state commitment)
+    /// * `steps` - Cells from `EvmChip::assign_steps`, in trace order
+    ///
+    /// # Returns
+    ///
+    /// Cell holding the trace commitment (the seed for an empty trace)
+    pub fn hash_steps(
+        &self,
+        mut layouter: impl Layouter<F>,
+        seed: AssignedCell<F, F>,
+        steps: &[AssignedStep<F>],
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let mut link = seed;
+
+        for (i, step) in steps.iter().enumerate() {
+            let message = [
+                link,
+                step.opcode.clone(),
+                step.pc.clone(),
+                step.gas.clone(),
+                step.stack_depth.clone(),
+                step.memory_size.clone(),
+                step.stack[0].lo.clone(),
+                step.stack[0].hi.clone(),
+                step.stack[1].lo.clone(),
+                step.stack[1].hi.clone(),
+                step.stack[2].lo.clone(),
+                step.stack[2].hi.clone(),
+            ];
+            link = self.hash(layouter.namespace(|| format!("link {}", i)), message)?;
+        }
+
+        Ok(link)
+    }
+}
@@ -0,0 +1,804 @@
+//! Keccak-256 over byte cells
+//!
+//! Lanes are held in sparse form: bit `i` of a 64-bit lane becomes base-8 digit
+//! `i`, so the XOR of a few lanes is their sum followed by a parity
+//! normalization. Every step of Keccak-f[1600] is a lane row: a linear
+//! combination of earlier lanes, split into chunks of at most
+//! `KECCAK_CHUNK_DIGITS` digits that are mapped digit by digit through the
+//! chunk table and recombined, rotated, into the output lane. Theta, rho with
+//! pi, and chi each take one row per lane; iota adds the round constant to the
+//! next row reading lane `(0, 0)`. Bytes enter and leave the sparse form
+//! through the sparse byte table.
+//!
+//! Rows only meet through copy constraints, so several can share a circuit
+//! row in side-by-side columns: `KECCAK_ROUND_LANES` of them per circuit row
+//! lay out one round per row.
+
+use halo2_proofs::{
+    arithmetic::Field,
+    circuit::{AssignedCell, Layouter, Region, Value},
+    pasta::group::ff::PrimeField,
+    plonk::{Advice, Column, ConstraintSystem, Error, Expression, Fixed, Selector},
+    poly::Rotation,
+};
+use std::marker::PhantomData;
+
+use crate::chips::evm_chip::u64_to_field;
+use crate::chips::tables::KeccakTableConfig;
+use crate::chips::word_chip::{assign_value, limb_value};
+
+/// Bytes absorbed per permutation
+pub const KECCAK_RATE: usize = 136;
+
+/// Rounds of Keccak-f[1600]
+pub const KECCAK_ROUNDS: usize = 24;
+
+/// Base-8 digits of the largest chunk in the chunk table
+pub const KECCAK_CHUNK_DIGITS: usize = 4;
+
+/// Chunks of a lane row: 16 full ones and the part a rotation splits off
+const CHUNKS: usize = 64 / KECCAK_CHUNK_DIGITS + 1;
+
+/// Lanes combined by a lane row (the five lanes of a theta column sum)
+const TERMS: usize = 5;
+
+/// Iota round constants
+const ROUND_CONSTANTS: [u64; KECCAK_ROUNDS] = [
+    0x0000000000000001,
+    0x0000000000008082,
+    0x800000000000808A,
+    0x8000000080008000,
+    0x000000000000808B,
+    0x0000000080000001,
+    0x8000000080008081,
+    0x8000000000008009,
+    0x000000000000008A,
+    0x0000000000000088,
+    0x0000000080008009,
+    0x000000008000000A,
+    0x000000008000808B,
+    0x800000000000008B,
+    0x8000000000008089,
+    0x8000000000008003,
+    0x8000000000008002,
+    0x8000000000000080,
+    0x000000000000800A,
+    0x800000008000000A,
+    0x8000000080008081,
+    0x8000000000008080,
+    0x0000000080000001,
+    0x8000000080008008,
+];
+
+/// Rho rotation of lane `(x, y)`, indexed `[x][y]`
+const ROTATIONS: [[u32; 5]; 5] = [
+    [0, 36, 3, 41, 18],
+    [1, 44, 10, 45, 2],
+    [62, 6, 43, 15, 61],
+    [28, 55, 25, 21, 56],
+    [27, 20, 39, 8, 14],
+];
+
+/// Digit-wise functions of the chunk table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeccakFunction {
+    /// Parity of a digit: the XOR of the bits summed into it
+    Normalize,
+    /// `a ^ (!b & c)` of the digit `4a + 2b + c`
+    Chi,
+}
+
+impl KeccakFunction {
+    /// Every function, in table order
+    pub const ALL: [KeccakFunction; 2] = [KeccakFunction::Normalize, KeccakFunction::Chi];
+
+    /// Table tag of the function (disabled rows hit the zero row of `Normalize`)
+    pub fn tag(&self) -> u64 {
+        match self {
+            KeccakFunction::Normalize => 0,
+            KeccakFunction::Chi => 1,
+        }
+    }
+
+    /// Apply the function to one base-8 digit
+    pub fn apply(&self, digit: u64) -> u64 {
+        match self {
+            KeccakFunction::Normalize => digit & 1,
+            KeccakFunction::Chi => (digit >> 2 & 1) ^ (!(digit >> 1) & digit & 1),
+        }
+    }
+}
+
+/// Sparse form of a lane: bit `i` becomes base-8 digit `i`
+pub fn sparse_lane<F: PrimeField>(bits: u64) -> F {
+    (0..64)
+        .rev()
+        .fold(F::ZERO, |acc, i| acc * F::from(8) + F::from(bits >> i & 1))
+}
+
+/// Lane rows of one Keccak-f[1600] round: theta, rho with pi, and chi
+pub const KECCAK_ROUND_LANES: usize = 5 + 25 + 25;
+
+/// Columns of one lane or byte row
+#[derive(Clone, Debug)]
+pub struct KeccakLaneColumns {
+    /// Lanes combined by a lane row
+    pub terms: [Column<Advice>; TERMS],
+    /// Chunks of the combination; the bytes of a byte row
+    pub input: [Column<Advice>; CHUNKS],
+    /// Chunks mapped through the function; the sparse bytes of a byte row
+    pub output: [Column<Advice>; CHUNKS],
+    /// Output lane; the lane of a byte row
+    pub lane: Column<Advice>,
+    /// Second output lane, rotated differently (theta's `rot(c, 1)`)
+    pub lane_b: Column<Advice>,
+    /// Coefficient of every term
+    pub coefficients: [Column<Fixed>; TERMS],
+    /// Sparse constant added to the combination (iota)
+    pub constant: Column<Fixed>,
+    /// Function tag of the row
+    pub function: Column<Fixed>,
+    /// Digits of every chunk
+    pub sizes: [Column<Fixed>; CHUNKS],
+    /// Weight of every chunk in the combination
+    pub weights: [Column<Fixed>; CHUNKS],
+    /// Weight of every mapped chunk in the output lane
+    pub weights_a: [Column<Fixed>; CHUNKS],
+    /// Weight of every mapped chunk in the second output lane
+    pub weights_b: [Column<Fixed>; CHUNKS],
+    /// Enabled on lane rows
+    pub s_lane: Selector,
+    /// Enabled on byte rows
+    pub s_bytes: Selector,
+}
+
+/// Configuration for the KeccakChip
+#[derive(Clone, Debug)]
+pub struct KeccakChipConfig {
+    /// Side-by-side columns of the lane and byte rows sharing a circuit row
+    pub slots: Vec<KeccakLaneColumns>,
+    /// Chunk and sparse byte tables
+    pub table: KeccakTableConfig,
+}
+
+/// Lane cell with the bits it holds in sparse form
+#[derive(Clone, Debug)]
+struct Lane<F: Field> {
+    cell: AssignedCell<F, F>,
+    bits: Value<u64>,
+}
+
+/// Lane of a byte row and its byte cells
+type LaneBytes<F> = (Lane<F>, Vec<AssignedCell<F, F>>);
+
+/// Chip computing Keccak-256 digests of byte cells
+pub struct KeccakChip<F: PrimeField> {
+    config: KeccakChipConfig,
+    _marker: PhantomData<F>,
+}
+
+impl<F: PrimeField> KeccakChip<F> {
+    /// Construct a new KeccakChip
+    pub fn construct(config: KeccakChipConfig) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Configure the lane and byte rows with their lookups, one per row
+    pub fn configure(meta: &mut ConstraintSystem<F>) -> KeccakChipConfig {
+        Self::configure_with_lanes(meta, 1)
+    }
+
+    /// Configure `lanes_per_row` lane or byte rows side by side per circuit
+    /// row
+    ///
+    /// Rows only meet through copy constraints, so they can be laid out in
+    /// any shape: a permutation takes `KECCAK_ROUNDS * KECCAK_ROUND_LANES`
+    /// lane rows, and `r * KECCAK_ROUND_LANES` lanes per row fit `r` rounds
+    /// in every circuit row. Wider rows trade columns and lookups for fewer
+    /// rows.
+    ///
+    /// A lane row constrains `sum(coefficient * term) + constant =
+    /// sum(weight * input)`, `lane = sum(weight_a * output)` and `lane_b =
+    /// sum(weight_b * output)`, and looks up every `(function, size, input,
+    /// output)` chunk. A byte row constrains `lane = sum(8^(8k) * output_k)`
+    /// and looks up every `(input_k, output_k)` byte with its sparse form.
+    pub fn configure_with_lanes(
+        meta: &mut ConstraintSystem<F>,
+        lanes_per_row: usize,
+    ) -> KeccakChipConfig {
+        let constants = meta.fixed_column();
+        meta.enable_constant(constants);
+        let table = KeccakTableConfig::configure(meta);
+        let slots = (0..lanes_per_row.max(1))
+            .map(|_| Self::configure_slot(meta, &table))
+            .collect();
+        KeccakChipConfig { slots, table }
+    }
+
+    /// Configure the columns, gates and lookups of one lane or byte row
+    fn configure_slot(
+        meta: &mut ConstraintSystem<F>,
+        table: &KeccakTableConfig,
+    ) -> KeccakLaneColumns {
+        let terms = [(); TERMS].map(|_| meta.advice_column());
+        let input = [(); CHUNKS].map(|_| meta.advice_column());
+        let output = [(); CHUNKS].map(|_| meta.advice_column());
+        let lane = meta.advice_column();
+        let lane_b = meta.advice_column();
+        for column in terms.iter().chain(&input[..8]).chain([&lane, &lane_b]) {
+            meta.enable_equality(*column);
+        }
+        let coefficients = [(); TERMS].map(|_| meta.fixed_column());
+        let constant = meta.fixed_column();
+        let function = meta.fixed_column();
+        let sizes = [(); CHUNKS].map(|_| meta.fixed_column());
+        let weights = [(); CHUNKS].map(|_| meta.fixed_column());
+        let weights_a = [(); CHUNKS].map(|_| meta.fixed_column());
+        let weights_b = [(); CHUNKS].map(|_| meta.fixed_column());
+
+        let s_lane = meta.complex_selector();
+        let s_bytes = meta.complex_selector();
+
+        meta.create_gate("keccak lane", |meta| {
+            let s = meta.query_selector(s_lane);
+            let mut combination = meta.query_fixed(constant);
+            for (term, coefficient) in terms.iter().zip(coefficients) {
+                combination = combination
+                    + meta.query_fixed(coefficient) * meta.query_advice(*term, Rotation::cur());
+            }
+            let mut chunks = Expression::Constant(F::ZERO);
+            let mut lane_a = Expression::Constant(F::ZERO);
+            let mut lane_b_sum = Expression::Constant(F::ZERO);
+            for j in 0..CHUNKS {
+                let chunk = meta.query_advice(input[j], Rotation::cur());
+                let mapped = meta.query_advice(output[j], Rotation::cur());
+                chunks = chunks + meta.query_fixed(weights[j]) * chunk;
+                lane_a = lane_a + meta.query_fixed(weights_a[j]) * mapped.clone();
+                lane_b_sum = lane_b_sum + meta.query_fixed(weights_b[j]) * mapped;
+            }
+            vec![
+                s.clone() * (combination - chunks),
+                s.clone() * (meta.query_advice(lane, Rotation::cur()) - lane_a),
+                s * (meta.query_advice(lane_b, Rotation::cur()) - lane_b_sum),
+            ]
+        });
+
+        meta.create_gate("keccak bytes", |meta| {
+            let s = meta.query_selector(s_bytes);
+            let sum = (0..8).rev().fold(Expression::Constant(F::ZERO), |acc, k| {
+                acc * Expression::Constant(sparse_lane::<F>(1 << 8))
+                    + meta.query_advice(output[k], Rotation::cur())
+            });
+            vec![s * (meta.query_advice(lane, Rotation::cur()) - sum)]
+        });
+
+        for j in 0..CHUNKS {
+            meta.lookup(|meta| {
+                let s = meta.query_selector(s_lane);
+                vec![
+                    (s.clone() * meta.query_fixed(function), table.function),
+                    (s.clone() * meta.query_fixed(sizes[j]), table.size),
+                    (
+                        s.clone() * meta.query_advice(input[j], Rotation::cur()),
+                        table.input,
+                    ),
+                    (
+                        s * meta.query_advice(output[j], Rotation::cur()),
+                        table.output,
+                    ),
+                ]
+            });
+        }
+        for k in 0..8 {
+            meta.lookup(|meta| {
+                let s = meta.query_selector(s_bytes);
+                vec![
+                    (
+                        s.clone() * meta.query_advice(input[k], Rotation::cur()),
+                        table.byte,
+                    ),
+                    (
+                        s * meta.query_advice(output[k], Rotation::cur()),
+                        table.sparse,
+                    ),
+                ]
+            });
+        }
+
+        KeccakLaneColumns {
+            terms,
+            input,
+            output,
+            lane,
+            lane_b,
+            coefficients,
+            constant,
+            function,
+            sizes,
+            weights,
+            weights_a,
+            weights_b,
+            s_lane,
+            s_bytes,
+        }
+    }
+
+    /// Load the chunk and sparse byte tables
+    pub fn load_table(&self, layouter: &mut impl Layouter<F>) -> Result<(), Error> {
+        self.config.table.load(layouter)
+    }
+
+    /// Keccak-256 digest of a fixed-length message
+    ///
+    /// The padding is assigned as constants, so the message length is part of
+    /// the circuit's shape.
+    pub fn hash(
+        &self,
+        mut layouter: impl Layouter<F>,
+        message: &[AssignedCell<F, F>],
+    ) -> Result<[AssignedCell<F, F>; 32], Error> {
+        let padded_len = (message.len() / KECCAK_RATE + 1) * KECCAK_RATE;
+        let padding = layouter.assign_region(
+            || "keccak padding",
+            |mut region| {
+                (message.len()..padded_len)
+                    .enumerate()
+                    .map(|(row, position)| {
+                        let mut byte = 0u64;
+                        if position == message.len() {
+                            byte |= 0x01;
+                        }
+                        if position == padded_len - 1 {
+                            byte |= 0x80;
+                        }
+                        region.assign_advice_from_constant(
+                            || "padding",
+                            self.config.slots[0].input[0],
+                            row,
+                            u64_to_field::<F>(byte),
+                        )
+                    })
+                    .collect::<Result<Vec<_>, Error>>()
+            },
+        )?;
+        let padded: Vec<_> = message.iter().cloned().chain(padding).collect();
+        self.digests(layouter, &padded)?
+            .pop()
+            .ok_or(Error::Synthesis)
+    }
+
+    /// Digests of a padded message after each of its blocks
+    ///
+    /// The caller pads the message and picks the digest of its last block,
+    /// which lets the message length vary within a fixed number of blocks.
+    /// Every byte is range checked by the sparse byte lookup.
+    pub fn digests(
+        &self,
+        mut layouter: impl Layouter<F>,
+        padded: &[AssignedCell<F, F>],
+    ) -> Result<Vec<[AssignedCell<F, F>; 32]>, Error> {
+        if padded.is_empty() || !padded.len().is_multiple_of(KECCAK_RATE) {
+            return Err(Error::Synthesis);
+        }
+
+        let mut state: Vec<Option<Lane<F>>> = vec![None; 25];
+        let mut digests = Vec::with_capacity(padded.len() / KECCAK_RATE);
+        for (index, block) in padded.chunks(KECCAK_RATE).enumerate() {
+            let digest = layouter.assign_region(
+                || format!("keccak block {}", index),
+                |mut region| {
+                    let mut offset = 0;
+                    let mut absorbed = state.clone();
+                    for (i, bytes) in block.chunks(8).enumerate() {
+                        let (lane, _) = self.byte_row(&mut region, offset, Some(bytes), None)?;
+                        offset += 1;
+                        absorbed[i] = Some(match &absorbed[i] {
+                            None => lane,
+                            Some(previous) => {
+                                let (sum, _) = self.lane_row(
+                                    &mut region,
+                                    offset,
+                                    KeccakFunction::Normalize,
+                                    &[(1, Some(previous)), (1, Some(&lane))],
+                                    0,
+                                    (0, None),
+                                )?;
+                                offset += 1;
+                                sum
+                            }
+                        });
+                    }
+
+                    let mut lanes = absorbed;
+                    self.permute(&mut region, &mut offset, &mut lanes)?;
+
+                    let mut digest = Vec::with_capacity(32);
+                    for lane in &lanes[..4] {
+                        let (_, bytes) = self.byte_row(&mut region, offset, None, lane.as_ref())?;
+                        offset += 1;
+                        digest.extend(bytes);
+                    }
+                    let digest: [AssignedCell<F, F>; 32] =
+                        digest.try_into().map_err(|_| Error::Synthesis)?;
+                    Ok((digest, lanes))
+                },
+            )?;
+            digests.push(digest.0);
+            state = digest.1;
+        }
+        Ok(digests)
+    }
+
+    /// Apply Keccak-f[1600] to the lanes, leaving every lane normalized
+    fn permute(
+        &self,
+        region: &mut Region<'_, F>,
+        offset: &mut usize,
+        lanes: &mut [Option<Lane<F>>],
+    ) -> Result<(), Error> {
+        // Round constant not yet added to lane (0, 0)
+        let mut pending = 0u64;
+        for round_constant in ROUND_CONSTANTS {
+            // Theta: column parities c and rot(c, 1)
+            let mut parities = Vec::with_capacity(5);
+            for x in 0..5 {
+                let terms: Vec<_> = (0..5).map(|y| (1, lanes[x + 5 * y].as_ref())).collect();
+                let constant = if x == 0 { pending } else { 0 };
+                let (c, rotated) = self.lane_row(
+                    region,
+                    *offset,
+                    KeccakFunction::Normalize,
+                    &terms,
+                    constant,
+                    (0, Some(1)),
+                )?;
+                *offset += 1;
+                parities.push((c, rotated.ok_or(Error::Synthesis)?));
+            }
+
+            // Rho and pi: B[y][2x + 3y] = rot(A[x][y] ^ D[x], r[x][y])
+            let mut moved: Vec<Option<Lane<F>>> = vec![None; 25];
+            for x in 0..5 {
+                for y in 0..5 {
+                    let terms = [
+                        (1, lanes[x + 5 * y].as_ref()),
+                        (1, Some(&parities[(x + 4) % 5].0)),
+                        (1, Some(&parities[(x + 1) % 5].1)),
+                    ];
+                    let constant = if x == 0 && y == 0 { pending } else { 0 };
+                    let (lane, _) = self.lane_row(
+                        region,
+                        *offset,
+                        KeccakFunction::Normalize,
+                        &terms,
+                        constant,
+                        (ROTATIONS[x][y], None),
+                    )?;
+                    *offset += 1;
+                    moved[y + 5 * ((2 * x + 3 * y) % 5)] = Some(lane);
+                }
+            }
+
+            // Chi: A[x][y] = B[x][y] ^ (!B[x + 1][y] & B[x + 2][y])
+            for x in 0..5 {
+                for y in 0..5 {
+                    let terms = [
+                        (4, moved[x + 5 * y].as_ref()),
+                        (2, moved[(x + 1) % 5 + 5 * y].as_ref()),
+                        (1, moved[(x + 2) % 5 + 5 * y].as_ref()),
+                    ];
+                    let (lane, _) =
+                        self.lane_row(region, *offset, KeccakFunction::Chi, &terms, 0, (0, None))?;
+                    *offset += 1;
+                    lanes[x + 5 * y] = Some(lane);
+                }
+            }
+
+            // Iota is folded into the next row reading lane (0, 0)
+            pending = round_constant;
+        }
+
+        let (lane, _) = self.lane_row(
+            region,
+            *offset,
+            KeccakFunction::Normalize,
+            &[(1, lanes[0].as_ref())],
+            pending,
+            (0, None),
+        )?;
+        *offset += 1;
+        lanes[0] = Some(lane);
+        Ok(())
+    }
+
+    /// Circuit row and columns of the lane or byte row at `position`
+    fn place(&self, position: usize) -> (usize, &KeccakLaneColumns) {
+        let slots = &self.config.slots;
+        (position / slots.len(), &slots[position % slots.len()])
+    }
+
+    /// Assign a lane row and return its output lanes
+    ///
+    /// `terms` are `(coefficient, lane)` pairs, with `None` for a lane known
+    /// to be zero; the output lanes are rotated left by `rotations`.
+    fn lane_row(
+        &self,
+        region: &mut Region<'_, F>,
+        position: usize,
+        function: KeccakFunction,
+        terms: &[(u64, Option<&Lane<F>>)],
+        constant: u64,
+        rotations: (u32, Option<u32>),
+    ) -> Result<(Lane<F>, Option<Lane<F>>), Error> {
+        let (offset, config) = self.place(position);
+        config.s_lane.enable(region, offset)?;
+        let fixed = |region: &mut Region<'_, F>, column, value: F| {
+            region.assign_fixed(|| "keccak fixed", column, offset, || Value::known(value))
+        };
+        fixed(region, config.function, u64_to_field(function.tag()))?;
+        fixed(region, config.constant, sparse_lane(constant))?;
+
+        // Digits of the combination
+        let mut digits = Value::known(std::array::from_fn::<u64, 64, _>(|i| constant >> i & 1));
+        for k in 0..TERMS {
+            let (coefficient, lane) = terms.get(k).copied().unwrap_or((0, None));
+            match lane {
+                Some(lane) => {
+                    fixed(region, config.coefficients[k], u64_to_field(coefficient))?;
+                    lane.cell
+                        .copy_advice(|| "term", region, config.terms[k], offset)?;
+                    digits = digits.zip(lane.bits).map(|(mut digits, bits)| {
+                        for (i, digit) in digits.iter_mut().enumerate() {
+                            *digit += coefficient * (bits >> i & 1);
+                        }
+                        digits
+                    });
+                }
+                None => {
+                    fixed(region, config.coefficients[k], F::ZERO)?;
+                    assign_value(region, config.terms[k], offset, Value::known(F::ZERO))?;
+                }
+            }
+        }
+
+        let layout = chunk_layout(rotations);
+        let powers: Vec<F> = (0..64)
+            .map(|i| F::from(8).pow_vartime([i as u64]))
+            .collect();
+        for j in 0..CHUNKS {
+            let (start, size) = layout.get(j).copied().unwrap_or((0, 0));
+            let weight = |rotation: u32| match size {
+                0 => F::ZERO,
+                _ => powers[(start + rotation as usize) % 64],
+            };
+            fixed(region, config.sizes[j], u64_to_field(size as u64))?;
+            fixed(region, config.weights[j], weight(0))?;
+            fixed(region, config.weights_a[j], weight(rotations.0))?;
+            fixed(
+                region,
+                config.weights_b[j],
+                rotations.1.map_or(F::ZERO, weight),
+            )?;
+            let chunk = |map: &dyn Fn(u64) -> u64| {
+                digits.map(|digits| {
+                    (0..size)
+                        .rev()
+                        .fold(0u64, |acc, i| acc * 8 + map(digits[start + i]))
+                })
+            };
+            assign_value(
+                region,
+                config.input[j],
+                offset,
+                chunk(&|d| d).map(u64_to_field),
+            )?;
+            assign_value(
+                region,
+                config.output[j],
+                offset,
+                chunk(&|d| function.apply(d)).map(u64_to_field),
+            )?;
+        }
+
+        let bits =
+            digits.map(|digits| (0..64).fold(0u64, |acc, i| acc | function.apply(digits[i]) << i));
+        let lane = |rotation: u32| bits.map(|bits| bits.rotate_left(rotation));
+        let bits_a = lane(rotations.0);
+        let lane_a = Lane {
+            cell: assign_value(region, config.lane, offset, bits_a.map(sparse_lane))?,
+            bits: bits_a,
+        };
+        let lane_b = match rotations.1 {
+            Some(rotation) => {
+                let bits_b = lane(rotation);
+                Some(Lane {
+                    cell: assign_value(region, config.lane_b, offset, bits_b.map(sparse_lane))?,
+                    bits: bits_b,
+                })
+            }
+            None => {
+                assign_value(region, config.lane_b, offset, Value::known(F::ZERO))?;
+                None
+            }
+        };
+        Ok((lane_a, lane_b))
+    }
+
+    /// Assign a byte row converting eight little-endian bytes to or from a lane
+    ///
+    /// Copies `bytes` in and returns their lane, or copies `lane` in and
+    /// returns its bytes.
+    fn byte_row(
+        &self,
+        region: &mut Region<'_, F>,
+        position: usize,
+        bytes: Option<&[AssignedCell<F, F>]>,
+        lane: Option<&Lane<F>>,
+    ) -> Result<LaneBytes<F>, Error> {
+        let (offset, config) = self.place(position);
+        config.s_bytes.enable(region, offset)?;
+
+        let bits = match (bytes, lane) {
+            (Some(bytes), _) => bytes.iter().rev().fold(Value::known(0u64), |acc, byte| {
+                acc.zip(byte.value())
+                    .map(|(acc, byte)| acc << 8 | limb_value(*byte) as u64)
+            }),
+            (None, Some(lane)) => lane.bits,
+            (None, None) => return Err(Error::Synthesis),
+        };
+
+        let mut cells = Vec::with_capacity(8);
+        for k in 0..8 {
+            let byte = bits.map(|bits| bits >> (8 * k) & 0xff);
+            cells.push(match bytes {
+                Some(bytes) => bytes[k].copy_advice(|| "byte", region, config.input[k], offset)?,
+                None => assign_value(region, config.input[k], offset, byte.map(u64_to_field))?,
+            });
+            assign_value(region, config.output[k], offset, byte.map(sparse_lane))?;
+        }
+        for j in 8..CHUNKS {
+            assign_value(region, config.input[j], offset, Value::known(F::ZERO))?;
+            assign_value(region, config.output[j], offset, Value::known(F::ZERO))?;
+        }
+
+        let cell = match lane {
+            Some(lane) => lane
+                .cell
+                .copy_advice(|| "lane", region, config.lane, offset)?,
+            None => assign_value(region, config.lane, offset, bits.map(sparse_lane))?,
+        };
+        Ok((Lane { cell, bits }, cells))
+    }
+}
+
+/// Chunks `(start digit, size)` of a lane row: cut every `KECCAK_CHUNK_DIGITS`
+/// digits and wherever an output rotation wraps, so that each chunk lands
+/// contiguously in every output lane
+fn chunk_layout(rotations: (u32, Option<u32>)) -> Vec<(usize, usize)> {
+    let mut cuts: Vec<usize> = (0..64).step_by(KECCAK_CHUNK_DIGITS).collect();
+    for rotation in [Some(rotations.0), rotations.1].into_iter().flatten() {
+        cuts.push((64 - rotation as usize) % 64);
+    }
+    cuts.sort_unstable();
+    cuts.dedup();
+    cuts.iter()
+        .zip(cuts.iter().skip(1).chain([&64]))
+        .map(|(&start, &end)| (start, end - start))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::keccak256;
+    use halo2_proofs::{
+        circuit::SimpleFloorPlanner,
+        dev::MockProver,
+        pasta::Fp,
+        plonk::{Circuit, Instance},
+    };
+
+    /// Circuit hashing a message into public digest bytes, with `LANES`
+    /// lane rows per circuit row
+    #[derive(Default)]
+    struct KeccakTestCircuit<const LANES: usize> {
+        message: Vec<u8>,
+    }
+
+    impl<const LANES: usize> Circuit<Fp> for KeccakTestCircuit<LANES> {
+        type Config = (KeccakChipConfig, Column<Advice>, Column<Instance>);
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self {
+                message: vec![0; self.message.len()],
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            let bytes = meta.advice_column();
+            meta.enable_equality(bytes);
+            let digest = meta.instance_column();
+            meta.enable_equality(digest);
+            (KeccakChip::configure_with_lanes(meta, LANES), bytes, digest)
+        }
+
+        fn synthesize(
+            &self,
+            (config, bytes, digest): Self::Config,
+            mut layouter: impl Layouter<Fp>,
+        ) -> Result<(), Error> {
+            let chip = KeccakChip::construct(config);
+            chip.load_table(&mut layouter)?;
+            let message = layouter.assign_region(
+                || "message",
+                |mut region| {
+                    self.message
+                        .iter()
+                        .enumerate()
+                        .map(|(row, byte)| {
+                            assign_value(
+                                &mut region,
+                                bytes,
+                                row,
+                                Value::known(Fp::from(*byte as u64)),
+                            )
+                        })
+                        .collect::<Result<Vec<_>, Error>>()
+                },
+            )?;
+            let cells = chip.hash(layouter.namespace(|| "keccak"), &message)?;
+            for (row, cell) in cells.iter().enumerate() {
+                layouter.constrain_instance(cell.cell(), digest, row)?;
+            }
+            Ok(())
+        }
+    }
+
+    fn run<const LANES: usize>(message: &[u8], digest: [u8; 32]) -> MockProver<Fp> {
+        let circuit = KeccakTestCircuit::<LANES> {
+            message: message.to_vec(),
+        };
+        let public_inputs = digest.iter().map(|byte| Fp::from(*byte as u64)).collect();
+        MockProver::run(14, &circuit, vec![public_inputs]).unwrap()
+    }
+
+    #[test]
+    fn test_keccak_functions() {
+        for digit in 0..8 {
+            let (a, b, c) = (digit >> 2, digit >> 1 & 1, digit & 1);
+            assert_eq!(KeccakFunction::Chi.apply(digit), a ^ ((1 - b) & c));
+            assert_eq!(KeccakFunction::Normalize.apply(digit), digit % 2);
+        }
+        assert_eq!(chunk_layout((0, None)).len(), 16);
+        assert_eq!(chunk_layout((0, Some(1)))[15], (60, 3));
+        assert_eq!(chunk_layout((36, None)).len(), 16);
+        assert_eq!(chunk_layout((3, None)).len(), 17);
+    }
+
+    #[test]
+    fn test_keccak_digests() {
+        for message in [&b""[..], b"abc", &[0xffu8; KECCAK_RATE]] {
+            let digest = keccak256(message).0;
+            assert_eq!(run::<1>(message, digest).verify(), Ok(()));
+        }
+
+        let mut wrong = keccak256(b"abc").0;
+        wrong[31] ^= 1;
+        assert!(run::<1>(b"abc", wrong).verify().is_err());
+    }
+
+    #[test]
+    fn test_keccak_lanes_per_row() {
+        // Eleven lanes per row lay out a round every five rows
+        let digest = keccak256(b"abc").0;
+        assert_eq!(run::<11>(b"abc", digest).verify(), Ok(()));
+
+        let mut wrong = digest;
+        wrong[0] ^= 1;
+        assert!(run::<11>(b"abc", wrong).verify().is_err());
+    }
+}
@@ -0,0 +1,378 @@
+//! Memory expansion chip for memory gas and MSIZE
+//!
+//! Memory grows in 32-byte words to cover every range an opcode touches, and
+//! growing it costs the difference of `memory_cost` after and before. For the
+//! opcodes in `OpCode::memory_gas_only` the chip reads the range from the
+//! stack, sets the next step's memory size to
+//! `max(size, ceil32(offset + length))` (the same size for an empty range) and
+//! the step's dynamic gas to the expansion cost plus the data gas of the
+//! range. RETURN and REVERT end their frame, so the size they grow memory to
+//! stays in the chip. Sizes are counted in words range checked to
+//! `MEMORY_WORD_BYTES` bytes, which also bounds the range of a non-empty
+//! access.
+
+use alloy_primitives::U256;
+use halo2_proofs::{
+    arithmetic::Field,
+    circuit::{AssignedCell, Layouter, Value},
+    pasta::group::ff::PrimeField,
+    plonk::{Advice, Column, ConstraintSystem, Error, Expression, Selector},
+    poly::Rotation,
+};
+use std::marker::PhantomData;
+
+use crate::chips::comparator_chip::{ComparatorChip, ComparatorChipConfig};
+use crate::chips::evm_chip::{
+    AssignedStep, MEMORY_QUAD_DIVISOR, MEMORY_WORD_BYTES, MEMORY_WORD_GAS, MemoryLength, OpCode,
+    u64_to_field,
+};
+use crate::chips::word_chip::{
+    AssignedWord, WordChip, WordChipConfig, assign_constant_word, assign_value,
+};
+
+/// Bytes the quotient of the squared word count is range checked to
+const QUAD_BYTES: usize = 8;
+
+/// Scale making the remainder of `words^2 / 512` span two whole bytes
+const QUAD_SCALE: u64 = 65536 / MEMORY_QUAD_DIVISOR;
+
+/// Configuration for the MemoryChip
+#[derive(Clone, Debug)]
+pub struct MemoryChipConfig {
+    /// Advice columns; the expansion gate documents its row layout
+    pub cells: [Column<Advice>; 5],
+    /// Memory growth and dynamic gas of an expanding step
+    pub s_expansion: Selector,
+    /// Range checks for the word counts and remainders
+    pub word_config: WordChipConfig,
+    /// IsZero gadget for the length of the range
+    pub comparator_config: ComparatorChipConfig,
+}
+
+/// A step whose memory expansion the chip prices
+#[derive(Clone, Debug)]
+pub struct MemoryStep<'a, F: Field> {
+    /// Cells of the step
+    pub step: &'a AssignedStep<F>,
+    /// Cells of the next step, whose memory size the expansion sets (none when
+    /// the step ends its frame)
+    pub next: Option<&'a AssignedStep<F>>,
+    /// Opcode of the step, one of `OpCode::memory_gas_only`
+    pub op: OpCode,
+    /// Stack words of the step
+    pub stack: [U256; 3],
+    /// Memory size in bytes before the step
+    pub size: u64,
+}
+
+/// Witness of one expansion, in words
+#[derive(Clone, Debug, Default)]
+struct Expansion {
+    words: u64,
+    end_words: u128,
+    end_rem: u128,
+    grows: bool,
+    gap: u128,
+    new_words: u128,
+    length_words: u128,
+    length_rem: u128,
+}
+
+impl Expansion {
+    /// `ceil(x / 32)` and `8 * (x + 31) - 256 * ceil(x / 32)`, wrapping for
+    /// values no step can reach
+    fn ceil_words(x: u128) -> (u128, u128) {
+        let x = x.wrapping_add(31);
+        (x / 32, (x % 32) * 8)
+    }
+
+    /// Expansion of `words` words of memory by the range `offset..offset + length`
+    fn of(words: u64, offset: u128, length: u128) -> Self {
+        let (length_words, length_rem) = Self::ceil_words(length);
+        if length == 0 {
+            return Self {
+                words,
+                new_words: words as u128,
+                length_words,
+                length_rem,
+                ..Self::default()
+            };
+        }
+
+        let (end_words, end_rem) = Self::ceil_words(offset.wrapping_add(length));
+        let grows = end_words >= words as u128;
+        Self {
+            words,
+            end_words,
+            end_rem,
+            grows,
+            gap: end_words.abs_diff(words as u128),
+            new_words: end_words.max(words as u128),
+            length_words,
+            length_rem,
+        }
+    }
+}
+
+/// Quotient and remainder of `128 * words^2 / 65536`
+fn split_quad(words: u128) -> (u128, u128) {
+    let scaled = (QUAD_SCALE as u128).wrapping_mul(words.wrapping_mul(words));
+    (scaled >> 16, scaled & 0xffff)
+}
+
+/// Chip constraining memory growth and its gas
+pub struct MemoryChip<F: PrimeField> {
+    config: MemoryChipConfig,
+    _marker: PhantomData<F>,
+}
+
+impl<F: PrimeField> MemoryChip<F> {
+    /// Construct a new MemoryChip
+    pub fn construct(config: MemoryChipConfig) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Configure the expansion gate
+    ///
+    /// # Arguments
+    ///
+    /// * `cells` - Advice columns, which may be shared with other word gadgets
+    /// * `word_config` - Range checks for the word counts
+    /// * `comparator_config` - IsZero gadget for the length
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        cells: [Column<Advice>; 5],
+        word_config: WordChipConfig,
+        comparator_config: ComparatorChipConfig,
+    ) -> MemoryChipConfig {
+        for column in cells {
+            meta.enable_equality(column);
+        }
+
+        let s_expansion = meta.selector();
+
+        // Rows:
+        //   [offset_lo,   offset_hi,    length_lo,  length_hi, z       ]
+        //   [size,        words,        end_words,  end_rem,   grows   ]
+        //   [new_size,    new_words,    gap,        quad,      quad_rem]
+        //   [dynamic_gas, length_words, length_rem, new_quad,  new_rem ]
+        //   [word_gas,    byte_gas,     ...                            ]
+        // where z flags an empty range, `x_words = ceil(x / 32)` with
+        // `8 * (x + 31) = 256 * x_words + x_rem` and
+        // `128 * words^2 = 65536 * quad + quad_rem`.
+        meta.create_gate("memory_expansion", |meta| {
+            let s = meta.query_selector(s_expansion);
+            let mut q = |column: usize, row: i32| meta.query_advice(cells[column], Rotation(row));
+            let [offset_lo, offset_hi, length_lo, length_hi, z] =
+                [q(0, 0), q(1, 0), q(2, 0), q(3, 0), q(4, 0)];
+            let [size, words, end_words, end_rem, grows] =
+                [q(0, 1), q(1, 1), q(2, 1), q(3, 1), q(4, 1)];
+            let [new_size, new_words, gap, quad, quad_rem] =
+                [q(0, 2), q(1, 2), q(2, 2), q(3, 2), q(4, 2)];
+            let [dynamic_gas, length_words, length_rem, new_quad, new_rem] =
+                [q(0, 3), q(1, 3), q(2, 3), q(3, 3), q(4, 3)];
+            let [word_gas, byte_gas] = [q(0, 4), q(1, 4)];
+
+            let constant = |value: u64| Expression::Constant(u64_to_field::<F>(value));
+            let nonempty = constant(1) - z;
+            let ceil_words = |x: Expression<F>, words: Expression<F>, rem: Expression<F>| {
+                constant(8) * (x + constant(31)) - constant(256) * words - rem
+            };
+            let quad_split = |words: Expression<F>, quad: Expression<F>, rem: Expression<F>| {
+                constant(QUAD_SCALE) * words.clone() * words - constant(65536) * quad - rem
+            };
+            let growth = end_words.clone() - words.clone();
+
+            vec![
+                // A non-empty range lies in the low limbs
+                s.clone() * nonempty.clone() * offset_hi,
+                s.clone() * nonempty.clone() * length_hi,
+                // ...and ends within end_words words
+                s.clone()
+                    * nonempty.clone()
+                    * ceil_words(offset_lo + length_lo.clone(), end_words.clone(), end_rem),
+                // The range is bigger exactly when grows is set, which makes
+                // new_words the larger word count
+                s.clone() * grows.clone() * (constant(1) - grows.clone()),
+                s.clone()
+                    * nonempty.clone()
+                    * (gap - (constant(2) * grows.clone() - constant(1)) * growth.clone()),
+                s.clone() * (new_words.clone() - words.clone() - nonempty * grows * growth),
+                s.clone() * (size - constant(32) * words.clone()),
+                s.clone() * (new_size - constant(32) * new_words.clone()),
+                s.clone() * quad_split(words.clone(), quad.clone(), quad_rem),
+                s.clone() * quad_split(new_words.clone(), new_quad.clone(), new_rem),
+                s.clone() * ceil_words(length_lo.clone(), length_words.clone(), length_rem),
+                s * (dynamic_gas
+                    - constant(MEMORY_WORD_GAS) * (new_words - words)
+                    - (new_quad - quad)
+                    - word_gas * length_words
+                    - byte_gas * length_lo),
+            ]
+        });
+
+        MemoryChipConfig {
+            cells,
+            s_expansion,
+            word_config,
+            comparator_config,
+        }
+    }
+
+    /// Constrain the memory growth and dynamic gas of an expanding step
+    pub fn assign(
+        &self,
+        mut layouter: impl Layouter<F>,
+        memory: &MemoryStep<'_, F>,
+    ) -> Result<(), Error> {
+        let range = memory.op.memory_ranges()[0];
+        let (offset, length) = range.bounds(&memory.stack).ok_or(Error::Synthesis)?;
+        let low = |word: U256| word.as_limbs()[0] as u128 | (word.as_limbs()[1] as u128) << 64;
+
+        let comparator = ComparatorChip::construct(self.config.comparator_config.clone());
+        let z = match range.length {
+            MemoryLength::Slot(slot) => Some(comparator.is_equal(
+                layouter.namespace(|| "length == 0"),
+                &memory.step.stack[slot],
+                None,
+            )?),
+            MemoryLength::Fixed(_) => None,
+        };
+
+        let expansion = Expansion::of(memory.size / 32, low(offset), low(length));
+        let (quad, quad_rem) = split_quad(expansion.words as u128);
+        let (new_quad, new_rem) = split_quad(expansion.new_words);
+        let (word_gas, byte_gas) = memory.op.data_gas();
+
+        let cells = self.config.cells;
+        let checked = layouter.assign_region(
+            || "memory expansion",
+            |mut region| {
+                self.config.s_expansion.enable(&mut region, 0)?;
+                let witnesses = [
+                    (1, 1, expansion.words as u128),
+                    (2, 1, expansion.end_words),
+                    (3, 1, expansion.end_rem),
+                    (4, 1, expansion.grows as u128),
+                    (1, 2, expansion.new_words),
+                    (2, 2, expansion.gap),
+                    (3, 2, quad),
+                    (4, 2, quad_rem),
+                    (1, 3, expansion.length_words),
+                    (2, 3, expansion.length_rem),
+                    (3, 3, new_quad),
+                    (4, 3, new_rem),
+                ];
+                let mut assigned = Vec::with_capacity(witnesses.len());
+                for (column, row, value) in witnesses {
+                    let value = Value::known(F::from_u128(value));
+                    assigned.push(assign_value(&mut region, cells[column], row, value)?);
+                }
+                let [
+                    words,
+                    end_words,
+                    end_rem,
+                    _,
+                    _,
+                    gap,
+                    quad,
+                    quad_rem,
+                    length_words,
+                    length_rem,
+                    new_quad,
+                    new_rem,
+                ]: [_; 12] = assigned.try_into().map_err(|_| Error::Synthesis)?;
+
+                let offset_word = &memory.step.stack[range.offset];
+                offset_word
+                    .lo
+                    .copy_advice(|| "offset_lo", &mut region, cells[0], 0)?;
+                offset_word
+                    .hi
+                    .copy_advice(|| "offset_hi", &mut region, cells[1], 0)?;
+                match (&z, range.length) {
+                    (Some(z), MemoryLength::Slot(slot)) => {
+                        let length_word = &memory.step.stack[slot];
+                        length_word
+                            .lo
+                            .copy_advice(|| "length_lo", &mut region, cells[2], 0)?;
+                        length_word
+                            .hi
+                            .copy_advice(|| "length_hi", &mut region, cells[3], 0)?;
+                        z.lo.copy_advice(|| "z", &mut region, cells[4], 0)?;
+                    }
+                    _ => {
+                        assign_constant_word(&mut region, [cells[2], cells[3]], 0, length)?;
+                        region.assign_advice_from_constant(|| "z", cells[4], 0, F::ZERO)?;
+                    }
+                }
+
+                memory
+                    .step
+                    .memory_size
+                    .copy_advice(|| "size", &mut region, cells[0], 1)?;
+                match memory.next {
+                    Some(next) => {
+                        next.memory_size
+                            .copy_advice(|| "new_size", &mut region, cells[0], 2)?;
+                    }
+                    None => {
+                        let new_size = Value::known(F::from_u128(32 * expansion.new_words));
+                        assign_value(&mut region, cells[0], 2, new_size)?;
+                    }
+                }
+                memory
+                    .step
+                    .dynamic_gas
+                    .copy_advice(|| "dynamic_gas", &mut region, cells[0], 3)?;
+
+                for (column, price) in [(0, word_gas), (1, byte_gas)] {
+                    region.assign_advice_from_constant(
+                        || "price",
+                        cells[column],
+                        4,
+                        u64_to_field::<F>(price),
+                    )?;
+                }
+
+                Ok([
+                    (words, MEMORY_WORD_BYTES),
+                    (end_words, MEMORY_WORD_BYTES),
+                    (end_rem, 1),
+                    (gap, MEMORY_WORD_BYTES),
+                    (quad, QUAD_BYTES),
+                    (quad_rem, 2),
+                    (length_words, MEMORY_WORD_BYTES),
+                    (length_rem, 1),
+                    (new_quad, QUAD_BYTES),
+                    (new_rem, 2),
+                ])
+            },
+        )?;
+
+        let word_chip = WordChip::construct(self.config.word_config.clone());
+        for (i, (cell, bytes)) in checked.iter().enumerate() {
+            word_chip.range_check(layouter.namespace(|| format!("range {}", i)), cell, *bytes)?;
+        }
+        Ok(())
+    }
+
+    /// Constrain the result of MSIZE to the memory size before the step
+    pub fn assign_size(
+        &self,
+        mut layouter: impl Layouter<F>,
+        size: &AssignedCell<F, F>,
+        result: &AssignedWord<F>,
+    ) -> Result<(), Error> {
+        layouter.assign_region(
+            || "msize",
+            |mut region| {
+                region.constrain_equal(size.cell(), result.lo.cell())?;
+                region.constrain_constant(result.hi.cell(), F::ZERO)
+            },
+        )
+    }
+}
@@ -3,8 +3,42 @@
 //! This module contains low-level Halo2 gadgets that implement
 //! cryptographic constraints for EVM opcodes and arithmetic operations.
 
+pub mod access_chip;
 pub mod add_chip;
+pub mod bitwise_chip;
+pub mod comparator_chip;
+pub mod div_chip;
 pub mod evm_chip;
+pub mod exp_chip;
+pub mod hash_chip;
+pub mod keccak_chip;
+pub mod memory_chip;
+pub mod mod_arith_chip;
+pub mod mpt_chip;
+pub mod mul_add_chip;
+pub mod sha3_chip;
+pub mod shift_chip;
+pub mod smt_chip;
+pub mod sstore_chip;
+pub mod tables;
+pub mod word_chip;
 
+pub use access_chip::*;
 pub use add_chip::*;
+pub use bitwise_chip::*;
+pub use comparator_chip::*;
+pub use div_chip::*;
 pub use evm_chip::*;
+pub use exp_chip::*;
+pub use hash_chip::*;
+pub use keccak_chip::*;
+pub use memory_chip::*;
+pub use mod_arith_chip::*;
+pub use mpt_chip::*;
+pub use mul_add_chip::*;
+pub use sha3_chip::*;
+pub use shift_chip::*;
+pub use smt_chip::*;
+pub use sstore_chip::*;
+pub use tables::*;
+pub use word_chip::*;
@@ -0,0 +1,285 @@
+//! Modular arithmetic chip for ADDMOD and MULMOD
+//!
+//! Both opcodes first reduce `a` modulo N, then form the exact 512-bit sum or
+//! product and divide it by N. Since the reduced `a` is below N, the quotient
+//! of that division always fits in a word. With N = 0 the reduced `a` is 0 and
+//! the division by zero yields 0, matching the EVM.
+
+use alloy_primitives::U256;
+use halo2_proofs::{circuit::Layouter, pasta::group::ff::PrimeField, plonk::Error};
+use std::marker::PhantomData;
+
+use crate::chips::div_chip::{DivChip, DivChipConfig, DivOp};
+use crate::chips::evm_chip::OpCode;
+use crate::chips::mul_add_chip::MulAddChip;
+use crate::chips::word_chip::{AssignedWord, assign_constant_word};
+
+/// Modular operation performed by an opcode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModOp {
+    /// `(a + b) mod N`
+    AddMod,
+    /// `(a * b) mod N`
+    MulMod,
+}
+
+impl ModOp {
+    /// The modular operation performed by an opcode, if any
+    pub fn from_opcode(op: OpCode) -> Option<Self> {
+        match op {
+            OpCode::AddMod => Some(ModOp::AddMod),
+            OpCode::MulMod => Some(ModOp::MulMod),
+            _ => None,
+        }
+    }
+}
+
+/// Configuration for the ModArithChip
+///
+/// No gates of its own: it chains the division and multiply-add gadgets.
+#[derive(Clone, Debug)]
+pub struct ModArithChipConfig {
+    /// Division gadget, which also carries the multiply-add configuration
+    pub div_config: DivChipConfig,
+}
+
+/// Chip constraining ADDMOD and MULMOD over word limbs
+pub struct ModArithChip<F: PrimeField> {
+    config: ModArithChipConfig,
+    _marker: PhantomData<F>,
+}
+
+impl<F: PrimeField> ModArithChip<F> {
+    /// Construct a new ModArithChip
+    pub fn construct(config: ModArithChipConfig) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Configure the chip on top of the division gadget
+    pub fn configure(div_config: DivChipConfig) -> ModArithChipConfig {
+        ModArithChipConfig { div_config }
+    }
+
+    /// Constrain `result = op(a, b) mod n`
+    ///
+    /// # Arguments
+    ///
+    /// * `op` - Modular operation to apply
+    /// * `a` - Top stack operand
+    /// * `b` - Second stack operand
+    /// * `n` - Third stack operand (modulus)
+    /// * `result` - Word claimed to hold the result
+    pub fn assign(
+        &self,
+        mut layouter: impl Layouter<F>,
+        op: ModOp,
+        a: &AssignedWord<F>,
+        b: &AssignedWord<F>,
+        n: &AssignedWord<F>,
+        result: &AssignedWord<F>,
+    ) -> Result<(), Error> {
+        let div_chip = DivChip::construct(self.config.div_config.clone());
+        let mul_add = MulAddChip::construct(self.config.div_config.mul_add_config.clone());
+
+        let reduced = div_chip.divide(layouter.namespace(|| "a mod n"), DivOp::Mod, a, None, n)?;
+
+        // 512-bit intermediate: reduced * b, or reduced * 1 + b
+        let [one, zero] = self.constant_words(layouter.namespace(|| "constants"))?;
+        let [lo, hi] = match op {
+            ModOp::AddMod => mul_add.mul_add(layouter.namespace(|| "a + b"), &reduced, &one, b)?,
+            ModOp::MulMod => mul_add.mul_add(layouter.namespace(|| "a * b"), &reduced, b, &zero)?,
+        };
+
+        let computed = div_chip.divide(
+            layouter.namespace(|| "mod n"),
+            DivOp::Mod,
+            &lo,
+            Some(&hi),
+            n,
+        )?;
+
+        layouter.assign_region(
+            || "modular result",
+            |mut region| {
+                region.constrain_equal(computed.lo.cell(), result.lo.cell())?;
+                region.constrain_equal(computed.hi.cell(), result.hi.cell())
+            },
+        )
+    }
+
+    /// The words 1 and 0, pinned to constants
+    fn constant_words(
+        &self,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<[AssignedWord<F>; 2], Error> {
+        let cells = self.config.div_config.cells;
+        layouter.assign_region(
+            || "constant words",
+            |mut region| {
+                Ok([
+                    assign_constant_word(&mut region, [cells[0], cells[1]], 0, U256::from(1u64))?,
+                    assign_constant_word(&mut region, [cells[2], cells[3]], 0, U256::ZERO)?,
+                ])
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chips::comparator_chip::ComparatorChip;
+    use crate::chips::tables::ByteTableConfig;
+    use crate::chips::word_chip::assign_value;
+    use crate::chips::word_chip::{WordChip, word_limbs};
+    use alloy_primitives::U512;
+    use halo2_proofs::{
+        circuit::{SimpleFloorPlanner, Value},
+        dev::MockProver,
+        pasta::Fp,
+        plonk::{Advice, Circuit, Column, ConstraintSystem},
+    };
+
+    /// Test circuit checking `result = op(a, b) mod n`
+    struct ModCircuit {
+        op: ModOp,
+        inputs: [U256; 3],
+        result: U256,
+    }
+
+    impl Circuit<Fp> for ModCircuit {
+        type Config = (ModArithChipConfig, Column<Advice>);
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self {
+                op: self.op,
+                inputs: [U256::ZERO; 3],
+                result: U256::ZERO,
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            let constants = meta.fixed_column();
+            meta.enable_constant(constants);
+            let byte_table = ByteTableConfig::configure(meta);
+            let word_config = WordChip::configure(meta, byte_table);
+            let words = meta.advice_column();
+            meta.enable_equality(words);
+
+            let cells = [(); 5].map(|_| meta.advice_column());
+            let comparator_config = ComparatorChip::configure(meta, cells, word_config.clone());
+            let mul_add_config = MulAddChip::configure(meta, cells, word_config);
+            let div_config = DivChip::configure(meta, cells, comparator_config, mul_add_config);
+            (ModArithChip::<Fp>::configure(div_config), words)
+        }
+
+        fn synthesize(
+            &self,
+            (config, column): Self::Config,
+            mut layouter: impl Layouter<Fp>,
+        ) -> Result<(), Error> {
+            let word_config = config.div_config.word_config.clone();
+            word_config.byte_table.load(&mut layouter)?;
+
+            let words = layouter.assign_region(
+                || "words",
+                |mut region| {
+                    let values = self.inputs.iter().chain([&self.result]);
+                    let mut words = Vec::with_capacity(4);
+                    for (i, word) in values.enumerate() {
+                        let [lo, hi] = word_limbs::<Fp>(*word);
+                        let lo = assign_value(&mut region, column, 2 * i, Value::known(lo))?;
+                        let hi = assign_value(&mut region, column, 2 * i + 1, Value::known(hi))?;
+                        words.push(AssignedWord { lo, hi });
+                    }
+                    Ok(words)
+                },
+            )?;
+
+            let word_chip = WordChip::construct(word_config);
+            for word in &words {
+                word_chip.range_check_word(layouter.namespace(|| "word"), word)?;
+            }
+            let [a, b, n, result] = [&words[0], &words[1], &words[2], &words[3]];
+            ModArithChip::construct(config).assign(layouter, self.op, a, b, n, result)
+        }
+    }
+
+    fn run(op: ModOp, inputs: [U256; 3], result: U256) -> MockProver<Fp> {
+        let circuit = ModCircuit { op, inputs, result };
+        MockProver::run(12, &circuit, vec![]).unwrap()
+    }
+
+    /// Native EVM semantics, with the sum or product taken over 512 bits
+    fn expected(op: ModOp, [a, b, n]: [U256; 3]) -> U256 {
+        if n.is_zero() {
+            return U256::ZERO;
+        }
+        let (a, b, n) = (U512::from(a), U512::from(b), U512::from(n));
+        let value: U512 = match op {
+            ModOp::AddMod => a + b,
+            ModOp::MulMod => a * b,
+        };
+        (value % n).to::<U256>()
+    }
+
+    fn samples() -> Vec<U256> {
+        vec![
+            U256::ZERO,
+            U256::from(10u64),
+            U256::from(u128::MAX),
+            U256::MAX,
+        ]
+    }
+
+    #[test]
+    fn test_modular_arithmetic() {
+        for op in [ModOp::AddMod, ModOp::MulMod] {
+            for a in samples() {
+                for b in samples() {
+                    for n in [U256::ZERO, U256::from(1u64), U256::from(7u64), U256::MAX] {
+                        let inputs = [a, b, n];
+                        run(op, inputs, expected(op, inputs)).assert_satisfied();
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_wrong_result_fails() {
+        let max = U256::MAX;
+        let cases = [
+            // The sum and product wrap modulo 2^256 before the reduction
+            (
+                ModOp::AddMod,
+                [max, max, U256::from(7u64)],
+                (max + max) % U256::from(7u64),
+            ),
+            (
+                ModOp::MulMod,
+                [max, max, U256::from(10u64)],
+                (max * max) % U256::from(10u64),
+            ),
+            // N = 0 returns 0
+            (
+                ModOp::MulMod,
+                [U256::from(3u64), U256::from(5u64), U256::ZERO],
+                U256::from(15u64),
+            ),
+        ];
+        for (op, inputs, result) in cases {
+            assert_ne!(expected(op, inputs), result);
+            assert!(
+                run(op, inputs, result).verify().is_err(),
+                "{:?}{:?}",
+                op,
+                inputs
+            );
+        }
+    }
+}
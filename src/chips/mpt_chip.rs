@@ -0,0 +1,991 @@
+//! Merkle-Patricia trie proof chip
+//!
+//! Proves that a chain of trie nodes opens a hashed key under a root, as
+//! `utils::mpt::verify_proof` does natively for inclusion proofs. Every node
+//! is laid out one byte per row, followed by its Keccak padding up to whole
+//! blocks, and walked item by item:
+//!
+//! - the list header fixes the node length, and every item is an RLP string
+//!   whose prefix is looked up in the MPT table;
+//! - a branch (17 items) selects the 32-byte child reference at item
+//!   `nibble + 1` and appends the nibble to the path;
+//! - an extension or leaf (2 items) appends its hex-prefix path, and an
+//!   extension selects its child reference at item 2;
+//! - the selected reference must be the Keccak digest of the next node.
+//!
+//! The path nibbles are accumulated into a field element that must equal
+//! the key once the leaf is reached at depth 64. Both are Keccak digests, so
+//! two distinct ones agreeing modulo the field would need a Keccak collision
+//! of that form. The leaf then yields the account's storage root, end-aligned
+//! in its value, or the slot value. Nodes shorter than 32 bytes, which the
+//! trie embeds in their parent instead of hashing, are not supported.
+
+use alloy_primitives::Bytes;
+use halo2_proofs::{
+    circuit::{AssignedCell, Layouter, Region, Value},
+    pasta::group::ff::PrimeField,
+    plonk::{Advice, Column, ConstraintSystem, Error, Expression, Fixed, Selector, VirtualCells},
+    poly::Rotation,
+};
+use std::marker::PhantomData;
+
+use crate::chips::evm_chip::u64_to_field;
+use crate::chips::keccak_chip::{KECCAK_RATE, KeccakChip, KeccakChipConfig};
+use crate::chips::tables::MptTableConfig;
+use crate::chips::word_chip::{AssignedWord, LIMB_BYTES, assign_value};
+use crate::utils::mpt::rlp_list;
+
+/// Rows before a node: a child reference's bytes look back to its prefix
+pub const MPT_PREFIX_ROWS: usize = 33;
+
+/// Rows after a node: an account leaf's storage root is found from the end
+pub const MPT_POSTFIX_ROWS: usize = 66;
+
+/// Depth of every leaf, in nibbles
+pub const MPT_KEY_NIBBLES: u64 = 64;
+
+// Columns of a node row
+const BYTE: usize = 0;
+// Row is within the node
+const IN: usize = 1;
+// Row is in the list header
+const HDR: usize = 2;
+// Bytes of the current item after this row, and its inverse
+const REM: usize = 3;
+const REM_INV: usize = 4;
+// Row starts an item, with the kind and extra bytes of its prefix
+const START: usize = 5;
+const KIND: usize = 6;
+const EXTRA: usize = 7;
+// Items started so far
+const IDX: usize = 8;
+// Row starts the selected child reference; selections so far
+const SEL: usize = 9;
+const COUNT: usize = 10;
+// Row is in the path item of an extension or leaf; holds path bytes; holds
+// the hex-prefix byte
+const ITEM0: usize = 11;
+const CONTENT: usize = 12;
+const FIRST: usize = 13;
+// Nibbles of the byte, and the odd-length flag of the hex-prefix byte
+const HI: usize = 14;
+const LO: usize = 15;
+const ODD: usize = 16;
+// Path so far and its length in nibbles
+const ACC: usize = 17;
+const DEPTH: usize = 18;
+// Child reference (or storage root) so far, as 16-byte limbs
+const REF_HI: usize = 19;
+const REF_LO: usize = 20;
+// Row is in a storage leaf's value item; holds a value byte
+const ITEM1: usize = 21;
+const VALUE: usize = 22;
+// Value so far, as 16-byte limbs
+const VAL_HI: usize = 23;
+const VAL_LO: usize = 24;
+// Byte with the Keccak padding added; last block flag at a block's end
+const PADDED: usize = 25;
+const LAST: usize = 26;
+// Node is a branch; node length
+const BRANCH: usize = 27;
+const LEN: usize = 28;
+const COLUMNS: usize = 29;
+
+/// Tag of an MPT table row
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MptTag {
+    /// RLP string prefix
+    Item = 1,
+    /// Short RLP list prefix
+    List = 2,
+    /// Byte and its nibbles
+    Nibble = 3,
+}
+
+/// Kind of RLP string an item prefix starts
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ItemKind {
+    /// Up to 55 bytes, length in the prefix
+    Short = 0,
+    /// One length byte after the prefix
+    Long = 1,
+    /// The prefix is the whole item
+    Single = 2,
+}
+
+impl ItemKind {
+    /// Kind of the item starting with `prefix` and the bytes it adds to the
+    /// item besides those a long item's length byte counts
+    ///
+    /// `None` for prefixes no supported node item starts with.
+    pub fn of(prefix: u8) -> Option<(Self, u64)> {
+        match prefix {
+            0x00..=0x7f => Some((ItemKind::Single, 0)),
+            0x80..=0xb7 => Some((ItemKind::Short, (prefix - 0x80) as u64)),
+            0xb8 => Some((ItemKind::Long, 1)),
+            _ => None,
+        }
+    }
+}
+
+/// Leaf a proof ends in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MptLeaf {
+    /// State trie leaf, yielding the account's storage root
+    Account,
+    /// Storage trie leaf, yielding the slot value
+    Storage,
+}
+
+/// Root a proof opens and what its leaf yields, as 16-byte limbs
+#[derive(Clone, Debug)]
+pub struct MptOpening<F: PrimeField> {
+    /// Digest of the first node
+    pub root: AssignedWord<F>,
+    /// Storage root of an account leaf, or value of a storage leaf
+    pub leaf: AssignedWord<F>,
+}
+
+/// Configuration for the MptChip
+#[derive(Clone, Debug)]
+pub struct MptChipConfig {
+    /// Advice columns; the constants above document their roles
+    pub cells: [Column<Advice>; COLUMNS],
+    /// Offset of the row in the node
+    pub position: Column<Fixed>,
+    /// Rows that may hold the list header
+    pub q_header: Column<Fixed>,
+    /// Last row of every block
+    pub q_block_end: Column<Fixed>,
+    /// Rows of an account leaf
+    pub q_account: Column<Fixed>,
+    /// Rows of a storage leaf
+    pub q_storage: Column<Fixed>,
+    /// First row of a node
+    pub s_first: Selector,
+    /// First row of the first node of a proof
+    pub s_root: Selector,
+    /// Rows of a node and its padding
+    pub s_body: Selector,
+    /// Last row of every block
+    pub s_block_end: Selector,
+    /// Rows after a node's blocks
+    pub s_post: Selector,
+    /// First and later bytes composed into a limb
+    pub s_compose_first: Selector,
+    pub s_compose: Selector,
+    /// First and later blocks of a node picking its digest
+    pub s_select_first: Selector,
+    pub s_select: Selector,
+    /// Leaf path against the key
+    pub s_path: Selector,
+    /// Keccak digests of the nodes and keys
+    pub keccak_config: KeccakChipConfig,
+    /// Item prefixes, list prefixes and nibbles
+    pub table: MptTableConfig,
+}
+
+/// Cells of a node region linked to the rest of the proof
+struct NodeCells<F: PrimeField> {
+    padded: Vec<AssignedCell<F, F>>,
+    last: Vec<AssignedCell<F, F>>,
+    acc_in: AssignedCell<F, F>,
+    depth_in: AssignedCell<F, F>,
+    acc: AssignedCell<F, F>,
+    depth: AssignedCell<F, F>,
+    reference: AssignedWord<F>,
+    value: AssignedWord<F>,
+}
+
+/// Chip proving Merkle-Patricia trie openings
+pub struct MptChip<F: PrimeField> {
+    config: MptChipConfig,
+    _marker: PhantomData<F>,
+}
+
+impl<F: PrimeField> MptChip<F> {
+    /// Construct a new MptChip
+    pub fn construct(config: MptChipConfig) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Configure the node walk, digest selection and path gates
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        keccak_config: KeccakChipConfig,
+    ) -> MptChipConfig {
+        let cells = [(); COLUMNS].map(|_| meta.advice_column());
+        for column in [
+            BYTE, ACC, DEPTH, REF_HI, REF_LO, VAL_HI, VAL_LO, PADDED, LAST,
+        ] {
+            meta.enable_equality(cells[column]);
+        }
+        let position = meta.fixed_column();
+        let q_header = meta.fixed_column();
+        let q_block_end = meta.fixed_column();
+        let q_account = meta.fixed_column();
+        let q_storage = meta.fixed_column();
+
+        let s_first = meta.complex_selector();
+        let s_root = meta.selector();
+        let s_body = meta.complex_selector();
+        let s_block_end = meta.selector();
+        let s_post = meta.selector();
+        let s_compose_first = meta.selector();
+        let s_compose = meta.selector();
+        let s_select_first = meta.selector();
+        let s_select = meta.selector();
+        let s_path = meta.selector();
+        let table = MptTableConfig::configure(meta);
+
+        let c = |value: u64| Expression::Constant(u64_to_field::<F>(value));
+        let half = Expression::Constant(F::from(2).invert().unwrap());
+        let q = |meta: &mut VirtualCells<'_, F>, column: usize, at: i32| {
+            meta.query_advice(cells[column], Rotation(at))
+        };
+        // `prev * 256 + byte` on flagged rows
+        let accumulate = |prev: Expression<F>, flag: Expression<F>, byte: Expression<F>| {
+            prev * (c(1) + c(255) * flag.clone()) + flag * byte
+        };
+
+        // The list header is one byte (0xc0..=0xf7, payload length in the
+        // byte), or 0xf8 or 0xf9 with one or two length bytes. The rows before
+        // the node hold no items, selections or accumulated bytes.
+        meta.create_gate("mpt node header", |meta| {
+            let s = meta.query_selector(s_first);
+            let byte = q(meta, BYTE, 0);
+            let len = q(meta, LEN, 0);
+            let hdr1 = q(meta, HDR, 1);
+            let hdr2 = q(meta, HDR, 2);
+            let branch = q(meta, BRANCH, 0);
+            let q_leaf = meta.query_fixed(q_account) + meta.query_fixed(q_storage);
+
+            let mut constraints = vec![
+                c(1) - q(meta, IN, 0),
+                c(1) - q(meta, IN, -1),
+                c(1) - q(meta, HDR, 0),
+                hdr1.clone() * (c(1) - hdr1.clone()),
+                hdr2.clone() * (c(1) - hdr2.clone()),
+                hdr2.clone() * (c(1) - hdr1.clone()),
+                (hdr1.clone() - hdr2.clone()) * (byte.clone() - c(0xf8)),
+                hdr2.clone() * (byte - c(0xf9)),
+                (hdr1 - hdr2.clone()) * (len.clone() - c(2) - q(meta, BYTE, 1)),
+                hdr2 * (len - c(3) - c(256) * q(meta, BYTE, 1) - q(meta, BYTE, 2)),
+                branch.clone() * (c(1) - branch.clone()),
+                q_leaf * branch,
+            ];
+            for column in [IDX, COUNT, CONTENT, REF_HI, REF_LO, VAL_HI, VAL_LO] {
+                constraints.push(q(meta, column, -1));
+            }
+            for j in 1..=32 {
+                constraints.push(q(meta, SEL, -j));
+            }
+            constraints
+                .into_iter()
+                .map(|constraint| s.clone() * constraint)
+                .collect::<Vec<_>>()
+        });
+
+        meta.lookup(|meta| {
+            let s = meta.query_selector(s_first);
+            let short = s * (c(1) - q(meta, HDR, 1));
+            vec![
+                (short.clone() * c(MptTag::List as u64), table.tag),
+                (short.clone() * q(meta, BYTE, 0), table.byte),
+                (short * (q(meta, LEN, 0) - c(1)), table.a),
+            ]
+        });
+
+        meta.create_gate("mpt root node", |meta| {
+            let s = meta.query_selector(s_root);
+            vec![s.clone() * q(meta, ACC, -1), s * q(meta, DEPTH, -1)]
+        });
+
+        meta.create_gate("mpt node byte", |meta| {
+            let s = meta.query_selector(s_body);
+            let byte = q(meta, BYTE, 0);
+            let in_prev = q(meta, IN, -1);
+            let in_cur = q(meta, IN, 0);
+            let hdr = q(meta, HDR, 0);
+            let rem = q(meta, REM, 0);
+            let rem_prev = q(meta, REM, -1);
+            let start = q(meta, START, 0);
+            let start_prev = q(meta, START, -1);
+            let kind = q(meta, KIND, 0);
+            let idx = q(meta, IDX, 0);
+            let sel = q(meta, SEL, 0);
+            let content = q(meta, CONTENT, 0);
+            let first = q(meta, FIRST, 0);
+            let odd = q(meta, ODD, 0);
+            let item1 = q(meta, ITEM1, 0);
+            let value = q(meta, VALUE, 0);
+            let branch = q(meta, BRANCH, 0);
+            let len = q(meta, LEN, 0);
+            let q_account = meta.query_fixed(q_account);
+            let q_storage = meta.query_fixed(q_storage);
+            let q_leaf = q_account.clone() + q_storage.clone();
+
+            let is_body = in_cur.clone() - hdr.clone();
+            // Last byte of the node
+            let end = in_cur.clone() - q(meta, IN, 1);
+            let single = kind.clone() * (kind.clone() - c(1)) * half.clone();
+            let long = kind.clone() * (c(2) - kind);
+            let path_byte = content.clone() - first.clone();
+            let child = sel.clone() * branch.clone();
+
+            let mut f_hi = q_account.clone() * (q(meta, IN, 49) - q(meta, IN, 65));
+            let mut f_lo = q_account.clone() * (q(meta, IN, 33) - q(meta, IN, 49));
+            for j in 1..=16 {
+                f_hi = f_hi + q(meta, SEL, -j);
+                f_lo = f_lo + q(meta, SEL, -16 - j);
+            }
+            let in_16 = q(meta, IN, 16);
+
+            vec![
+                // The node is a prefix of the rows, `len` long, zero after
+                in_cur.clone() * (c(1) - in_cur.clone()),
+                in_cur.clone() * (c(1) - in_prev.clone()),
+                (in_prev.clone() - in_cur.clone()) * (meta.query_fixed(position) - len.clone()),
+                (c(1) - in_cur.clone()) * byte.clone(),
+                (c(1) - meta.query_fixed(q_header)) * hdr.clone(),
+                hdr * (c(1) - in_cur.clone()),
+                // Items start once the previous one is done and end with the node
+                rem.clone() * (c(1) - rem.clone() * q(meta, REM_INV, 0)),
+                start.clone() - is_body.clone() * (c(1) - rem_prev.clone() * q(meta, REM_INV, -1)),
+                rem.clone()
+                    - start.clone() * (q(meta, EXTRA, 0) + long * q(meta, BYTE, 1))
+                    - (is_body.clone() - start.clone()) * (rem_prev - c(1)),
+                end.clone() * rem.clone(),
+                idx.clone() - q(meta, IDX, -1) - start.clone(),
+                end.clone() * (idx.clone() - c(2) - c(15) * branch.clone()),
+                // One child reference of an inner node is selected
+                sel.clone() * (c(1) - sel.clone()),
+                sel.clone() * (c(1) - start.clone()),
+                sel.clone() * (byte.clone() - c(0xa0)),
+                sel.clone() * (c(1) - branch.clone()) * (idx.clone() - c(2)),
+                q_leaf.clone() * sel.clone(),
+                q(meta, COUNT, 0) - q(meta, COUNT, -1) - sel,
+                end * (q(meta, COUNT, 0) - c(1) + q_leaf.clone()),
+                // Path item of an extension or leaf, after its prefix
+                q(meta, ITEM0, 0)
+                    - (c(1) - branch.clone())
+                        * idx.clone()
+                        * (c(2) - idx.clone())
+                        * is_body.clone(),
+                content.clone()
+                    - q(meta, ITEM0, 0) * (c(1) - start.clone() + start.clone() * single.clone()),
+                first.clone() - content * (c(1) - q(meta, CONTENT, -1)),
+                odd.clone() * (c(1) - odd.clone()),
+                odd.clone() * (c(1) - first.clone()),
+                first.clone() * (q(meta, HI, 0) - c(2) * q_leaf - odd.clone()),
+                first.clone() * (c(1) - odd.clone()) * q(meta, LO, 0),
+                // Path: the odd nibble, path bytes and branch nibbles
+                q(meta, ACC, 0)
+                    - q(meta, ACC, -1)
+                        * (c(1)
+                            + c(15) * first.clone() * odd.clone()
+                            + c(255) * path_byte.clone()
+                            + c(15) * child.clone())
+                    - first.clone() * odd.clone() * q(meta, LO, 0)
+                    - path_byte.clone() * byte.clone()
+                    - child.clone() * (idx.clone() - c(1)),
+                q(meta, DEPTH, 0) - q(meta, DEPTH, -1) - first * odd - c(2) * path_byte - child,
+                // Child reference, or an account's storage root
+                q(meta, REF_HI, 0) - accumulate(q(meta, REF_HI, -1), f_hi, byte.clone()),
+                q(meta, REF_LO, 0) - accumulate(q(meta, REF_LO, -1), f_lo, byte.clone()),
+                q_account.clone() * (q(meta, IN, 65) - q(meta, IN, 66)) * (byte.clone() - c(0xa0)),
+                q_account * (q(meta, IN, 32) - q(meta, IN, 33)) * (byte.clone() - c(0xa0)),
+                // Storage value: a single byte, or the bytes after both prefixes
+                item1.clone() - q_storage * idx.clone() * (idx - c(1)) * half.clone() * is_body,
+                item1.clone()
+                    * (c(1) - start.clone())
+                    * start_prev.clone()
+                    * (byte.clone() - c(0x80) - rem),
+                value.clone()
+                    - item1 * (start.clone() * single + (c(1) - start) * (c(1) - start_prev)),
+                q(meta, VAL_HI, 0)
+                    - accumulate(
+                        q(meta, VAL_HI, -1),
+                        value.clone() * in_16.clone(),
+                        byte.clone(),
+                    ),
+                q(meta, VAL_LO, 0)
+                    - accumulate(q(meta, VAL_LO, -1), value * (c(1) - in_16), byte.clone()),
+                // Keccak padding: 0x01 after the node, 0x80 ending its last block
+                q(meta, PADDED, 0)
+                    - byte
+                    - (in_prev - in_cur)
+                    - c(0x80) * meta.query_fixed(q_block_end) * q(meta, LAST, 0),
+                branch - q(meta, BRANCH, -1),
+                len - q(meta, LEN, -1),
+            ]
+            .into_iter()
+            .map(|constraint| s.clone() * constraint)
+            .collect::<Vec<_>>()
+        });
+
+        meta.lookup(|meta| {
+            let s = meta.query_selector(s_body) * q(meta, START, 0);
+            vec![
+                (s.clone() * c(MptTag::Item as u64), table.tag),
+                (s.clone() * q(meta, BYTE, 0), table.byte),
+                (s.clone() * q(meta, KIND, 0), table.a),
+                (s * q(meta, EXTRA, 0), table.b),
+            ]
+        });
+
+        meta.lookup(|meta| {
+            let s = meta.query_selector(s_body) * q(meta, IN, 0);
+            vec![
+                (s.clone() * c(MptTag::Nibble as u64), table.tag),
+                (s.clone() * q(meta, BYTE, 0), table.byte),
+                (s.clone() * q(meta, HI, 0), table.a),
+                (s * q(meta, LO, 0), table.b),
+            ]
+        });
+
+        // A block is the last one when the node ends within it
+        meta.create_gate("mpt block end", |meta| {
+            let s = meta.query_selector(s_block_end);
+            let block = KECCAK_RATE as i32;
+            vec![s * (q(meta, LAST, 0) - q(meta, IN, -block) + q(meta, IN, 0))]
+        });
+
+        meta.create_gate("mpt node end", |meta| {
+            let s = meta.query_selector(s_post);
+            vec![s.clone() * q(meta, IN, 0), s * q(meta, BYTE, 0)]
+        });
+
+        meta.create_gate("mpt compose first", |meta| {
+            let s = meta.query_selector(s_compose_first);
+            vec![s * (q(meta, ACC, 0) - q(meta, BYTE, 0))]
+        });
+
+        meta.create_gate("mpt compose", |meta| {
+            let s = meta.query_selector(s_compose);
+            vec![s * (q(meta, ACC, 0) - q(meta, ACC, -1) * c(256) - q(meta, BYTE, 0))]
+        });
+
+        // The digest of the last block: sum(last * digest) over the blocks
+        meta.create_gate("mpt select first", |meta| {
+            let s = meta.query_selector(s_select_first);
+            let last = q(meta, LAST, 0);
+            vec![
+                s.clone() * (q(meta, VAL_HI, 0) - last.clone() * q(meta, REF_HI, 0)),
+                s * (q(meta, VAL_LO, 0) - last * q(meta, REF_LO, 0)),
+            ]
+        });
+
+        meta.create_gate("mpt select", |meta| {
+            let s = meta.query_selector(s_select);
+            let last = q(meta, LAST, 0);
+            vec![
+                s.clone()
+                    * (q(meta, VAL_HI, 0)
+                        - q(meta, VAL_HI, -1)
+                        - last.clone() * q(meta, REF_HI, 0)),
+                s * (q(meta, VAL_LO, 0) - q(meta, VAL_LO, -1) - last * q(meta, REF_LO, 0)),
+            ]
+        });
+
+        meta.create_gate("mpt path", |meta| {
+            let s = meta.query_selector(s_path);
+            let shift = Expression::Constant(F::from_u128(1 << 64).square());
+            vec![
+                s.clone() * (q(meta, ACC, 0) - q(meta, REF_HI, 0) * shift - q(meta, REF_LO, 0)),
+                s * (q(meta, DEPTH, 0) - c(MPT_KEY_NIBBLES)),
+            ]
+        });
+
+        MptChipConfig {
+            cells,
+            position,
+            q_header,
+            q_block_end,
+            q_account,
+            q_storage,
+            s_first,
+            s_root,
+            s_body,
+            s_block_end,
+            s_post,
+            s_compose_first,
+            s_compose,
+            s_select_first,
+            s_select,
+            s_path,
+            keccak_config,
+            table,
+        }
+    }
+
+    /// Load the MPT table and the Keccak tables
+    pub fn load_table(&self, layouter: &mut impl Layouter<F>) -> Result<(), Error> {
+        self.config.table.load(layouter)?;
+        KeccakChip::<F>::construct(self.config.keccak_config.clone()).load_table(layouter)
+    }
+
+    /// Assign bytes to be hashed; the Keccak chip range checks them
+    pub fn assign_bytes(
+        &self,
+        mut layouter: impl Layouter<F>,
+        bytes: &[u8],
+    ) -> Result<Vec<AssignedCell<F, F>>, Error> {
+        layouter.assign_region(
+            || "mpt bytes",
+            |mut region| {
+                bytes
+                    .iter()
+                    .enumerate()
+                    .map(|(row, byte)| {
+                        assign_value(
+                            &mut region,
+                            self.config.cells[BYTE],
+                            row,
+                            Value::known(u64_to_field::<F>(*byte as u64)),
+                        )
+                    })
+                    .collect()
+            },
+        )
+    }
+
+    /// Big-endian value of up to 16 bytes
+    pub fn compose(
+        &self,
+        mut layouter: impl Layouter<F>,
+        bytes: &[AssignedCell<F, F>],
+    ) -> Result<AssignedCell<F, F>, Error> {
+        if bytes.is_empty() || bytes.len() > LIMB_BYTES {
+            return Err(Error::Synthesis);
+        }
+        layouter.assign_region(
+            || "mpt compose",
+            |mut region| {
+                let mut acc = Value::known(F::ZERO);
+                let mut cell = None;
+                for (row, byte) in bytes.iter().enumerate() {
+                    if row == 0 {
+                        self.config.s_compose_first.enable(&mut region, row)?;
+                    } else {
+                        self.config.s_compose.enable(&mut region, row)?;
+                    }
+                    byte.copy_advice(|| "byte", &mut region, self.config.cells[BYTE], row)?;
+                    acc = acc * Value::known(F::from(256)) + byte.value().copied();
+                    cell = Some(assign_value(&mut region, self.config.cells[ACC], row, acc)?);
+                }
+                cell.ok_or(Error::Synthesis)
+            },
+        )
+    }
+
+    /// Keccak-256 digest of fixed-length bytes, as 16-byte limbs
+    pub fn hash(
+        &self,
+        mut layouter: impl Layouter<F>,
+        bytes: &[AssignedCell<F, F>],
+    ) -> Result<AssignedWord<F>, Error> {
+        let keccak = KeccakChip::construct(self.config.keccak_config.clone());
+        let digest = keccak.hash(layouter.namespace(|| "keccak"), bytes)?;
+        self.digest_word(layouter, &digest)
+    }
+
+    fn digest_word(
+        &self,
+        mut layouter: impl Layouter<F>,
+        digest: &[AssignedCell<F, F>; 32],
+    ) -> Result<AssignedWord<F>, Error> {
+        Ok(AssignedWord {
+            hi: self.compose(layouter.namespace(|| "hi"), &digest[..LIMB_BYTES])?,
+            lo: self.compose(layouter.namespace(|| "lo"), &digest[LIMB_BYTES..])?,
+        })
+    }
+
+    /// Open `key` (the digest of the address or slot) through `proof`
+    ///
+    /// `children` holds the item of every node but the last that references
+    /// the next one, as `utils::mpt::verify_proof` returns it. The number of
+    /// nodes and the Keccak blocks of each are part of the circuit's shape.
+    pub fn prove(
+        &self,
+        mut layouter: impl Layouter<F>,
+        key: &AssignedWord<F>,
+        proof: &[Bytes],
+        children: &[usize],
+        leaf: MptLeaf,
+    ) -> Result<MptOpening<F>, Error> {
+        if proof.is_empty() || children.len() + 1 != proof.len() {
+            return Err(Error::Synthesis);
+        }
+        let keccak = KeccakChip::construct(self.config.keccak_config.clone());
+
+        let mut acc = F::ZERO;
+        let mut depth = 0;
+        let mut previous: Option<(NodeCells<F>, AssignedWord<F>)> = None;
+        let mut root = None;
+        for (index, node) in proof.iter().enumerate() {
+            let role = (index + 1 == proof.len()).then_some(leaf);
+            let (rows, node_depth) =
+                node_rows::<F>(node, role, children.get(index).copied(), acc, depth)?;
+            acc = rows[rows.len() - MPT_POSTFIX_ROWS - 1][ACC];
+            depth = node_depth;
+
+            let cells = layouter.assign_region(
+                || format!("mpt node {}", index),
+                |mut region| self.assign_node(&mut region, &rows, role, index == 0),
+            )?;
+
+            // Digest of the node: that of its last block
+            let digests = keccak.digests(layouter.namespace(|| "keccak"), &cells.padded)?;
+            let mut words = Vec::with_capacity(digests.len());
+            for digest in &digests {
+                words.push(self.digest_word(layouter.namespace(|| "digest"), digest)?);
+            }
+            let digest = self.select(layouter.namespace(|| "select"), &cells.last, &words)?;
+
+            if let Some((parent, _)) = &previous {
+                layouter.assign_region(
+                    || "mpt link",
+                    |mut region| {
+                        region.constrain_equal(parent.reference.hi.cell(), digest.hi.cell())?;
+                        region.constrain_equal(parent.reference.lo.cell(), digest.lo.cell())?;
+                        region.constrain_equal(parent.acc.cell(), cells.acc_in.cell())?;
+                        region.constrain_equal(parent.depth.cell(), cells.depth_in.cell())
+                    },
+                )?;
+            }
+            if root.is_none() {
+                root = Some(digest.clone());
+            }
+            previous = Some((cells, digest));
+        }
+
+        let (last, _) = previous.ok_or(Error::Synthesis)?;
+        layouter.assign_region(
+            || "mpt path",
+            |mut region| {
+                self.config.s_path.enable(&mut region, 0)?;
+                last.acc
+                    .copy_advice(|| "path", &mut region, self.config.cells[ACC], 0)?;
+                last.depth
+                    .copy_advice(|| "depth", &mut region, self.config.cells[DEPTH], 0)?;
+                key.hi
+                    .copy_advice(|| "key hi", &mut region, self.config.cells[REF_HI], 0)?;
+                key.lo
+                    .copy_advice(|| "key lo", &mut region, self.config.cells[REF_LO], 0)?;
+                Ok(())
+            },
+        )?;
+
+        Ok(MptOpening {
+            root: root.ok_or(Error::Synthesis)?,
+            leaf: match leaf {
+                MptLeaf::Account => last.reference,
+                MptLeaf::Storage => last.value,
+            },
+        })
+    }
+
+    /// Pick the digest of the block flagged last
+    fn select(
+        &self,
+        mut layouter: impl Layouter<F>,
+        last: &[AssignedCell<F, F>],
+        digests: &[AssignedWord<F>],
+    ) -> Result<AssignedWord<F>, Error> {
+        layouter.assign_region(
+            || "mpt select",
+            |mut region| {
+                let mut sum = None;
+                for (row, (flag, digest)) in last.iter().zip(digests).enumerate() {
+                    if row == 0 {
+                        self.config.s_select_first.enable(&mut region, row)?;
+                    } else {
+                        self.config.s_select.enable(&mut region, row)?;
+                    }
+                    let flag =
+                        flag.copy_advice(|| "last", &mut region, self.config.cells[LAST], row)?;
+                    digest
+                        .hi
+                        .copy_advice(|| "hi", &mut region, self.config.cells[REF_HI], row)?;
+                    digest
+                        .lo
+                        .copy_advice(|| "lo", &mut region, self.config.cells[REF_LO], row)?;
+                    let picked =
+                        |limb: &AssignedCell<F, F>, previous: Option<&AssignedCell<F, F>>| {
+                            let base = previous
+                                .map_or(Value::known(F::ZERO), |cell| cell.value().copied());
+                            base + flag.value().copied() * limb.value().copied()
+                        };
+                    let hi = picked(
+                        &digest.hi,
+                        sum.as_ref().map(|word: &AssignedWord<F>| &word.hi),
+                    );
+                    let lo = picked(
+                        &digest.lo,
+                        sum.as_ref().map(|word: &AssignedWord<F>| &word.lo),
+                    );
+                    sum = Some(AssignedWord {
+                        hi: assign_value(&mut region, self.config.cells[VAL_HI], row, hi)?,
+                        lo: assign_value(&mut region, self.config.cells[VAL_LO], row, lo)?,
+                    });
+                }
+                sum.ok_or(Error::Synthesis)
+            },
+        )
+    }
+
+    /// Assign a node region from its witness rows
+    fn assign_node(
+        &self,
+        region: &mut Region<'_, F>,
+        rows: &[[F; COLUMNS]],
+        leaf: Option<MptLeaf>,
+        root: bool,
+    ) -> Result<NodeCells<F>, Error> {
+        let config = &self.config;
+        let body = rows.len() - MPT_PREFIX_ROWS - MPT_POSTFIX_ROWS;
+        let mut padded = Vec::with_capacity(body);
+        let mut last = Vec::with_capacity(body / KECCAK_RATE);
+        let mut kept: Vec<Option<AssignedCell<F, F>>> = vec![None; 8];
+
+        for (row, values) in rows.iter().enumerate() {
+            let p = row as i64 - MPT_PREFIX_ROWS as i64;
+            let in_body = p >= 0 && (p as usize) < body;
+            let block_end = in_body && p as usize % KECCAK_RATE == KECCAK_RATE - 1;
+            for (column, value) in values.iter().enumerate() {
+                let cell = assign_value(region, config.cells[column], row, Value::known(*value))?;
+                if in_body && column == PADDED {
+                    padded.push(cell.clone());
+                }
+                if block_end && column == LAST {
+                    last.push(cell.clone());
+                }
+                let slot = match (p, column) {
+                    (-1, ACC) => Some(0),
+                    (-1, DEPTH) => Some(1),
+                    _ if p == body as i64 - 1 => [ACC, DEPTH, REF_HI, REF_LO, VAL_HI, VAL_LO]
+                        .iter()
+                        .position(|&kept| kept == column)
+                        .map(|i| i + 2),
+                    _ => None,
+                };
+                if let Some(slot) = slot {
+                    kept[slot] = Some(cell);
+                }
+            }
+
+            if !in_body {
+                if p >= body as i64 {
+                    config.s_post.enable(region, row)?;
+                }
+                continue;
+            }
+            let fixed = [
+                (config.position, u64_to_field::<F>(p as u64)),
+                (config.q_header, F::from((p < 3) as u64)),
+                (config.q_block_end, F::from(block_end as u64)),
+                (
+                    config.q_account,
+                    F::from((leaf == Some(MptLeaf::Account)) as u64),
+                ),
+                (
+                    config.q_storage,
+                    F::from((leaf == Some(MptLeaf::Storage)) as u64),
+                ),
+            ];
+            for (column, value) in fixed {
+                region.assign_fixed(|| "mpt fixed", column, row, || Value::known(value))?;
+            }
+            config.s_body.enable(region, row)?;
+            if p == 0 {
+                config.s_first.enable(region, row)?;
+                if root {
+                    config.s_root.enable(region, row)?;
+                }
+            }
+            if block_end {
+                config.s_block_end.enable(region, row)?;
+            }
+        }
+
+        let mut kept = kept.into_iter();
+        let mut next = || kept.next().flatten().ok_or(Error::Synthesis);
+        Ok(NodeCells {
+            padded,
+            last,
+            acc_in: next()?,
+            depth_in: next()?,
+            acc: next()?,
+            depth: next()?,
+            reference: AssignedWord {
+                hi: next()?,
+                lo: next()?,
+            },
+            value: AssignedWord {
+                hi: next()?,
+                lo: next()?,
+            },
+        })
+    }
+}
+
+/// Witness rows of a node region, from `MPT_PREFIX_ROWS` before the node to
+/// `MPT_POSTFIX_ROWS` after its blocks, following the "mpt node byte" gate,
+/// and the path depth after the node
+fn node_rows<F: PrimeField>(
+    node: &[u8],
+    leaf: Option<MptLeaf>,
+    child: Option<usize>,
+    acc_in: F,
+    depth_in: u64,
+) -> Result<(Vec<[F; COLUMNS]>, u64), Error> {
+    let len = node.len();
+    let body = (len / KECCAK_RATE + 1) * KECCAK_RATE;
+    let branch = rlp_list(node).map_err(|_| Error::Synthesis)?.len() == 17;
+    let header = match node.first() {
+        Some(0xc0..=0xf7) => 1,
+        Some(0xf8) => 2,
+        Some(0xf9) => 3,
+        _ => return Err(Error::Synthesis),
+    };
+    if branch && leaf.is_some() {
+        return Err(Error::Synthesis);
+    }
+    let selected = child.map(|item| item as u64 + 1);
+    let account = leaf == Some(MptLeaf::Account);
+    let storage = leaf == Some(MptLeaf::Storage);
+    let within = |p: i64| p < len as i64;
+    let flag = |set: bool| F::from(set as u64);
+    let byte_at = |p: i64| {
+        usize::try_from(p)
+            .ok()
+            .and_then(|p| node.get(p))
+            .map_or(0, |byte| *byte as u64)
+    };
+
+    let mut rows = vec![[F::ZERO; COLUMNS]; MPT_PREFIX_ROWS + body + MPT_POSTFIX_ROWS];
+    for (row, values) in rows.iter_mut().enumerate() {
+        let p = row as i64 - MPT_PREFIX_ROWS as i64;
+        values[IN] = flag(within(p));
+        values[BYTE] = u64_to_field(byte_at(p));
+        values[BRANCH] = flag(branch);
+        values[LEN] = u64_to_field(len as u64);
+    }
+    let before = MPT_PREFIX_ROWS - 1;
+    rows[before][ACC] = acc_in;
+    rows[before][DEPTH] = u64_to_field(depth_in);
+
+    let (mut rem, mut idx, mut count) = (0u64, 0u64, 0u64);
+    let (mut content_prev, mut start_prev) = (false, false);
+    let (mut acc, mut depth) = (acc_in, depth_in);
+    let [mut ref_hi, mut ref_lo, mut val_hi, mut val_lo] = [F::ZERO; 4];
+    for p in 0..body as i64 {
+        let row = MPT_PREFIX_ROWS + p as usize;
+        let byte = byte_at(p);
+        let in_cur = within(p);
+        let hdr = p < header;
+        let is_body = in_cur && !hdr;
+        let start = is_body && rem == 0;
+        let (kind, extra) = if start {
+            ItemKind::of(byte as u8).ok_or(Error::Synthesis)?
+        } else {
+            (ItemKind::Short, 0)
+        };
+        rem = if start {
+            extra
+                + if kind == ItemKind::Long {
+                    byte_at(p + 1)
+                } else {
+                    0
+                }
+        } else if is_body {
+            rem - 1
+        } else {
+            0
+        };
+        idx += start as u64;
+        let sel = start && Some(idx) == selected;
+        count += sel as u64;
+        let item0 = !branch && idx == 1 && is_body;
+        let content = item0 && (!start || kind == ItemKind::Single);
+        let first = content && !content_prev;
+        let odd = first && byte >> 4 & 1 == 1;
+        let path_byte = content && !first;
+        let child_sel = sel && branch;
+
+        let shift = |acc: F, nibbles: u32| acc * F::from(1 << (4 * nibbles));
+        if odd {
+            acc = shift(acc, 1) + F::from(byte & 0x0f);
+            depth += 1;
+        }
+        if path_byte {
+            acc = shift(acc, 2) + F::from(byte);
+            depth += 2;
+        }
+        if child_sel {
+            acc = shift(acc, 1) + F::from(idx - 1);
+            depth += 1;
+        }
+
+        let selected_before = |range: std::ops::RangeInclusive<usize>| {
+            range.filter(|j| rows[row - j][SEL] == F::ONE).count() as u64
+        };
+        let span = |a: i64, b: i64| account && within(p + a) && !within(p + b);
+        let f_hi = selected_before(1..=16) + span(49, 65) as u64;
+        let f_lo = selected_before(17..=32) + span(33, 49) as u64;
+        let accumulate = |prev: F, flag: u64| {
+            if flag == 1 {
+                prev * F::from(256) + F::from(byte)
+            } else {
+                prev
+            }
+        };
+        ref_hi = accumulate(ref_hi, f_hi);
+        ref_lo = accumulate(ref_lo, f_lo);
+
+        let item1 = storage && idx == 2 && is_body;
+        let value = item1 && ((start && kind == ItemKind::Single) || (!start && !start_prev));
+        let high = within(p + 16);
+        val_hi = accumulate(val_hi, (value && high) as u64);
+        val_lo = accumulate(val_lo, (value && !high) as u64);
+
+        let block_end = p as usize % KECCAK_RATE == KECCAK_RATE - 1;
+        let last = block_end && within(p - KECCAK_RATE as i64) && !in_cur;
+        let padded = byte + (within(p - 1) && !in_cur) as u64 + if last { 0x80 } else { 0 };
+
+        let values = &mut rows[row];
+        values[HDR] = flag(hdr);
+        values[REM] = u64_to_field(rem);
+        values[REM_INV] = u64_to_field::<F>(rem).invert().unwrap_or(F::ZERO);
+        values[START] = flag(start);
+        values[KIND] = u64_to_field(if start { kind as u64 } else { 0 });
+        values[EXTRA] = u64_to_field(extra);
+        values[IDX] = u64_to_field(idx);
+        values[SEL] = flag(sel);
+        values[COUNT] = u64_to_field(count);
+        values[ITEM0] = flag(item0);
+        values[CONTENT] = flag(content);
+        values[FIRST] = flag(first);
+        values[HI] = u64_to_field(byte >> 4);
+        values[LO] = u64_to_field(byte & 0x0f);
+        values[ODD] = flag(odd);
+        values[ACC] = acc;
+        values[DEPTH] = u64_to_field(depth);
+        values[REF_HI] = ref_hi;
+        values[REF_LO] = ref_lo;
+        values[ITEM1] = flag(item1);
+        values[VALUE] = flag(value);
+        values[VAL_HI] = val_hi;
+        values[VAL_LO] = val_lo;
+        values[PADDED] = u64_to_field(padded);
+        values[LAST] = flag(last);
+
+        content_prev = content;
+        start_prev = start;
+    }
+    Ok((rows, depth))
+}
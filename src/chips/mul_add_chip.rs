@@ -0,0 +1,367 @@
+//! Multiply-add chip over 256-bit words
+//!
+//! Constrains `a * b + c = hi * 2^256 + lo` with a 512-bit result. The factors
+//! are split into 64-bit sub-limbs so that every partial product fits in the
+//! field, and the schoolbook sum is checked one 128-bit chunk at a time with
+//! range checked carries between chunks. DIV/MOD and MULMOD build on it.
+
+use alloy_primitives::U256;
+use halo2_proofs::{
+    circuit::Layouter,
+    pasta::group::ff::PrimeField,
+    plonk::{Advice, Column, ConstraintSystem, Error, Expression, Selector},
+    poly::Rotation,
+};
+use std::marker::PhantomData;
+
+use crate::chips::evm_chip::u64_to_field;
+use crate::chips::word_chip::{
+    AssignedWord, LIMB_BYTES, WordChip, WordChipConfig, assign_value, limb_shift,
+};
+
+/// Bytes per factor sub-limb
+const SUB_LIMB_BYTES: usize = 8;
+
+/// Bytes per chunk carry
+///
+/// A chunk sums at most seven 128-bit partial products, four of them shifted by
+/// 2^64, plus a limb and the incoming carry: the outgoing carry stays below 2^67.
+const CARRY_BYTES: usize = 9;
+
+/// Configuration for the MulAddChip
+#[derive(Clone, Debug)]
+pub struct MulAddChipConfig {
+    /// Advice columns, laid out as documented on the gate
+    pub cells: [Column<Advice>; 5],
+    /// Enabled on the first of the five rows of a multiply-add
+    pub s_mul_add: Selector,
+    /// Range checks for sub-limbs, carries and the result
+    pub word_config: WordChipConfig,
+}
+
+/// Chip constraining `a * b + c` as a 512-bit value
+pub struct MulAddChip<F: PrimeField> {
+    config: MulAddChipConfig,
+    _marker: PhantomData<F>,
+}
+
+impl<F: PrimeField> MulAddChip<F> {
+    /// Construct a new MulAddChip
+    pub fn construct(config: MulAddChipConfig) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Configure the multiply-add gate
+    ///
+    /// # Arguments
+    ///
+    /// * `cells` - Advice columns, which may be shared with other word gadgets
+    /// * `word_config` - Range check configuration shared with the stack words
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        cells: [Column<Advice>; 5],
+        word_config: WordChipConfig,
+    ) -> MulAddChipConfig {
+        for column in cells {
+            meta.enable_equality(column);
+        }
+
+        let s_mul_add = meta.selector();
+
+        // Gate: a * b + c = hi * 2^256 + lo. Rows:
+        //   [a_0,   a_1,   a_2,   a_3,   c.lo  ]
+        //   [b_0,   b_1,   b_2,   b_3,   c.hi  ]
+        //   [a.lo,  a.hi,  b.lo,  b.hi,  carry0]
+        //   [lo.lo, lo.hi, hi.lo, hi.hi, carry1]
+        //   [carry2, -,    -,     -,     -     ]
+        // where x_i are the 64-bit sub-limbs of x, least significant first.
+        meta.create_gate("mul_add", |meta| {
+            let s = meta.query_selector(s_mul_add);
+            let mut q = |column: usize, row: i32| meta.query_advice(cells[column], Rotation(row));
+            let a = [q(0, 0), q(1, 0), q(2, 0), q(3, 0)];
+            let b = [q(0, 1), q(1, 1), q(2, 1), q(3, 1)];
+            let (c_lo, c_hi) = (q(4, 0), q(4, 1));
+            let (a_lo, a_hi, b_lo, b_hi) = (q(0, 2), q(1, 2), q(2, 2), q(3, 2));
+            let (lo_lo, lo_hi, hi_lo, hi_hi) = (q(0, 3), q(1, 3), q(2, 3), q(3, 3));
+            let carries = [q(4, 2), q(4, 3), q(0, 4)];
+
+            let sub_shift = Expression::Constant(u64_to_field::<F>(1 << 32).square());
+            let shift = Expression::Constant(limb_shift::<F>());
+
+            // Sum of the partial products a_i * b_j with i + j = k
+            let product = |k: usize| {
+                (0..4)
+                    .filter(|i| k >= *i && k - i < 4)
+                    .map(|i| a[i].clone() * b[k - i].clone())
+                    .reduce(|acc, term| acc + term)
+                    .unwrap()
+            };
+            let chunk = |k: usize| product(2 * k) + product(2 * k + 1) * sub_shift.clone();
+
+            vec![
+                s.clone() * (a[0].clone() + a[1].clone() * sub_shift.clone() - a_lo),
+                s.clone() * (a[2].clone() + a[3].clone() * sub_shift.clone() - a_hi),
+                s.clone() * (b[0].clone() + b[1].clone() * sub_shift.clone() - b_lo),
+                s.clone() * (b[2].clone() + b[3].clone() * sub_shift.clone() - b_hi),
+                s.clone() * (chunk(0) + c_lo - lo_lo - carries[0].clone() * shift.clone()),
+                s.clone()
+                    * (chunk(1) + c_hi + carries[0].clone()
+                        - lo_hi
+                        - carries[1].clone() * shift.clone()),
+                s.clone() * (chunk(2) + carries[1].clone() - hi_lo - carries[2].clone() * shift),
+                s * (product(6) + carries[2].clone() - hi_hi),
+            ]
+        });
+
+        MulAddChipConfig {
+            cells,
+            s_mul_add,
+            word_config,
+        }
+    }
+
+    /// Constrain `a * b + c`, returning its `[lo, hi]` 256-bit halves
+    ///
+    /// `c` must already be range checked; the factors are range checked through
+    /// their sub-limbs and the returned halves are range checked here.
+    pub fn mul_add(
+        &self,
+        mut layouter: impl Layouter<F>,
+        a: &AssignedWord<F>,
+        b: &AssignedWord<F>,
+        c: &AssignedWord<F>,
+    ) -> Result<[AssignedWord<F>; 2], Error> {
+        let cells = self.config.cells;
+        let (sub_limbs, carries, halves) = layouter.assign_region(
+            || "mul add",
+            |mut region| {
+                self.config.s_mul_add.enable(&mut region, 0)?;
+
+                let witness = a
+                    .value()
+                    .zip(b.value())
+                    .zip(c.value())
+                    .map(|((a, b), c)| MulAddWitness::new(a, b, c));
+
+                let mut sub_limbs = Vec::with_capacity(8);
+                for (row, word) in [a, b].into_iter().enumerate() {
+                    for i in 0..4 {
+                        let limb = witness.as_ref().map(|w| {
+                            let limbs = if row == 0 { &w.a } else { &w.b };
+                            u64_to_field::<F>(limbs[i])
+                        });
+                        sub_limbs.push(assign_value(&mut region, cells[i], row, limb)?);
+                    }
+                    word.lo
+                        .copy_advice(|| "lo", &mut region, cells[2 * row], 2)?;
+                    word.hi
+                        .copy_advice(|| "hi", &mut region, cells[2 * row + 1], 2)?;
+                }
+                c.lo.copy_advice(|| "c lo", &mut region, cells[4], 0)?;
+                c.hi.copy_advice(|| "c hi", &mut region, cells[4], 1)?;
+
+                let mut halves = Vec::with_capacity(4);
+                for (i, column) in cells.into_iter().take(4).enumerate() {
+                    let limb = witness.as_ref().map(|w| F::from_u128(w.result[i]));
+                    halves.push(assign_value(&mut region, column, 3, limb)?);
+                }
+
+                let mut carries = Vec::with_capacity(3);
+                for (i, (column, row)) in [(4, 2), (4, 3), (0, 4)].into_iter().enumerate() {
+                    let carry = witness.as_ref().map(|w| F::from_u128(w.carries[i]));
+                    carries.push(assign_value(&mut region, cells[column], row, carry)?);
+                }
+
+                Ok((sub_limbs, carries, halves))
+            },
+        )?;
+
+        let word_chip = WordChip::construct(self.config.word_config.clone());
+        let checks = [
+            (&sub_limbs, SUB_LIMB_BYTES),
+            (&carries, CARRY_BYTES),
+            (&halves, LIMB_BYTES),
+        ];
+        for (cells, num_bytes) in checks {
+            for cell in cells.iter() {
+                word_chip.range_check(layouter.namespace(|| "mul add"), cell, num_bytes)?;
+            }
+        }
+
+        let word = |i: usize| AssignedWord {
+            lo: halves[2 * i].clone(),
+            hi: halves[2 * i + 1].clone(),
+        };
+        Ok([word(0), word(1)])
+    }
+}
+
+/// Native values behind a multiply-add region
+struct MulAddWitness {
+    /// 64-bit sub-limbs of `a`, least significant first
+    a: [u64; 4],
+    /// 64-bit sub-limbs of `b`, least significant first
+    b: [u64; 4],
+    /// 128-bit limbs of the 512-bit result, least significant first
+    result: [u128; 4],
+    /// Carries out of the first three 128-bit chunks
+    carries: [u128; 3],
+}
+
+impl MulAddWitness {
+    fn new(a: U256, b: U256, c: U256) -> Self {
+        let (a, b) = (*a.as_limbs(), *b.as_limbs());
+        let c = c.as_limbs();
+        let c = [
+            c[0] as u128 | (c[1] as u128) << 64,
+            c[2] as u128 | (c[3] as u128) << 64,
+        ];
+
+        // Same chunking as the gate; every chunk sum stays below 2^196
+        let product = |k: usize| {
+            (0..4)
+                .filter(|i| k >= *i && k - i < 4)
+                .map(|i| U256::from(a[i]) * U256::from(b[k - i]))
+                .fold(U256::ZERO, |acc, term| acc + term)
+        };
+        let mask: U256 = U256::from(u128::MAX);
+
+        let mut result = [0u128; 4];
+        let mut carries = [0u128; 3];
+        let mut carry = U256::ZERO;
+        for k in 0..4 {
+            let extra = if k < 2 { U256::from(c[k]) } else { U256::ZERO };
+            let sum: U256 = product(2 * k) + (product(2 * k + 1) << 64) + extra + carry;
+            result[k] = (sum & mask).to::<u128>();
+            carry = sum >> 128;
+            if k < 3 {
+                carries[k] = carry.to::<u128>();
+            }
+        }
+
+        Self {
+            a,
+            b,
+            result,
+            carries,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chips::tables::ByteTableConfig;
+    use crate::chips::word_chip::word_limbs;
+    use halo2_proofs::{
+        circuit::{SimpleFloorPlanner, Value},
+        dev::MockProver,
+        pasta::Fp,
+        plonk::Circuit,
+    };
+
+    /// Test circuit checking `a * b + c = hi * 2^256 + lo`
+    struct MulAddCircuit {
+        inputs: [U256; 3],
+        expected: [U256; 2],
+    }
+
+    impl Circuit<Fp> for MulAddCircuit {
+        type Config = (MulAddChipConfig, Column<Advice>);
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self {
+                inputs: [U256::ZERO; 3],
+                expected: [U256::ZERO; 2],
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            let byte_table = ByteTableConfig::configure(meta);
+            let word_config = WordChip::configure(meta, byte_table);
+            let words = meta.advice_column();
+            meta.enable_equality(words);
+            let cells = [(); 5].map(|_| meta.advice_column());
+            (MulAddChip::configure(meta, cells, word_config), words)
+        }
+
+        fn synthesize(
+            &self,
+            (config, column): Self::Config,
+            mut layouter: impl Layouter<Fp>,
+        ) -> Result<(), Error> {
+            config.word_config.byte_table.load(&mut layouter)?;
+
+            let words = layouter.assign_region(
+                || "words",
+                |mut region| {
+                    let values = self.inputs.iter().chain(self.expected.iter());
+                    let mut words = Vec::with_capacity(5);
+                    for (i, word) in values.enumerate() {
+                        let [lo, hi] = word_limbs::<Fp>(*word);
+                        let lo = assign_value(&mut region, column, 2 * i, Value::known(lo))?;
+                        let hi = assign_value(&mut region, column, 2 * i + 1, Value::known(hi))?;
+                        words.push(AssignedWord { lo, hi });
+                    }
+                    Ok(words)
+                },
+            )?;
+
+            let chip = MulAddChip::construct(config);
+            let halves = chip.mul_add(
+                layouter.namespace(|| "mul add"),
+                &words[0],
+                &words[1],
+                &words[2],
+            )?;
+            layouter.assign_region(
+                || "expected",
+                |mut region| {
+                    for (half, expected) in halves.iter().zip(&words[3..]) {
+                        region.constrain_equal(half.lo.cell(), expected.lo.cell())?;
+                        region.constrain_equal(half.hi.cell(), expected.hi.cell())?;
+                    }
+                    Ok(())
+                },
+            )
+        }
+    }
+
+    fn run(inputs: [U256; 3], expected: [U256; 2]) -> MockProver<Fp> {
+        let circuit = MulAddCircuit { inputs, expected };
+        MockProver::run(10, &circuit, vec![]).unwrap()
+    }
+
+    #[test]
+    fn test_mul_add() {
+        let one = U256::from(1u64);
+        let cases = [
+            (
+                [U256::ZERO, U256::ZERO, U256::ZERO],
+                [U256::ZERO, U256::ZERO],
+            ),
+            (
+                [U256::from(6u64), U256::from(7u64), one],
+                [U256::from(43u64), U256::ZERO],
+            ),
+            ([one << 128, one << 128, U256::ZERO], [U256::ZERO, one]),
+            // (2^256 - 1)^2 + (2^256 - 1) = (2^256 - 1) * 2^256
+            ([U256::MAX, U256::MAX, U256::MAX], [U256::ZERO, U256::MAX]),
+        ];
+        for (inputs, expected) in cases {
+            run(inputs, expected).assert_satisfied();
+        }
+    }
+
+    #[test]
+    fn test_wrong_product_fails() {
+        let inputs = [U256::MAX, U256::from(2u64), U256::ZERO];
+        // The product wraps modulo 2^256 without the high half
+        let prover = run(inputs, [U256::MAX - U256::from(1u64), U256::ZERO]);
+        assert!(prover.verify().is_err());
+    }
+}
@@ -0,0 +1,273 @@
+//! KECCAK256 over a window of memory
+//!
+//! KECCAK256 hashes `size` bytes of memory from its offset. The size is a
+//! witness, so the memory circuit reads a fixed window of `SHA3_MAX_BYTES`
+//! bytes from the offset and this chip hashes a prefix of it: a flag `in`
+//! marks the hashed bytes, is boolean and never rises again once it falls,
+//! and counts up to the size. The padded block keeps the hashed bytes, adds
+//! `0x01` where the flag falls and ends in `0x80`; the window fits one block
+//! with its padding, so a single permutation digests it. The digest bytes
+//! then recompose the limbs of the word KECCAK256 pushes.
+
+use halo2_proofs::{
+    circuit::{AssignedCell, Layouter, Value},
+    pasta::group::ff::PrimeField,
+    plonk::{Advice, Column, ConstraintSystem, Error, Expression, Selector},
+    poly::Rotation,
+};
+use std::marker::PhantomData;
+
+use crate::chips::evm_chip::u64_to_field;
+use crate::chips::keccak_chip::{KECCAK_RATE, KeccakChip, KeccakChipConfig};
+use crate::chips::word_chip::{AssignedWord, LIMB_BYTES, assign_value, limb_value};
+
+/// Most bytes a KECCAK256 step can hash: four words, which covers the
+/// 64-byte key and slot of a mapping access
+pub const SHA3_MAX_BYTES: usize = 128;
+
+// Columns of a window row
+const BYTE: usize = 0;
+const IN: usize = 1;
+const PADDED: usize = 2;
+const COUNT: usize = 3;
+
+// Columns of a digest row
+const ACC: usize = 3;
+
+/// Configuration for the Sha3Chip
+#[derive(Clone, Debug)]
+pub struct Sha3ChipConfig {
+    /// Advice columns; the gates document their roles
+    pub cells: [Column<Advice>; 4],
+    /// First byte of the window
+    pub s_first: Selector,
+    /// Later byte of the window
+    pub s_window: Selector,
+    /// Padding byte right after the window
+    pub s_end: Selector,
+    /// First byte of a digest limb
+    pub s_compose_first: Selector,
+    /// Later byte of a digest limb
+    pub s_compose: Selector,
+    /// Keccak-256 of the padded block
+    pub keccak_config: KeccakChipConfig,
+}
+
+/// Chip binding the word KECCAK256 pushes to the memory it hashes
+pub struct Sha3Chip<F: PrimeField> {
+    config: Sha3ChipConfig,
+    _marker: PhantomData<F>,
+}
+
+impl<F: PrimeField> Sha3Chip<F> {
+    /// Construct a new Sha3Chip
+    pub fn construct(config: Sha3ChipConfig) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Configure the window and digest gates
+    ///
+    /// A window row holds `(byte, in, padded, count)` with `padded = in *
+    /// byte + (in_prev - in)`, `in_prev` being 1 before the first byte, and
+    /// `count` the running sum of `in`. The row after the window pads with
+    /// `in_prev`, the `0x01` of a full window. A digest row holds `(byte,
+    /// acc)`, `acc` accumulating the big-endian bytes of a limb.
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        keccak_config: KeccakChipConfig,
+    ) -> Sha3ChipConfig {
+        let cells = [(); 4].map(|_| meta.advice_column());
+        for column in &cells {
+            meta.enable_equality(*column);
+        }
+        let s_first = meta.selector();
+        let s_window = meta.selector();
+        let s_end = meta.selector();
+        let s_compose_first = meta.selector();
+        let s_compose = meta.selector();
+
+        let one = || Expression::Constant(F::ONE);
+        let q = |meta: &mut halo2_proofs::plonk::VirtualCells<'_, F>, column: usize, rotation| {
+            meta.query_advice(cells[column], rotation)
+        };
+
+        meta.create_gate("sha3 first byte", |meta| {
+            let s = meta.query_selector(s_first);
+            let byte = q(meta, BYTE, Rotation::cur());
+            let flag = q(meta, IN, Rotation::cur());
+            let padded = q(meta, PADDED, Rotation::cur());
+            let count = q(meta, COUNT, Rotation::cur());
+            vec![
+                s.clone() * flag.clone() * (one() - flag.clone()),
+                s.clone() * (padded - flag.clone() * byte - (one() - flag.clone())),
+                s * (count - flag),
+            ]
+        });
+
+        meta.create_gate("sha3 window byte", |meta| {
+            let s = meta.query_selector(s_window);
+            let byte = q(meta, BYTE, Rotation::cur());
+            let flag = q(meta, IN, Rotation::cur());
+            let flag_prev = q(meta, IN, Rotation::prev());
+            let padded = q(meta, PADDED, Rotation::cur());
+            let count = q(meta, COUNT, Rotation::cur());
+            let count_prev = q(meta, COUNT, Rotation::prev());
+            vec![
+                s.clone() * flag.clone() * (one() - flag.clone()),
+                // Once the flag falls it stays down
+                s.clone() * flag.clone() * (one() - flag_prev.clone()),
+                s.clone() * (padded - flag.clone() * byte - (flag_prev - flag.clone())),
+                s * (count - count_prev - flag),
+            ]
+        });
+
+        meta.create_gate("sha3 window end", |meta| {
+            let s = meta.query_selector(s_end);
+            let padded = q(meta, PADDED, Rotation::cur());
+            let flag_prev = q(meta, IN, Rotation::prev());
+            vec![s * (padded - flag_prev)]
+        });
+
+        meta.create_gate("sha3 compose first", |meta| {
+            let s = meta.query_selector(s_compose_first);
+            vec![s * (q(meta, ACC, Rotation::cur()) - q(meta, BYTE, Rotation::cur()))]
+        });
+
+        meta.create_gate("sha3 compose", |meta| {
+            let s = meta.query_selector(s_compose);
+            let acc = q(meta, ACC, Rotation::cur());
+            let acc_prev = q(meta, ACC, Rotation::prev());
+            let byte = q(meta, BYTE, Rotation::cur());
+            vec![s * (acc - acc_prev * Expression::Constant(F::from(256)) - byte)]
+        });
+
+        Sha3ChipConfig {
+            cells,
+            s_first,
+            s_window,
+            s_end,
+            s_compose_first,
+            s_compose,
+            keccak_config,
+        }
+    }
+
+    /// Load the Keccak tables
+    pub fn load_table(&self, layouter: &mut impl Layouter<F>) -> Result<(), Error> {
+        KeccakChip::<F>::construct(self.config.keccak_config.clone()).load_table(layouter)
+    }
+
+    /// Constrain `digest` to the Keccak-256 of the first `size` bytes of
+    /// `window`
+    ///
+    /// # Arguments
+    ///
+    /// * `window` - The `SHA3_MAX_BYTES` bytes from the offset, range checked
+    ///   by the caller
+    /// * `size` - Stack word holding the number of bytes hashed
+    /// * `digest` - Stack word KECCAK256 pushes
+    pub fn assign(
+        &self,
+        mut layouter: impl Layouter<F>,
+        window: &[AssignedCell<F, F>],
+        size: &AssignedWord<F>,
+        digest: &AssignedWord<F>,
+    ) -> Result<(), Error> {
+        if window.len() != SHA3_MAX_BYTES {
+            return Err(Error::Synthesis);
+        }
+        let cells = &self.config.cells;
+
+        let padded = layouter.assign_region(
+            || "sha3 window",
+            |mut region| {
+                let length = size.lo.value().map(|lo| limb_value(*lo));
+                let mut padded = Vec::with_capacity(KECCAK_RATE);
+                let mut flag_prev = Value::known(true);
+                let mut count = Value::known(0u64);
+                for (row, byte) in window.iter().enumerate() {
+                    if row == 0 {
+                        self.config.s_first.enable(&mut region, row)?;
+                    } else {
+                        self.config.s_window.enable(&mut region, row)?;
+                    }
+                    byte.copy_advice(|| "byte", &mut region, cells[BYTE], row)?;
+                    let flag = length.map(|length| (row as u128) < length);
+                    assign_value(
+                        &mut region,
+                        cells[IN],
+                        row,
+                        flag.map(|flag| u64_to_field::<F>(flag as u64)),
+                    )?;
+                    let value = flag.zip(flag_prev).zip(byte.value().copied()).map(
+                        |((flag, flag_prev), byte)| {
+                            if flag {
+                                byte
+                            } else {
+                                u64_to_field::<F>((flag_prev && !flag) as u64)
+                            }
+                        },
+                    );
+                    padded.push(assign_value(&mut region, cells[PADDED], row, value)?);
+                    count = count.zip(flag).map(|(count, flag)| count + flag as u64);
+                    let count_cell =
+                        assign_value(&mut region, cells[COUNT], row, count.map(u64_to_field))?;
+                    if row + 1 == SHA3_MAX_BYTES {
+                        region.constrain_equal(count_cell.cell(), size.lo.cell())?;
+                    }
+                    flag_prev = flag;
+                }
+                region.constrain_constant(size.hi.cell(), F::ZERO)?;
+
+                // A full window is followed by the 0x01 its flag never set
+                let end = SHA3_MAX_BYTES;
+                self.config.s_end.enable(&mut region, end)?;
+                let value = flag_prev.map(|flag| u64_to_field::<F>(flag as u64));
+                padded.push(assign_value(&mut region, cells[PADDED], end, value)?);
+                for row in end + 1..KECCAK_RATE {
+                    let byte = if row + 1 == KECCAK_RATE { 0x80 } else { 0 };
+                    padded.push(region.assign_advice_from_constant(
+                        || "padding",
+                        cells[PADDED],
+                        row,
+                        u64_to_field::<F>(byte),
+                    )?);
+                }
+                Ok(padded)
+            },
+        )?;
+
+        let keccak = KeccakChip::construct(self.config.keccak_config.clone());
+        let hash = keccak
+            .digests(layouter.namespace(|| "keccak"), &padded)?
+            .pop()
+            .ok_or(Error::Synthesis)?;
+
+        layouter.assign_region(
+            || "sha3 digest",
+            |mut region| {
+                let mut acc = Value::known(F::ZERO);
+                for (row, byte) in hash.iter().enumerate() {
+                    if row % LIMB_BYTES == 0 {
+                        self.config.s_compose_first.enable(&mut region, row)?;
+                        acc = Value::known(F::ZERO);
+                    } else {
+                        self.config.s_compose.enable(&mut region, row)?;
+                    }
+                    byte.copy_advice(|| "digest byte", &mut region, cells[BYTE], row)?;
+                    acc = acc * Value::known(F::from(256)) + byte.value().copied();
+                    let cell = assign_value(&mut region, cells[ACC], row, acc)?;
+                    if row + 1 == LIMB_BYTES {
+                        region.constrain_equal(cell.cell(), digest.hi.cell())?;
+                    } else if row + 1 == 2 * LIMB_BYTES {
+                        region.constrain_equal(cell.cell(), digest.lo.cell())?;
+                    }
+                }
+                Ok(())
+            },
+        )
+    }
+}
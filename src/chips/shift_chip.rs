@@ -0,0 +1,809 @@
+//! Shift chip for SHL, SHR, SAR, BYTE and SIGNEXTEND
+//!
+//! The shift amount is split into its low byte and the rest, and the low byte
+//! is mapped to a power of two through a fixed lookup table. Amounts that do
+//! not fit the table select a zero power. Shifting then becomes arithmetic on
+//! words:
+//!
+//! - `SHL`: `x * 2^s mod 2^256`
+//! - `SHR`: `x / 2^s`
+//! - `SAR`: `!(!x / 2^s)` for negative `x`
+//!
+//! A zero power makes the product and the quotient 0, which matches the EVM for
+//! shifts of 256 bits or more. BYTE and SIGNEXTEND use the same table with
+//! their own exponent encodings and read the byte or sign bit out of the
+//! quotient limbs.
+
+use alloy_primitives::U256;
+use halo2_proofs::{
+    circuit::{AssignedCell, Layouter, Value},
+    pasta::group::ff::PrimeField,
+    plonk::{Advice, Column, ConstraintSystem, Error, Expression, Selector},
+    poly::Rotation,
+};
+use std::marker::PhantomData;
+
+use crate::chips::comparator_chip::ComparatorChip;
+use crate::chips::div_chip::{DivChip, DivChipConfig, DivOp};
+use crate::chips::evm_chip::{OpCode, u64_to_field};
+use crate::chips::mul_add_chip::MulAddChip;
+use crate::chips::tables::PowerTableConfig;
+use crate::chips::word_chip::{
+    AssignedWord, LIMB_BYTES, WordChip, WordChipConfig, assign_constant_word, assign_value,
+    limb_shift, limb_value, word_limbs,
+};
+
+/// Shift or byte operation performed by an opcode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShiftOp {
+    /// `value << shift`
+    Shl,
+    /// `value >> shift`, filling with zeros
+    Shr,
+    /// `value >> shift`, filling with the sign bit
+    Sar,
+    /// Byte `i` of `value`, counting from the most significant byte
+    Byte,
+    /// Sign extension of the low `b + 1` bytes of `value`
+    SignExtend,
+}
+
+impl ShiftOp {
+    /// The shift or byte operation performed by an opcode, if any
+    pub fn from_opcode(op: OpCode) -> Option<Self> {
+        match op {
+            OpCode::Shl => Some(ShiftOp::Shl),
+            OpCode::Shr => Some(ShiftOp::Shr),
+            OpCode::Sar => Some(ShiftOp::Sar),
+            OpCode::Byte => Some(ShiftOp::Byte),
+            OpCode::SignExtend => Some(ShiftOp::SignExtend),
+            _ => None,
+        }
+    }
+}
+
+/// Exponent encoding of a power-of-two table lookup
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerKind {
+    /// `2^e`, the multiplier of a shift by `e` bits
+    Shift,
+    /// `2^(8 * (31 - e))`, the weight of byte `e`, or 0 past the last byte
+    Byte,
+    /// `2^(8 * e + 7)`, the sign bit of a `(e + 1)`-byte value, or 0 past 31 bytes
+    SignBit,
+}
+
+impl PowerKind {
+    /// Every encoding, in table order
+    pub const ALL: [PowerKind; 3] = [PowerKind::Shift, PowerKind::Byte, PowerKind::SignBit];
+
+    /// Tag identifying the encoding in the table
+    pub fn tag(&self) -> u64 {
+        match self {
+            PowerKind::Shift => 1,
+            PowerKind::Byte => 2,
+            PowerKind::SignBit => 3,
+        }
+    }
+
+    /// Power of two for an exponent byte
+    pub fn power(&self, e: u64) -> U256 {
+        let bits = match self {
+            PowerKind::Shift => Some(e),
+            PowerKind::Byte => (e < 32).then(|| 8 * (31 - e)),
+            PowerKind::SignBit => (e < 31).then(|| 8 * e + 7),
+        };
+        match bits {
+            Some(bits) if bits < 256 => U256::from(1u64) << bits as usize,
+            _ => U256::ZERO,
+        }
+    }
+}
+
+/// Configuration for the ShiftChip
+#[derive(Clone, Debug)]
+pub struct ShiftChipConfig {
+    /// Advice columns; each gate documents its own row layout
+    pub cells: [Column<Advice>; 5],
+    /// Power lookup with the `Shift` encoding
+    pub s_power_shift: Selector,
+    /// Power lookup with the `Byte` encoding
+    pub s_power_byte: Selector,
+    /// Power lookup with the `SignBit` encoding
+    pub s_power_sign_bit: Selector,
+    /// Conditional bitwise NOT of a word
+    pub s_not: Selector,
+    /// Split of the lowest bit off a limb
+    pub s_low_bit: Selector,
+    /// Split of the lowest byte off a limb
+    pub s_low_byte: Selector,
+    /// Sign extension result
+    pub s_sign_extend: Selector,
+    /// Power-of-two table
+    pub table: PowerTableConfig,
+    /// Division gadget, which also carries the comparator and multiply-add
+    pub div_config: DivChipConfig,
+    /// Range checks for the split values
+    pub word_config: WordChipConfig,
+}
+
+/// Chip constraining shift and byte opcodes over word limbs
+pub struct ShiftChip<F: PrimeField> {
+    config: ShiftChipConfig,
+    _marker: PhantomData<F>,
+}
+
+impl<F: PrimeField> ShiftChip<F> {
+    /// Construct a new ShiftChip
+    pub fn construct(config: ShiftChipConfig) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Configure the power lookup and the shift gates
+    ///
+    /// # Arguments
+    ///
+    /// * `cells` - Advice columns, which may be shared with other word gadgets
+    /// * `div_config` - Division gadget used for right shifts
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        cells: [Column<Advice>; 5],
+        div_config: DivChipConfig,
+    ) -> ShiftChipConfig {
+        for column in cells {
+            meta.enable_equality(column);
+        }
+
+        let s_power_shift = meta.complex_selector();
+        let s_power_byte = meta.complex_selector();
+        let s_power_sign_bit = meta.complex_selector();
+        let s_not = meta.selector();
+        let s_low_bit = meta.selector();
+        let s_low_byte = meta.selector();
+        let s_sign_extend = meta.selector();
+
+        let table = PowerTableConfig::configure(meta);
+        let one = Expression::Constant(F::ONE);
+
+        // Gate: power of two of an exponent word. Rows:
+        //   [e.lo,   e.hi,   e_byte,   rest,  inv  ]
+        //   [pow.lo, pow.hi, in_range, t.lo,  t.hi ]
+        // `e.lo = e_byte + 256 * rest` with `rest` range checked, and `in_range`
+        // is `e.hi + rest == 0`. The power is the table entry `t` for `e_byte`
+        // when in range and 0 otherwise.
+        meta.create_gate("shift_power", |meta| {
+            let s = meta.query_selector(s_power_shift)
+                + meta.query_selector(s_power_byte)
+                + meta.query_selector(s_power_sign_bit);
+            let mut q =
+                |column: usize, rotation: Rotation| meta.query_advice(cells[column], rotation);
+            let (e_lo, e_hi) = (q(0, Rotation::cur()), q(1, Rotation::cur()));
+            let (e_byte, rest, inv) = (
+                q(2, Rotation::cur()),
+                q(3, Rotation::cur()),
+                q(4, Rotation::cur()),
+            );
+            let (pow_lo, pow_hi) = (q(0, Rotation::next()), q(1, Rotation::next()));
+            let in_range = q(2, Rotation::next());
+            let (t_lo, t_hi) = (q(3, Rotation::next()), q(4, Rotation::next()));
+            let radix = Expression::Constant(u64_to_field::<F>(256));
+            let high = e_hi + rest.clone();
+
+            vec![
+                s.clone() * (e_lo - e_byte - rest * radix),
+                s.clone() * (in_range.clone() - one.clone() + high.clone() * inv),
+                s.clone() * high * in_range.clone(),
+                s.clone() * (pow_lo - in_range.clone() * t_lo),
+                s * (pow_hi - in_range * t_hi),
+            ]
+        });
+
+        // Lookup: (tag, e_byte, t.lo, t.hi) must be a row of the power table
+        meta.lookup(|meta| {
+            let selectors = [s_power_shift, s_power_byte, s_power_sign_bit]
+                .map(|selector| meta.query_selector(selector));
+            let tag = selectors
+                .iter()
+                .zip(PowerKind::ALL)
+                .map(|(s, kind)| s.clone() * Expression::Constant(u64_to_field::<F>(kind.tag())))
+                .reduce(|acc, term| acc + term)
+                .expect("three power encodings");
+            let s = selectors
+                .into_iter()
+                .reduce(|acc, s| acc + s)
+                .expect("three power encodings");
+            let e_byte = meta.query_advice(cells[2], Rotation::cur());
+            let t_lo = meta.query_advice(cells[3], Rotation::next());
+            let t_hi = meta.query_advice(cells[4], Rotation::next());
+
+            vec![
+                (tag, table.tag),
+                (s.clone() * e_byte, table.exponent),
+                (s.clone() * t_lo, table.lo),
+                (s * t_hi, table.hi),
+            ]
+        });
+
+        // Gate: out = !x when sign is set, x otherwise. Row:
+        //   [x.lo, x.hi, sign, out.lo, out.hi]
+        meta.create_gate("shift_not", |meta| {
+            let s = meta.query_selector(s_not);
+            let mut q = |column: usize| meta.query_advice(cells[column], Rotation::cur());
+            let (x_lo, x_hi, sign, out_lo, out_hi) = (q(0), q(1), q(2), q(3), q(4));
+            let max = Expression::Constant(limb_shift::<F>() - F::ONE);
+            let two = Expression::Constant(F::from(2));
+
+            vec![
+                s.clone()
+                    * (out_lo - x_lo.clone() - sign.clone() * (max.clone() - two.clone() * x_lo)),
+                s * (out_hi - x_hi.clone() - sign * (max - two * x_hi)),
+            ]
+        });
+
+        // Gate: value = 2 * high + low with a boolean low bit, or
+        // value = 256 * high + low with a byte checked by the caller. Row:
+        //   [value, high, low]
+        meta.create_gate("shift_low_bits", |meta| {
+            let s_bit = meta.query_selector(s_low_bit);
+            let s_byte = meta.query_selector(s_low_byte);
+            let mut q = |column: usize| meta.query_advice(cells[column], Rotation::cur());
+            let (value, high, low) = (q(0), q(1), q(2));
+            let two = Expression::Constant(F::from(2));
+            let radix = Expression::Constant(u64_to_field::<F>(256));
+
+            vec![
+                s_bit.clone() * (value.clone() - two * high.clone() - low.clone()),
+                s_bit * low.clone() * (one.clone() - low.clone()),
+                s_byte * (value - radix * high - low),
+            ]
+        });
+
+        // Gate: sign extension. Rows:
+        //   [r.lo, r.hi, n.lo,      n.hi,   sign  ]
+        //   [x.lo, x.hi, pow_zero,  out.lo, out.hi]
+        // `r` holds the bits below the sign bit and `n = -pow` when the sign is
+        // set, whose bits cover everything from the sign bit up. The limbs of r
+        // and n never overlap, so their sum needs no carry. A zero power (31
+        // bytes or more) leaves x unchanged.
+        meta.create_gate("shift_sign_extend", |meta| {
+            let s = meta.query_selector(s_sign_extend);
+            let mut q =
+                |column: usize, rotation: Rotation| meta.query_advice(cells[column], rotation);
+            let (r_lo, r_hi) = (q(0, Rotation::cur()), q(1, Rotation::cur()));
+            let (n_lo, n_hi) = (q(2, Rotation::cur()), q(3, Rotation::cur()));
+            let sign = q(4, Rotation::cur());
+            let (x_lo, x_hi) = (q(0, Rotation::next()), q(1, Rotation::next()));
+            let pow_zero = q(2, Rotation::next());
+            let (out_lo, out_hi) = (q(3, Rotation::next()), q(4, Rotation::next()));
+            let extend = one.clone() - pow_zero;
+
+            vec![
+                s.clone()
+                    * (out_lo
+                        - x_lo.clone()
+                        - extend.clone() * (r_lo + sign.clone() * n_lo - x_lo)),
+                s * (out_hi - x_hi.clone() - extend * (r_hi + sign * n_hi - x_hi)),
+            ]
+        });
+
+        ShiftChipConfig {
+            cells,
+            s_power_shift,
+            s_power_byte,
+            s_power_sign_bit,
+            s_not,
+            s_low_bit,
+            s_low_byte,
+            s_sign_extend,
+            table,
+            word_config: div_config.word_config.clone(),
+            div_config,
+        }
+    }
+
+    /// Load the power-of-two lookup table
+    pub fn load_table(&self, layouter: &mut impl Layouter<F>) -> Result<(), Error> {
+        self.config.table.load(layouter)
+    }
+
+    /// Constrain `result = op(a, b)`
+    ///
+    /// # Arguments
+    ///
+    /// * `op` - Shift or byte operation to apply
+    /// * `a` - Top stack operand (shift amount, byte index or byte count)
+    /// * `b` - Second stack operand (value)
+    /// * `result` - Word claimed to hold the result
+    pub fn assign(
+        &self,
+        mut layouter: impl Layouter<F>,
+        op: ShiftOp,
+        a: &AssignedWord<F>,
+        b: &AssignedWord<F>,
+        result: &AssignedWord<F>,
+    ) -> Result<(), Error> {
+        let div_chip = DivChip::construct(self.config.div_config.clone());
+        let comparator =
+            ComparatorChip::construct(self.config.div_config.comparator_config.clone());
+
+        let computed = match op {
+            ShiftOp::Shl => {
+                let power = self.power(layouter.namespace(|| "power"), PowerKind::Shift, a)?;
+                let zero = self.constant_word(layouter.namespace(|| "zero"), U256::ZERO)?;
+                let mul_add = MulAddChip::construct(self.config.div_config.mul_add_config.clone());
+                let [lo, _] =
+                    mul_add.mul_add(layouter.namespace(|| "b * 2^a"), b, &power, &zero)?;
+                lo
+            }
+            ShiftOp::Shr => {
+                let power = self.power(layouter.namespace(|| "power"), PowerKind::Shift, a)?;
+                div_chip.divide(
+                    layouter.namespace(|| "b / 2^a"),
+                    DivOp::Div,
+                    b,
+                    None,
+                    &power,
+                )?
+            }
+            ShiftOp::Sar => {
+                let power = self.power(layouter.namespace(|| "power"), PowerKind::Shift, a)?;
+                let sign = comparator.sign_bit(layouter.namespace(|| "sign"), b)?;
+                let flipped = self.not_if(layouter.namespace(|| "flip b"), b, &sign)?;
+                let shifted = div_chip.divide(
+                    layouter.namespace(|| "b / 2^a"),
+                    DivOp::Div,
+                    &flipped,
+                    None,
+                    &power,
+                )?;
+                self.not_if(layouter.namespace(|| "flip back"), &shifted, &sign)?
+            }
+            ShiftOp::Byte => {
+                let power = self.power(layouter.namespace(|| "power"), PowerKind::Byte, a)?;
+                let shifted = div_chip.divide(
+                    layouter.namespace(|| "b / 2^k"),
+                    DivOp::Div,
+                    b,
+                    None,
+                    &power,
+                )?;
+                self.low_bits(layouter.namespace(|| "byte"), &shifted.lo, false)?
+            }
+            ShiftOp::SignExtend => {
+                let power = self.power(layouter.namespace(|| "power"), PowerKind::SignBit, a)?;
+                let shifted = div_chip.divide(
+                    layouter.namespace(|| "b / 2^k"),
+                    DivOp::Div,
+                    b,
+                    None,
+                    &power,
+                )?;
+                let low = div_chip.divide(
+                    layouter.namespace(|| "b % 2^k"),
+                    DivOp::Mod,
+                    b,
+                    None,
+                    &power,
+                )?;
+                let sign = self.low_bits(layouter.namespace(|| "sign"), &shifted.lo, true)?;
+                let negated =
+                    div_chip.negate_if(layouter.namespace(|| "-2^k"), &power, &sign.lo, None)?;
+                let power_zero =
+                    comparator.is_equal(layouter.namespace(|| "2^k == 0"), &power, None)?;
+                self.sign_extend(
+                    layouter.namespace(|| "sign extend"),
+                    b,
+                    &low,
+                    &negated,
+                    &sign.lo,
+                    &power_zero.lo,
+                )?
+            }
+        };
+
+        layouter.assign_region(
+            || "shift result",
+            |mut region| {
+                region.constrain_equal(computed.lo.cell(), result.lo.cell())?;
+                region.constrain_equal(computed.hi.cell(), result.hi.cell())
+            },
+        )
+    }
+
+    /// Power of two for the low byte of `e` under an encoding, 0 when `e` does
+    /// not fit in a byte
+    fn power(
+        &self,
+        mut layouter: impl Layouter<F>,
+        kind: PowerKind,
+        e: &AssignedWord<F>,
+    ) -> Result<AssignedWord<F>, Error> {
+        let cells = self.config.cells;
+        let (power, rest) = layouter.assign_region(
+            || "power",
+            |mut region| {
+                let selector = match kind {
+                    PowerKind::Shift => self.config.s_power_shift,
+                    PowerKind::Byte => self.config.s_power_byte,
+                    PowerKind::SignBit => self.config.s_power_sign_bit,
+                };
+                selector.enable(&mut region, 0)?;
+
+                let e_lo = e.lo.copy_advice(|| "e lo", &mut region, cells[0], 0)?;
+                let e_hi = e.hi.copy_advice(|| "e hi", &mut region, cells[1], 0)?;
+
+                let e_lo = e_lo.value().map(|v| limb_value(*v));
+                let e_byte = e_lo.map(|lo| (lo & 0xff) as u64);
+                let rest = e_lo.map(|lo| F::from_u128(lo >> 8));
+                let high = e_hi.value().copied() + rest;
+                let in_range = high.map(|h| h.is_zero_vartime());
+                let table = e_byte.map(|e| word_limbs::<F>(kind.power(e)));
+                let power = table
+                    .zip(in_range)
+                    .map(|(t, in_range)| if in_range { t } else { [F::ZERO; 2] });
+
+                assign_value(&mut region, cells[2], 0, e_byte.map(F::from))?;
+                let rest = assign_value(&mut region, cells[3], 0, rest)?;
+                assign_value(
+                    &mut region,
+                    cells[4],
+                    0,
+                    high.map(|h| h.invert().unwrap_or(F::ZERO)),
+                )?;
+
+                let [pow_lo, pow_hi] = power.transpose_array();
+                let [t_lo, t_hi] = table.transpose_array();
+                let power = AssignedWord {
+                    lo: assign_value(&mut region, cells[0], 1, pow_lo)?,
+                    hi: assign_value(&mut region, cells[1], 1, pow_hi)?,
+                };
+                assign_value(
+                    &mut region,
+                    cells[2],
+                    1,
+                    in_range.map(|r| F::from(r as u64)),
+                )?;
+                assign_value(&mut region, cells[3], 1, t_lo)?;
+                assign_value(&mut region, cells[4], 1, t_hi)?;
+                Ok((power, rest))
+            },
+        )?;
+
+        WordChip::construct(self.config.word_config.clone()).range_check(
+            layouter.namespace(|| "exponent rest"),
+            &rest,
+            LIMB_BYTES - 1,
+        )?;
+        Ok(power)
+    }
+
+    /// `!x` when `sign` is set, `x` otherwise
+    fn not_if(
+        &self,
+        mut layouter: impl Layouter<F>,
+        x: &AssignedWord<F>,
+        sign: &AssignedCell<F, F>,
+    ) -> Result<AssignedWord<F>, Error> {
+        let cells = self.config.cells;
+        layouter.assign_region(
+            || "not if",
+            |mut region| {
+                self.config.s_not.enable(&mut region, 0)?;
+
+                x.lo.copy_advice(|| "x lo", &mut region, cells[0], 0)?;
+                x.hi.copy_advice(|| "x hi", &mut region, cells[1], 0)?;
+                let sign = sign.copy_advice(|| "sign", &mut region, cells[2], 0)?;
+
+                let out = x
+                    .value()
+                    .zip(sign.value())
+                    .map(|(x, sign)| word_limbs::<F>(if *sign == F::ONE { !x } else { x }));
+                let [out_lo, out_hi] = out.transpose_array();
+                Ok(AssignedWord {
+                    lo: assign_value(&mut region, cells[3], 0, out_lo)?,
+                    hi: assign_value(&mut region, cells[4], 0, out_hi)?,
+                })
+            },
+        )
+    }
+
+    /// Lowest bit or byte of a range checked limb, as a word
+    fn low_bits(
+        &self,
+        mut layouter: impl Layouter<F>,
+        limb: &AssignedCell<F, F>,
+        bit: bool,
+    ) -> Result<AssignedWord<F>, Error> {
+        let cells = self.config.cells;
+        let (high, low) = layouter.assign_region(
+            || "low bits",
+            |mut region| {
+                if bit {
+                    self.config.s_low_bit.enable(&mut region, 0)?;
+                } else {
+                    self.config.s_low_byte.enable(&mut region, 0)?;
+                }
+
+                let value = limb.copy_advice(|| "limb", &mut region, cells[0], 0)?;
+                let value = value.value().map(|v| limb_value(*v));
+                let bits = if bit { 1 } else { 8 };
+                let high = value.map(|v| F::from_u128(v >> bits));
+                let low = value.map(|v| F::from_u128(v & ((1 << bits) - 1)));
+
+                let high = assign_value(&mut region, cells[1], 0, high)?;
+                let low = assign_value(&mut region, cells[2], 0, low)?;
+                let zero = assign_value(&mut region, cells[3], 0, Value::known(F::ZERO))?;
+                region.constrain_constant(zero.cell(), F::ZERO)?;
+                Ok((high, AssignedWord { lo: low, hi: zero }))
+            },
+        )?;
+
+        let word_chip = WordChip::construct(self.config.word_config.clone());
+        word_chip.range_check(layouter.namespace(|| "high"), &high, LIMB_BYTES)?;
+        if !bit {
+            word_chip.range_check(layouter.namespace(|| "low"), &low.lo, 1)?;
+        }
+        Ok(low)
+    }
+
+    /// Sign extension of `x` from the bits of `low` and the negated power
+    fn sign_extend(
+        &self,
+        mut layouter: impl Layouter<F>,
+        x: &AssignedWord<F>,
+        low: &AssignedWord<F>,
+        negated: &AssignedWord<F>,
+        sign: &AssignedCell<F, F>,
+        power_zero: &AssignedCell<F, F>,
+    ) -> Result<AssignedWord<F>, Error> {
+        let cells = self.config.cells;
+        layouter.assign_region(
+            || "sign extend",
+            |mut region| {
+                self.config.s_sign_extend.enable(&mut region, 0)?;
+
+                let r_lo = low.lo.copy_advice(|| "r lo", &mut region, cells[0], 0)?;
+                let r_hi = low.hi.copy_advice(|| "r hi", &mut region, cells[1], 0)?;
+                let n_lo = negated
+                    .lo
+                    .copy_advice(|| "n lo", &mut region, cells[2], 0)?;
+                let n_hi = negated
+                    .hi
+                    .copy_advice(|| "n hi", &mut region, cells[3], 0)?;
+                let sign = sign.copy_advice(|| "sign", &mut region, cells[4], 0)?;
+                let x_lo = x.lo.copy_advice(|| "x lo", &mut region, cells[0], 1)?;
+                let x_hi = x.hi.copy_advice(|| "x hi", &mut region, cells[1], 1)?;
+                let power_zero =
+                    power_zero.copy_advice(|| "power zero", &mut region, cells[2], 1)?;
+
+                let value = |cell: &AssignedCell<F, F>| cell.value().copied();
+                let extend = value(&power_zero).map(|z| F::ONE - z);
+                let out =
+                    |x: Value<F>, r: Value<F>, n: Value<F>| x + extend * (r + value(&sign) * n - x);
+                Ok(AssignedWord {
+                    lo: assign_value(
+                        &mut region,
+                        cells[3],
+                        1,
+                        out(value(&x_lo), value(&r_lo), value(&n_lo)),
+                    )?,
+                    hi: assign_value(
+                        &mut region,
+                        cells[4],
+                        1,
+                        out(value(&x_hi), value(&r_hi), value(&n_hi)),
+                    )?,
+                })
+            },
+        )
+    }
+
+    /// A word pinned to a constant
+    fn constant_word(
+        &self,
+        mut layouter: impl Layouter<F>,
+        word: U256,
+    ) -> Result<AssignedWord<F>, Error> {
+        let cells = self.config.cells;
+        layouter.assign_region(
+            || "constant word",
+            |mut region| assign_constant_word(&mut region, [cells[0], cells[1]], 0, word),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chips::comparator_chip::ComparatorChip;
+    use crate::chips::mul_add_chip::MulAddChip;
+    use crate::chips::tables::ByteTableConfig;
+    use alloy_primitives::I256;
+    use halo2_proofs::{circuit::SimpleFloorPlanner, dev::MockProver, pasta::Fp, plonk::Circuit};
+
+    /// Test circuit checking `result = op(a, b)`
+    struct ShiftCircuit {
+        op: ShiftOp,
+        a: U256,
+        b: U256,
+        result: U256,
+    }
+
+    impl Circuit<Fp> for ShiftCircuit {
+        type Config = (ShiftChipConfig, Column<Advice>);
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self {
+                op: self.op,
+                a: U256::ZERO,
+                b: U256::ZERO,
+                result: U256::ZERO,
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            let constants = meta.fixed_column();
+            meta.enable_constant(constants);
+            let byte_table = ByteTableConfig::configure(meta);
+            let word_config = WordChip::configure(meta, byte_table);
+            let words = meta.advice_column();
+            meta.enable_equality(words);
+
+            let cells = [(); 5].map(|_| meta.advice_column());
+            let comparator_config = ComparatorChip::configure(meta, cells, word_config.clone());
+            let mul_add_config = MulAddChip::configure(meta, cells, word_config);
+            let div_config = DivChip::configure(meta, cells, comparator_config, mul_add_config);
+            (ShiftChip::configure(meta, cells, div_config), words)
+        }
+
+        fn synthesize(
+            &self,
+            (config, column): Self::Config,
+            mut layouter: impl Layouter<Fp>,
+        ) -> Result<(), Error> {
+            config.word_config.byte_table.load(&mut layouter)?;
+            let chip = ShiftChip::construct(config.clone());
+            chip.load_table(&mut layouter)?;
+
+            let [a, b, result] = layouter.assign_region(
+                || "words",
+                |mut region| {
+                    let mut words = Vec::with_capacity(3);
+                    for (i, word) in [self.a, self.b, self.result].into_iter().enumerate() {
+                        let [lo, hi] = word_limbs::<Fp>(word);
+                        let lo = assign_value(&mut region, column, 2 * i, Value::known(lo))?;
+                        let hi = assign_value(&mut region, column, 2 * i + 1, Value::known(hi))?;
+                        words.push(AssignedWord { lo, hi });
+                    }
+                    Ok([words[0].clone(), words[1].clone(), words[2].clone()])
+                },
+            )?;
+
+            let word_chip = WordChip::construct(config.word_config.clone());
+            for word in [&a, &b, &result] {
+                word_chip.range_check_word(layouter.namespace(|| "word"), word)?;
+            }
+            chip.assign(layouter, self.op, &a, &b, &result)
+        }
+    }
+
+    fn run(op: ShiftOp, a: U256, b: U256, result: U256) -> MockProver<Fp> {
+        let circuit = ShiftCircuit { op, a, b, result };
+        MockProver::run(11, &circuit, vec![]).unwrap()
+    }
+
+    /// Native EVM semantics of each operation
+    fn expected(op: ShiftOp, a: U256, b: U256) -> U256 {
+        let small = (a < U256::from(256u64)).then(|| a.to::<usize>());
+        match op {
+            ShiftOp::Shl => small.map_or(U256::ZERO, |s| b << s),
+            ShiftOp::Shr => small.map_or(U256::ZERO, |s| b >> s),
+            ShiftOp::Sar => {
+                let negative = b.bit(255);
+                match small {
+                    Some(s) => I256::from_raw(b).asr(s).into_raw(),
+                    None if negative => U256::MAX,
+                    None => U256::ZERO,
+                }
+            }
+            ShiftOp::Byte => match small {
+                Some(i) if i < 32 => U256::from(b.byte(31 - i)),
+                _ => U256::ZERO,
+            },
+            ShiftOp::SignExtend => match small {
+                Some(n) if n < 31 => {
+                    let bit = 8 * n + 7;
+                    let mask = (U256::from(1u64) << (bit + 1)) - U256::from(1u64);
+                    if b.bit(bit) { b | !mask } else { b & mask }
+                }
+                _ => b,
+            },
+        }
+    }
+
+    const OPS: [ShiftOp; 5] = [
+        ShiftOp::Shl,
+        ShiftOp::Shr,
+        ShiftOp::Sar,
+        ShiftOp::Byte,
+        ShiftOp::SignExtend,
+    ];
+
+    fn amounts() -> Vec<U256> {
+        [0u64, 1, 4, 30, 31, 127, 128, 255, 256]
+            .into_iter()
+            .map(U256::from)
+            .chain([U256::from(1u64) << 128, U256::MAX])
+            .collect()
+    }
+
+    fn values() -> Vec<U256> {
+        vec![
+            U256::ZERO,
+            U256::from(0x80u64),
+            U256::from(0x1234_5678_9abc_def0u64) * U256::from(u128::MAX),
+            U256::MAX - U256::from(0x7fu64),
+        ]
+    }
+
+    #[test]
+    fn test_shifts() {
+        for op in OPS {
+            for a in amounts() {
+                for b in values() {
+                    run(op, a, b, expected(op, a, b)).assert_satisfied();
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_power_table() {
+        assert_eq!(PowerKind::Shift.power(255), U256::from(1u64) << 255);
+        assert_eq!(PowerKind::Byte.power(0), U256::from(1u64) << 248);
+        assert_eq!(PowerKind::Byte.power(31), U256::from(1u64));
+        assert_eq!(PowerKind::Byte.power(32), U256::ZERO);
+        assert_eq!(PowerKind::SignBit.power(0), U256::from(0x80u64));
+        assert_eq!(PowerKind::SignBit.power(30), U256::from(1u64) << 247);
+        assert_eq!(PowerKind::SignBit.power(31), U256::ZERO);
+    }
+
+    #[test]
+    fn test_wrong_shift_fails() {
+        let one = U256::from(1u64);
+        let cases = [
+            (ShiftOp::Shl, U256::from(4u64), one, U256::from(8u64)),
+            // Shifts of 256 bits or more clear the word
+            (ShiftOp::Shl, U256::from(256u64), one, one),
+            (ShiftOp::Shr, one << 128, U256::MAX, U256::MAX),
+            // SAR keeps the sign, SHR does not
+            (ShiftOp::Sar, U256::from(4u64), U256::MAX, U256::MAX >> 4),
+            (
+                ShiftOp::Byte,
+                U256::from(32u64),
+                U256::MAX,
+                U256::from(0xffu64),
+            ),
+            (
+                ShiftOp::SignExtend,
+                U256::ZERO,
+                U256::from(0x80u64),
+                U256::from(0x80u64),
+            ),
+        ];
+        for (op, a, b, result) in cases {
+            assert_ne!(expected(op, a, b), result);
+            assert!(
+                run(op, a, b, result).verify().is_err(),
+                "{:?}({}, {})",
+                op,
+                a,
+                b
+            );
+        }
+    }
+}
@@ -0,0 +1,307 @@
+//! Poseidon sparse Merkle tree updates
+//!
+//! Proves that setting a storage slot from one value to another takes the
+//! tree from one root to the other, following `utils::smt`. The slot's key is
+//! hashed from its address and slot limbs and decomposed into the bits of its
+//! leaf's path; both leaves are then hashed up the same path with the same
+//! siblings.
+
+use halo2_gadgets::poseidon::primitives::{P128Pow5T3, Spec};
+use halo2_proofs::{
+    arithmetic::Field,
+    circuit::{AssignedCell, Layouter, Value},
+    pasta::group::ff::PrimeField,
+    plonk::{Advice, Column, ConstraintSystem, Error, Expression, Selector},
+    poly::Rotation,
+};
+use std::marker::PhantomData;
+
+use crate::chips::hash_chip::{HashChip, HashChipConfig, POSEIDON_RATE, POSEIDON_WIDTH};
+use crate::chips::word_chip::{AssignedWord, WordChip, WordChipConfig, assign_value, limb_value};
+use crate::utils::smt::SMT_DEPTH;
+
+/// Bytes the rest of a key above its path is range checked to
+const REST_BYTES: usize = 24;
+
+// Columns of a path bit row
+const BIT: usize = 0;
+const ACC: usize = 1;
+
+// Columns of a level row
+const SIBLING: usize = 1;
+const OLD: usize = 2;
+const NEW: usize = 3;
+const LEFT_OLD: usize = 4;
+const RIGHT_OLD: usize = 5;
+const LEFT_NEW: usize = 6;
+const RIGHT_NEW: usize = 7;
+
+// Columns of a leaf row
+const LO: usize = 0;
+const HI: usize = 1;
+const INV_LO: usize = 2;
+const INV_HI: usize = 3;
+const ZERO_LO: usize = 4;
+const ZERO_HI: usize = 5;
+const HASH: usize = 6;
+const LEAF: usize = 7;
+
+/// Cells holding the roots before and after an update
+pub type Roots<F> = (AssignedCell<F, F>, AssignedCell<F, F>);
+
+/// Configuration for the SmtChip
+#[derive(Clone, Debug)]
+pub struct SmtChipConfig<F: Field> {
+    /// Advice columns; the gates document their roles
+    pub cells: [Column<Advice>; 8],
+    /// Path bit row followed by the rest of the key
+    pub s_bit: Selector,
+    /// Tree level row
+    pub s_level: Selector,
+    /// Leaf row
+    pub s_leaf: Selector,
+    /// Poseidon hashes of keys, leaves and nodes
+    pub hash_config: HashChipConfig<F>,
+    /// Range check for the rest of a key
+    pub word_config: WordChipConfig,
+}
+
+/// Chip proving sparse Merkle tree updates
+pub struct SmtChip<F: Field> {
+    config: SmtChipConfig<F>,
+    _marker: PhantomData<F>,
+}
+
+impl<F> SmtChip<F>
+where
+    F: PrimeField,
+    P128Pow5T3: Spec<F, POSEIDON_WIDTH, POSEIDON_RATE>,
+{
+    /// Construct a new SmtChip
+    pub fn construct(config: SmtChipConfig<F>) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Configure the path, level and leaf gates
+    ///
+    /// A bit row holds a path bit and `acc`, the key shifted right by the
+    /// row's level: `acc = 2 * acc_next + bit`. A level row holds the path bit,
+    /// the sibling, the old and new nodes and the children they make:
+    /// `(left, right)` is `(node, sibling)`, swapped when the bit is set. A
+    /// leaf row holds the value limbs, their zero flags with the inverses
+    /// proving them, the hash of the key and value, and the leaf: the hash, or
+    /// zero for a zero value.
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        hash_config: HashChipConfig<F>,
+        word_config: WordChipConfig,
+    ) -> SmtChipConfig<F> {
+        let cells = [(); 8].map(|_| meta.advice_column());
+        for column in cells {
+            meta.enable_equality(column);
+        }
+        let s_bit = meta.selector();
+        let s_level = meta.selector();
+        let s_leaf = meta.selector();
+        let one = || Expression::Constant(F::ONE);
+
+        meta.create_gate("smt path bit", |meta| {
+            let s = meta.query_selector(s_bit);
+            let bit = meta.query_advice(cells[BIT], Rotation::cur());
+            let acc = meta.query_advice(cells[ACC], Rotation::cur());
+            let acc_next = meta.query_advice(cells[ACC], Rotation::next());
+            vec![
+                s.clone() * bit.clone() * (one() - bit.clone()),
+                s * (acc - Expression::Constant(F::from(2)) * acc_next - bit),
+            ]
+        });
+
+        meta.create_gate("smt level", |meta| {
+            let s = meta.query_selector(s_level);
+            let query = |meta: &mut halo2_proofs::plonk::VirtualCells<'_, F>, column: usize| {
+                meta.query_advice(cells[column], Rotation::cur())
+            };
+            let bit = query(meta, BIT);
+            let sibling = query(meta, SIBLING);
+            let mut constraints = Vec::new();
+            for (node, left, right) in [(OLD, LEFT_OLD, RIGHT_OLD), (NEW, LEFT_NEW, RIGHT_NEW)] {
+                let node = query(meta, node);
+                let left = query(meta, left);
+                let right = query(meta, right);
+                constraints.push(
+                    s.clone()
+                        * (left.clone()
+                            - node.clone()
+                            - bit.clone() * (sibling.clone() - node.clone())),
+                );
+                constraints.push(s.clone() * (right - (node + sibling.clone() - left)));
+            }
+            constraints
+        });
+
+        meta.create_gate("smt leaf", |meta| {
+            let s = meta.query_selector(s_leaf);
+            let query = |meta: &mut halo2_proofs::plonk::VirtualCells<'_, F>, column: usize| {
+                meta.query_advice(cells[column], Rotation::cur())
+            };
+            let mut constraints = Vec::new();
+            for (limb, inv, zero) in [(LO, INV_LO, ZERO_LO), (HI, INV_HI, ZERO_HI)] {
+                let limb = query(meta, limb);
+                let inv = query(meta, inv);
+                let zero = query(meta, zero);
+                constraints.push(s.clone() * (zero.clone() - one() + limb.clone() * inv));
+                constraints.push(s.clone() * limb * zero);
+            }
+            let is_zero = query(meta, ZERO_LO) * query(meta, ZERO_HI);
+            constraints.push(s * (query(meta, LEAF) - (one() - is_zero) * query(meta, HASH)));
+            constraints
+        });
+
+        SmtChipConfig {
+            cells,
+            s_bit,
+            s_level,
+            s_leaf,
+            hash_config,
+            word_config,
+        }
+    }
+
+    /// Prove an update of the slot with the given limbs
+    ///
+    /// # Arguments
+    ///
+    /// * `slot` - `(lo, hi)` limbs of the address, then of the slot
+    /// * `old` - Value before the update
+    /// * `new` - Value after the update
+    /// * `siblings` - Siblings of the leaf's path, from the leaf up
+    ///
+    /// # Returns
+    ///
+    /// Cells holding the roots before and after the update
+    pub fn update(
+        &self,
+        mut layouter: impl Layouter<F>,
+        slot: [AssignedCell<F, F>; 4],
+        old: &AssignedWord<F>,
+        new: &AssignedWord<F>,
+        siblings: &[Value<F>],
+    ) -> Result<Roots<F>, Error> {
+        let config = &self.config;
+        let hash_chip = HashChip::construct(config.hash_config.clone());
+        let key = hash_chip.hash(layouter.namespace(|| "slot key"), slot)?;
+
+        // Decompose the key into its path bits and the rest above them
+        let (bits, rest) = layouter.assign_region(
+            || "smt path",
+            |mut region| {
+                let mut acc = key.copy_advice(|| "key", &mut region, config.cells[ACC], 0)?;
+                let mut bits = Vec::with_capacity(SMT_DEPTH);
+                let inv_2 = F::from(2).invert().unwrap();
+                for row in 0..SMT_DEPTH {
+                    config.s_bit.enable(&mut region, row)?;
+                    let bit = acc
+                        .value()
+                        .map(|acc| F::from((limb_value(*acc) & 1) as u64));
+                    let next = acc.value().zip(bit).map(|(acc, bit)| (*acc - bit) * inv_2);
+                    bits.push(assign_value(&mut region, config.cells[BIT], row, bit)?);
+                    acc = assign_value(&mut region, config.cells[ACC], row + 1, next)?;
+                }
+                Ok((bits, acc))
+            },
+        )?;
+        WordChip::construct(config.word_config.clone()).range_check(
+            layouter.namespace(|| "key rest"),
+            &rest,
+            REST_BYTES,
+        )?;
+
+        let mut nodes = Vec::with_capacity(2);
+        for (name, word) in [("old", old), ("new", new)] {
+            let hash = hash_chip.hash(
+                layouter.namespace(|| format!("{} leaf hash", name)),
+                [key.clone(), word.lo.clone(), word.hi.clone()],
+            )?;
+            nodes.push(layouter.assign_region(
+                || format!("{} leaf", name),
+                |mut region| {
+                    config.s_leaf.enable(&mut region, 0)?;
+                    let mut zeros = Vec::with_capacity(2);
+                    for (limb, column, inv, zero) in [
+                        (&word.lo, LO, INV_LO, ZERO_LO),
+                        (&word.hi, HI, INV_HI, ZERO_HI),
+                    ] {
+                        limb.copy_advice(|| "limb", &mut region, config.cells[column], 0)?;
+                        let inverse = limb.value().map(|v| v.invert().unwrap_or(F::ZERO));
+                        assign_value(&mut region, config.cells[inv], 0, inverse)?;
+                        let is_zero = limb.value().map(|v| F::from(v.is_zero_vartime() as u64));
+                        assign_value(&mut region, config.cells[zero], 0, is_zero)?;
+                        zeros.push(is_zero);
+                    }
+                    hash.copy_advice(|| "hash", &mut region, config.cells[HASH], 0)?;
+                    let leaf = hash
+                        .value()
+                        .zip(zeros[0].zip(zeros[1]))
+                        .map(|(hash, (lo, hi))| *hash * (F::ONE - lo * hi));
+                    assign_value(&mut region, config.cells[LEAF], 0, leaf)
+                },
+            )?);
+        }
+        let [mut old_node, mut new_node] =
+            <[_; 2]>::try_from(nodes).map_err(|_| Error::Synthesis)?;
+
+        // Hash both leaves up the same path
+        for (level, (bit, sibling)) in bits.iter().zip(siblings).enumerate() {
+            let children = layouter.assign_region(
+                || format!("smt level {}", level),
+                |mut region| {
+                    config.s_level.enable(&mut region, 0)?;
+                    bit.copy_advice(|| "bit", &mut region, config.cells[BIT], 0)?;
+                    assign_value(&mut region, config.cells[SIBLING], 0, *sibling)?;
+                    let mut children = Vec::with_capacity(4);
+                    for (node, column, left, right) in [
+                        (&old_node, OLD, LEFT_OLD, RIGHT_OLD),
+                        (&new_node, NEW, LEFT_NEW, RIGHT_NEW),
+                    ] {
+                        node.copy_advice(|| "node", &mut region, config.cells[column], 0)?;
+                        let pair = node.value().zip(*sibling).zip(bit.value()).map(
+                            |((node, sibling), bit)| {
+                                if *bit == F::ZERO {
+                                    (*node, sibling)
+                                } else {
+                                    (sibling, *node)
+                                }
+                            },
+                        );
+                        children.push(assign_value(
+                            &mut region,
+                            config.cells[left],
+                            0,
+                            pair.map(|(left, _)| left),
+                        )?);
+                        children.push(assign_value(
+                            &mut region,
+                            config.cells[right],
+                            0,
+                            pair.map(|(_, right)| right),
+                        )?);
+                    }
+                    Ok(children)
+                },
+            )?;
+            old_node = hash_chip.hash(
+                layouter.namespace(|| format!("old node {}", level)),
+                [children[0].clone(), children[1].clone()],
+            )?;
+            new_node = hash_chip.hash(
+                layouter.namespace(|| format!("new node {}", level)),
+                [children[2].clone(), children[3].clone()],
+            )?;
+        }
+
+        Ok((old_node, new_node))
+    }
+}
@@ -0,0 +1,350 @@
+//! SSTORE metering chip for EIP-2200 gas and refunds
+//!
+//! What an SSTORE costs and refunds depends on the value of its slot at the
+//! start of the transaction (original), before the write (current) and after
+//! it (new, the second stack operand). The chip takes the original and current
+//! values as witnesses and chains their hash onto the access commitment, so the
+//! trace commitment binds them. Equality and zero flags of the three values
+//! then select the step's dynamic gas and the change it makes to the refund
+//! counter, which the chip carries from one SSTORE to the next. The prices of
+//! the fork's `SStoreSchedule` are constants of the circuit.
+//!
+//! Writes in reverted frames are not rolled back, so neither are their
+//! refunds.
+
+use halo2_gadgets::poseidon::primitives::{P128Pow5T3, Spec};
+use halo2_proofs::{
+    arithmetic::Field,
+    circuit::{AssignedCell, Layouter, Value},
+    pasta::group::ff::PrimeField,
+    plonk::{Advice, Column, ConstraintSystem, Error, Expression, Selector},
+    poly::Rotation,
+};
+use std::marker::PhantomData;
+
+use crate::chips::comparator_chip::{ComparatorChip, ComparatorChipConfig};
+use crate::chips::evm_chip::{AssignedStep, SStoreSchedule, u64_to_field};
+use crate::chips::hash_chip::{HashChip, POSEIDON_RATE, POSEIDON_WIDTH};
+use crate::chips::word_chip::{AssignedWord, assign_value, word_limbs};
+use crate::utils::evm_parser::SStoreRecord;
+
+/// Elements hashed per SSTORE: the `(lo, hi)` limbs of the original and the
+/// current value
+pub const SSTORE_VALUE_INPUTS: usize = 4;
+
+/// The elements the slot values of an SSTORE are hashed from
+pub fn sstore_value_fields<F: Field>(record: &SStoreRecord) -> [F; SSTORE_VALUE_INPUTS] {
+    let [original_lo, original_hi] = word_limbs::<F>(record.original);
+    let [current_lo, current_hi] = word_limbs::<F>(record.current);
+    [original_lo, original_hi, current_lo, current_hi]
+}
+
+/// Configuration for the SStoreChip
+#[derive(Clone, Debug)]
+pub struct SStoreChipConfig {
+    /// Advice columns; each gate documents its own row layout
+    pub cells: [Column<Advice>; 5],
+    /// Dynamic gas of the SSTORE step from its flags
+    pub s_gas: Selector,
+    /// Refund counter after the SSTORE from its flags
+    pub s_refund: Selector,
+    /// Equality and zero flags of the slot values
+    pub comparator_config: ComparatorChipConfig,
+}
+
+/// An SSTORE step with the cells the chip constrains
+#[derive(Clone, Debug)]
+pub struct SStoreStep<'a, F: Field> {
+    /// Cells of the step
+    pub step: &'a AssignedStep<F>,
+    /// Original and current value of the slot
+    pub record: SStoreRecord,
+    /// Warm flag of the slot from `AccessChip` (Berlin on)
+    pub is_warm: Option<AssignedCell<F, F>>,
+    /// Whether the step's dynamic gas is priced (it has a successor)
+    pub priced: bool,
+}
+
+/// Chip metering SSTORE gas and refunds
+pub struct SStoreChip<F: PrimeField> {
+    config: SStoreChipConfig,
+    _marker: PhantomData<F>,
+}
+
+impl<F> SStoreChip<F>
+where
+    F: PrimeField,
+    P128Pow5T3: Spec<F, POSEIDON_WIDTH, POSEIDON_RATE>,
+{
+    /// Construct a new SStoreChip
+    pub fn construct(config: SStoreChipConfig) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Configure the gas and refund gates
+    ///
+    /// # Arguments
+    ///
+    /// * `cells` - Advice columns, which may be shared with other word gadgets
+    /// * `comparator_config` - IsZero gadget for the value flags
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        cells: [Column<Advice>; 5],
+        comparator_config: ComparatorChipConfig,
+    ) -> SStoreChipConfig {
+        for column in cells {
+            meta.enable_equality(column);
+        }
+
+        let s_gas = meta.selector();
+        let s_refund = meta.selector();
+
+        // Rows shared by both gates:
+        //   [eq_cn, eq_oc, z_o,    dynamic_gas,   is_warm     ]
+        //   [cold,  set,   reset,  legacy,        z_c         ]
+        //   [z_n,   eq_on, clear,  refund_before, refund_after]
+        // where eq_xy flags x == y and z_x flags x == 0 for the original (o),
+        // current (c) and new (n) values.
+        let query = |meta: &mut halo2_proofs::plonk::VirtualCells<'_, F>| {
+            let mut q = |column: usize, row: i32| meta.query_advice(cells[column], Rotation(row));
+            [
+                [q(0, 0), q(1, 0), q(2, 0), q(3, 0), q(4, 0)],
+                [q(0, 1), q(1, 1), q(2, 1), q(3, 1), q(4, 1)],
+                [q(0, 2), q(1, 2), q(2, 2), q(3, 2), q(4, 2)],
+            ]
+        };
+        let one = || Expression::Constant(F::ONE);
+
+        // Gate: cold surcharge, first write to a clean slot and, before
+        // Istanbul, a set of a zero slot
+        meta.create_gate("sstore_gas", |meta| {
+            let s = meta.query_selector(s_gas);
+            let [
+                [eq_cn, eq_oc, z_o, dynamic_gas, is_warm],
+                [cold, set, reset, legacy, z_c],
+                [z_n, ..],
+            ] = query(meta);
+
+            let first_write = z_o.clone() * set + (one() - z_o) * reset;
+            vec![
+                s * (dynamic_gas
+                    - cold * (one() - is_warm)
+                    - (one() - eq_cn) * eq_oc * first_write
+                    - legacy * z_c * (one() - z_n)),
+            ]
+        });
+
+        // Gate: clearing refunds (undone when a cleared slot is set again),
+        // restoring the original value refunds its first write and, before
+        // Istanbul, clearing a slot refunds a set
+        meta.create_gate("sstore_refund", |meta| {
+            let s = meta.query_selector(s_refund);
+            let [
+                [eq_cn, _, z_o, _, _],
+                [_, set, reset, legacy, z_c],
+                [z_n, eq_on, clear, refund_before, refund_after],
+            ] = query(meta);
+
+            let first_write = z_o.clone() * set + (one() - z_o.clone()) * reset;
+            let change = (one() - eq_cn)
+                * ((one() - z_o) * (z_n.clone() - z_c.clone()) * clear + eq_on * first_write)
+                + legacy * (one() - z_c) * z_n;
+            vec![s * (refund_after - refund_before - change)]
+        });
+
+        SStoreChipConfig {
+            cells,
+            s_gas,
+            s_refund,
+            comparator_config,
+        }
+    }
+
+    /// Meter every SSTORE and chain its slot values onto `seed`
+    ///
+    /// # Arguments
+    ///
+    /// * `hash_chip` - Poseidon chip for the value hashes and the chain
+    /// * `seed` - First link of the chain (the access commitment)
+    /// * `sstores` - SSTORE steps in trace order
+    /// * `schedule` - Prices of the fork
+    ///
+    /// # Returns
+    ///
+    /// Cell holding the state commitment (the seed without SSTOREs)
+    pub fn assign(
+        &self,
+        mut layouter: impl Layouter<F>,
+        hash_chip: &HashChip<F>,
+        seed: AssignedCell<F, F>,
+        sstores: &[SStoreStep<'_, F>],
+        schedule: SStoreSchedule,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let mut link = seed;
+        let mut refund: Option<AssignedCell<F, F>> = None;
+
+        for (i, sstore) in sstores.iter().enumerate() {
+            let mut layouter = layouter.namespace(|| format!("sstore {}", i));
+            let (original, current) =
+                self.assign_values(layouter.namespace(|| "values"), sstore)?;
+
+            refund = Some(self.meter(
+                layouter.namespace(|| "metering"),
+                sstore,
+                &original,
+                &current,
+                refund.as_ref(),
+                schedule,
+            )?);
+
+            let values = hash_chip.hash(
+                layouter.namespace(|| "values hash"),
+                [original.lo, original.hi, current.lo, current.hi],
+            )?;
+            link = hash_chip.hash(layouter.namespace(|| "link"), [link, values])?;
+        }
+
+        Ok(link)
+    }
+
+    /// Assign the original and current value of the slot
+    fn assign_values(
+        &self,
+        mut layouter: impl Layouter<F>,
+        sstore: &SStoreStep<'_, F>,
+    ) -> Result<(AssignedWord<F>, AssignedWord<F>), Error> {
+        let fields = sstore_value_fields::<F>(&sstore.record);
+        layouter.assign_region(
+            || "sstore values",
+            |mut region| {
+                let mut cells = Vec::with_capacity(SSTORE_VALUE_INPUTS);
+                for (column, value) in self.config.cells.into_iter().zip(fields) {
+                    cells.push(assign_value(&mut region, column, 0, Value::known(value))?);
+                }
+                let [original_lo, original_hi, current_lo, current_hi]: [_; SSTORE_VALUE_INPUTS] =
+                    cells.try_into().map_err(|_| Error::Synthesis)?;
+                Ok((
+                    AssignedWord {
+                        lo: original_lo,
+                        hi: original_hi,
+                    },
+                    AssignedWord {
+                        lo: current_lo,
+                        hi: current_hi,
+                    },
+                ))
+            },
+        )
+    }
+
+    /// Constrain the step's dynamic gas and the refund counter after it
+    ///
+    /// # Returns
+    ///
+    /// Cell holding the refund counter after the step
+    fn meter(
+        &self,
+        mut layouter: impl Layouter<F>,
+        sstore: &SStoreStep<'_, F>,
+        original: &AssignedWord<F>,
+        current: &AssignedWord<F>,
+        refund_before: Option<&AssignedCell<F, F>>,
+        schedule: SStoreSchedule,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let comparator = ComparatorChip::construct(self.config.comparator_config.clone());
+        let new = &sstore.step.stack[1];
+        let eq_cn =
+            comparator.is_equal(layouter.namespace(|| "current == new"), current, Some(new))?;
+        let eq_oc = comparator.is_equal(
+            layouter.namespace(|| "original == current"),
+            original,
+            Some(current),
+        )?;
+        let eq_on = comparator.is_equal(
+            layouter.namespace(|| "original == new"),
+            original,
+            Some(new),
+        )?;
+        let z_o = comparator.is_equal(layouter.namespace(|| "original == 0"), original, None)?;
+        let z_c = comparator.is_equal(layouter.namespace(|| "current == 0"), current, None)?;
+        let z_n = comparator.is_equal(layouter.namespace(|| "new == 0"), new, None)?;
+
+        let refund = new.value().map(|new| {
+            let change = schedule.refund(sstore.record.original, sstore.record.current, new);
+            let magnitude = u64_to_field::<F>(change.unsigned_abs());
+            if change < 0 { -magnitude } else { magnitude }
+        });
+
+        let cells = self.config.cells;
+        layouter.assign_region(
+            || "sstore metering",
+            |mut region| {
+                self.config.s_refund.enable(&mut region, 0)?;
+                if sstore.priced {
+                    self.config.s_gas.enable(&mut region, 0)?;
+                    sstore.step.dynamic_gas.copy_advice(
+                        || "dynamic_gas",
+                        &mut region,
+                        cells[3],
+                        0,
+                    )?;
+                }
+
+                let flags = [
+                    (&eq_cn, 0, 0),
+                    (&eq_oc, 0, 1),
+                    (&z_o, 0, 2),
+                    (&z_c, 1, 4),
+                    (&z_n, 2, 0),
+                    (&eq_on, 2, 1),
+                ];
+                for (flag, row, column) in flags {
+                    flag.lo
+                        .copy_advice(|| "flag", &mut region, cells[column], row)?;
+                }
+
+                match &sstore.is_warm {
+                    Some(is_warm) => {
+                        is_warm.copy_advice(|| "is_warm", &mut region, cells[4], 0)?;
+                    }
+                    None => {
+                        region.assign_advice_from_constant(|| "is_warm", cells[4], 0, F::ONE)?;
+                    }
+                }
+
+                let prices = [
+                    (schedule.cold, 1, 0),
+                    (schedule.set, 1, 1),
+                    (schedule.reset, 1, 2),
+                    (schedule.legacy, 1, 3),
+                    (schedule.clear, 2, 2),
+                ];
+                for (price, row, column) in prices {
+                    region.assign_advice_from_constant(
+                        || "price",
+                        cells[column],
+                        row,
+                        u64_to_field::<F>(price),
+                    )?;
+                }
+
+                let before = match refund_before {
+                    Some(before) => {
+                        before.copy_advice(|| "refund_before", &mut region, cells[3], 2)?
+                    }
+                    None => region.assign_advice_from_constant(
+                        || "refund_before",
+                        cells[3],
+                        2,
+                        F::ZERO,
+                    )?,
+                };
+                let after = before.value().copied() + refund;
+                assign_value(&mut region, cells[4], 2, after)
+            },
+        )
+    }
+}
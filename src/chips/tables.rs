@@ -0,0 +1,421 @@
+//! Fixed lookup tables shared by the EVM chips
+//!
+//! Tables are loaded once per circuit and referenced by lookup arguments
+//! from the execution region.
+
+use halo2_proofs::{
+    arithmetic::Field,
+    circuit::{Layouter, Value},
+    pasta::group::ff::PrimeField,
+    plonk::{ConstraintSystem, Error, TableColumn},
+};
+
+use crate::chips::bitwise_chip::{BitwiseOp, CHUNK_BITS};
+use crate::chips::evm_chip::{Hardfork, OpcodeProperties, u64_to_field};
+use crate::chips::keccak_chip::{KECCAK_CHUNK_DIGITS, KeccakFunction, sparse_lane};
+use crate::chips::mpt_chip::{ItemKind, MptTag};
+use crate::chips::shift_chip::PowerKind;
+use crate::chips::word_chip::word_limbs;
+
+/// Fixed table mapping every opcode byte to its execution properties
+///
+/// Row layout: `(opcode, gas, pops, pushes, pc_delta, checked, sequential, dynamic,
+/// memory)`.
+/// Bytes that are not modelled yet (or not enabled in the loaded fork) map to
+/// all-zero properties, which leaves their state transition unconstrained.
+#[derive(Clone, Debug)]
+pub struct OpcodeTableConfig {
+    /// Opcode byte
+    pub opcode: TableColumn,
+    /// Static gas cost
+    pub gas: TableColumn,
+    /// Stack items consumed
+    pub pops: TableColumn,
+    /// Stack items produced
+    pub pushes: TableColumn,
+    /// PC increment for sequential opcodes (1 + immediate size)
+    pub pc_delta: TableColumn,
+    /// Whether gas and stack depth transitions are constrained
+    pub checked: TableColumn,
+    /// Whether the next PC is `pc + pc_delta`
+    pub sequential: TableColumn,
+    /// Whether dynamic gas may be charged
+    pub dynamic: TableColumn,
+    /// Whether memory may grow
+    pub memory: TableColumn,
+}
+
+impl OpcodeTableConfig {
+    /// Allocate the table columns
+    pub fn configure<F: Field>(meta: &mut ConstraintSystem<F>) -> Self {
+        Self {
+            opcode: meta.lookup_table_column(),
+            gas: meta.lookup_table_column(),
+            pops: meta.lookup_table_column(),
+            pushes: meta.lookup_table_column(),
+            pc_delta: meta.lookup_table_column(),
+            checked: meta.lookup_table_column(),
+            sequential: meta.lookup_table_column(),
+            dynamic: meta.lookup_table_column(),
+            memory: meta.lookup_table_column(),
+        }
+    }
+
+    /// Load all 256 opcode rows, priced with the fork's gas schedule
+    pub fn load<F: Field>(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        hardfork: Hardfork,
+    ) -> Result<(), Error> {
+        layouter.assign_table(
+            || "opcode table",
+            |mut table| {
+                for byte in 0..=255u8 {
+                    let props = OpcodeProperties::of(byte, hardfork);
+                    let offset = byte as usize;
+                    let row = [
+                        (self.opcode, byte as u64),
+                        (self.gas, props.gas),
+                        (self.pops, props.pops),
+                        (self.pushes, props.pushes),
+                        (self.pc_delta, props.pc_delta),
+                        (self.checked, props.checked as u64),
+                        (self.sequential, props.sequential as u64),
+                        (self.dynamic, props.dynamic as u64),
+                        (self.memory, props.memory as u64),
+                    ];
+                    for (column, value) in row {
+                        table.assign_cell(
+                            || "opcode table cell",
+                            column,
+                            offset,
+                            || Value::known(u64_to_field::<F>(value)),
+                        )?;
+                    }
+                }
+                Ok(())
+            },
+        )
+    }
+}
+
+/// Fixed table containing every byte value `0..=255`
+#[derive(Clone, Debug)]
+pub struct ByteTableConfig {
+    /// Byte value column
+    pub value: TableColumn,
+}
+
+impl ByteTableConfig {
+    /// Allocate the table column
+    pub fn configure<F: Field>(meta: &mut ConstraintSystem<F>) -> Self {
+        Self {
+            value: meta.lookup_table_column(),
+        }
+    }
+
+    /// Load the 256 byte values
+    pub fn load<F: Field>(&self, layouter: &mut impl Layouter<F>) -> Result<(), Error> {
+        layouter.assign_table(
+            || "byte table",
+            |mut table| {
+                for byte in 0..256u64 {
+                    table.assign_cell(
+                        || "byte",
+                        self.value,
+                        byte as usize,
+                        || Value::known(u64_to_field::<F>(byte)),
+                    )?;
+                }
+                Ok(())
+            },
+        )
+    }
+}
+
+/// Fixed table of chunk-wise AND/OR/XOR results
+///
+/// Row layout: `(tag, a, b, a op b)` for every operation and every pair of
+/// `CHUNK_BITS`-bit chunks, preceded by an all-zero row that disabled lookups hit.
+#[derive(Clone, Debug)]
+pub struct BitwiseTableConfig {
+    /// Operation tag (`BitwiseOp::tag`)
+    pub tag: TableColumn,
+    /// Left operand chunk
+    pub a: TableColumn,
+    /// Right operand chunk
+    pub b: TableColumn,
+    /// Result chunk
+    pub result: TableColumn,
+}
+
+impl BitwiseTableConfig {
+    /// Allocate the table columns
+    pub fn configure<F: Field>(meta: &mut ConstraintSystem<F>) -> Self {
+        Self {
+            tag: meta.lookup_table_column(),
+            a: meta.lookup_table_column(),
+            b: meta.lookup_table_column(),
+            result: meta.lookup_table_column(),
+        }
+    }
+
+    /// Load the zero row followed by every `(op, a, b)` combination
+    pub fn load<F: Field>(&self, layouter: &mut impl Layouter<F>) -> Result<(), Error> {
+        let chunk_values = 1u64 << CHUNK_BITS;
+
+        layouter.assign_table(
+            || "bitwise table",
+            |mut table| {
+                let mut rows = vec![[0u64; 4]];
+                for op in BitwiseOp::ALL {
+                    for a in 0..chunk_values {
+                        for b in 0..chunk_values {
+                            rows.push([op.tag(), a, b, op.apply(a, b)]);
+                        }
+                    }
+                }
+
+                for (offset, row) in rows.into_iter().enumerate() {
+                    let columns = [self.tag, self.a, self.b, self.result];
+                    for (column, value) in columns.into_iter().zip(row) {
+                        table.assign_cell(
+                            || "bitwise table cell",
+                            column,
+                            offset,
+                            || Value::known(u64_to_field::<F>(value)),
+                        )?;
+                    }
+                }
+                Ok(())
+            },
+        )
+    }
+}
+
+/// Fixed table of the powers of two used by shifts and byte operations
+///
+/// Row layout: `(tag, e, lo, hi)` where `(lo, hi)` are the limbs of
+/// `PowerKind::power(e)` for every kind and every exponent byte `e`, preceded by
+/// an all-zero row that disabled lookups hit.
+#[derive(Clone, Debug)]
+pub struct PowerTableConfig {
+    /// Exponent encoding (`PowerKind::tag`)
+    pub tag: TableColumn,
+    /// Exponent byte
+    pub exponent: TableColumn,
+    /// Low limb of the power
+    pub lo: TableColumn,
+    /// High limb of the power
+    pub hi: TableColumn,
+}
+
+impl PowerTableConfig {
+    /// Allocate the table columns
+    pub fn configure<F: Field>(meta: &mut ConstraintSystem<F>) -> Self {
+        Self {
+            tag: meta.lookup_table_column(),
+            exponent: meta.lookup_table_column(),
+            lo: meta.lookup_table_column(),
+            hi: meta.lookup_table_column(),
+        }
+    }
+
+    /// Load the zero row followed by every `(kind, e)` combination
+    pub fn load<F: Field>(&self, layouter: &mut impl Layouter<F>) -> Result<(), Error> {
+        layouter.assign_table(
+            || "power table",
+            |mut table| {
+                let mut rows = vec![[F::ZERO; 4]];
+                for kind in PowerKind::ALL {
+                    for e in 0..256u64 {
+                        let [lo, hi] = word_limbs::<F>(kind.power(e));
+                        rows.push([u64_to_field(kind.tag()), u64_to_field(e), lo, hi]);
+                    }
+                }
+
+                for (offset, row) in rows.into_iter().enumerate() {
+                    let columns = [self.tag, self.exponent, self.lo, self.hi];
+                    for (column, value) in columns.into_iter().zip(row) {
+                        table.assign_cell(
+                            || "power table cell",
+                            column,
+                            offset,
+                            || Value::known(value),
+                        )?;
+                    }
+                }
+                Ok(())
+            },
+        )
+    }
+}
+
+/// Fixed tables of the Keccak chip
+///
+/// The chunk table has rows `(function, size, input, output)` for every
+/// sparse chunk of up to `KECCAK_CHUNK_DIGITS` base-8 digits, with `output`
+/// mapping each digit through the function, preceded by the size-0 row of
+/// every function. The sparse byte table maps every byte to its sparse form.
+#[derive(Clone, Debug)]
+pub struct KeccakTableConfig {
+    /// Function tag (`KeccakFunction::tag`)
+    pub function: TableColumn,
+    /// Digits of the chunk
+    pub size: TableColumn,
+    /// Sparse chunk
+    pub input: TableColumn,
+    /// Chunk with the function applied to every digit
+    pub output: TableColumn,
+    /// Byte value
+    pub byte: TableColumn,
+    /// Sparse form of the byte
+    pub sparse: TableColumn,
+}
+
+impl KeccakTableConfig {
+    /// Allocate the table columns
+    pub fn configure<F: Field>(meta: &mut ConstraintSystem<F>) -> Self {
+        Self {
+            function: meta.lookup_table_column(),
+            size: meta.lookup_table_column(),
+            input: meta.lookup_table_column(),
+            output: meta.lookup_table_column(),
+            byte: meta.lookup_table_column(),
+            sparse: meta.lookup_table_column(),
+        }
+    }
+
+    /// Load every chunk of every size and every byte
+    pub fn load<F: PrimeField>(&self, layouter: &mut impl Layouter<F>) -> Result<(), Error> {
+        layouter.assign_table(
+            || "keccak chunk table",
+            |mut table| {
+                let mut offset = 0;
+                for function in KeccakFunction::ALL {
+                    for size in 0..=KECCAK_CHUNK_DIGITS {
+                        for input in 0..1u64 << (3 * size) {
+                            let output = (0..size)
+                                .rev()
+                                .fold(0, |acc, i| acc * 8 + function.apply(input >> (3 * i) & 7));
+                            let row = [
+                                (self.function, function.tag()),
+                                (self.size, size as u64),
+                                (self.input, input),
+                                (self.output, output),
+                            ];
+                            for (column, value) in row {
+                                table.assign_cell(
+                                    || "keccak chunk cell",
+                                    column,
+                                    offset,
+                                    || Value::known(u64_to_field::<F>(value)),
+                                )?;
+                            }
+                            offset += 1;
+                        }
+                    }
+                }
+                Ok(())
+            },
+        )?;
+
+        layouter.assign_table(
+            || "keccak byte table",
+            |mut table| {
+                for byte in 0..256u64 {
+                    let row = [
+                        (self.byte, u64_to_field::<F>(byte)),
+                        (self.sparse, sparse_lane(byte)),
+                    ];
+                    for (column, value) in row {
+                        table.assign_cell(
+                            || "keccak byte cell",
+                            column,
+                            byte as usize,
+                            || Value::known(value),
+                        )?;
+                    }
+                }
+                Ok(())
+            },
+        )
+    }
+}
+
+/// Fixed table of the trie node layout
+///
+/// Rows `(tag, byte, a, b)` after an all-zero row: every RLP string prefix a
+/// node item can start with (`MptTag::Item`, with the item kind and the
+/// bytes it adds to the item), every short list prefix with its payload
+/// length (`MptTag::List`) and every byte with its nibbles (`MptTag::Nibble`).
+#[derive(Clone, Debug)]
+pub struct MptTableConfig {
+    /// Row tag (`MptTag`)
+    pub tag: TableColumn,
+    /// Byte value
+    pub byte: TableColumn,
+    /// Item kind, list payload length or high nibble
+    pub a: TableColumn,
+    /// Item extra bytes or low nibble
+    pub b: TableColumn,
+}
+
+impl MptTableConfig {
+    /// Allocate the table columns
+    pub fn configure<F: Field>(meta: &mut ConstraintSystem<F>) -> Self {
+        Self {
+            tag: meta.lookup_table_column(),
+            byte: meta.lookup_table_column(),
+            a: meta.lookup_table_column(),
+            b: meta.lookup_table_column(),
+        }
+    }
+
+    /// Load the prefixes and nibbles
+    pub fn load<F: PrimeField>(&self, layouter: &mut impl Layouter<F>) -> Result<(), Error> {
+        let mut rows = vec![(0, 0, 0, 0)];
+        for byte in 0..=0xb8u64 {
+            let (kind, extra) = ItemKind::of(byte as u8).ok_or(Error::Synthesis)?;
+            rows.push((MptTag::Item as u64, byte, kind as u64, extra));
+        }
+        for byte in 0xc0..=0xf7u64 {
+            rows.push((MptTag::List as u64, byte, byte - 0xc0, 0));
+        }
+        for byte in 0..256u64 {
+            rows.push((MptTag::Nibble as u64, byte, byte >> 4, byte & 0x0f));
+        }
+
+        layouter.assign_table(
+            || "mpt table",
+            |mut table| {
+                for (offset, (tag, byte, a, b)) in rows.iter().enumerate() {
+                    for (column, value) in
+                        [(self.tag, tag), (self.byte, byte), (self.a, a), (self.b, b)]
+                    {
+                        table.assign_cell(
+                            || "mpt cell",
+                            column,
+                            offset,
+                            || Value::known(u64_to_field::<F>(*value)),
+                        )?;
+                    }
+                }
+                Ok(())
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unknown_opcode_is_unconstrained() {
+        let props = OpcodeProperties::of(0xef, Hardfork::default());
+        assert!(!props.checked);
+        assert!(!props.sequential);
+        assert_eq!(props.gas, 0);
+    }
+}
@@ -0,0 +1,435 @@
+//! 256-bit EVM word chip
+//!
+//! EVM words do not fit in a field element, so the circuit carries every word
+//! as two 128-bit limbs `(lo, hi)`. Each limb is range checked by a running sum
+//! over its big-endian bytes, eight per row, with every byte looked up in the
+//! byte table.
+
+use alloy_primitives::U256;
+use halo2_proofs::{
+    arithmetic::Field,
+    circuit::{AssignedCell, Layouter, Region, Value},
+    pasta::group::ff::PrimeField,
+    plonk::{Advice, Column, ConstraintSystem, Error, Expression, Selector, VirtualCells},
+    poly::Rotation,
+};
+use std::marker::PhantomData;
+
+use crate::chips::evm_chip::u64_to_field;
+use crate::chips::tables::ByteTableConfig;
+
+/// Bytes per word limb
+pub const LIMB_BYTES: usize = 16;
+
+/// Bytes decomposed per range check row
+pub const ROW_BYTES: usize = 8;
+
+/// Split a word into its `(lo, hi)` 128-bit limbs as field elements
+pub fn word_limbs<F: Field>(word: U256) -> [F; 2] {
+    let limbs = word.as_limbs();
+    let shift = u64_to_field::<F>(1 << 32).square();
+    [
+        u64_to_field::<F>(limbs[0]) + u64_to_field::<F>(limbs[1]) * shift,
+        u64_to_field::<F>(limbs[2]) + u64_to_field::<F>(limbs[3]) * shift,
+    ]
+}
+
+/// 2^128 as a field element (weight of the high limb)
+pub fn limb_shift<F: Field>() -> F {
+    u64_to_field::<F>(1 << 32).square().square()
+}
+
+/// Low 128 bits of a field element as an integer
+///
+/// Limbs that are not range checked may not fit; their excess bits are dropped,
+/// which makes any decomposition built from the result fail to match the cell.
+pub(crate) fn limb_value<F: PrimeField>(value: F) -> u128 {
+    let repr = value.to_repr();
+    let mut low = [0u8; 16];
+    low.copy_from_slice(&repr.as_ref()[..16]);
+    u128::from_le_bytes(low)
+}
+
+/// Word made of two 128-bit limbs
+pub(crate) fn limbs_to_word(lo: u128, hi: u128) -> U256 {
+    (U256::from(hi) << 128) | U256::from(lo)
+}
+
+/// Assign a free advice cell
+pub(crate) fn assign_value<F: Field>(
+    region: &mut Region<'_, F>,
+    column: Column<Advice>,
+    offset: usize,
+    value: Value<F>,
+) -> Result<AssignedCell<F, F>, Error> {
+    region.assign_advice(|| "value", column, offset, || value)
+}
+
+/// Assign a word whose limbs are pinned to the constants of `word`
+pub(crate) fn assign_constant_word<F: Field>(
+    region: &mut Region<'_, F>,
+    columns: [Column<Advice>; 2],
+    offset: usize,
+    word: U256,
+) -> Result<AssignedWord<F>, Error> {
+    let [lo, hi] = word_limbs::<F>(word);
+    let mut constant = |column: Column<Advice>, value: F| {
+        let cell = assign_value(region, column, offset, Value::known(value))?;
+        region.constrain_constant(cell.cell(), value)?;
+        Ok::<_, Error>(cell)
+    };
+    Ok(AssignedWord {
+        lo: constant(columns[0], lo)?,
+        hi: constant(columns[1], hi)?,
+    })
+}
+
+/// Cells holding the two limbs of a word
+#[derive(Clone, Debug)]
+pub struct AssignedWord<F: Field> {
+    /// Low 128 bits
+    pub lo: AssignedCell<F, F>,
+    /// High 128 bits
+    pub hi: AssignedCell<F, F>,
+}
+
+impl<F: Field> AssignedWord<F> {
+    /// Both limb cells, low limb first
+    pub fn limbs(&self) -> [&AssignedCell<F, F>; 2] {
+        [&self.lo, &self.hi]
+    }
+}
+
+impl<F: PrimeField> AssignedWord<F> {
+    /// Value of the word, assuming both limbs are range checked
+    pub fn value(&self) -> Value<U256> {
+        let lo = self.lo.value().map(|v| limb_value(*v));
+        let hi = self.hi.value().map(|v| limb_value(*v));
+        lo.zip(hi).map(|(lo, hi)| limbs_to_word(lo, hi))
+    }
+}
+
+/// Configuration for the WordChip
+#[derive(Clone, Debug)]
+pub struct WordChipConfig {
+    /// Bytes of the value being decomposed (most significant first)
+    pub bytes: [Column<Advice>; ROW_BYTES],
+    /// Running sum of the bytes seen so far
+    pub acc: Column<Advice>,
+    /// Enabled on every decomposition row (byte lookups)
+    pub s_byte: Selector,
+    /// Enabled on the first row of a decomposition
+    pub s_first: Selector,
+    /// Enabled on every row followed by another row of the same value
+    pub s_acc: Selector,
+    /// `s_pad[j]` zeroes the leading `j + 1` bytes of a first row, for values
+    /// whose size is not a multiple of `ROW_BYTES`
+    pub s_pad: [Selector; ROW_BYTES - 1],
+    /// Byte range table
+    pub byte_table: ByteTableConfig,
+}
+
+/// Chip range checking word limbs and other byte-bounded values
+pub struct WordChip<F: PrimeField> {
+    config: WordChipConfig,
+    _marker: PhantomData<F>,
+}
+
+impl<F: PrimeField> WordChip<F> {
+    /// Construct a new WordChip
+    pub fn construct(config: WordChipConfig) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Configure the running-sum decomposition
+    ///
+    /// A value of `n` bytes occupies `ceil(n / 8)` rows of eight big-endian
+    /// bytes, the first row left-padded with zero bytes. With `r_j` the bytes of
+    /// row `j` read as one number, `acc_0 = r_0` and `acc_{j+1} = 2^64 * acc_j + r_{j+1}`,
+    /// so the last accumulator equals the value.
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        byte_table: ByteTableConfig,
+    ) -> WordChipConfig {
+        let bytes = [(); ROW_BYTES].map(|_| meta.advice_column());
+        let acc = meta.advice_column();
+        meta.enable_equality(acc);
+
+        let s_byte = meta.complex_selector();
+        let s_first = meta.selector();
+        let s_acc = meta.selector();
+        let s_pad = [(); ROW_BYTES - 1].map(|_| meta.selector());
+
+        // Lookup: every decomposed byte is in 0..=255
+        for column in bytes {
+            meta.lookup(|meta| {
+                let s = meta.query_selector(s_byte);
+                let byte = meta.query_advice(column, Rotation::cur());
+                vec![(s * byte, byte_table.value)]
+            });
+        }
+
+        // The bytes of a row as one big-endian number
+        let row_value = |meta: &mut VirtualCells<'_, F>, rotation: Rotation| {
+            bytes
+                .iter()
+                .fold(Expression::Constant(F::ZERO), |acc, &column| {
+                    acc * Expression::Constant(u64_to_field::<F>(256))
+                        + meta.query_advice(column, rotation)
+                })
+        };
+
+        // Gate: running sum starts at the first row
+        meta.create_gate("word_first_row", |meta| {
+            let s = meta.query_selector(s_first);
+            let row = row_value(meta, Rotation::cur());
+            let acc = meta.query_advice(acc, Rotation::cur());
+
+            vec![s * (acc - row)]
+        });
+
+        // Gate: running sum shifts in the next row
+        meta.create_gate("word_running_sum", |meta| {
+            let s = meta.query_selector(s_acc);
+            let acc_cur = meta.query_advice(acc, Rotation::cur());
+            let acc_next = meta.query_advice(acc, Rotation::next());
+            let row_next = row_value(meta, Rotation::next());
+            let radix = Expression::Constant(u64_to_field::<F>(1 << 32).square());
+
+            vec![s * (acc_next - acc_cur * radix - row_next)]
+        });
+
+        // Gate: padding bytes of the first row are zero
+        meta.create_gate("word_padding", |meta| {
+            let mut constraints = Vec::new();
+            for (j, &selector) in s_pad.iter().enumerate() {
+                let s = meta.query_selector(selector);
+                for &column in &bytes[..=j] {
+                    constraints.push(s.clone() * meta.query_advice(column, Rotation::cur()));
+                }
+            }
+            constraints
+        });
+
+        WordChipConfig {
+            bytes,
+            acc,
+            s_byte,
+            s_first,
+            s_acc,
+            s_pad,
+            byte_table,
+        }
+    }
+
+    /// Constrain a cell to fit in `num_bytes` bytes
+    ///
+    /// # Arguments
+    ///
+    /// * `cell` - Cell to range check
+    /// * `num_bytes` - Number of bytes the value must fit in (at least 1)
+    pub fn range_check(
+        &self,
+        mut layouter: impl Layouter<F>,
+        cell: &AssignedCell<F, F>,
+        num_bytes: usize,
+    ) -> Result<(), Error> {
+        layouter.assign_region(
+            || "range check",
+            |mut region| {
+                // Big-endian bytes of the value, left-padded to whole rows; values
+                // that do not fit leave a non-byte remainder in the first unpadded
+                // byte and fail the lookup
+                let rows = num_bytes.div_ceil(ROW_BYTES);
+                let pad = rows * ROW_BYTES - num_bytes;
+                let bytes = cell.value().map(|v| {
+                    let mut bytes = vec![F::ZERO; pad];
+                    bytes.extend(field_to_be_bytes(*v, num_bytes));
+                    bytes
+                });
+
+                let mut acc = Value::known(F::ZERO);
+                let mut last = None;
+                for row in 0..rows {
+                    self.config.s_byte.enable(&mut region, row)?;
+                    if row == 0 {
+                        self.config.s_first.enable(&mut region, row)?;
+                        if pad > 0 {
+                            self.config.s_pad[pad - 1].enable(&mut region, row)?;
+                        }
+                    }
+                    if row + 1 < rows {
+                        self.config.s_acc.enable(&mut region, row)?;
+                    }
+
+                    for (i, column) in self.config.bytes.into_iter().enumerate() {
+                        let byte = bytes.as_ref().map(|b| b[row * ROW_BYTES + i]);
+                        region.assign_advice(|| "byte", column, row, || byte)?;
+                        acc = acc * Value::known(u64_to_field::<F>(256)) + byte;
+                    }
+                    last = Some(region.assign_advice(|| "acc", self.config.acc, row, || acc)?);
+                }
+
+                match last {
+                    Some(acc_cell) => region.constrain_equal(acc_cell.cell(), cell.cell()),
+                    None => Err(Error::Synthesis),
+                }
+            },
+        )
+    }
+
+    /// Range check both limbs of a word to 128 bits
+    pub fn range_check_word(
+        &self,
+        mut layouter: impl Layouter<F>,
+        word: &AssignedWord<F>,
+    ) -> Result<(), Error> {
+        for (i, limb) in word.limbs().into_iter().enumerate() {
+            self.range_check(
+                layouter.namespace(|| format!("limb {}", i)),
+                limb,
+                LIMB_BYTES,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Big-endian byte decomposition of a field element over `num_bytes` bytes
+///
+/// Whatever does not fit in the lower `num_bytes - 1` bytes ends up in the first
+/// entry, so an out-of-range value yields a first "byte" outside `0..=255`.
+fn field_to_be_bytes<F: PrimeField>(value: F, num_bytes: usize) -> Vec<F> {
+    let repr = value.to_repr();
+    let le = repr.as_ref();
+
+    let mut low = F::ZERO;
+    let mut weight = F::ONE;
+    let mut bytes = Vec::with_capacity(num_bytes);
+    for &byte in le.iter().take(num_bytes - 1) {
+        let byte = u64_to_field::<F>(byte as u64);
+        low += byte * weight;
+        weight *= u64_to_field::<F>(256);
+        bytes.push(byte);
+    }
+    bytes.push((value - low) * weight.invert().unwrap());
+    bytes.reverse();
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2_proofs::{circuit::SimpleFloorPlanner, dev::MockProver, pasta::Fp, plonk::Circuit};
+
+    /// Test circuit range checking a single value to `num_bytes` bytes
+    #[derive(Default)]
+    struct RangeCheckCircuit {
+        value: Fp,
+        num_bytes: usize,
+    }
+
+    impl Circuit<Fp> for RangeCheckCircuit {
+        type Config = (WordChipConfig, Column<Advice>);
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self {
+                value: Fp::ZERO,
+                num_bytes: self.num_bytes,
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            let byte_table = ByteTableConfig::configure(meta);
+            let value = meta.advice_column();
+            meta.enable_equality(value);
+            (WordChip::configure(meta, byte_table), value)
+        }
+
+        fn synthesize(
+            &self,
+            (config, value): Self::Config,
+            mut layouter: impl Layouter<Fp>,
+        ) -> Result<(), Error> {
+            config.byte_table.load(&mut layouter)?;
+            let cell = layouter.assign_region(
+                || "value",
+                |mut region| {
+                    region.assign_advice(|| "value", value, 0, || Value::known(self.value))
+                },
+            )?;
+            WordChip::construct(config).range_check(layouter, &cell, self.num_bytes)
+        }
+    }
+
+    #[test]
+    fn test_word_limbs() {
+        let word = (U256::from(7u64) << 128) + U256::from(u64::MAX);
+        let [lo, hi] = word_limbs::<Fp>(word);
+        assert_eq!(lo, Fp::from(u64::MAX));
+        assert_eq!(hi, Fp::from(7u64));
+    }
+
+    #[test]
+    fn test_field_to_be_bytes() {
+        let bytes = field_to_be_bytes(Fp::from(0x0102_0304u64), 4);
+        assert_eq!(
+            bytes,
+            vec![Fp::from(1), Fp::from(2), Fp::from(3), Fp::from(4)]
+        );
+
+        // 2^16 does not fit in two bytes: the overflow lands in the first entry
+        let bytes = field_to_be_bytes(Fp::from(0x1_0000u64), 2);
+        assert_eq!(bytes, vec![Fp::from(256), Fp::from(0)]);
+    }
+
+    #[test]
+    fn test_range_check_in_range() {
+        for value in [Fp::ZERO, Fp::from(u64::MAX), limb_shift::<Fp>() - Fp::ONE] {
+            let circuit = RangeCheckCircuit {
+                value,
+                num_bytes: LIMB_BYTES,
+            };
+            let prover = MockProver::run(9, &circuit, vec![]).unwrap();
+            prover.assert_satisfied();
+        }
+    }
+
+    #[test]
+    fn test_range_check_partial_row() {
+        // 17 bytes span three rows with seven padding bytes
+        let max = limb_shift::<Fp>() * Fp::from(256) - Fp::ONE;
+        for (value, num_bytes, ok) in [
+            (max, 17, true),
+            (max + Fp::ONE, 17, false),
+            (Fp::from(255), 1, true),
+            (Fp::from(256), 1, false),
+        ] {
+            let circuit = RangeCheckCircuit { value, num_bytes };
+            let prover = MockProver::run(9, &circuit, vec![]).unwrap();
+            assert_eq!(
+                prover.verify().is_ok(),
+                ok,
+                "{:?} in {} bytes",
+                value,
+                num_bytes
+            );
+        }
+    }
+
+    #[test]
+    fn test_range_check_out_of_range_fails() {
+        for value in [limb_shift::<Fp>(), -Fp::ONE] {
+            let circuit = RangeCheckCircuit {
+                value,
+                num_bytes: LIMB_BYTES,
+            };
+            let prover = MockProver::run(9, &circuit, vec![]).unwrap();
+            assert!(prover.verify().is_err());
+        }
+    }
+}
@@ -0,0 +1,809 @@
+//! Memory circuit for EVM read/write consistency
+//!
+//! Every MLOAD, MSTORE, MSTORE8 and KECCAK256 is split into byte accesses keyed by
+//! `(call_id, address, rw_counter)`, the read/write counter numbering the
+//! accesses in trace order. In trace order the bytes of an access recompose
+//! the stack word it loads or stores. A Beneš network of switches then routes
+//! the accesses into key order, where the accesses of an address are adjacent
+//! and in execution order: every read must return the byte of the access
+//! before it, or zero when it is the first access of its address. A trace
+//! that starts mid-execution may first read anything below its initial
+//! memory size, since that memory was written before the trace.
+//!
+//! The switches are witnesses but the wiring of the network (see `network`)
+//! only depends on the number of accesses, so the layout follows from the
+//! opcodes alone.
+
+use alloy_primitives::U256;
+use halo2_proofs::{
+    arithmetic::Field,
+    circuit::{AssignedCell, Layouter, Region, SimpleFloorPlanner, Value},
+    pasta::group::ff::PrimeField,
+    plonk::{Advice, Circuit, Column, ConstraintSystem, Error, Expression, Selector},
+    poly::Rotation,
+};
+use std::marker::PhantomData;
+
+use crate::chips::access_chip::u128_to_field;
+use crate::chips::evm_chip::u64_to_field;
+use crate::chips::tables::ByteTableConfig;
+use crate::chips::word_chip::{AssignedWord, WordChip, WordChipConfig, assign_value, word_limbs};
+use crate::circuits::network::{Tuple, assign_network, configure_switch};
+use crate::utils::evm_parser::MemoryOp;
+
+/// Bytes a memory offset is range checked to
+pub const OFFSET_BYTES: usize = 4;
+
+/// Bytes a call frame id is range checked to
+pub const CALL_ID_BYTES: usize = 3;
+
+/// Weight of the call id in an address key, past any offset plus a word
+const CALL_ID_SHIFT: u64 = 1 << 40;
+
+/// Weight of the address key in a sort key, past any read/write counter
+const RW_SHIFT: u64 = 1 << 32;
+
+/// Bytes the gap between consecutive sort keys is range checked to
+const KEY_BYTES: usize = 12;
+
+/// Bytes the distance from a free read to the initial memory size is range
+/// checked to
+const BOUND_BYTES: usize = 5;
+
+/// Bytes of a stored low limb above the byte MSTORE8 writes
+const REST_BYTES: usize = 15;
+
+/// Fields of an access carried through the network
+const TUPLE: usize = 4;
+
+// Columns of an access, in trace and in key order
+const CALL_ID: usize = 0;
+const ADDRESS: usize = 1;
+const RW: usize = 2;
+const WV: usize = 3;
+const IS_WRITE: usize = 4;
+const BYTE: usize = 5;
+
+// Columns in trace order only
+const ACC: usize = 6;
+
+// Columns in key order only
+const INV: usize = 6;
+const SAME: usize = 7;
+const FREE: usize = 8;
+const GAP: usize = 9;
+const BOUND: usize = 10;
+const SIZE: usize = 11;
+
+// Swap bit of a switch, after its two input and two output tuples
+const SWAP: usize = 4 * TUPLE;
+
+/// Cells to range check, with their number of bytes
+type RangeChecks<F> = Vec<(AssignedCell<F, F>, usize)>;
+
+/// Byte cells of an access, at consecutive addresses
+pub type AccessBytes<F> = Vec<AssignedCell<F, F>>;
+
+/// Accesses laid out in trace order: the tuple of every entry, the cells to
+/// range check and the bytes of every access
+type TraceOrder<F> = (Vec<Tuple<F>>, RangeChecks<F>, Vec<AccessBytes<F>>);
+
+/// Configuration for the memory circuit
+#[derive(Clone, Debug)]
+pub struct MemoryCircuitConfig {
+    /// Advice columns; the gates document their roles
+    pub cells: [Column<Advice>; SWAP + 1],
+    /// Access row: boolean write flag and looked up byte packed into one value
+    pub s_access: Selector,
+    /// Byte of an access followed by the next byte of the same access
+    pub s_next: Selector,
+    /// First byte of a 128-bit limb
+    pub s_limb: Selector,
+    /// Later byte of a 128-bit limb
+    pub s_limb_acc: Selector,
+    /// Byte stored by MSTORE8, the low byte of the stack word's low limb
+    pub s_low_byte: Selector,
+    /// Switch of the network
+    pub s_switch: Selector,
+    /// Access in key order
+    pub s_sorted: Selector,
+    /// Access in key order followed by another
+    pub s_sorted_pair: Selector,
+    /// First access in key order
+    pub s_first: Selector,
+    /// Range checks for offsets, call ids and key gaps
+    pub word_config: WordChipConfig,
+}
+
+/// An MLOAD, MSTORE, MSTORE8 or KECCAK256 step whose access the circuit
+/// checks
+#[derive(Clone, Debug)]
+pub struct MemoryAccess<'a, F: Field> {
+    /// Record of the access; its length and direction set the layout
+    pub op: MemoryOp,
+    /// Stack word holding the offset, or none for an access continuing the
+    /// previous word access (the later words of a KECCAK256 window)
+    pub offset: Option<&'a AssignedWord<F>>,
+    /// Stack word loaded or stored (none for a load by the last step)
+    pub value: Option<&'a AssignedWord<F>>,
+    /// Whether the access is in the call frame of the previous one
+    pub same_frame: bool,
+}
+
+/// Byte access, ordered by its sort key
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Entry {
+    call_id: u64,
+    address: u64,
+    rw: u64,
+    is_write: bool,
+    byte: u8,
+}
+
+impl Entry {
+    fn address_key(&self) -> u128 {
+        self.call_id as u128 * CALL_ID_SHIFT as u128 + self.address as u128
+    }
+
+    fn key(&self) -> u128 {
+        self.address_key() * RW_SHIFT as u128 + self.rw as u128
+    }
+
+    fn tuple<F: Field>(&self) -> [F; TUPLE] {
+        let wv = 256 * self.is_write as u64 + self.byte as u64;
+        [self.call_id, self.address, self.rw, wv].map(u64_to_field::<F>)
+    }
+}
+
+impl MemoryCircuitConfig {
+    /// Configure the access, network and key order gates
+    ///
+    /// An access row holds `(call_id, address, rw, wv, is_write, byte)` with
+    /// `wv = 256 * is_write + byte`; the network carries the first four. In
+    /// trace order `acc` accumulates the big-endian bytes of each limb of a
+    /// word. A switch row holds two input and two output tuples and a swap
+    /// bit. In key order a row adds `inv` and `same` (whether the address key
+    /// `call_id * 2^40 + address` equals the previous row's), `free` (a first
+    /// read below the initial memory size), `gap` (the next sort key
+    /// `address_key * 2^32 + rw` minus this one, minus one), `bound`
+    /// (`size - 1 - address` for a free read) and `size`.
+    pub fn configure<F: PrimeField>(
+        meta: &mut ConstraintSystem<F>,
+        word_config: WordChipConfig,
+    ) -> Self {
+        let cells = [(); SWAP + 1].map(|_| meta.advice_column());
+        for column in &cells[..SWAP] {
+            meta.enable_equality(*column);
+        }
+
+        let s_access = meta.complex_selector();
+        let s_next = meta.selector();
+        let s_limb = meta.selector();
+        let s_limb_acc = meta.selector();
+        let s_low_byte = meta.selector();
+        let s_switch = meta.complex_selector();
+        let s_sorted = meta.selector();
+        let s_sorted_pair = meta.selector();
+        let s_first = meta.selector();
+
+        let constant = |value: u64| Expression::Constant(u64_to_field::<F>(value));
+        let one = || constant(1);
+
+        meta.create_gate("memory access", |meta| {
+            let s = meta.query_selector(s_access);
+            let wv = meta.query_advice(cells[WV], Rotation::cur());
+            let is_write = meta.query_advice(cells[IS_WRITE], Rotation::cur());
+            let byte = meta.query_advice(cells[BYTE], Rotation::cur());
+            vec![
+                s.clone() * (wv - constant(256) * is_write.clone() - byte),
+                s * is_write.clone() * (one() - is_write),
+            ]
+        });
+
+        meta.lookup(|meta| {
+            let s = meta.query_selector(s_access);
+            let byte = meta.query_advice(cells[BYTE], Rotation::cur());
+            vec![(s * byte, word_config.byte_table.value)]
+        });
+
+        meta.create_gate("memory access bytes", |meta| {
+            let s = meta.query_selector(s_next);
+            let query = |meta: &mut halo2_proofs::plonk::VirtualCells<'_, F>, column, rotation| {
+                meta.query_advice(cells[column], rotation)
+            };
+            let address = query(meta, ADDRESS, Rotation::cur());
+            let address_next = query(meta, ADDRESS, Rotation::next());
+            let call_id = query(meta, CALL_ID, Rotation::cur());
+            let call_id_next = query(meta, CALL_ID, Rotation::next());
+            vec![
+                s.clone() * (address_next - address - one()),
+                s * (call_id_next - call_id),
+            ]
+        });
+
+        meta.create_gate("memory limb start", |meta| {
+            let s = meta.query_selector(s_limb);
+            let acc = meta.query_advice(cells[ACC], Rotation::cur());
+            let byte = meta.query_advice(cells[BYTE], Rotation::cur());
+            vec![s * (acc - byte)]
+        });
+
+        meta.create_gate("memory limb accumulation", |meta| {
+            let s = meta.query_selector(s_limb_acc);
+            let acc = meta.query_advice(cells[ACC], Rotation::cur());
+            let acc_prev = meta.query_advice(cells[ACC], Rotation::prev());
+            let byte = meta.query_advice(cells[BYTE], Rotation::cur());
+            vec![s * (acc - constant(256) * acc_prev - byte)]
+        });
+
+        meta.create_gate("memory low byte", |meta| {
+            let s = meta.query_selector(s_low_byte);
+            let limb = meta.query_advice(cells[ACC], Rotation::cur());
+            let rest = meta.query_advice(cells[ACC], Rotation::next());
+            let byte = meta.query_advice(cells[BYTE], Rotation::cur());
+            vec![s * (limb - byte - constant(256) * rest)]
+        });
+
+        configure_switch(meta, "memory switch", s_switch, &cells, TUPLE);
+
+        meta.create_gate("memory free read", |meta| {
+            let s = meta.query_selector(s_sorted);
+            let free = meta.query_advice(cells[FREE], Rotation::cur());
+            let bound = meta.query_advice(cells[BOUND], Rotation::cur());
+            let size = meta.query_advice(cells[SIZE], Rotation::cur());
+            let address = meta.query_advice(cells[ADDRESS], Rotation::cur());
+            vec![
+                s.clone() * free.clone() * (one() - free.clone()),
+                s * (bound - free * (size - one() - address)),
+            ]
+        });
+
+        meta.create_gate("memory first read", |meta| {
+            let s = meta.query_selector(s_first);
+            let is_write = meta.query_advice(cells[IS_WRITE], Rotation::cur());
+            let free = meta.query_advice(cells[FREE], Rotation::cur());
+            let byte = meta.query_advice(cells[BYTE], Rotation::cur());
+            vec![s * (one() - is_write) * (one() - free) * byte]
+        });
+
+        meta.create_gate("memory key order", |meta| {
+            let s = meta.query_selector(s_sorted_pair);
+            let cur = |meta: &mut halo2_proofs::plonk::VirtualCells<'_, F>, column| {
+                meta.query_advice(cells[column], Rotation::cur())
+            };
+            let next = |meta: &mut halo2_proofs::plonk::VirtualCells<'_, F>, column| {
+                meta.query_advice(cells[column], Rotation::next())
+            };
+            let address_key = |call_id: Expression<F>, address: Expression<F>| {
+                call_id * constant(CALL_ID_SHIFT) + address
+            };
+            let key_cur = address_key(cur(meta, CALL_ID), cur(meta, ADDRESS));
+            let key_next = address_key(next(meta, CALL_ID), next(meta, ADDRESS));
+            let diff = key_next.clone() - key_cur.clone();
+            let sort_key = |address_key: Expression<F>, rw: Expression<F>| {
+                address_key * constant(RW_SHIFT) + rw
+            };
+            let sort_cur = sort_key(key_cur, cur(meta, RW));
+            let sort_next = sort_key(key_next, next(meta, RW));
+
+            let same = next(meta, SAME);
+            let inv = next(meta, INV);
+            let gap = cur(meta, GAP);
+            let is_read = one() - next(meta, IS_WRITE);
+            let byte = cur(meta, BYTE);
+            let byte_next = next(meta, BYTE);
+            let free = next(meta, FREE);
+            vec![
+                // same = 1 exactly when the address keys are equal
+                s.clone() * (same.clone() - one() + diff.clone() * inv),
+                s.clone() * diff * same.clone(),
+                // Sort keys strictly increase
+                s.clone() * (gap - (sort_next - sort_cur - one())),
+                // A read returns the previous byte of its address, or zero
+                s.clone() * is_read.clone() * same.clone() * (byte_next.clone() - byte),
+                s.clone() * is_read * (one() - same) * (one() - free) * byte_next,
+                s * (next(meta, SIZE) - cur(meta, SIZE)),
+            ]
+        });
+
+        Self {
+            cells,
+            s_access,
+            s_next,
+            s_limb,
+            s_limb_acc,
+            s_low_byte,
+            s_switch,
+            s_sorted,
+            s_sorted_pair,
+            s_first,
+            word_config,
+        }
+    }
+
+    /// Check the accesses, in trace order, for read/write consistency, and
+    /// return the byte cells of every access
+    ///
+    /// # Arguments
+    ///
+    /// * `accesses` - Memory accesses in trace order
+    /// * `size_cell` - Memory size before the first step
+    /// * `size` - Value of `size_cell`
+    pub fn assign<F: PrimeField>(
+        &self,
+        mut layouter: impl Layouter<F>,
+        accesses: &[MemoryAccess<'_, F>],
+        size_cell: &AssignedCell<F, F>,
+        size: u64,
+    ) -> Result<Vec<AccessBytes<F>>, Error> {
+        // Byte accesses in trace order, padded to a power of two with writes
+        // of zero that come after every real access
+        let mut entries: Vec<Entry> = Vec::new();
+        for access in accesses {
+            for (i, byte) in access.op.bytes().into_iter().enumerate() {
+                entries.push(Entry {
+                    call_id: access.op.call_id as u64,
+                    address: access.op.offset.saturating_add(i as u64),
+                    rw: entries.len() as u64 + 1,
+                    is_write: access.op.is_write,
+                    byte,
+                });
+            }
+        }
+        if entries.is_empty() {
+            return Ok(vec![Vec::new(); accesses.len()]);
+        }
+        let real = entries.len();
+        let n = real.max(2).next_power_of_two();
+        while entries.len() < n {
+            entries.push(Entry {
+                call_id: 0,
+                address: 0,
+                rw: entries.len() as u64 + 1,
+                is_write: true,
+                byte: 0,
+            });
+        }
+
+        let mut checks: RangeChecks<F> = Vec::new();
+        let (inputs, traced_checks, bytes) = layouter.assign_region(
+            || "memory accesses",
+            |mut region| self.assign_trace_order(&mut region, accesses, &entries, real),
+        )?;
+        checks.extend(traced_checks);
+
+        // Route every access to its rank in key order
+        let mut order: Vec<usize> = (0..n).collect();
+        order.sort_by_key(|&i| entries[i]);
+        let mut perm = vec![0; n];
+        for (rank, &i) in order.iter().enumerate() {
+            perm[i] = rank;
+        }
+        let values: Vec<Vec<F>> = entries.iter().map(|entry| entry.tuple().to_vec()).collect();
+        let sorted_inputs = layouter.assign_region(
+            || "memory network",
+            |mut region| {
+                assign_network(
+                    &mut region,
+                    self.s_switch,
+                    &self.cells,
+                    &inputs,
+                    &values,
+                    &perm,
+                )
+            },
+        )?;
+
+        let mut sorted = entries.clone();
+        sorted.sort();
+        let sorted_checks = layouter.assign_region(
+            || "memory in key order",
+            |mut region| {
+                self.assign_key_order(&mut region, &sorted, &sorted_inputs, size_cell, size)
+            },
+        )?;
+        checks.extend(sorted_checks);
+
+        let word_chip = WordChip::construct(self.word_config.clone());
+        for (i, (cell, num_bytes)) in checks.iter().enumerate() {
+            word_chip.range_check(
+                layouter.namespace(|| format!("memory range {}", i)),
+                cell,
+                *num_bytes,
+            )?;
+        }
+
+        Ok(bytes)
+    }
+
+    /// Lay out the accesses in trace order and tie their bytes to the stack
+    ///
+    /// Returns the tuple cells of every entry, the cells to range check and
+    /// the byte cells of every access.
+    fn assign_trace_order<F: PrimeField>(
+        &self,
+        region: &mut Region<'_, F>,
+        accesses: &[MemoryAccess<'_, F>],
+        entries: &[Entry],
+        real: usize,
+    ) -> Result<TraceOrder<F>, Error> {
+        let mut tuples = Vec::with_capacity(entries.len());
+        let mut checks = Vec::new();
+        let mut bytes = Vec::with_capacity(accesses.len());
+        let mut previous_call: Option<AssignedCell<F, F>> = None;
+        let mut previous_length = 0;
+        let mut row = 0;
+
+        for access in accesses {
+            let length = access.op.bytes().len();
+            let mut acc = Value::known(F::ZERO);
+            let mut access_bytes = Vec::with_capacity(length);
+            for i in 0..length {
+                let entry = &entries[tuples.len()];
+                self.s_access.enable(region, row)?;
+                let (tuple, byte) = self.assign_entry(region, row, entry, false)?;
+                access_bytes.push(byte);
+                if i == 0 {
+                    match access.offset {
+                        // The offset is the stack operand, a small single-limb value
+                        Some(offset) => {
+                            region.constrain_equal(tuple[ADDRESS].cell(), offset.lo.cell())?;
+                            region.constrain_constant(offset.hi.cell(), F::ZERO)?;
+                            checks.push((tuple[ADDRESS].clone(), OFFSET_BYTES));
+                            match (&previous_call, access.same_frame) {
+                                (Some(previous), true) => region
+                                    .constrain_equal(previous.cell(), tuple[CALL_ID].cell())?,
+                                _ => checks.push((tuple[CALL_ID].clone(), CALL_ID_BYTES)),
+                            }
+                            previous_call = Some(tuple[CALL_ID].clone());
+                        }
+                        // The first byte follows the last byte of the previous word
+                        None if previous_length == 32 => self.s_next.enable(region, row - 1)?,
+                        None => return Err(Error::Synthesis),
+                    }
+                }
+                if i + 1 < length {
+                    self.s_next.enable(region, row)?;
+                }
+
+                // Big-endian bytes recompose the high limb, then the low one
+                let byte = Value::known(u64_to_field::<F>(entry.byte as u64));
+                if length == 32 {
+                    if i % 16 == 0 {
+                        self.s_limb.enable(region, row)?;
+                        acc = byte;
+                    } else {
+                        self.s_limb_acc.enable(region, row)?;
+                        acc = acc * Value::known(u64_to_field::<F>(256)) + byte;
+                    }
+                    let acc_cell = assign_value(region, self.cells[ACC], row, acc)?;
+                    if let Some(value) = access.value {
+                        if i == 15 {
+                            region.constrain_equal(acc_cell.cell(), value.hi.cell())?;
+                        } else if i == 31 {
+                            region.constrain_equal(acc_cell.cell(), value.lo.cell())?;
+                        }
+                    }
+                }
+                tuples.push(tuple);
+                row += 1;
+            }
+            previous_length = length;
+            bytes.push(access_bytes);
+
+            // MSTORE8 writes the low byte of the stored word
+            if length == 1 {
+                if let Some(value) = access.value {
+                    self.s_low_byte.enable(region, row - 1)?;
+                    value
+                        .lo
+                        .copy_advice(|| "stored limb", region, self.cells[ACC], row - 1)?;
+                    let inv_256 = u64_to_field::<F>(256).invert().unwrap();
+                    let byte = u64_to_field::<F>(entries[tuples.len() - 1].byte as u64);
+                    let rest = value.lo.value().map(|lo| (*lo - byte) * inv_256);
+                    let rest = assign_value(region, self.cells[ACC], row, rest)?;
+                    checks.push((rest, REST_BYTES));
+                    row += 1;
+                }
+            }
+        }
+
+        debug_assert_eq!(tuples.len(), real);
+        for entry in &entries[real..] {
+            self.s_access.enable(region, row)?;
+            tuples.push(self.assign_entry(region, row, entry, true)?.0);
+            row += 1;
+        }
+
+        Ok((tuples, checks, bytes))
+    }
+
+    /// Assign the fields of an access row, returning the tuple and byte cells
+    ///
+    /// The counter and the direction follow from the opcodes, and padding
+    /// is constant throughout.
+    fn assign_entry<F: PrimeField>(
+        &self,
+        region: &mut Region<'_, F>,
+        row: usize,
+        entry: &Entry,
+        padding: bool,
+    ) -> Result<(Tuple<F>, AssignedCell<F, F>), Error> {
+        let [call_id, address, rw, wv] = entry.tuple::<F>();
+        let mut assign = |column: usize, value: F, constant: bool| {
+            if constant {
+                region.assign_advice_from_constant(|| "access", self.cells[column], row, value)
+            } else {
+                assign_value(region, self.cells[column], row, Value::known(value))
+            }
+        };
+        let tuple = vec![
+            assign(CALL_ID, call_id, padding)?,
+            assign(ADDRESS, address, padding)?,
+            assign(RW, rw, true)?,
+            assign(WV, wv, false)?,
+        ];
+        assign(IS_WRITE, u64_to_field(entry.is_write as u64), true)?;
+        let byte = assign(BYTE, u64_to_field(entry.byte as u64), padding)?;
+        Ok((tuple, byte))
+    }
+
+    /// Lay out the accesses in key order and check every read
+    ///
+    /// Returns the cells to range check.
+    fn assign_key_order<F: PrimeField>(
+        &self,
+        region: &mut Region<'_, F>,
+        sorted: &[Entry],
+        tuples: &[Vec<AssignedCell<F, F>>],
+        size_cell: &AssignedCell<F, F>,
+        size: u64,
+    ) -> Result<RangeChecks<F>, Error> {
+        let mut checks = Vec::new();
+        let known = |value: u64| Value::known(u64_to_field::<F>(value));
+
+        for (row, (entry, tuple)) in sorted.iter().zip(tuples).enumerate() {
+            self.s_access.enable(region, row)?;
+            self.s_sorted.enable(region, row)?;
+            if row == 0 {
+                self.s_first.enable(region, row)?;
+                size_cell.copy_advice(|| "size", region, self.cells[SIZE], row)?;
+            } else {
+                assign_value(region, self.cells[SIZE], row, known(size))?;
+            }
+
+            for (j, cell) in tuple.iter().enumerate() {
+                cell.copy_advice(|| "access", region, self.cells[j], row)?;
+            }
+            assign_value(
+                region,
+                self.cells[IS_WRITE],
+                row,
+                known(entry.is_write as u64),
+            )?;
+            assign_value(region, self.cells[BYTE], row, known(entry.byte as u64))?;
+
+            let previous = row.checked_sub(1).map(|r| sorted[r]);
+            let diff = previous.map_or(F::ZERO, |previous| {
+                u128_to_field::<F>(entry.address_key()) - u128_to_field::<F>(previous.address_key())
+            });
+            let same = previous.is_some_and(|p| p.address_key() == entry.address_key());
+            assign_value(
+                region,
+                self.cells[INV],
+                row,
+                Value::known(diff.invert().unwrap_or(F::ZERO)),
+            )?;
+            assign_value(region, self.cells[SAME], row, known(same as u64))?;
+
+            let free = !entry.is_write && !same && entry.address < size;
+            assign_value(region, self.cells[FREE], row, known(free as u64))?;
+            let bound = if free { size - 1 - entry.address } else { 0 };
+            checks.push((
+                assign_value(region, self.cells[BOUND], row, known(bound))?,
+                BOUND_BYTES,
+            ));
+
+            let gap = sorted
+                .get(row + 1)
+                .map_or(0, |next| next.key() - entry.key() - 1);
+            let gap = assign_value(
+                region,
+                self.cells[GAP],
+                row,
+                Value::known(u128_to_field(gap)),
+            )?;
+            if row + 1 < sorted.len() {
+                self.s_sorted_pair.enable(region, row)?;
+                checks.push((gap, KEY_BYTES));
+            }
+        }
+
+        Ok(checks)
+    }
+}
+
+/// Circuit proving the read/write consistency of a list of memory accesses
+///
+/// Stack words are free cells here; `EvmCircuit` ties them to its steps.
+#[derive(Default, Clone, Debug)]
+pub struct MemoryCircuit<F: Field> {
+    /// Accesses in trace order
+    pub ops: Vec<MemoryOp>,
+    /// Memory size in bytes before the first access
+    pub initial_size: u64,
+    _marker: PhantomData<F>,
+}
+
+impl<F: Field> MemoryCircuit<F> {
+    /// Create a new memory circuit
+    pub fn new(ops: Vec<MemoryOp>, initial_size: u64) -> Self {
+        Self {
+            ops,
+            initial_size,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<F: PrimeField> Circuit<F> for MemoryCircuit<F> {
+    type Config = MemoryCircuitConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        let ops = self
+            .ops
+            .iter()
+            .map(|op| MemoryOp {
+                length: op.length,
+                is_write: op.is_write,
+                ..MemoryOp::default()
+            })
+            .collect();
+        Self::new(ops, 0)
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let constants = meta.fixed_column();
+        meta.enable_constant(constants);
+        let byte_table = ByteTableConfig::configure(meta);
+        let word_config = WordChip::configure(meta, byte_table);
+        MemoryCircuitConfig::configure(meta, word_config)
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        config.word_config.byte_table.load(&mut layouter)?;
+
+        // One row of free cells per access: offset and value limbs
+        let (size, words) = layouter.assign_region(
+            || "stack words",
+            |mut region| {
+                let known = |value: U256| word_limbs::<F>(value).map(Value::known);
+                let size = assign_value(
+                    &mut region,
+                    config.cells[0],
+                    0,
+                    Value::known(u64_to_field(self.initial_size)),
+                )?;
+                let mut words = Vec::with_capacity(self.ops.len());
+                for (i, op) in self.ops.iter().enumerate() {
+                    let limbs = known(U256::from(op.offset))
+                        .into_iter()
+                        .chain(known(op.value))
+                        .enumerate()
+                        .map(|(j, limb)| assign_value(&mut region, config.cells[j], i + 1, limb))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    let [offset_lo, offset_hi, value_lo, value_hi] =
+                        <[_; 4]>::try_from(limbs).map_err(|_| Error::Synthesis)?;
+                    words.push((
+                        AssignedWord {
+                            lo: offset_lo,
+                            hi: offset_hi,
+                        },
+                        AssignedWord {
+                            lo: value_lo,
+                            hi: value_hi,
+                        },
+                    ));
+                }
+                Ok((size, words))
+            },
+        )?;
+
+        let accesses: Vec<MemoryAccess<'_, F>> = self
+            .ops
+            .iter()
+            .zip(&words)
+            .enumerate()
+            .map(|(i, (op, (offset, value)))| MemoryAccess {
+                op: *op,
+                offset: Some(offset),
+                value: Some(value),
+                same_frame: i > 0 && self.ops[i - 1].call_id == op.call_id,
+            })
+            .collect();
+
+        config.assign(
+            layouter.namespace(|| "memory"),
+            &accesses,
+            &size,
+            self.initial_size,
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2_proofs::{dev::MockProver, pasta::Fp};
+
+    fn op(is_write: bool, call_id: usize, offset: u64, value: U256) -> MemoryOp {
+        MemoryOp {
+            step: 0,
+            call_id,
+            offset,
+            length: 32,
+            value,
+            is_write,
+        }
+    }
+
+    fn run(ops: Vec<MemoryOp>, initial_size: u64) -> MockProver<Fp> {
+        let circuit = MemoryCircuit::<Fp>::new(ops, initial_size);
+        MockProver::run(12, &circuit, vec![]).unwrap()
+    }
+
+    #[test]
+    fn test_memory_reads_follow_writes() {
+        let word = U256::from_be_bytes([0x11; 32]) + U256::from(0x2a);
+        let store_byte = MemoryOp {
+            length: 1,
+            ..op(true, 0, 40, U256::from(0xee))
+        };
+        let mut shifted = word.to_be_bytes::<32>()[16..].to_vec();
+        shifted.resize(32, 0);
+        shifted[24] = 0xee;
+        let ops = vec![
+            op(true, 0, 0, word),
+            op(false, 0, 0, word),
+            store_byte,
+            // Half of the stored word, then the byte, then untouched memory
+            op(false, 0, 16, U256::from_be_slice(&shifted)),
+            op(false, 0, 64, U256::ZERO),
+        ];
+        run(ops.clone(), 0).assert_satisfied();
+
+        // Every read must see the bytes written before it
+        let mut wrong = ops.clone();
+        wrong[1].value += U256::from(1);
+        assert!(run(wrong, 0).verify().is_err());
+        let mut wrong = ops;
+        wrong[3].value = word;
+        assert!(run(wrong, 0).verify().is_err());
+    }
+
+    #[test]
+    fn test_memory_first_reads() {
+        let value = U256::from(7);
+        // Untouched memory reads as zero, below the initial size as anything
+        let read_twice = vec![op(false, 0, 0, value), op(false, 0, 0, value)];
+        assert!(run(read_twice.clone(), 0).verify().is_err());
+        run(read_twice.clone(), 32).assert_satisfied();
+
+        // Later reads still agree with the first one
+        let mut disagree = read_twice;
+        disagree[1].value = U256::ZERO;
+        assert!(run(disagree, 32).verify().is_err());
+
+        // Every call frame starts with its own empty memory
+        let frames = vec![op(true, 1, 0, value), op(false, 2, 0, value)];
+        assert!(run(frames, 0).verify().is_err());
+        let frames = vec![op(true, 1, 0, value), op(false, 2, 0, U256::ZERO)];
+        run(frames, 0).assert_satisfied();
+    }
+}
@@ -5,8 +5,15 @@
 
 pub mod arithmetic;
 pub mod main_circuit;
+pub mod memory;
+pub mod mpt;
+mod network;
+pub mod stack;
 pub mod storage;
 
 pub use arithmetic::ArithmeticCircuit;
 pub use main_circuit::EvmCircuit;
+pub use memory::MemoryCircuit;
+pub use mpt::MptCircuit;
+pub use stack::StackCircuit;
 pub use storage::StorageCircuit;
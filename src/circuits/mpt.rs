@@ -0,0 +1,253 @@
+//! State proof circuit for Ethereum storage slots
+//!
+//! Proves an `eth_getProof` response against a public state root: the
+//! account proof opens `keccak(address)` in the state trie, and every storage
+//! proof opens `keccak(slot)` in the storage trie whose root the account leaf
+//! holds. The slots and their values are public, which ties proven SLOADs to
+//! a real block. Only inclusion is proven; slots proven empty by a storage
+//! proof are rejected when building the circuit.
+//!
+//! The number of nodes of every proof and the Keccak blocks of every node are
+//! part of the circuit's shape, and so of its verifying key.
+
+use alloy_primitives::{U256, keccak256};
+use halo2_proofs::{
+    circuit::{Layouter, SimpleFloorPlanner},
+    pasta::group::ff::PrimeField,
+    plonk::{Circuit, Column, ConstraintSystem, Error, Instance},
+};
+use std::marker::PhantomData;
+
+use crate::chips::keccak_chip::KeccakChip;
+use crate::chips::mpt_chip::{MptChip, MptChipConfig, MptLeaf};
+use crate::chips::word_chip::{AssignedWord, LIMB_BYTES, word_limbs};
+use crate::errors::{ProverError, Result as ProverResult};
+use crate::utils::evm_parser::StateProof;
+use crate::utils::mpt::verify_proof;
+
+/// Public inputs before those of the slots: the state root and the address,
+/// as low and high limbs
+pub const ACCOUNT_INPUTS: usize = 4;
+
+/// Public inputs of every storage proof: the slot and its value, as low and
+/// high limbs
+pub const PUBLIC_INPUTS_PER_SLOT: usize = 4;
+
+/// Bytes of an address in its low limb
+const ADDRESS_LO_BYTES: usize = LIMB_BYTES;
+
+/// Configuration for the state proof circuit
+#[derive(Clone, Debug)]
+pub struct MptCircuitConfig {
+    /// Trie openings and the Keccak digests they need
+    pub mpt_config: MptChipConfig,
+    /// State root and address, then the slots and values
+    pub public_input: Column<Instance>,
+}
+
+/// Circuit proving storage slots of an account against a state root
+#[derive(Clone, Debug)]
+pub struct MptCircuit<F: PrimeField> {
+    /// Account and storage proofs with the state root they open against
+    pub state_proof: StateProof,
+    /// Child items walked through the account proof
+    account_children: Vec<usize>,
+    /// Child items walked through every storage proof
+    storage_children: Vec<Vec<usize>>,
+    _marker: PhantomData<F>,
+}
+
+impl<F: PrimeField> MptCircuit<F> {
+    /// Create a circuit for a state proof, which must verify natively and
+    /// open every slot to a stored (non-zero) value
+    pub fn new(state_proof: StateProof) -> ProverResult<Self> {
+        state_proof.verify()?;
+        let proof = &state_proof.proof;
+        let account_children = verify_proof(
+            state_proof.state_root,
+            state_proof.account_key(),
+            &proof.account_proof,
+        )?
+        .children;
+
+        let mut storage_children = Vec::with_capacity(proof.storage_proof.len());
+        for storage in &proof.storage_proof {
+            let slot = storage.key.as_b256();
+            let path = verify_proof(proof.storage_hash, keccak256(slot), &storage.proof)?;
+            if path.value.is_none() {
+                return Err(ProverError::InvalidInput(format!(
+                    "Slot {} is empty; only stored slots can be proven",
+                    slot
+                )));
+            }
+            storage_children.push(path.children);
+        }
+
+        Ok(Self {
+            state_proof,
+            account_children,
+            storage_children,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Public inputs: the low and high limbs of the state root and the
+    /// address, then of every slot and its value
+    pub fn public_inputs(&self) -> Vec<F> {
+        let proof = &self.state_proof.proof;
+        let account = [
+            U256::from_be_bytes(self.state_proof.state_root.0),
+            U256::from_be_slice(proof.address.as_slice()),
+        ];
+        let slots = proof
+            .storage_proof
+            .iter()
+            .flat_map(|storage| [U256::from_be_bytes(storage.key.as_b256().0), storage.value]);
+        account
+            .into_iter()
+            .chain(slots)
+            .flat_map(word_limbs::<F>)
+            .collect()
+    }
+}
+
+impl<F: PrimeField> Circuit<F> for MptCircuit<F> {
+    type Config = MptCircuitConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        // The proofs fix the shape of the circuit
+        self.clone()
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let keccak_config = KeccakChip::configure(meta);
+        let mpt_config = MptChip::configure(meta, keccak_config);
+        let public_input = meta.instance_column();
+        meta.enable_equality(public_input);
+
+        MptCircuitConfig {
+            mpt_config,
+            public_input,
+        }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let chip = MptChip::construct(config.mpt_config.clone());
+        chip.load_table(&mut layouter)?;
+        let proof = &self.state_proof.proof;
+
+        // Account: keccak(address) opens the state trie at the public root
+        let address =
+            chip.assign_bytes(layouter.namespace(|| "address"), proof.address.as_slice())?;
+        let account_key = chip.hash(layouter.namespace(|| "account key"), &address)?;
+        let account = chip.prove(
+            layouter.namespace(|| "account proof"),
+            &account_key,
+            &proof.account_proof,
+            &self.account_children,
+            MptLeaf::Account,
+        )?;
+        let split = address.len() - ADDRESS_LO_BYTES;
+        let address_word = AssignedWord {
+            lo: chip.compose(layouter.namespace(|| "address lo"), &address[split..])?,
+            hi: chip.compose(layouter.namespace(|| "address hi"), &address[..split])?,
+        };
+        let mut words = vec![account.root, address_word];
+
+        // Slots: keccak(slot) opens the account's storage trie
+        for (index, (storage, children)) in proof
+            .storage_proof
+            .iter()
+            .zip(&self.storage_children)
+            .enumerate()
+        {
+            let slot = chip.assign_bytes(
+                layouter.namespace(|| format!("slot {}", index)),
+                storage.key.as_b256().as_slice(),
+            )?;
+            let key = chip.hash(layouter.namespace(|| format!("slot {} key", index)), &slot)?;
+            let opening = chip.prove(
+                layouter.namespace(|| format!("storage proof {}", index)),
+                &key,
+                &storage.proof,
+                children,
+                MptLeaf::Storage,
+            )?;
+            layouter.assign_region(
+                || format!("storage root {}", index),
+                |mut region| {
+                    region.constrain_equal(opening.root.lo.cell(), account.leaf.lo.cell())?;
+                    region.constrain_equal(opening.root.hi.cell(), account.leaf.hi.cell())
+                },
+            )?;
+            words.push(AssignedWord {
+                lo: chip.compose(
+                    layouter.namespace(|| format!("slot {} lo", index)),
+                    &slot[LIMB_BYTES..],
+                )?,
+                hi: chip.compose(
+                    layouter.namespace(|| format!("slot {} hi", index)),
+                    &slot[..LIMB_BYTES],
+                )?,
+            });
+            words.push(opening.leaf);
+        }
+
+        for (index, word) in words.iter().enumerate() {
+            layouter.constrain_instance(word.lo.cell(), config.public_input, 2 * index)?;
+            layouter.constrain_instance(word.hi.cell(), config.public_input, 2 * index + 1)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::evm_parser::load_state_proof;
+    use halo2_proofs::{dev::MockProver, pasta::Fp};
+
+    const K: u32 = 15;
+
+    /// Fixture proof keeping only the storage proof of `slot`
+    fn fixture(slot: u64) -> StateProof {
+        let mut state_proof = load_state_proof("tests/fixtures/eth_get_proof.json").unwrap();
+        state_proof
+            .proof
+            .storage_proof
+            .retain(|storage| U256::from_be_bytes(storage.key.as_b256().0) == U256::from(slot));
+        assert_eq!(state_proof.proof.storage_proof.len(), 1);
+        state_proof
+    }
+
+    fn run(circuit: &MptCircuit<Fp>, public_inputs: Vec<Fp>) -> MockProver<Fp> {
+        MockProver::run(K, circuit, vec![public_inputs]).unwrap()
+    }
+
+    #[test]
+    fn test_mpt_circuit_storage_slot() {
+        let circuit = MptCircuit::<Fp>::new(fixture(0)).unwrap();
+        assert_eq!(
+            circuit.public_inputs().len(),
+            ACCOUNT_INPUTS + PUBLIC_INPUTS_PER_SLOT
+        );
+        assert_eq!(run(&circuit, circuit.public_inputs()).verify(), Ok(()));
+
+        // A different value for the slot is rejected
+        let mut public_inputs = circuit.public_inputs();
+        public_inputs[ACCOUNT_INPUTS + 2] += Fp::one();
+        assert!(run(&circuit, public_inputs).verify().is_err());
+    }
+
+    #[test]
+    fn test_mpt_circuit_extension_node() {
+        // Slot 2 is reached through a one-nibble extension node
+        let circuit = MptCircuit::<Fp>::new(fixture(2)).unwrap();
+        assert_eq!(run(&circuit, circuit.public_inputs()).verify(), Ok(()));
+    }
+}
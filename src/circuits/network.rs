@@ -0,0 +1,209 @@
+//! Beneš network routing accesses from trace order into key order
+//!
+//! The memory and stack circuits check accesses in the order of their keys,
+//! but lay them out in trace order. A Beneš network of `n` inputs connects the
+//! two: every switch row passes its two input tuples on in order or swapped,
+//! and the wiring between switches only depends on `n`, so the layout stays
+//! independent of the witness.
+
+use halo2_proofs::{
+    circuit::{AssignedCell, Region, Value},
+    pasta::group::ff::PrimeField,
+    plonk::{Advice, Column, ConstraintSystem, Error, Expression, Selector},
+    poly::Rotation,
+};
+
+use crate::chips::evm_chip::u64_to_field;
+use crate::chips::word_chip::assign_value;
+
+/// Cells of the fields an access carries through the network
+pub(crate) type Tuple<F> = Vec<AssignedCell<F, F>>;
+
+/// Switch of a Beneš network
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Switch {
+    pub(crate) inputs: [usize; 2],
+    pub(crate) swap: bool,
+}
+
+/// Route a Beneš network moving input `i` to output `perm[i]`
+///
+/// Wires `0..n` are the inputs, and switch `k` writes wires `n + 2k` and
+/// `n + 2k + 1`: its inputs in order, or swapped. Returns the switches, each
+/// after the ones writing its inputs, and the output wires. Only the swaps
+/// depend on `perm`, whose length must be a power of two (at least 2).
+pub(crate) fn route(perm: &[usize]) -> (Vec<Switch>, Vec<usize>) {
+    let inputs: Vec<usize> = (0..perm.len()).collect();
+    let mut switches = Vec::new();
+    let outputs = route_into(perm, &inputs, perm.len(), &mut switches);
+    (switches, outputs)
+}
+
+fn route_into(
+    perm: &[usize],
+    inputs: &[usize],
+    first_wire: usize,
+    switches: &mut Vec<Switch>,
+) -> Vec<usize> {
+    let push = |switches: &mut Vec<Switch>, inputs: [usize; 2], swap: bool| {
+        let wire = first_wire + 2 * switches.len();
+        switches.push(Switch { inputs, swap });
+        [wire, wire + 1]
+    };
+
+    let n = perm.len();
+    if n == 2 {
+        return push(switches, [inputs[0], inputs[1]], perm[0] == 1).to_vec();
+    }
+
+    // The two inputs of a switch take different halves, and so do the
+    // sources of the two outputs of a switch; colour the cycles this makes
+    let mut inverse = vec![0; n];
+    for (i, &o) in perm.iter().enumerate() {
+        inverse[o] = i;
+    }
+    let mut lower: Vec<Option<bool>> = vec![None; n];
+    for start in (0..n).step_by(2) {
+        let mut i = start;
+        while lower[i].is_none() {
+            lower[i] = Some(false);
+            lower[i ^ 1] = Some(true);
+            i = inverse[perm[i ^ 1] ^ 1];
+        }
+    }
+    let lower: Vec<bool> = lower.into_iter().map(|side| side == Some(true)).collect();
+
+    let half = n / 2;
+    let mut upper_inputs = Vec::with_capacity(half);
+    let mut lower_inputs = Vec::with_capacity(half);
+    let mut upper_perm = vec![0; half];
+    let mut lower_perm = vec![0; half];
+    for k in 0..half {
+        let swap = lower[2 * k];
+        let [up, down] = push(switches, [inputs[2 * k], inputs[2 * k + 1]], swap);
+        upper_inputs.push(up);
+        lower_inputs.push(down);
+        let (u, l) = if swap {
+            (2 * k + 1, 2 * k)
+        } else {
+            (2 * k, 2 * k + 1)
+        };
+        upper_perm[k] = perm[u] / 2;
+        lower_perm[k] = perm[l] / 2;
+    }
+
+    let upper_outputs = route_into(&upper_perm, &upper_inputs, first_wire, switches);
+    let lower_outputs = route_into(&lower_perm, &lower_inputs, first_wire, switches);
+
+    // Output 2k comes out of the upper half unless its source went lower
+    let mut outputs = Vec::with_capacity(n);
+    for k in 0..half {
+        let swap = lower[inverse[2 * k]];
+        outputs.extend(push(switches, [upper_outputs[k], lower_outputs[k]], swap));
+    }
+    outputs
+}
+
+/// Configure the switch gate
+///
+/// A switch row holds two input and two output tuples of `tuple` fields in
+/// `cells`, then a swap bit: the outputs are the inputs, swapped when the bit
+/// is set.
+pub(crate) fn configure_switch<F: PrimeField>(
+    meta: &mut ConstraintSystem<F>,
+    name: &'static str,
+    s_switch: Selector,
+    cells: &[Column<Advice>],
+    tuple: usize,
+) {
+    meta.create_gate(name, |meta| {
+        let s = meta.query_selector(s_switch);
+        let one = Expression::Constant(F::ONE);
+        let swap = meta.query_advice(cells[4 * tuple], Rotation::cur());
+        let mut constraints = vec![s.clone() * swap.clone() * (one - swap.clone())];
+        for j in 0..tuple {
+            let [a, b, c, d] =
+                [0, 1, 2, 3].map(|k| meta.query_advice(cells[k * tuple + j], Rotation::cur()));
+            constraints
+                .push(s.clone() * (c.clone() - a.clone() - swap.clone() * (b.clone() - a.clone())));
+            constraints.push(s.clone() * (d - (a + b - c)));
+        }
+        constraints
+    });
+}
+
+/// Route the tuples `inputs`, whose fields hold `values`, so that input `i`
+/// comes out at rank `perm[i]`, one switch per row
+///
+/// Returns the tuples in rank order.
+pub(crate) fn assign_network<F: PrimeField>(
+    region: &mut Region<'_, F>,
+    s_switch: Selector,
+    cells: &[Column<Advice>],
+    inputs: &[Tuple<F>],
+    values: &[Vec<F>],
+    perm: &[usize],
+) -> Result<Vec<Tuple<F>>, Error> {
+    let tuple = values.first().map_or(0, Vec::len);
+    let (switches, outputs) = route(perm);
+    let mut wires = inputs.to_vec();
+    let mut values = values.to_vec();
+
+    for (row, switch) in switches.iter().enumerate() {
+        s_switch.enable(region, row)?;
+        let [a, b] = switch.inputs;
+        for (side, wire) in [a, b].into_iter().enumerate() {
+            for (j, cell) in wires[wire].iter().enumerate() {
+                cell.copy_advice(|| "switch input", region, cells[side * tuple + j], row)?;
+            }
+        }
+        region.assign_advice(
+            || "swap",
+            cells[4 * tuple],
+            row,
+            || Value::known(u64_to_field::<F>(switch.swap as u64)),
+        )?;
+
+        let swapped = if switch.swap { [b, a] } else { [a, b] };
+        for (side, wire) in swapped.into_iter().enumerate() {
+            let columns = &cells[(side + 2) * tuple..(side + 3) * tuple];
+            let tuple_values = values[wire].clone();
+            let tuple_cells = tuple_values
+                .iter()
+                .zip(columns)
+                .map(|(&value, &column)| assign_value(region, column, row, Value::known(value)))
+                .collect::<Result<Vec<_>, _>>()?;
+            wires.push(tuple_cells);
+            values.push(tuple_values);
+        }
+    }
+
+    Ok(outputs.iter().map(|&wire| wires[wire].clone()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_route_permutes() {
+        for n in [2, 4, 8, 16, 64] {
+            // A fixed scramble of 0..n
+            let perm: Vec<usize> = (0..n).map(|i| (i * 5 + 3) % n).collect();
+            let (switches, outputs) = route(&perm);
+            assert_eq!(
+                switches.len(),
+                n / 2 * (2 * n.trailing_zeros() as usize - 1)
+            );
+
+            let mut wires: Vec<usize> = (0..n).collect();
+            for switch in &switches {
+                let [a, b] = switch.inputs.map(|wire| wires[wire]);
+                wires.extend(if switch.swap { [b, a] } else { [a, b] });
+            }
+            for (i, &o) in perm.iter().enumerate() {
+                assert_eq!(wires[outputs[o]], i);
+            }
+        }
+    }
+}
@@ -0,0 +1,679 @@
+//! Stack circuit for EVM read/write consistency
+//!
+//! Every step reads the stack items it pops and writes the ones it pushes
+//! (see `OpCode::stack_slots`), each item addressed by its pointer, the
+//! number of items below it. The accesses are keyed by `(call_id, pointer,
+//! rw_counter)`, the read/write counter numbering them in trace order. In
+//! trace order every pointer is the step's stack depth plus an offset its
+//! opcode fixes. A Beneš network (see `network`) routes the accesses into key
+//! order, where the accesses of an item are adjacent and in execution order:
+//! every read must return the word of the access before it. An item must be
+//! written before it is read, except below the initial stack depth, whose
+//! items were pushed before the trace.
+//!
+//! The EVM chip keeps every depth within `0..=1024` and at least what its
+//! step pops, so pointers never leave `0..1024` and need no range check.
+
+use alloy_primitives::U256;
+use halo2_proofs::{
+    arithmetic::Field,
+    circuit::{AssignedCell, Layouter, Region, SimpleFloorPlanner, Value},
+    pasta::group::ff::PrimeField,
+    plonk::{Advice, Circuit, Column, ConstraintSystem, Error, Expression, Fixed, Selector},
+    poly::Rotation,
+};
+use std::marker::PhantomData;
+
+use crate::chips::access_chip::u128_to_field;
+use crate::chips::evm_chip::u64_to_field;
+use crate::chips::tables::ByteTableConfig;
+use crate::chips::word_chip::{AssignedWord, WordChip, WordChipConfig, assign_value, word_limbs};
+use crate::circuits::memory::CALL_ID_BYTES;
+use crate::circuits::network::{Tuple, assign_network, configure_switch};
+use crate::utils::evm_parser::StackOp;
+
+/// Weight of the call id in an item key, past any pointer
+const CALL_ID_SHIFT: u64 = 1 << 16;
+
+/// Weight of the item key in a sort key, past any read/write counter
+const RW_SHIFT: u64 = 1 << 32;
+
+/// Bytes the gap between consecutive sort keys is range checked to
+const KEY_BYTES: usize = 9;
+
+/// Bytes the distance from a first read to the initial stack depth is range
+/// checked to
+const BOUND_BYTES: usize = 2;
+
+/// Fields of an access carried through the network
+const TUPLE: usize = 6;
+
+// Columns of an access, in trace and in key order
+const CALL_ID: usize = 0;
+const POINTER: usize = 1;
+const RW: usize = 2;
+const IS_WRITE: usize = 3;
+const LO: usize = 4;
+const HI: usize = 5;
+
+// Columns in trace order only
+const DEPTH: usize = 6;
+
+// Columns in key order only
+const INV: usize = 6;
+const SAME: usize = 7;
+const FREE: usize = 8;
+const GAP: usize = 9;
+const BOUND: usize = 10;
+const INITIAL_DEPTH: usize = 11;
+
+// Swap bit of a switch, after its two input and two output tuples
+const SWAP: usize = 4 * TUPLE;
+
+/// Cells to range check, with their number of bytes
+type RangeChecks<F> = Vec<(AssignedCell<F, F>, usize)>;
+
+/// Tuples of the entries in trace order, cells to range check and the word
+/// of every access
+type TraceOrder<F> = (Vec<Tuple<F>>, RangeChecks<F>, Vec<AssignedWord<F>>);
+
+/// Configuration for the stack circuit
+#[derive(Clone, Debug)]
+pub struct StackCircuitConfig {
+    /// Advice columns; the gates document their roles
+    pub cells: [Column<Advice>; SWAP + 1],
+    /// Pointer of an access minus the stack depth of its step
+    pub shift: Column<Fixed>,
+    /// Access in trace order
+    pub s_access: Selector,
+    /// Switch of the network
+    pub s_switch: Selector,
+    /// Access in key order
+    pub s_sorted: Selector,
+    /// Access in key order followed by another
+    pub s_sorted_pair: Selector,
+    /// First access in key order
+    pub s_first: Selector,
+    /// Range checks for call ids, key gaps and first reads
+    pub word_config: WordChipConfig,
+}
+
+/// Stack item a step reads or writes, for the circuit to check
+#[derive(Clone, Debug)]
+pub struct StackAccess<'a, F: Field> {
+    /// Record of the access; its direction sets the layout
+    pub op: StackOp,
+    /// Pointer minus the stack depth of the step, fixed by the opcode
+    pub shift: i64,
+    /// Stack depth of the step
+    pub depth: &'a AssignedCell<F, F>,
+    /// Stack word of the step holding the item (none for items below the
+    /// top three, or pushes the next step does not show)
+    pub value: Option<&'a AssignedWord<F>>,
+    /// Whether the access is in the call frame of the previous one
+    pub same_frame: bool,
+}
+
+/// Stack access, ordered by its sort key
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Entry {
+    call_id: u64,
+    pointer: u64,
+    rw: u64,
+    is_write: bool,
+    value: U256,
+}
+
+impl Entry {
+    fn item_key(&self) -> u128 {
+        self.call_id as u128 * CALL_ID_SHIFT as u128 + self.pointer as u128
+    }
+
+    fn key(&self) -> u128 {
+        self.item_key() * RW_SHIFT as u128 + self.rw as u128
+    }
+
+    fn tuple<F: PrimeField>(&self) -> [F; TUPLE] {
+        let [lo, hi] = word_limbs::<F>(self.value);
+        let [call_id, pointer, rw, is_write] =
+            [self.call_id, self.pointer, self.rw, self.is_write as u64].map(u64_to_field::<F>);
+        [call_id, pointer, rw, is_write, lo, hi]
+    }
+}
+
+/// Field element of a signed offset
+fn i64_to_field<F: PrimeField>(value: i64) -> F {
+    let magnitude = u64_to_field::<F>(value.unsigned_abs());
+    if value < 0 { -magnitude } else { magnitude }
+}
+
+impl StackCircuitConfig {
+    /// Configure the access, network and key order gates
+    ///
+    /// An access row holds `(call_id, pointer, rw, is_write, lo, hi)`, all
+    /// carried through the network, and in trace order the step's `depth`,
+    /// with `pointer = depth + shift`. A switch row holds two input and two
+    /// output tuples and a swap bit. In key order a row adds `inv` and `same`
+    /// (whether the item key `call_id * 2^16 + pointer` equals the previous
+    /// row's), `free` (a first read below the initial depth), `gap` (the next
+    /// sort key `item_key * 2^32 + rw` minus this one, minus one), `bound`
+    /// (`initial_depth - 1 - pointer` for a free read) and `initial_depth`.
+    pub fn configure<F: PrimeField>(
+        meta: &mut ConstraintSystem<F>,
+        word_config: WordChipConfig,
+    ) -> Self {
+        let cells = [(); SWAP + 1].map(|_| meta.advice_column());
+        for column in &cells[..SWAP] {
+            meta.enable_equality(*column);
+        }
+        let shift = meta.fixed_column();
+
+        let s_access = meta.selector();
+        let s_switch = meta.selector();
+        let s_sorted = meta.selector();
+        let s_sorted_pair = meta.selector();
+        let s_first = meta.selector();
+
+        let constant = |value: u64| Expression::Constant(u64_to_field::<F>(value));
+        let one = || constant(1);
+
+        meta.create_gate("stack pointer", |meta| {
+            let s = meta.query_selector(s_access);
+            let pointer = meta.query_advice(cells[POINTER], Rotation::cur());
+            let depth = meta.query_advice(cells[DEPTH], Rotation::cur());
+            let shift = meta.query_fixed(shift);
+            vec![s * (pointer - depth - shift)]
+        });
+
+        configure_switch(meta, "stack switch", s_switch, &cells, TUPLE);
+
+        meta.create_gate("stack free read", |meta| {
+            let s = meta.query_selector(s_sorted);
+            let free = meta.query_advice(cells[FREE], Rotation::cur());
+            let bound = meta.query_advice(cells[BOUND], Rotation::cur());
+            let initial_depth = meta.query_advice(cells[INITIAL_DEPTH], Rotation::cur());
+            let pointer = meta.query_advice(cells[POINTER], Rotation::cur());
+            vec![
+                s.clone() * free.clone() * (one() - free.clone()),
+                s * (bound - free * (initial_depth - one() - pointer)),
+            ]
+        });
+
+        meta.create_gate("stack first read", |meta| {
+            let s = meta.query_selector(s_first);
+            let is_write = meta.query_advice(cells[IS_WRITE], Rotation::cur());
+            let free = meta.query_advice(cells[FREE], Rotation::cur());
+            vec![s * (one() - is_write) * (one() - free)]
+        });
+
+        meta.create_gate("stack key order", |meta| {
+            let s = meta.query_selector(s_sorted_pair);
+            let cur = |meta: &mut halo2_proofs::plonk::VirtualCells<'_, F>, column| {
+                meta.query_advice(cells[column], Rotation::cur())
+            };
+            let next = |meta: &mut halo2_proofs::plonk::VirtualCells<'_, F>, column| {
+                meta.query_advice(cells[column], Rotation::next())
+            };
+            let item_key = |call_id: Expression<F>, pointer: Expression<F>| {
+                call_id * constant(CALL_ID_SHIFT) + pointer
+            };
+            let key_cur = item_key(cur(meta, CALL_ID), cur(meta, POINTER));
+            let key_next = item_key(next(meta, CALL_ID), next(meta, POINTER));
+            let diff = key_next.clone() - key_cur.clone();
+            let sort_key =
+                |item_key: Expression<F>, rw: Expression<F>| item_key * constant(RW_SHIFT) + rw;
+            let sort_cur = sort_key(key_cur, cur(meta, RW));
+            let sort_next = sort_key(key_next, next(meta, RW));
+
+            let same = next(meta, SAME);
+            let inv = next(meta, INV);
+            let gap = cur(meta, GAP);
+            let is_read = one() - next(meta, IS_WRITE);
+            let free = next(meta, FREE);
+            vec![
+                // same = 1 exactly when the item keys are equal
+                s.clone() * (same.clone() - one() + diff.clone() * inv),
+                s.clone() * diff * same.clone(),
+                // Sort keys strictly increase
+                s.clone() * (gap - (sort_next - sort_cur - one())),
+                // A read returns the previous word of its item
+                s.clone() * is_read.clone() * same.clone() * (next(meta, LO) - cur(meta, LO)),
+                s.clone() * is_read.clone() * same.clone() * (next(meta, HI) - cur(meta, HI)),
+                // The first access of an item writes it, unless it is free
+                s.clone() * is_read * (one() - same) * (one() - free),
+                s * (next(meta, INITIAL_DEPTH) - cur(meta, INITIAL_DEPTH)),
+            ]
+        });
+
+        Self {
+            cells,
+            shift,
+            s_access,
+            s_switch,
+            s_sorted,
+            s_sorted_pair,
+            s_first,
+            word_config,
+        }
+    }
+
+    /// Check the accesses, in trace order, for read/write consistency, and
+    /// return the word cells of every access
+    ///
+    /// # Arguments
+    ///
+    /// * `accesses` - Stack accesses in trace order
+    /// * `depth_cell` - Stack depth before the first step
+    /// * `depth` - Value of `depth_cell`
+    pub fn assign<F: PrimeField>(
+        &self,
+        mut layouter: impl Layouter<F>,
+        accesses: &[StackAccess<'_, F>],
+        depth_cell: &AssignedCell<F, F>,
+        depth: u64,
+    ) -> Result<Vec<AssignedWord<F>>, Error> {
+        if accesses.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // Accesses in trace order, padded to a power of two with writes of
+        // zero that come after every real access
+        let mut entries: Vec<Entry> = accesses
+            .iter()
+            .enumerate()
+            .map(|(i, access)| Entry {
+                call_id: access.op.call_id as u64,
+                pointer: access.op.pointer,
+                rw: i as u64 + 1,
+                is_write: access.op.is_write,
+                value: access.op.value,
+            })
+            .collect();
+        let n = entries.len().max(2).next_power_of_two();
+        while entries.len() < n {
+            entries.push(Entry {
+                call_id: 0,
+                pointer: 0,
+                rw: entries.len() as u64 + 1,
+                is_write: true,
+                value: U256::ZERO,
+            });
+        }
+
+        let mut checks: RangeChecks<F> = Vec::new();
+        let (inputs, traced_checks, words) = layouter.assign_region(
+            || "stack accesses",
+            |mut region| self.assign_trace_order(&mut region, accesses, &entries),
+        )?;
+        checks.extend(traced_checks);
+
+        // Route every access to its rank in key order
+        let mut order: Vec<usize> = (0..n).collect();
+        order.sort_by_key(|&i| entries[i]);
+        let mut perm = vec![0; n];
+        for (rank, &i) in order.iter().enumerate() {
+            perm[i] = rank;
+        }
+        let values: Vec<Vec<F>> = entries.iter().map(|entry| entry.tuple().to_vec()).collect();
+        let sorted_inputs = layouter.assign_region(
+            || "stack network",
+            |mut region| {
+                assign_network(
+                    &mut region,
+                    self.s_switch,
+                    &self.cells,
+                    &inputs,
+                    &values,
+                    &perm,
+                )
+            },
+        )?;
+
+        let mut sorted = entries.clone();
+        sorted.sort();
+        let sorted_checks = layouter.assign_region(
+            || "stack in key order",
+            |mut region| {
+                self.assign_key_order(&mut region, &sorted, &sorted_inputs, depth_cell, depth)
+            },
+        )?;
+        checks.extend(sorted_checks);
+
+        let word_chip = WordChip::construct(self.word_config.clone());
+        for (i, (cell, num_bytes)) in checks.iter().enumerate() {
+            word_chip.range_check(
+                layouter.namespace(|| format!("stack range {}", i)),
+                cell,
+                *num_bytes,
+            )?;
+        }
+
+        Ok(words)
+    }
+
+    /// Lay out the accesses in trace order and tie them to the steps
+    ///
+    /// Returns the tuple cells of every entry, the cells to range check and
+    /// the word of every access.
+    fn assign_trace_order<F: PrimeField>(
+        &self,
+        region: &mut Region<'_, F>,
+        accesses: &[StackAccess<'_, F>],
+        entries: &[Entry],
+    ) -> Result<TraceOrder<F>, Error> {
+        let mut checks = Vec::new();
+        let mut tuples = Vec::with_capacity(entries.len());
+        let mut words = Vec::with_capacity(accesses.len());
+        let mut previous_call: Option<AssignedCell<F, F>> = None;
+
+        for (row, entry) in entries.iter().enumerate() {
+            self.s_access.enable(region, row)?;
+            let access = accesses.get(row);
+            let padding = access.is_none();
+            let shift = access.map_or(0, |access| access.shift);
+            region.assign_fixed(
+                || "shift",
+                self.shift,
+                row,
+                || Value::known(i64_to_field::<F>(shift)),
+            )?;
+
+            // The counter and the direction follow from the opcodes, and
+            // padding is constant throughout
+            let values = entry.tuple::<F>();
+            let mut assign = |column: usize, value: F, constant: bool| {
+                if constant {
+                    region.assign_advice_from_constant(|| "access", self.cells[column], row, value)
+                } else {
+                    assign_value(region, self.cells[column], row, Value::known(value))
+                }
+            };
+            let tuple = vec![
+                assign(CALL_ID, values[CALL_ID], padding)?,
+                assign(POINTER, values[POINTER], padding)?,
+                assign(RW, values[RW], true)?,
+                assign(IS_WRITE, values[IS_WRITE], true)?,
+                assign(LO, values[LO], padding)?,
+                assign(HI, values[HI], padding)?,
+            ];
+
+            match access {
+                Some(access) => {
+                    access
+                        .depth
+                        .copy_advice(|| "depth", region, self.cells[DEPTH], row)?;
+                    match (&previous_call, access.same_frame) {
+                        (Some(previous), true) => {
+                            region.constrain_equal(previous.cell(), tuple[CALL_ID].cell())?
+                        }
+                        _ => checks.push((tuple[CALL_ID].clone(), CALL_ID_BYTES)),
+                    }
+                    previous_call = Some(tuple[CALL_ID].clone());
+
+                    let word = AssignedWord {
+                        lo: tuple[LO].clone(),
+                        hi: tuple[HI].clone(),
+                    };
+                    if let Some(value) = access.value {
+                        region.constrain_equal(word.lo.cell(), value.lo.cell())?;
+                        region.constrain_equal(word.hi.cell(), value.hi.cell())?;
+                    }
+                    words.push(word);
+                }
+                None => {
+                    region.assign_advice_from_constant(
+                        || "depth",
+                        self.cells[DEPTH],
+                        row,
+                        F::ZERO,
+                    )?;
+                }
+            }
+            tuples.push(tuple);
+        }
+
+        Ok((tuples, checks, words))
+    }
+
+    /// Lay out the accesses in key order and check every read
+    ///
+    /// Returns the cells to range check.
+    fn assign_key_order<F: PrimeField>(
+        &self,
+        region: &mut Region<'_, F>,
+        sorted: &[Entry],
+        tuples: &[Tuple<F>],
+        depth_cell: &AssignedCell<F, F>,
+        depth: u64,
+    ) -> Result<RangeChecks<F>, Error> {
+        let mut checks = Vec::new();
+        let known = |value: u64| Value::known(u64_to_field::<F>(value));
+
+        for (row, (entry, tuple)) in sorted.iter().zip(tuples).enumerate() {
+            self.s_sorted.enable(region, row)?;
+            if row == 0 {
+                self.s_first.enable(region, row)?;
+                depth_cell.copy_advice(
+                    || "initial depth",
+                    region,
+                    self.cells[INITIAL_DEPTH],
+                    row,
+                )?;
+            } else {
+                assign_value(region, self.cells[INITIAL_DEPTH], row, known(depth))?;
+            }
+
+            for (j, cell) in tuple.iter().enumerate() {
+                cell.copy_advice(|| "access", region, self.cells[j], row)?;
+            }
+
+            let previous = row.checked_sub(1).map(|r| sorted[r]);
+            let diff = previous.map_or(F::ZERO, |previous| {
+                u128_to_field::<F>(entry.item_key()) - u128_to_field::<F>(previous.item_key())
+            });
+            let same = previous.is_some_and(|p| p.item_key() == entry.item_key());
+            assign_value(
+                region,
+                self.cells[INV],
+                row,
+                Value::known(diff.invert().unwrap_or(F::ZERO)),
+            )?;
+            assign_value(region, self.cells[SAME], row, known(same as u64))?;
+
+            let free = !entry.is_write && !same && entry.pointer < depth;
+            assign_value(region, self.cells[FREE], row, known(free as u64))?;
+            let bound = if free { depth - 1 - entry.pointer } else { 0 };
+            checks.push((
+                assign_value(region, self.cells[BOUND], row, known(bound))?,
+                BOUND_BYTES,
+            ));
+
+            let gap = sorted
+                .get(row + 1)
+                .map_or(0, |next| next.key() - entry.key() - 1);
+            let gap = assign_value(
+                region,
+                self.cells[GAP],
+                row,
+                Value::known(u128_to_field(gap)),
+            )?;
+            if row + 1 < sorted.len() {
+                self.s_sorted_pair.enable(region, row)?;
+                checks.push((gap, KEY_BYTES));
+            }
+        }
+
+        Ok(checks)
+    }
+}
+
+/// Circuit proving the read/write consistency of a stack log
+///
+/// Depths and words are free cells here; `EvmCircuit` ties them to its
+/// steps.
+#[derive(Default, Clone, Debug)]
+pub struct StackCircuit<F: Field> {
+    /// Accesses in trace order
+    pub ops: Vec<StackOp>,
+    /// Stack depth before the first access
+    pub initial_depth: u64,
+    _marker: PhantomData<F>,
+}
+
+impl<F: Field> StackCircuit<F> {
+    /// Create a new stack circuit
+    pub fn new(ops: Vec<StackOp>, initial_depth: u64) -> Self {
+        Self {
+            ops,
+            initial_depth,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<F: PrimeField> Circuit<F> for StackCircuit<F> {
+    type Config = StackCircuitConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        let ops = self
+            .ops
+            .iter()
+            .map(|op| StackOp {
+                is_write: op.is_write,
+                ..StackOp::default()
+            })
+            .collect();
+        Self::new(ops, 0)
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let constants = meta.fixed_column();
+        meta.enable_constant(constants);
+        let byte_table = ByteTableConfig::configure(meta);
+        let word_config = WordChip::configure(meta, byte_table);
+        StackCircuitConfig::configure(meta, word_config)
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        config.word_config.byte_table.load(&mut layouter)?;
+
+        // One free cell per access: its pointer as the depth, with no shift
+        let (initial_depth, depths) = layouter.assign_region(
+            || "stack depths",
+            |mut region| {
+                let known = |value: u64| Value::known(u64_to_field::<F>(value));
+                let initial_depth =
+                    assign_value(&mut region, config.cells[0], 0, known(self.initial_depth))?;
+                let depths = self
+                    .ops
+                    .iter()
+                    .enumerate()
+                    .map(|(i, op)| {
+                        assign_value(&mut region, config.cells[0], i + 1, known(op.pointer))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok((initial_depth, depths))
+            },
+        )?;
+
+        let accesses: Vec<StackAccess<'_, F>> = self
+            .ops
+            .iter()
+            .zip(&depths)
+            .enumerate()
+            .map(|(i, (op, depth))| StackAccess {
+                op: *op,
+                shift: 0,
+                depth,
+                value: None,
+                same_frame: i > 0 && self.ops[i - 1].call_id == op.call_id,
+            })
+            .collect();
+
+        config.assign(
+            layouter.namespace(|| "stack"),
+            &accesses,
+            &initial_depth,
+            self.initial_depth,
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2_proofs::{dev::MockProver, pasta::Fp};
+
+    fn op(is_write: bool, call_id: usize, pointer: u64, value: u64) -> StackOp {
+        StackOp {
+            step: 0,
+            call_id,
+            rw_counter: 0,
+            pointer,
+            value: U256::from(value),
+            is_write,
+        }
+    }
+
+    fn run(ops: Vec<StackOp>, initial_depth: u64) -> MockProver<Fp> {
+        let circuit = StackCircuit::<Fp>::new(ops, initial_depth);
+        MockProver::run(10, &circuit, vec![]).unwrap()
+    }
+
+    #[test]
+    fn test_stack_reads_follow_writes() {
+        let big = U256::MAX - U256::from(5);
+        // PUSH 2, PUSH 3, ADD, then DUP1 copying the sum and SWAP1
+        let ops = vec![
+            op(true, 0, 0, 2),
+            op(true, 0, 1, 3),
+            op(false, 0, 1, 3),
+            op(false, 0, 0, 2),
+            op(true, 0, 0, 5),
+            op(false, 0, 0, 5),
+            op(true, 0, 1, 5),
+            StackOp {
+                value: big,
+                ..op(true, 0, 1, 0)
+            },
+            StackOp {
+                value: big,
+                ..op(false, 0, 1, 0)
+            },
+        ];
+        run(ops.clone(), 0).assert_satisfied();
+
+        // Every read must see the word written last
+        let mut wrong = ops.clone();
+        wrong[3].value = U256::from(3);
+        assert!(run(wrong, 0).verify().is_err());
+        let mut wrong = ops;
+        wrong[8].value = U256::from(5);
+        assert!(run(wrong, 0).verify().is_err());
+    }
+
+    #[test]
+    fn test_stack_first_reads() {
+        // Items below the initial depth were pushed before the trace
+        let read_twice = vec![op(false, 0, 3, 7), op(false, 0, 3, 7)];
+        assert!(run(read_twice.clone(), 3).verify().is_err());
+        run(read_twice.clone(), 4).assert_satisfied();
+
+        // Later reads still agree with the first one
+        let mut disagree = read_twice;
+        disagree[1].value = U256::ZERO;
+        assert!(run(disagree, 4).verify().is_err());
+
+        // Every call frame has its own stack
+        let frames = vec![op(true, 1, 0, 7), op(false, 2, 0, 7)];
+        assert!(run(frames, 0).verify().is_err());
+        let frames = vec![op(true, 1, 0, 7), op(false, 1, 0, 7)];
+        run(frames, 0).assert_satisfied();
+    }
+}
@@ -1,99 +1,389 @@
 //! Storage circuit for EVM state diffs
 //!
-//! Proves storage slot updates using Merkle proofs.
+//! Proves the read/write consistency of storage slots. The SLOAD and SSTORE
+//! accesses of a trace are laid out in `(address, key, rw_counter)` order, so
+//! the accesses of a slot are adjacent and in execution order, and every
+//! access must start from the value the one before it left. The first access
+//! of a slot starts from its pre-state value, which is public.
+//!
+//! The accesses are also applied, in the same order, to a Poseidon sparse
+//! Merkle tree of the pre-state (`utils::smt`). Each one proves
+//! `root_after = update(root_before, key, old, new)` and starts from the root
+//! the previous one left, so the public roots before and after all the
+//! accesses make a succinct state transition. Accesses of different slots
+//! commute, so key order reaches the same final root as trace order.
 
+use alloy_primitives::{Address, U256};
+use halo2_gadgets::poseidon::primitives::{P128Pow5T3, Spec};
 use halo2_proofs::{
     arithmetic::Field,
-    circuit::{Layouter, SimpleFloorPlanner, Value},
-    plonk::{Advice, Circuit, Column, ConstraintSystem, Error, Selector},
+    circuit::{AssignedCell, Layouter, SimpleFloorPlanner, Value},
+    pasta::group::ff::PrimeField,
+    plonk::{Advice, Circuit, Column, ConstraintSystem, Error, Expression, Instance, Selector},
     poly::Rotation,
 };
+use std::collections::HashMap;
+use std::marker::PhantomData;
+
+use crate::chips::OpCode;
+use crate::chips::hash_chip::{HashChip, POSEIDON_RATE, POSEIDON_WIDTH};
+use crate::chips::smt_chip::{Roots, SmtChip, SmtChipConfig};
+use crate::chips::tables::ByteTableConfig;
+use crate::chips::word_chip::{
+    AssignedWord, LIMB_BYTES, WordChip, WordChipConfig, assign_value, word_limbs,
+};
+use crate::errors::Result as ProverResult;
+use crate::utils::evm_parser::{AccessKey, EvmTrace};
+use crate::utils::smt::{MerkleUpdate, SparseMerkleTree, slot_key};
+
+/// Public inputs before those of the accesses: the state roots before and
+/// after them
+pub const ROOT_INPUTS: usize = 2;
+
+/// Public inputs of every access in key order: address, key and pre-state
+/// value, as low and high limbs
+pub const PUBLIC_INPUTS_PER_ACCESS: usize = 6;
+
+/// Bytes the high limb of an address and a read/write counter are range checked to
+const SHORT_BYTES: usize = 4;
 
-/// Storage slot update
-#[derive(Debug, Clone)]
-pub struct StorageUpdate<F: Field> {
+// Columns of an access in key order
+const ADDRESS_LO: usize = 0;
+const ADDRESS_HI: usize = 1;
+const KEY_LO: usize = 2;
+const KEY_HI: usize = 3;
+const RW: usize = 4;
+const OLD_LO: usize = 5;
+const OLD_HI: usize = 6;
+const NEW_LO: usize = 7;
+const NEW_HI: usize = 8;
+const PRE_LO: usize = 9;
+const PRE_HI: usize = 10;
+const GAP: usize = 11;
+// Flags of the first sort limb differing from the previous access
+const FIRST_DIFF: usize = 12;
+
+/// Sort limbs, most significant first
+const ORDER: [usize; 5] = [ADDRESS_HI, ADDRESS_LO, KEY_HI, KEY_LO, RW];
+
+/// Storage slot access, in trace order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct StorageUpdate {
+    /// Contract owning the slot
+    pub address: Address,
     /// Storage slot key
-    pub key: F,
-    /// Old value
-    pub old_value: F,
-    /// New value
-    pub new_value: F,
+    pub key: U256,
+    /// Value before the access
+    pub old_value: U256,
+    /// Value after the access (the old value for SLOAD)
+    pub new_value: U256,
 }
 
 /// Configuration for storage circuit
 #[derive(Clone, Debug)]
-pub struct StorageCircuitConfig {
-    /// Storage key column
-    pub key: Column<Advice>,
-    /// Old value column
-    pub old_value: Column<Advice>,
-    /// New value column
-    pub new_value: Column<Advice>,
-    /// Selector for storage updates
-    pub s_storage: Selector,
+pub struct StorageCircuitConfig<F: Field> {
+    /// Advice columns of an access; the gates document their roles
+    pub cells: [Column<Advice>; FIRST_DIFF + ORDER.len()],
+    /// First access in key order
+    pub s_first: Selector,
+    /// Access in key order followed by another
+    pub s_pair: Selector,
+    /// Range checks for the sort limbs and the new values
+    pub word_config: WordChipConfig,
+    /// Sparse Merkle tree updates
+    pub smt_config: SmtChipConfig<F>,
+    /// State roots, then the addresses, keys and pre-state values of the
+    /// accesses in key order
+    pub public_input: Column<Instance>,
 }
 
 /// Circuit for proving storage state transitions
-#[derive(Default, Clone, Debug)]
-pub struct StorageCircuit<F: Field> {
-    /// Storage updates to prove
-    pub updates: Vec<StorageUpdate<F>>,
+#[derive(Clone, Debug)]
+pub struct StorageCircuit<F: PrimeField> {
+    /// Storage accesses to prove, in trace order
+    pub updates: Vec<StorageUpdate>,
+    /// Tree of the pre-state (holding just the accessed slots when absent)
+    pub state: Option<SparseMerkleTree<F>>,
+    _marker: PhantomData<F>,
 }
 
-impl<F: Field> StorageCircuit<F> {
+impl<F> StorageCircuit<F>
+where
+    F: PrimeField,
+    P128Pow5T3: Spec<F, POSEIDON_WIDTH, POSEIDON_RATE>,
+{
     /// Create a new storage circuit
-    pub fn new(updates: Vec<StorageUpdate<F>>) -> Self {
-        Self { updates }
+    pub fn new(updates: Vec<StorageUpdate>) -> Self {
+        Self {
+            updates,
+            state: None,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Start from a pre-state tree, which must hold every accessed slot's
+    /// pre-state value
+    pub fn with_state(mut self, state: SparseMerkleTree<F>) -> Self {
+        self.state = Some(state);
+        self
+    }
+
+    /// Create a circuit for the SLOAD and SSTORE accesses of a trace
+    ///
+    /// An SLOAD reads the next step's top of stack, or the slot's current
+    /// value when it is the last step. An SSTORE starts from the current value
+    /// of its SSTORE record and writes its second operand.
+    pub fn from_trace(trace: &EvmTrace) -> Self {
+        let operand = |step: usize, i: usize| {
+            trace
+                .stack_states
+                .get(step)
+                .and_then(|stack| stack.get(i))
+                .copied()
+        };
+        let mut sstores = trace.sstore_records().into_iter();
+        let mut current: HashMap<AccessKey, U256> = HashMap::new();
+        let mut updates = Vec::new();
+
+        for access in trace.state_accesses() {
+            let Some(key) = access.key.slot else {
+                continue;
+            };
+            let (old_value, new_value) = match OpCode::from_u8(trace.opcodes[access.step]) {
+                Some(OpCode::SLoad) => {
+                    let value = operand(access.step + 1, 0)
+                        .or_else(|| current.get(&access.key).copied())
+                        .unwrap_or_default();
+                    (value, value)
+                }
+                Some(OpCode::SStore) => (
+                    sstores.next().unwrap_or_default().current,
+                    operand(access.step, 1).unwrap_or_default(),
+                ),
+                _ => continue,
+            };
+            current.insert(access.key, new_value);
+            updates.push(StorageUpdate {
+                address: access.key.address,
+                key,
+                old_value,
+                new_value,
+            });
+        }
+
+        Self::new(updates)
     }
 
     /// Create a test storage update example for development
     pub fn test_update() -> Self {
         let updates = vec![StorageUpdate {
-            key: F::ONE,
-            old_value: F::ONE + F::ONE,          // 2
-            new_value: F::ONE + F::ONE + F::ONE, // 3
+            address: Address::ZERO,
+            key: U256::from(1u64),
+            old_value: U256::from(2u64),
+            new_value: U256::from(3u64),
         }];
         Self::new(updates)
     }
+
+    /// Accesses in `(address, key, rw_counter)` order, with their counters
+    ///
+    /// Counters number the accesses in trace order from 1.
+    fn sorted(&self) -> Vec<(StorageUpdate, u64)> {
+        let mut sorted: Vec<(StorageUpdate, u64)> = self.updates.iter().copied().zip(1..).collect();
+        sorted.sort_by_key(|(update, rw)| (update.address, update.key, *rw));
+        sorted
+    }
+
+    /// Pre-state value of the slot of every access in key order
+    fn pre_states(&self) -> Vec<U256> {
+        let sorted = self.sorted();
+        let mut pre = U256::ZERO;
+        sorted
+            .iter()
+            .enumerate()
+            .map(|(i, (update, _))| {
+                let first = i == 0 || {
+                    let previous = &sorted[i - 1].0;
+                    (previous.address, previous.key) != (update.address, update.key)
+                };
+                if first {
+                    pre = update.old_value;
+                }
+                pre
+            })
+            .collect()
+    }
+
+    /// Tree of the pre-state, built from the accessed slots if none was given
+    pub fn pre_state(&self) -> ProverResult<SparseMerkleTree<F>> {
+        if let Some(state) = &self.state {
+            return Ok(state.clone());
+        }
+
+        let mut state = SparseMerkleTree::new();
+        for ((update, _), pre) in self.sorted().iter().zip(self.pre_states()) {
+            state.update(slot_key(update.address, update.key), pre)?;
+        }
+        Ok(state)
+    }
+
+    /// Tree updates of the accesses in key order, starting from the pre-state
+    pub fn transitions(&self) -> ProverResult<Vec<MerkleUpdate<F>>> {
+        let mut state = self.pre_state()?;
+        self.sorted()
+            .iter()
+            .map(|(update, _)| state.update(slot_key(update.address, update.key), update.new_value))
+            .collect()
+    }
+
+    /// Public inputs: the state roots before and after the accesses, then for
+    /// every access in key order the low and high limbs of its address, key
+    /// and slot pre-state value
+    pub fn public_inputs(&self) -> ProverResult<Vec<F>> {
+        let root_before = self.pre_state()?.root();
+        let root_after = self
+            .transitions()?
+            .last()
+            .map_or(root_before, |transition| transition.new_root);
+        let accesses =
+            self.sorted()
+                .into_iter()
+                .zip(self.pre_states())
+                .flat_map(|((update, _), pre)| {
+                    [address_word(update.address), update.key, pre]
+                        .into_iter()
+                        .flat_map(word_limbs::<F>)
+                });
+        Ok([root_before, root_after]
+            .into_iter()
+            .chain(accesses)
+            .collect())
+    }
+}
+
+/// Address as a 256-bit word
+fn address_word(address: Address) -> U256 {
+    U256::from_be_slice(address.as_slice())
 }
 
-impl<F: Field> Circuit<F> for StorageCircuit<F> {
-    type Config = StorageCircuitConfig;
+/// Sort limbs of an access, in `ORDER`
+fn sort_limbs<F: PrimeField>((update, rw): &(StorageUpdate, u64)) -> [F; ORDER.len()] {
+    let [address_lo, address_hi] = word_limbs::<F>(address_word(update.address));
+    let [key_lo, key_hi] = word_limbs::<F>(update.key);
+    [address_hi, address_lo, key_hi, key_lo, F::from(*rw)]
+}
+
+impl<F> Circuit<F> for StorageCircuit<F>
+where
+    F: PrimeField,
+    P128Pow5T3: Spec<F, POSEIDON_WIDTH, POSEIDON_RATE>,
+{
+    type Config = StorageCircuitConfig<F>;
     type FloorPlanner = SimpleFloorPlanner;
 
     fn without_witnesses(&self) -> Self {
-        Self::default()
+        Self::new(vec![StorageUpdate::default(); self.updates.len()])
     }
 
+    /// An access row holds the limbs of `(address, key, rw)`, of the old and
+    /// new values and of its slot's pre-state value. `gap` and the first-diff
+    /// flags compare it with the row before: exactly one flag is set, the sort
+    /// limbs before the flagged one are equal and the flagged one grows by
+    /// `gap + 1`. Only the counter differing means the same slot.
     fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
-        let key = meta.advice_column();
-        let old_value = meta.advice_column();
-        let new_value = meta.advice_column();
-
-        meta.enable_equality(key);
-        meta.enable_equality(old_value);
-        meta.enable_equality(new_value);
-
-        let s_storage = meta.selector();
-
-        // Gate: Ensure values are valid (non-negative in field)
-        // TODO: Integrate with halo2_gadgets::poseidon for Merkle proofs
-        meta.create_gate("storage_update", |meta| {
-            let s = meta.query_selector(s_storage);
-            let _key = meta.query_advice(key, Rotation::cur());
-            let _old = meta.query_advice(old_value, Rotation::cur());
-            let _new = meta.query_advice(new_value, Rotation::cur());
-
-            // Placeholder constraint (always satisfied)
-            // Real impl would verify Merkle proof: root' = update(root, key, old, new)
-            vec![s * (Expression::Constant(F::ZERO))]
+        let cells = [(); FIRST_DIFF + ORDER.len()].map(|_| meta.advice_column());
+        for column in &cells[..FIRST_DIFF] {
+            meta.enable_equality(*column);
+        }
+        let public_input = meta.instance_column();
+        meta.enable_equality(public_input);
+
+        let byte_table = ByteTableConfig::configure(meta);
+        let word_config = WordChip::configure(meta, byte_table);
+        let hash_config = HashChip::configure(meta);
+        let smt_config = SmtChip::configure(meta, hash_config, word_config.clone());
+
+        let s_first = meta.selector();
+        let s_pair = meta.selector();
+        let one = || Expression::Constant(F::ONE);
+
+        meta.create_gate("storage pre-state", |meta| {
+            let s = meta.query_selector(s_first);
+            [(OLD_LO, PRE_LO), (OLD_HI, PRE_HI)]
+                .map(|(old, pre)| {
+                    let old = meta.query_advice(cells[old], Rotation::cur());
+                    let pre = meta.query_advice(cells[pre], Rotation::cur());
+                    s.clone() * (old - pre)
+                })
+                .to_vec()
+        });
+
+        meta.create_gate("storage key order", |meta| {
+            let s = meta.query_selector(s_pair);
+            let flags: Vec<Expression<F>> = (0..ORDER.len())
+                .map(|j| meta.query_advice(cells[FIRST_DIFF + j], Rotation::next()))
+                .collect();
+            let diffs: Vec<Expression<F>> = ORDER
+                .iter()
+                .map(|&column| {
+                    meta.query_advice(cells[column], Rotation::next())
+                        - meta.query_advice(cells[column], Rotation::cur())
+                })
+                .collect();
+            let gap = meta.query_advice(cells[GAP], Rotation::next());
+
+            let mut constraints = Vec::new();
+            for flag in &flags {
+                constraints.push(s.clone() * flag.clone() * (one() - flag.clone()));
+            }
+            let sum = flags
+                .iter()
+                .fold(Expression::Constant(F::ZERO), |acc, flag| {
+                    acc + flag.clone()
+                });
+            constraints.push(s.clone() * (sum - one()));
+
+            // Limbs before the first differing one are equal
+            for (i, diff) in diffs.iter().enumerate() {
+                let later = flags[i + 1..]
+                    .iter()
+                    .fold(Expression::Constant(F::ZERO), |acc, flag| {
+                        acc + flag.clone()
+                    });
+                constraints.push(s.clone() * later * diff.clone());
+            }
+
+            // ... and the first differing one grows
+            let step = flags
+                .iter()
+                .zip(&diffs)
+                .fold(Expression::Constant(F::ZERO), |acc, (flag, diff)| {
+                    acc + flag.clone() * (diff.clone() - one())
+                });
+            constraints.push(s.clone() * (gap - step));
+
+            // An access of the same slot starts from the previous one's value;
+            // the first of a slot starts from its pre-state value
+            let same = flags[ORDER.len() - 1].clone();
+            for (old, new, pre) in [(OLD_LO, NEW_LO, PRE_LO), (OLD_HI, NEW_HI, PRE_HI)] {
+                let old_next = meta.query_advice(cells[old], Rotation::next());
+                let new_cur = meta.query_advice(cells[new], Rotation::cur());
+                let pre_cur = meta.query_advice(cells[pre], Rotation::cur());
+                let pre_next = meta.query_advice(cells[pre], Rotation::next());
+                constraints.push(s.clone() * same.clone() * (old_next.clone() - new_cur));
+                constraints.push(s.clone() * same.clone() * (pre_next.clone() - pre_cur));
+                constraints.push(s.clone() * (one() - same.clone()) * (old_next - pre_next));
+            }
+            constraints
         });
 
         StorageCircuitConfig {
-            key,
-            old_value,
-            new_value,
-            s_storage,
+            cells,
+            s_first,
+            s_pair,
+            word_config,
+            smt_config,
+            public_input,
         }
     }
 
@@ -102,119 +392,366 @@ impl<F: Field> Circuit<F> for StorageCircuit<F> {
         config: Self::Config,
         mut layouter: impl Layouter<F>,
     ) -> Result<(), Error> {
-        for (i, update) in self.updates.iter().enumerate() {
-            layouter.assign_region(
-                || format!("storage_update_{}", i),
-                |mut region| {
-                    config.s_storage.enable(&mut region, 0)?;
-
-                    region.assign_advice(|| "key", config.key, 0, || Value::known(update.key))?;
-                    region.assign_advice(
-                        || "old_value",
-                        config.old_value,
-                        0,
-                        || Value::known(update.old_value),
-                    )?;
-                    region.assign_advice(
-                        || "new_value",
-                        config.new_value,
-                        0,
-                        || Value::known(update.new_value),
-                    )?;
+        config.word_config.byte_table.load(&mut layouter)?;
+
+        let sorted = self.sorted();
+        let pre_states = self.pre_states();
+        let transitions = self.transitions().map_err(|_| Error::Synthesis)?;
+        let rows = layouter.assign_region(
+            || "storage accesses",
+            |mut region| {
+                let mut rows = Vec::with_capacity(sorted.len());
+                for (row, (access, pre)) in sorted.iter().zip(&pre_states).enumerate() {
+                    if row == 0 {
+                        config.s_first.enable(&mut region, row)?;
+                    }
+                    if row + 1 < sorted.len() {
+                        config.s_pair.enable(&mut region, row)?;
+                    }
 
-                    Ok(())
-                },
+                    // Compare the sort limbs with the previous access
+                    let limbs = sort_limbs::<F>(access);
+                    let first_diff = row.checked_sub(1).map(|previous| {
+                        let previous = sort_limbs::<F>(&sorted[previous]);
+                        let j = (0..ORDER.len())
+                            .find(|&j| previous[j] != limbs[j])
+                            .unwrap_or(ORDER.len() - 1);
+                        (j, limbs[j] - previous[j] - F::ONE)
+                    });
+
+                    let (update, rw) = access;
+                    let [address, key, old, new, pre] = [
+                        address_word(update.address),
+                        update.key,
+                        update.old_value,
+                        update.new_value,
+                        *pre,
+                    ]
+                    .map(word_limbs::<F>);
+                    let values = [
+                        address[0],
+                        address[1],
+                        key[0],
+                        key[1],
+                        F::from(*rw),
+                        old[0],
+                        old[1],
+                        new[0],
+                        new[1],
+                        pre[0],
+                        pre[1],
+                        first_diff.map_or(F::ZERO, |(_, gap)| gap),
+                    ];
+                    let cells = values
+                        .into_iter()
+                        .enumerate()
+                        .map(|(column, value)| {
+                            assign_value(
+                                &mut region,
+                                config.cells[column],
+                                row,
+                                Value::known(value),
+                            )
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    for j in 0..ORDER.len() {
+                        let flag = first_diff.is_some_and(|(first, _)| first == j);
+                        assign_value(
+                            &mut region,
+                            config.cells[FIRST_DIFF + j],
+                            row,
+                            Value::known(F::from(flag as u64)),
+                        )?;
+                    }
+                    rows.push(cells);
+                }
+                Ok(rows)
+            },
+        )?;
+
+        let word_chip = WordChip::construct(config.word_config.clone());
+        for (row, cells) in rows.iter().enumerate() {
+            // Sort limbs must not wrap around the field for the order to hold
+            let mut checks = vec![
+                (ADDRESS_LO, LIMB_BYTES),
+                (ADDRESS_HI, SHORT_BYTES),
+                (KEY_LO, LIMB_BYTES),
+                (KEY_HI, LIMB_BYTES),
+                (RW, SHORT_BYTES),
+            ];
+            if row > 0 {
+                checks.push((GAP, LIMB_BYTES));
+            }
+            // New values go into the tree as limbs
+            checks.extend([(NEW_LO, LIMB_BYTES), (NEW_HI, LIMB_BYTES)]);
+            for (column, num_bytes) in checks {
+                word_chip.range_check(
+                    layouter.namespace(|| format!("access {} column {}", row, column)),
+                    &cells[column],
+                    num_bytes,
+                )?;
+            }
+
+            for (j, column) in [ADDRESS_LO, ADDRESS_HI, KEY_LO, KEY_HI, PRE_LO, PRE_HI]
+                .into_iter()
+                .enumerate()
+            {
+                layouter.constrain_instance(
+                    cells[column].cell(),
+                    config.public_input,
+                    ROOT_INPUTS + row * PUBLIC_INPUTS_PER_ACCESS + j,
+                )?;
+            }
+        }
+
+        // Chain the tree updates from the root before to the root after
+        let smt_chip = SmtChip::construct(config.smt_config.clone());
+        let word = |cells: &[AssignedCell<F, F>], lo: usize| AssignedWord {
+            lo: cells[lo].clone(),
+            hi: cells[lo + 1].clone(),
+        };
+        let mut roots: Option<Roots<F>> = None;
+        for (row, (cells, transition)) in rows.iter().zip(&transitions).enumerate() {
+            let siblings: Vec<Value<F>> = transition
+                .siblings
+                .iter()
+                .map(|sibling| Value::known(*sibling))
+                .collect();
+            let (old_root, new_root) = smt_chip.update(
+                layouter.namespace(|| format!("access {} tree update", row)),
+                [ADDRESS_LO, ADDRESS_HI, KEY_LO, KEY_HI].map(|column| cells[column].clone()),
+                &word(cells, OLD_LO),
+                &word(cells, NEW_LO),
+                &siblings,
             )?;
+            roots = Some(match roots {
+                None => (old_root, new_root),
+                Some((first, previous)) => {
+                    layouter.assign_region(
+                        || format!("access {} root link", row),
+                        |mut region| region.constrain_equal(previous.cell(), old_root.cell()),
+                    )?;
+                    (first, new_root)
+                }
+            });
         }
 
+        // Without accesses the state stays at the pre-state root
+        let (root_before, root_after) = match roots {
+            Some(roots) => roots,
+            None => {
+                let root = self.pre_state().map_err(|_| Error::Synthesis)?.root();
+                let root = layouter.assign_region(
+                    || "unchanged root",
+                    |mut region| assign_value(&mut region, config.cells[0], 0, Value::known(root)),
+                )?;
+                (root.clone(), root)
+            }
+        };
+        layouter.constrain_instance(root_before.cell(), config.public_input, 0)?;
+        layouter.constrain_instance(root_after.cell(), config.public_input, 1)?;
+
         Ok(())
     }
 }
 
-// Needed for gate constraint
-use halo2_proofs::plonk::Expression;
-
 #[cfg(test)]
 mod tests {
     use super::*;
     use halo2_proofs::{dev::MockProver, pasta::Fp};
 
+    fn update(key: u64, old_value: u64, new_value: u64) -> StorageUpdate {
+        StorageUpdate {
+            address: Address::with_last_byte(0xaa),
+            key: U256::from(key),
+            old_value: U256::from(old_value),
+            new_value: U256::from(new_value),
+        }
+    }
+
+    /// Rows for the tree updates, about 6k per access
+    fn k(circuit: &StorageCircuit<Fp>) -> u32 {
+        13 + circuit
+            .updates
+            .len()
+            .max(1)
+            .next_power_of_two()
+            .trailing_zeros()
+    }
+
+    fn run(circuit: &StorageCircuit<Fp>) -> MockProver<Fp> {
+        MockProver::run(k(circuit), circuit, vec![circuit.public_inputs().unwrap()]).unwrap()
+    }
+
     #[test]
     fn test_storage_circuit_basic() {
-        let updates = vec![StorageUpdate {
-            key: Fp::from(1),
-            old_value: Fp::from(100),
-            new_value: Fp::from(200),
-        }];
-        let circuit = StorageCircuit::new(updates);
-        let prover = MockProver::run(4, &circuit, vec![]).unwrap();
-        assert_eq!(prover.verify(), Ok(()));
+        let circuit = StorageCircuit::new(vec![update(1, 100, 200)]);
+        assert_eq!(run(&circuit).verify(), Ok(()));
     }
 
     #[test]
     fn test_storage_circuit_zero_values() {
-        let updates = vec![StorageUpdate {
-            key: Fp::from(0),
-            old_value: Fp::from(0),
-            new_value: Fp::from(0),
-        }];
-        let circuit = StorageCircuit::new(updates);
-        let prover = MockProver::run(4, &circuit, vec![]).unwrap();
-        assert_eq!(prover.verify(), Ok(()));
+        let circuit = StorageCircuit::new(vec![update(0, 0, 0)]);
+        assert_eq!(run(&circuit).verify(), Ok(()));
     }
 
     #[test]
     fn test_storage_circuit_large_values() {
-        let updates = vec![StorageUpdate {
-            key: Fp::from(999999),
-            old_value: Fp::from(888888),
-            new_value: Fp::from(777777),
-        }];
-        let circuit = StorageCircuit::new(updates);
-        let prover = MockProver::run(4, &circuit, vec![]).unwrap();
-        assert_eq!(prover.verify(), Ok(()));
+        let circuit = StorageCircuit::new(vec![StorageUpdate {
+            address: Address::repeat_byte(0xff),
+            key: U256::MAX,
+            old_value: U256::MAX,
+            new_value: U256::from(777777u64),
+        }]);
+        assert_eq!(run(&circuit).verify(), Ok(()));
     }
 
     #[test]
     fn test_storage_circuit_update_from_zero() {
-        let updates = vec![StorageUpdate {
-            key: Fp::from(42),
-            old_value: Fp::from(0),
-            new_value: Fp::from(1000),
-        }];
-        let circuit = StorageCircuit::new(updates);
-        let prover = MockProver::run(4, &circuit, vec![]).unwrap();
-        assert_eq!(prover.verify(), Ok(()));
+        let circuit = StorageCircuit::new(vec![update(42, 0, 1000)]);
+        assert_eq!(run(&circuit).verify(), Ok(()));
     }
 
     #[test]
     fn test_storage_circuit_update_to_zero() {
-        let updates = vec![StorageUpdate {
-            key: Fp::from(42),
-            old_value: Fp::from(1000),
-            new_value: Fp::from(0),
-        }];
-        let circuit = StorageCircuit::new(updates);
-        let prover = MockProver::run(4, &circuit, vec![]).unwrap();
-        assert_eq!(prover.verify(), Ok(()));
+        let circuit = StorageCircuit::new(vec![update(42, 1000, 0)]);
+        assert_eq!(run(&circuit).verify(), Ok(()));
     }
 
     #[test]
     fn test_storage_circuit_same_value() {
-        let updates = vec![StorageUpdate {
-            key: Fp::from(10),
-            old_value: Fp::from(500),
-            new_value: Fp::from(500),
-        }];
-        let circuit = StorageCircuit::new(updates);
-        let prover = MockProver::run(4, &circuit, vec![]).unwrap();
-        assert_eq!(prover.verify(), Ok(()));
+        let circuit = StorageCircuit::new(vec![update(10, 500, 500)]);
+        assert_eq!(run(&circuit).verify(), Ok(()));
     }
 
     #[test]
     fn test_update_helper() {
         let circuit = StorageCircuit::<Fp>::test_update();
-        let prover = MockProver::run(4, &circuit, vec![]).unwrap();
-        assert_eq!(prover.verify(), Ok(()));
+        assert_eq!(run(&circuit).verify(), Ok(()));
+    }
+
+    #[test]
+    fn test_storage_circuit_slot_consistency() {
+        // Interleaved slots, each continuing from its own previous value
+        let updates = vec![
+            update(2, 7, 7),
+            update(1, 0, 5),
+            update(2, 7, 9),
+            update(1, 5, 5),
+            StorageUpdate {
+                address: Address::with_last_byte(0xbb),
+                ..update(1, 3, 3)
+            },
+            update(2, 9, 0),
+        ];
+        let circuit = StorageCircuit::new(updates.clone());
+        assert_eq!(run(&circuit).verify(), Ok(()));
+
+        // A stale read of a slot fails
+        let mut stale = updates.clone();
+        stale[3].old_value = U256::ZERO;
+        stale[3].new_value = U256::ZERO;
+        assert!(run(&StorageCircuit::new(stale)).verify().is_err());
+    }
+
+    #[test]
+    fn test_storage_circuit_pre_state_is_public() {
+        let circuit = StorageCircuit::<Fp>::new(vec![update(1, 5, 6), update(1, 6, 6)]);
+        let mut public_inputs = circuit.public_inputs().unwrap();
+        assert_eq!(
+            public_inputs.len(),
+            ROOT_INPUTS + 2 * PUBLIC_INPUTS_PER_ACCESS
+        );
+        let pre = ROOT_INPUTS + 4;
+        assert_eq!(public_inputs[pre], Fp::from(5));
+        assert_eq!(public_inputs[pre + PUBLIC_INPUTS_PER_ACCESS], Fp::from(5));
+
+        // Declaring a different pre-state value fails
+        public_inputs[pre] = Fp::from(6);
+        public_inputs[pre + PUBLIC_INPUTS_PER_ACCESS] = Fp::from(6);
+        let prover = MockProver::run(k(&circuit), &circuit, vec![public_inputs]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    #[test]
+    fn test_storage_circuit_from_trace() {
+        // PUSH1 1, PUSH1 0, SSTORE, PUSH1 0, SLOAD, STOP
+        let words = |values: [u64; 3]| values.map(U256::from).to_vec();
+        let trace = EvmTrace {
+            opcodes: vec![0x60, 0x60, 0x55, 0x60, 0x54, 0x00],
+            stack_states: vec![
+                words([0, 0, 0]),
+                words([1, 0, 0]),
+                words([0, 1, 0]),
+                words([0, 0, 0]),
+                words([0, 0, 0]),
+                words([1, 0, 0]),
+            ],
+            pcs: vec![0, 2, 4, 5, 7, 8],
+            gas_values: vec![30_000, 29_997, 29_994, 7_894, 7_891, 7_791],
+            stack_sizes: None,
+            memory_sizes: None,
+            accesses: None,
+            prewarmed: None,
+            sstores: None,
+            refunds: None,
+            intrinsic_gas: None,
+            memory_ops: None,
+            stack_ops: None,
+            storage_ops: None,
+            tx_hash: None,
+            block_number: None,
+            bytecode: None,
+        };
+        let circuit = StorageCircuit::<Fp>::from_trace(&trace);
+        let expected = [update(0, 0, 1), update(0, 1, 1)].map(|update| StorageUpdate {
+            address: Address::ZERO,
+            ..update
+        });
+        assert_eq!(circuit.updates, expected);
+        assert_eq!(run(&circuit).verify(), Ok(()));
+    }
+
+    #[test]
+    fn test_storage_circuit_state_roots() {
+        // Start from a tree also holding a slot the accesses never touch
+        let mut state = SparseMerkleTree::<Fp>::new();
+        let untouched = slot_key(Address::with_last_byte(0xcc), U256::from(9u64));
+        state.update(untouched, U256::from(3u64)).unwrap();
+        state
+            .update(
+                slot_key(Address::with_last_byte(0xaa), U256::from(1u64)),
+                U256::from(5u64),
+            )
+            .unwrap();
+        let root_before = state.root();
+
+        let circuit =
+            StorageCircuit::new(vec![update(1, 5, 6), update(2, 0, 4)]).with_state(state.clone());
+        let mut public_inputs = circuit.public_inputs().unwrap();
+        assert_eq!(public_inputs[0], root_before);
+
+        // The root after matches applying the accesses to the native tree
+        for (key, value) in [(1u64, 6u64), (2, 4)] {
+            state
+                .update(
+                    slot_key(Address::with_last_byte(0xaa), U256::from(key)),
+                    U256::from(value),
+                )
+                .unwrap();
+        }
+        assert_eq!(public_inputs[1], state.root());
+        assert_eq!(run(&circuit).verify(), Ok(()));
+
+        // Claiming a different root after fails
+        public_inputs[1] = root_before;
+        let prover = MockProver::run(k(&circuit), &circuit, vec![public_inputs]).unwrap();
+        assert!(prover.verify().is_err());
+
+        // Without accesses both roots are the pre-state root
+        let circuit = StorageCircuit::<Fp>::new(vec![]);
+        let public_inputs = circuit.public_inputs().unwrap();
+        assert_eq!(public_inputs, vec![public_inputs[0]; ROOT_INPUTS]);
+        assert_eq!(run(&circuit).verify(), Ok(()));
     }
 }
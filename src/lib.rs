@@ -30,6 +30,7 @@ pub mod errors;
 pub mod prover;
 pub mod utils;
 
+pub use chips::evm_chip::Hardfork;
 use errors::ProverError;
 use serde::{Deserialize, Serialize};
 
@@ -44,6 +45,8 @@ pub struct ProverConfig {
     pub num_threads: Option<usize>,
     /// Optional RPC URL for fetching real traces
     pub rpc_url: Option<String>,
+    /// Fork selecting the gas schedule and valid opcodes
+    pub hardfork: Hardfork,
 }
 
 impl Default for ProverConfig {
@@ -53,6 +56,7 @@ impl Default for ProverConfig {
             parallel: true,
             num_threads: None, // Use all available cores
             rpc_url: None,
+            hardfork: Hardfork::default(),
         }
     }
 }
@@ -62,7 +66,7 @@ impl Default for ProverConfig {
 pub struct TraceInfo {
     /// Number of opcodes in the trace
     pub opcode_count: usize,
-    /// Estimated gas used
+    /// Gas used after refunds, as in the receipt (see `EvmTrace::gas_used`)
     pub gas_used: u64,
     /// Transaction hash (if from real network)
     pub tx_hash: Option<String>,
@@ -85,6 +89,15 @@ pub struct ProofOutput {
     pub k: u32,
     /// Verification key hash (for quick VK matching)
     pub vk_hash: String,
+    /// Opcode of every step (the circuit layout depends on them)
+    #[serde(default)]
+    pub opcodes: Vec<u8>,
+    /// Fork the opcode table was loaded for (part of the verification key)
+    #[serde(default)]
+    pub hardfork: Hardfork,
+    /// Number of keys warm before the first step (part of the circuit layout)
+    #[serde(default)]
+    pub num_prewarmed: usize,
 }
 
 /// Result type for prover operations
@@ -146,7 +159,7 @@ pub async fn prove_transaction(
     let trace = utils::evm_parser::fetch_trace_from_network(tx_hash, rpc_url).await?;
 
     // Validate trace
-    trace.validate()?;
+    trace.validate_for(config.hardfork)?;
 
     // Generate proof
     let trace_json = serde_json::to_string(&trace)?;
@@ -207,6 +220,7 @@ mod tests {
         let config = ProverConfig::default();
         assert_eq!(config.k, 17);
         assert!(config.parallel);
+        assert_eq!(config.hardfork, Hardfork::Cancun);
     }
 
     #[test]
@@ -7,7 +7,7 @@ use clap::{Parser, Subcommand};
 use colored::Colorize;
 use std::path::PathBuf;
 use zephyr_proof::{
-    fetch_real_trace, generate_proof, prove_transaction, ProofOutput, ProverConfig,
+    Hardfork, ProofOutput, ProverConfig, fetch_real_trace, generate_proof, prove_transaction,
     prover::verifier::verify_with_verbosity,
 };
 
@@ -30,6 +30,10 @@ struct Cli {
     /// Number of threads for parallel processing
     #[arg(short = 't', long, global = true)]
     threads: Option<usize>,
+
+    /// Hardfork whose gas schedule the trace follows (petersburg..cancun)
+    #[arg(long, global = true, default_value = "cancun")]
+    hardfork: Hardfork,
 }
 
 #[derive(Subcommand, Debug)]
@@ -100,6 +104,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         parallel: !cli.no_parallel,
         num_threads: cli.threads,
         rpc_url: None,
+        hardfork: cli.hardfork,
     };
 
     match cli.command {
@@ -141,7 +146,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             println!("Proof saved to: {}", output.display());
         }
 
-        Commands::Verify { proof_file, verbose } => {
+        Commands::Verify {
+            proof_file,
+            verbose,
+        } => {
             println!("Reading proof from: {}", proof_file.display());
 
             // Read proof file
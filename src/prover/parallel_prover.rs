@@ -4,15 +4,15 @@
 //! Supports real trace chunking for traces with 1M+ steps via recursive composition.
 
 use crate::{
+    ProofOutput, ProverConfig, TraceInfo,
     circuits::main_circuit::{EvmCircuit, ExecutionStep},
     errors::{ProverError, Result},
-    utils::evm_parser::{parse_evm_data, EvmTrace},
-    ProofOutput, ProverConfig, TraceInfo,
+    utils::evm_parser::{EvmTrace, MemoryOp, SStoreRecord, StackOp, StateAccess, parse_evm_data},
 };
-use base64::{engine::general_purpose, Engine as _};
+use base64::{Engine as _, engine::general_purpose};
 use halo2_proofs::{
     pasta::{EqAffine, Fp},
-    plonk::{create_proof, keygen_pk, keygen_vk, ProvingKey, VerifyingKey},
+    plonk::{ProvingKey, VerifyingKey, create_proof, keygen_pk, keygen_vk},
     poly::commitment::Params,
     transcript::{Blake2bWrite, Challenge255},
 };
@@ -46,6 +46,91 @@ pub fn generate_artifacts(k: u32, circuit: &EvmCircuit<Fp>) -> Result<ProofArtif
     Ok(ProofArtifacts { params, vk, pk })
 }
 
+/// Build the circuit step for trace entry `i`
+fn trace_step(
+    trace: &EvmTrace,
+    stack_depths: &[u64],
+    memory_sizes: &[u64],
+    i: usize,
+    opcode: u8,
+) -> ExecutionStep {
+    let stack_values = trace.stack_states.get(i).cloned().unwrap_or_default();
+    ExecutionStep {
+        opcode,
+        stack: [
+            stack_values.first().copied().unwrap_or_default(),
+            stack_values.get(1).copied().unwrap_or_default(),
+            stack_values.get(2).copied().unwrap_or_default(),
+        ],
+        pc: trace.pcs.get(i).copied().unwrap_or(i as u64),
+        gas: trace
+            .gas_values
+            .get(i)
+            .copied()
+            .unwrap_or(1000000 - (i as u64 * 3)),
+        stack_depth: stack_depths[i],
+        memory_size: memory_sizes[i],
+    }
+}
+
+/// Rows the circuit for `trace` lays out (the commitment does not affect the layout)
+fn trace_rows(trace: &EvmTrace, config: &ProverConfig) -> Result<usize> {
+    let stack_depths = trace.stack_depths();
+    let memory_sizes = trace.memory_usage();
+    let steps = trace
+        .opcodes
+        .iter()
+        .enumerate()
+        .map(|(i, opcode)| trace_step(trace, &stack_depths, &memory_sizes, i, *opcode))
+        .collect();
+
+    EvmCircuit::new(steps, Fp::zero())
+        .with_hardfork(config.hardfork)
+        .with_accesses(trace.prewarmed_keys(), trace.state_accesses())
+        .with_sstores(trace.sstore_records())
+        .rows_used()
+        .map_err(|e| ProverError::CircuitError(format!("Failed to lay out circuit: {:?}", e)))
+}
+
+/// Fail before keygen if the circuit does not fit in 2^k rows
+fn ensure_fits(circuit: &EvmCircuit<Fp>, k: u32) -> Result<()> {
+    let rows = circuit
+        .rows_used()
+        .map_err(|e| ProverError::CircuitError(format!("Failed to lay out circuit: {:?}", e)))?;
+    let usable = EvmCircuit::<Fp>::usable_rows(k);
+    if rows > usable {
+        return Err(ProverError::InvalidInput(format!(
+            "Circuit needs {} rows but k = {} leaves {} usable rows; increase k or chunk the trace",
+            rows, k, usable
+        )));
+    }
+    Ok(())
+}
+
+/// Steps per chunk so that every chunk's circuit fits in 2^k rows
+///
+/// A step costs far more than its execution row: its stack words are range
+/// checked, it is absorbed into the Poseidon chain and EXP runs a gadget. The
+/// whole trace is laid out once to measure the average cost per step.
+fn chunk_size_for(trace: &EvmTrace, config: &ProverConfig) -> Result<usize> {
+    let total_steps = trace.opcodes.len();
+    let usable = EvmCircuit::<Fp>::usable_rows(config.k);
+    let rows = trace_rows(trace, config)?;
+    if rows <= usable {
+        return Ok(total_steps.max(1));
+    }
+
+    let rows_per_step = rows.div_ceil(total_steps.max(1));
+    let chunk_size = usable / rows_per_step;
+    if chunk_size == 0 {
+        return Err(ProverError::InvalidInput(format!(
+            "A step needs about {} rows but k = {} leaves {} usable rows; increase k",
+            rows_per_step, config.k, usable
+        )));
+    }
+    Ok(chunk_size)
+}
+
 /// Serialize a real Halo2 proof to bytes
 fn serialize_proof(
     params: &Params<EqAffine>,
@@ -65,7 +150,7 @@ fn serialize_proof(
     create_proof(
         params,
         pk,
-        &[circuit.clone()],
+        std::slice::from_ref(circuit),
         &[instances_slice],
         &mut rng,
         &mut transcript,
@@ -96,7 +181,7 @@ pub async fn generate_proof_parallel(
     config: &ProverConfig,
 ) -> Result<ProofOutput> {
     // Validate trace first
-    trace.validate()?;
+    trace.validate_for(config.hardfork)?;
 
     // Set number of threads if specified
     if let Some(num_threads) = config.num_threads {
@@ -110,37 +195,28 @@ pub async fn generate_proof_parallel(
     let witness = parse_evm_data(trace)?;
 
     // Convert trace to circuit (parallel processing of steps)
+    let stack_depths = trace.stack_depths();
+    let memory_sizes = trace.memory_usage();
     let steps: Vec<_> = trace
         .opcodes
         .par_iter()
         .enumerate()
-        .map(|(i, opcode)| {
-            let stack_values = trace.stack_states.get(i).cloned().unwrap_or_default();
-            ExecutionStep {
-                opcode: *opcode,
-                stack: [
-                    Fp::from(stack_values.first().copied().unwrap_or(0)),
-                    Fp::from(stack_values.get(1).copied().unwrap_or(0)),
-                    Fp::from(stack_values.get(2).copied().unwrap_or(0)),
-                ],
-                pc: trace.pcs.get(i).copied().unwrap_or(i as u64),
-                gas: trace
-                    .gas_values
-                    .get(i)
-                    .copied()
-                    .unwrap_or(1000000 - (i as u64 * 3)),
-            }
-        })
+        .map(|(i, opcode)| trace_step(trace, &stack_depths, &memory_sizes, i, *opcode))
         .collect();
 
     // Use real trace commitment from witness
-    let trace_commitment = Fp::from(witness.public_inputs[0]);
+    let trace_commitment = witness.public_inputs[0];
 
-    let circuit = EvmCircuit::new(steps.clone(), trace_commitment);
+    let circuit = EvmCircuit::new(steps.clone(), trace_commitment)
+        .with_hardfork(config.hardfork)
+        .with_accesses(trace.prewarmed_keys(), trace.state_accesses())
+        .with_sstores(trace.sstore_records())
+        .with_stack_ops(trace.stack_records());
     let k = config.k;
     let public_inputs = vec![vec![trace_commitment]];
 
     // Generate real proof using Halo2 proving system
+    ensure_fits(&circuit, k)?;
     let artifacts = generate_artifacts(k, &circuit)?;
 
     let proof_bytes = serialize_proof(&artifacts.params, &artifacts.pk, &circuit, &public_inputs)?;
@@ -149,8 +225,7 @@ pub async fn generate_proof_parallel(
     // Generate metadata from real trace
     let metadata = TraceInfo {
         opcode_count: trace.opcodes.len(),
-        gas_used: trace.gas_values.first().copied().unwrap_or(0)
-            - trace.gas_values.last().copied().unwrap_or(0),
+        gas_used: trace.gas_used(config.hardfork),
         tx_hash: trace.tx_hash.clone(),
         block_number: trace.block_number,
     };
@@ -171,6 +246,9 @@ pub async fn generate_proof_parallel(
         num_steps,
         k,
         vk_hash,
+        opcodes: trace.opcodes.clone(),
+        hardfork: config.hardfork,
+        num_prewarmed: trace.prewarmed_keys().len(),
     })
 }
 
@@ -182,7 +260,7 @@ pub async fn generate_proof_parallel(
 /// # Arguments
 ///
 /// * `trace` - Large EVM trace to chunk
-/// * `chunk_size` - Maximum steps per chunk (default: 1<<14 = 16384 rows)
+/// * `chunk_size` - Maximum steps per chunk (see `chunk_size_for`)
 ///
 /// # Returns
 ///
@@ -190,8 +268,10 @@ pub async fn generate_proof_parallel(
 ///
 /// # Implementation Notes
 ///
-/// - Each chunk is sized to fit within circuit rows (2^k)
+/// - Each chunk is sized by the caller to fit within circuit rows (2^k)
 /// - Chunks maintain state continuity (final gas/stack of chunk N = initial of chunk N+1)
+/// - Keys accessed by earlier chunks are pre-warmed in later ones
+/// - SSTOREs keep the original values of their slots from the whole trace
 /// - Parallel processing uses Rayon for witness generation
 fn chunk_trace(trace: &EvmTrace, chunk_size: usize) -> Vec<EvmTrace> {
     let total_steps = trace.opcodes.len();
@@ -202,26 +282,84 @@ fn chunk_trace(trace: &EvmTrace, chunk_size: usize) -> Vec<EvmTrace> {
     }
 
     let num_chunks = total_steps.div_ceil(chunk_size);
+    let stack_depths = trace.stack_depths();
+    let memory_sizes = trace.memory_usage();
+    let prewarmed = trace.prewarmed_keys();
+    let accesses = trace.state_accesses();
+    let sstores = trace.sstore_records();
+    let memory_ops = trace.memory_records();
+    let stack_ops = trace.stack_records();
 
     (0..num_chunks)
         .map(|i| {
             let start = i * chunk_size;
             let end = std::cmp::min(start + chunk_size, total_steps);
 
+            let chunk_prewarmed = prewarmed
+                .iter()
+                .copied()
+                .chain(
+                    accesses
+                        .iter()
+                        .filter(|access| access.step < start)
+                        .map(|access| access.key),
+                )
+                .collect();
+            let chunk_accesses = accesses
+                .iter()
+                .filter(|access| (start..end).contains(&access.step))
+                .map(|access| StateAccess {
+                    step: access.step - start,
+                    ..*access
+                })
+                .collect();
+            // Original values stay those at the start of the transaction
+            let chunk_sstores = sstores
+                .iter()
+                .filter(|record| (start..end).contains(&record.step))
+                .map(|record| SStoreRecord {
+                    step: record.step - start,
+                    ..*record
+                })
+                .collect();
+
+            // Memory written before the chunk lies below its initial memory size
+            let chunk_memory_ops = memory_ops
+                .iter()
+                .filter(|op| (start..end).contains(&op.step))
+                .map(|op| MemoryOp {
+                    step: op.step - start,
+                    ..*op
+                })
+                .collect();
+
+            // Items pushed before the chunk lie below its initial stack depth
+            let chunk_stack_ops = stack_ops
+                .iter()
+                .filter(|op| (start..end).contains(&op.step))
+                .map(|op| StackOp {
+                    step: op.step - start,
+                    ..*op
+                })
+                .collect();
+
             EvmTrace {
                 opcodes: trace.opcodes[start..end].to_vec(),
                 stack_states: trace.stack_states[start..end].to_vec(),
                 pcs: trace.pcs[start..end].to_vec(),
                 gas_values: trace.gas_values[start..end].to_vec(),
-                memory_ops: trace.memory_ops.as_ref().map(|ops| {
-                    ops.iter()
-                        .filter(|op| {
-                            let pc_val = op.offset / 32; // Rough estimate
-                            pc_val >= start as u64 && pc_val < end as u64
-                        })
-                        .cloned()
-                        .collect()
-                }),
+                stack_sizes: Some(stack_depths[start..end].to_vec()),
+                memory_sizes: Some(memory_sizes[start..end].to_vec()),
+                accesses: Some(chunk_accesses),
+                prewarmed: Some(chunk_prewarmed),
+                sstores: Some(chunk_sstores),
+                refunds: trace
+                    .refunds
+                    .as_ref()
+                    .map(|refunds| refunds[start..end].to_vec()),
+                intrinsic_gas: None,
+                memory_ops: Some(chunk_memory_ops),
+                stack_ops: Some(chunk_stack_ops),
                 storage_ops: trace
                     .storage_ops
                     .as_ref()
@@ -235,13 +373,14 @@ fn chunk_trace(trace: &EvmTrace, chunk_size: usize) -> Vec<EvmTrace> {
 }
 
 /// Compute verification key hash for quick VK matching
-fn compute_vk_hash(k: u32, public_inputs: &[u64]) -> String {
+fn compute_vk_hash(k: u32, public_inputs: &[Fp]) -> String {
+    use halo2_proofs::pasta::group::ff::PrimeField;
     use sha2::{Digest, Sha256};
 
     let mut hasher = Sha256::new();
     hasher.update(k.to_le_bytes());
-    for &input in public_inputs {
-        hasher.update(input.to_le_bytes());
+    for input in public_inputs {
+        hasher.update(input.to_repr());
     }
 
     let hash = hasher.finalize();
@@ -262,19 +401,17 @@ fn compute_vk_hash(k: u32, public_inputs: &[u64]) -> String {
 /// # Implementation
 ///
 /// For traces exceeding circuit capacity (2^k rows):
-/// 1. Chunk trace into fixed-size segments (1<<14 rows default)
+/// 1. Chunk trace into segments sized from the measured rows per step
 /// 2. Generate sub-proofs in parallel using Rayon
 /// 3. Aggregate proofs recursively (stub for MVP - full recursive SNARK TBD)
 pub async fn generate_proof_chunked(
     trace: &EvmTrace,
     config: &ProverConfig,
 ) -> Result<ProofOutput> {
-    trace.validate()?;
+    trace.validate_for(config.hardfork)?;
 
-    // Determine chunk size based on circuit size
-    // Reserve some rows for constraints overhead
-    let max_rows = (1 << config.k) - 100;
-    let chunk_size = std::cmp::min(max_rows, 1 << 14); // Default: 16384 rows
+    // Determine chunk size from the rows the circuit actually lays out
+    let chunk_size = chunk_size_for(trace, config)?;
 
     // Chunk the trace
     let chunks = chunk_trace(trace, chunk_size);
@@ -284,6 +421,18 @@ pub async fn generate_proof_chunked(
         return generate_proof_parallel(trace, config).await;
     }
 
+    // Costly opcodes may cluster in one chunk, so check each before proving any
+    let usable = EvmCircuit::<Fp>::usable_rows(config.k);
+    for (i, chunk) in chunks.iter().enumerate() {
+        let rows = trace_rows(chunk, config)?;
+        if rows > usable {
+            return Err(ProverError::InvalidInput(format!(
+                "Chunk {} needs {} rows but k = {} leaves {} usable rows; increase k",
+                i, rows, config.k, usable
+            )));
+        }
+    }
+
     // Generate sub-proofs in parallel
     let sub_proofs: Vec<Result<ProofOutput>> = chunks
         .par_iter()
@@ -305,12 +454,11 @@ pub async fn generate_proof_chunked(
     // For MVP: Combine metadata from all chunks
     // Production: Implement recursive proof aggregation
     let total_opcodes: usize = valid_proofs.iter().map(|p| p.metadata.opcode_count).sum();
-    let total_gas: u64 = valid_proofs.iter().map(|p| p.metadata.gas_used).sum();
 
     // Use first proof as base and update metadata
     let mut combined_proof = valid_proofs.into_iter().next().unwrap();
     combined_proof.metadata.opcode_count = total_opcodes;
-    combined_proof.metadata.gas_used = total_gas;
+    combined_proof.metadata.gas_used = trace.gas_used(config.hardfork);
 
     Ok(combined_proof)
 }
@@ -330,42 +478,33 @@ pub async fn generate_proof_sequential(
     config: &ProverConfig,
 ) -> Result<ProofOutput> {
     // Validate trace first
-    trace.validate()?;
+    trace.validate_for(config.hardfork)?;
 
     // Parse trace into witness data
     let witness = parse_evm_data(trace)?;
 
     // Convert trace to circuit (sequential processing)
+    let stack_depths = trace.stack_depths();
+    let memory_sizes = trace.memory_usage();
     let steps: Vec<_> = trace
         .opcodes
         .iter()
         .enumerate()
-        .map(|(i, opcode)| {
-            let stack_values = trace.stack_states.get(i).cloned().unwrap_or_default();
-            ExecutionStep {
-                opcode: *opcode,
-                stack: [
-                    Fp::from(stack_values.first().copied().unwrap_or(0)),
-                    Fp::from(stack_values.get(1).copied().unwrap_or(0)),
-                    Fp::from(stack_values.get(2).copied().unwrap_or(0)),
-                ],
-                pc: trace.pcs.get(i).copied().unwrap_or(i as u64),
-                gas: trace
-                    .gas_values
-                    .get(i)
-                    .copied()
-                    .unwrap_or(1000000 - (i as u64 * 3)),
-            }
-        })
+        .map(|(i, opcode)| trace_step(trace, &stack_depths, &memory_sizes, i, *opcode))
         .collect();
 
-    let trace_commitment = Fp::from(witness.public_inputs[0]);
-    let circuit = EvmCircuit::new(steps.clone(), trace_commitment);
+    let trace_commitment = witness.public_inputs[0];
+    let circuit = EvmCircuit::new(steps.clone(), trace_commitment)
+        .with_hardfork(config.hardfork)
+        .with_accesses(trace.prewarmed_keys(), trace.state_accesses())
+        .with_sstores(trace.sstore_records())
+        .with_stack_ops(trace.stack_records());
 
     let k = config.k;
     let public_inputs = vec![vec![trace_commitment]];
 
     // Generate real proof using Halo2 proving system
+    ensure_fits(&circuit, k)?;
     let artifacts = generate_artifacts(k, &circuit)?;
 
     let proof_bytes = serialize_proof(&artifacts.params, &artifacts.pk, &circuit, &public_inputs)?;
@@ -373,8 +512,7 @@ pub async fn generate_proof_sequential(
 
     let metadata = TraceInfo {
         opcode_count: trace.opcodes.len(),
-        gas_used: trace.gas_values.first().copied().unwrap_or(0)
-            - trace.gas_values.last().copied().unwrap_or(0),
+        gas_used: trace.gas_used(config.hardfork),
         tx_hash: trace.tx_hash.clone(),
         block_number: trace.block_number,
     };
@@ -394,22 +532,60 @@ pub async fn generate_proof_sequential(
         num_steps,
         k,
         vk_hash,
+        opcodes: trace.opcodes.clone(),
+        hardfork: config.hardfork,
+        num_prewarmed: trace.prewarmed_keys().len(),
     })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::utils::evm_parser::EvmTrace;
+    use crate::utils::evm_parser::{EvmTrace, U256};
+
+    /// Helper to lift small stack literals into words
+    fn words(values: &[u64]) -> Vec<U256> {
+        values.iter().map(|&v| U256::from(v)).collect()
+    }
 
     /// Helper to create a test trace
+    fn create_push_trace(n: u64) -> EvmTrace {
+        EvmTrace {
+            opcodes: vec![0x60; n as usize], // PUSH1 operations
+            stack_states: vec![words(&[1, 0, 0]); n as usize],
+            pcs: (0..n).map(|i| i * 2).collect(),
+            gas_values: (0..n).map(|i| 1000 - i * 3).collect(),
+            stack_sizes: None,
+            memory_sizes: None,
+            accesses: None,
+            prewarmed: None,
+            sstores: None,
+            refunds: None,
+            intrinsic_gas: None,
+            memory_ops: None,
+            stack_ops: None,
+            storage_ops: None,
+            tx_hash: Some("0xtest_chunk".to_string()),
+            block_number: Some(12345),
+            bytecode: None,
+        }
+    }
+
     fn create_test_trace() -> EvmTrace {
         EvmTrace {
             opcodes: vec![0x60, 0x60, 0x01], // PUSH1, PUSH1, ADD
-            stack_states: vec![vec![1, 0, 0], vec![2, 1, 0], vec![3, 0, 0]],
+            stack_states: vec![words(&[0, 0, 0]), words(&[1, 0, 0]), words(&[2, 1, 0])],
             pcs: vec![0, 2, 4],
             gas_values: vec![1000, 997, 994],
+            stack_sizes: None,
+            memory_sizes: None,
+            accesses: None,
+            prewarmed: None,
+            sstores: None,
+            refunds: None,
+            intrinsic_gas: None,
             memory_ops: None,
+            stack_ops: None,
             storage_ops: None,
             tx_hash: None,
             block_number: None,
@@ -427,7 +603,7 @@ mod tests {
 
         let proof = result.unwrap();
         assert_eq!(proof.metadata.opcode_count, 3);
-        assert!(proof.proof.len() > 0);
+        assert!(!proof.proof.is_empty());
     }
 
     #[tokio::test]
@@ -449,7 +625,15 @@ mod tests {
             stack_states: vec![],
             pcs: vec![],
             gas_values: vec![],
+            stack_sizes: None,
+            memory_sizes: None,
+            accesses: None,
+            prewarmed: None,
+            sstores: None,
+            refunds: None,
+            intrinsic_gas: None,
             memory_ops: None,
+            stack_ops: None,
             storage_ops: None,
             tx_hash: None,
             block_number: None,
@@ -465,22 +649,7 @@ mod tests {
     async fn test_chunked_proof_generation() {
         // Create a smaller trace to test chunking logic without actually chunking
         // This avoids issues with Rayon thread pool initialization in tests
-        let trace_opcodes = vec![0x60; 100]; // 100 PUSH1 operations
-        let trace_stack_states = vec![vec![1, 0, 0]; 100];
-        let trace_pcs: Vec<u64> = (0..100).map(|i| i * 2).collect();
-        let trace_gas: Vec<u64> = (0..100).map(|i| 1000 - i * 3).collect();
-
-        let trace = EvmTrace {
-            opcodes: trace_opcodes,
-            stack_states: trace_stack_states,
-            pcs: trace_pcs,
-            gas_values: trace_gas,
-            memory_ops: None,
-            storage_ops: None,
-            tx_hash: Some("0xtest_chunk".to_string()),
-            block_number: Some(12345),
-            bytecode: None,
-        };
+        let trace = create_push_trace(100);
 
         let config = ProverConfig {
             k: 17, // Large enough to not actually chunk this small trace
@@ -496,6 +665,42 @@ mod tests {
         assert_eq!(proof.metadata.opcode_count, 100);
     }
 
+    #[test]
+    fn test_chunk_size_from_measured_rows() {
+        let trace = create_push_trace(100);
+
+        // Everything fits at a large k
+        let config = ProverConfig {
+            k: 17,
+            ..Default::default()
+        };
+        assert_eq!(chunk_size_for(&trace, &config).unwrap(), 100);
+
+        // Far fewer steps than rows fit at a small k, and every chunk fits
+        let config = ProverConfig {
+            k: 11,
+            ..Default::default()
+        };
+        let usable = EvmCircuit::<Fp>::usable_rows(config.k);
+        let chunk_size = chunk_size_for(&trace, &config).unwrap();
+        assert!(chunk_size < 100 && chunk_size < usable);
+        for chunk in chunk_trace(&trace, chunk_size) {
+            assert!(trace_rows(&chunk, &config).unwrap() <= usable);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_chunked_rejects_too_small_k() {
+        let trace = create_push_trace(10);
+        let config = ProverConfig {
+            k: 4,
+            ..Default::default()
+        };
+
+        let err = generate_proof_chunked(&trace, &config).await.unwrap_err();
+        assert!(err.to_string().contains("increase k"), "{}", err);
+    }
+
     #[test]
     fn test_chunk_trace() {
         let trace = create_test_trace();
@@ -3,42 +3,85 @@
 //! Verifies zkEVM proofs generated by the prover using real Halo2 verification.
 
 use crate::{
+    ProofOutput, ProverConfig,
+    chips::evm_chip::Hardfork,
     circuits::main_circuit::{EvmCircuit, ExecutionStep},
     errors::{ProverError, Result},
-    ProofOutput, ProverConfig,
+    utils::evm_parser::AccessKey,
 };
-use base64::{engine::general_purpose, Engine as _};
+use alloy_primitives::U256;
+use base64::{Engine as _, engine::general_purpose};
 use halo2_proofs::{
     pasta::{EqAffine, Fp},
-    plonk::{keygen_vk, verify_proof, SingleVerifier, VerifyingKey},
+    plonk::{SingleVerifier, VerifyingKey, keygen_vk, verify_proof},
     poly::commitment::Params,
     transcript::{Blake2bRead, Challenge255},
 };
 
+/// Parse a public input string back into a field element
+///
+/// Accepts the big-endian hex produced by `Fp`'s `Debug` impl ("0x1234...") or a
+/// plain decimal `u64`.
+fn parse_field_element(s: &str) -> Option<Fp> {
+    use halo2_proofs::pasta::group::ff::PrimeField;
+
+    let s = s.trim();
+    if let Some(digits) = s.strip_prefix("0x") {
+        if digits.len() > 64 {
+            return None;
+        }
+        let padded = format!("{:0>64}", digits);
+        let mut repr = [0u8; 32];
+        hex::decode_to_slice(&padded, &mut repr).ok()?;
+        repr.reverse(); // Fp::from_repr expects little-endian bytes
+        Option::from(Fp::from_repr(repr))
+    } else {
+        // Try parsing as decimal
+        s.parse::<u64>().ok().map(Fp::from)
+    }
+}
+
 /// Generate verification key with matching circuit structure
 ///
 /// This reconstructs the VK from params and a circuit with the same structure
-/// (same number of steps). The VK depends on circuit structure, not witness values.
+/// (same number of steps and opcodes). The VK depends on circuit structure, not
+/// witness values. Every step's opcode is a constant of the VK, so the proof must
+/// list exactly one opcode per step. The hardfork fills the opcode table, so it
+/// must match the prover's, and every pre-warmed access key adds rows to the
+/// access set checks.
 fn generate_vk_for_steps(
     k: u32,
     num_steps: usize,
+    opcodes: &[u8],
+    hardfork: Hardfork,
+    num_prewarmed: usize,
 ) -> Result<(Params<EqAffine>, VerifyingKey<EqAffine>)> {
+    if opcodes.len() != num_steps {
+        return Err(ProverError::VerificationError(format!(
+            "Opcode count {} does not match num_steps {}",
+            opcodes.len(),
+            num_steps
+        )));
+    }
+
     let params = Params::new(k);
 
-    // Create circuit with same structure (num_steps) but placeholder witness values
+    // Create circuit with same structure (num_steps, opcodes) but placeholder witness values
     // The VK only depends on the circuit constraints/structure, not the actual values
-    // Use opcode 0 (STOP) which has minimal constraints
-    let steps: Vec<ExecutionStep<Fp>> = (0..num_steps)
-        .enumerate()
-        .map(|(i, _)| ExecutionStep {
-            opcode: 0, // STOP opcode
-            stack: [Fp::zero(), Fp::zero(), Fp::zero()],
+    let steps: Vec<ExecutionStep> = (0..num_steps)
+        .map(|i| ExecutionStep {
+            opcode: opcodes[i],
+            stack: [U256::ZERO; 3],
             pc: i as u64,
             gas: 1000000 - (i as u64 * 3), // Decreasing gas like real execution
+            stack_depth: 0,
+            memory_size: 0,
         })
         .collect();
 
-    let circuit = EvmCircuit::new(steps, Fp::zero());
+    let circuit = EvmCircuit::new(steps, Fp::zero())
+        .with_hardfork(hardfork)
+        .with_accesses(vec![AccessKey::default(); num_prewarmed], Vec::new());
 
     let vk = keygen_vk(&params, &circuit)
         .map_err(|e| ProverError::Halo2Error(format!("Failed to generate VK: {:?}", e)))?;
@@ -82,6 +125,7 @@ pub async fn verify_with_verbosity(
         println!("Number of steps: {}", proof_output.num_steps);
         println!("Circuit k value: {}", proof_output.k);
         println!("VK hash: {}", proof_output.vk_hash);
+        println!("Hardfork: {}", proof_output.hardfork);
         println!("Public inputs count: {}", proof_output.public_inputs.len());
         for (i, pi) in proof_output.public_inputs.iter().enumerate() {
             println!("  Public input[{}]: {}", i, pi);
@@ -131,33 +175,35 @@ pub async fn verify_with_verbosity(
     let public_inputs: Vec<Fp> = proof_output
         .public_inputs
         .iter()
-        .filter_map(|s| {
-            // Parse hex format like "0x1234" or Fp debug format
-            let s = s.trim();
-            if s.starts_with("0x") {
-                let val = s.trim_start_matches("0x");
-                u64::from_str_radix(val, 16).ok().map(Fp::from)
-            } else {
-                // Try parsing as decimal
-                s.parse::<u64>().ok().map(Fp::from)
-            }
-        })
+        .filter_map(|s| parse_field_element(s))
         .collect();
 
     if verbose {
-        println!("Parsed {} public inputs as field elements", public_inputs.len());
+        println!(
+            "Parsed {} public inputs as field elements",
+            public_inputs.len()
+        );
         for (i, pi) in public_inputs.iter().enumerate() {
             println!("  Fp[{}]: {:?}", i, pi);
         }
     }
 
     if verbose {
-        println!("Generating VK for {} steps with k={}...", proof_output.num_steps, proof_output.k);
+        println!(
+            "Generating VK for {} steps with k={}...",
+            proof_output.num_steps, proof_output.k
+        );
     }
 
     // Generate verification key with matching circuit structure
     // Use k and num_steps from the proof output to ensure VK matches
-    let (params, vk) = generate_vk_for_steps(proof_output.k, proof_output.num_steps)?;
+    let (params, vk) = generate_vk_for_steps(
+        proof_output.k,
+        proof_output.num_steps,
+        &proof_output.opcodes,
+        proof_output.hardfork,
+        proof_output.num_prewarmed,
+    )?;
 
     if verbose {
         println!("VK generated successfully");
@@ -177,10 +223,15 @@ pub async fn verify_with_verbosity(
 
     if verbose {
         println!("Running verify_proof...");
-        println!("  Instances structure: {} circuits, {} columns, {} rows",
+        println!(
+            "  Instances structure: {} circuits, {} columns, {} rows",
             instances.len(),
             instances.first().map(|x| x.len()).unwrap_or(0),
-            instances.first().and_then(|x| x.first()).map(|x| x.len()).unwrap_or(0)
+            instances
+                .first()
+                .and_then(|x| x.first())
+                .map(|x| x.len())
+                .unwrap_or(0)
         );
     }
 
@@ -271,7 +322,7 @@ mod tests {
     #[tokio::test]
     async fn test_verify_invalid_vk_hash() {
         let proof = ProofOutput {
-            proof: general_purpose::STANDARD.encode(&vec![0u8; 128]),
+            proof: general_purpose::STANDARD.encode(vec![0u8; 128]),
             public_inputs: vec!["0x3".to_string()],
             metadata: TraceInfo {
                 opcode_count: 3,
@@ -282,6 +333,9 @@ mod tests {
             num_steps: 3,
             k: 17,
             vk_hash: "".to_string(), // Empty VK hash should fail
+            opcodes: vec![],
+            hardfork: Hardfork::default(),
+            num_prewarmed: 0,
         };
 
         let config = ProverConfig::default();
@@ -292,7 +346,7 @@ mod tests {
     #[tokio::test]
     async fn test_verify_proof_too_short() {
         let proof = ProofOutput {
-            proof: general_purpose::STANDARD.encode(&vec![0u8; 32]), // Too short
+            proof: general_purpose::STANDARD.encode(vec![0u8; 32]), // Too short
             public_inputs: vec!["0x3".to_string()],
             metadata: TraceInfo {
                 opcode_count: 3,
@@ -303,6 +357,9 @@ mod tests {
             num_steps: 3,
             k: 17,
             vk_hash: "vk_17".to_string(),
+            opcodes: vec![],
+            hardfork: Hardfork::default(),
+            num_prewarmed: 0,
         };
 
         let config = ProverConfig::default();
@@ -313,7 +370,7 @@ mod tests {
     #[tokio::test]
     async fn test_verify_missing_num_steps() {
         let proof = ProofOutput {
-            proof: general_purpose::STANDARD.encode(&vec![0u8; 256]),
+            proof: general_purpose::STANDARD.encode(vec![0u8; 256]),
             public_inputs: vec!["0x3".to_string()],
             metadata: TraceInfo {
                 opcode_count: 3,
@@ -324,6 +381,9 @@ mod tests {
             num_steps: 0, // Zero steps should fail
             k: 17,
             vk_hash: "vk_17".to_string(),
+            opcodes: vec![],
+            hardfork: Hardfork::default(),
+            num_prewarmed: 0,
         };
 
         let config = ProverConfig::default();
@@ -345,10 +405,70 @@ mod tests {
             num_steps: 3,
             k: 17,
             vk_hash: "vk_17".to_string(),
+            opcodes: vec![],
+            hardfork: Hardfork::default(),
+            num_prewarmed: 0,
+        };
+
+        let config = ProverConfig::default();
+        let result = verify(&proof, &config).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_verify_opcode_count_mismatch() {
+        let proof = ProofOutput {
+            proof: general_purpose::STANDARD.encode(vec![0u8; 128]),
+            public_inputs: vec!["0x3".to_string()],
+            metadata: TraceInfo {
+                opcode_count: 3,
+                gas_used: 9,
+                tx_hash: None,
+                block_number: None,
+            },
+            num_steps: 3,
+            k: 17,
+            vk_hash: "vk_17".to_string(),
+            opcodes: vec![0x60, 0x01], // One opcode short of num_steps
+            hardfork: Hardfork::default(),
+            num_prewarmed: 0,
         };
 
         let config = ProverConfig::default();
         let result = verify(&proof, &config).await;
         assert!(result.is_err());
     }
+
+    #[tokio::test]
+    async fn test_verify_missing_opcodes() {
+        let proof = ProofOutput {
+            proof: general_purpose::STANDARD.encode(vec![0u8; 128]),
+            public_inputs: vec!["0x3".to_string()],
+            metadata: TraceInfo {
+                opcode_count: 3,
+                gas_used: 9,
+                tx_hash: None,
+                block_number: None,
+            },
+            num_steps: 3,
+            k: 17,
+            vk_hash: "vk_17".to_string(),
+            opcodes: vec![], // Would otherwise rebuild the layout as STOP steps
+            hardfork: Hardfork::default(),
+            num_prewarmed: 0,
+        };
+
+        let config = ProverConfig::default();
+        let result = verify(&proof, &config).await;
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_parse_field_element_roundtrip() {
+        let value = Fp::from(u64::MAX) * Fp::from(u64::MAX) + Fp::from(7u64);
+        assert_eq!(parse_field_element(&format!("{:?}", value)), Some(value));
+        assert_eq!(parse_field_element("0x3"), Some(Fp::from(3u64)));
+        assert_eq!(parse_field_element("42"), Some(Fp::from(42u64)));
+        assert_eq!(parse_field_element("0xzz"), None);
+    }
 }
@@ -3,25 +3,67 @@
 //! Parses EVM execution traces from JSON, fetches them from Ethereum networks via Alloy,
 //! and simulates execution using REVM to extract real opcodes, stack, memory, and storage.
 
+use crate::chips::access_chip::{ACCESS_KEY_INPUTS, access_key_fields};
+use crate::chips::evm_chip::{Hardfork, OpCode, OpcodeProperties, SStoreSchedule, StateAccessKind};
+use crate::chips::hash_chip::{POSEIDON_RATE, POSEIDON_WIDTH, STEP_HASH_INPUTS};
+use crate::chips::sha3_chip::SHA3_MAX_BYTES;
+use crate::chips::sstore_chip::{SSTORE_VALUE_INPUTS, sstore_value_fields};
+use crate::chips::word_chip::word_limbs;
 use crate::errors::{ProverError, Result};
+use crate::utils::mpt::{TrieAccount, decode_storage_value, verify_proof};
 use alloy_consensus::Transaction as TransactionTrait;
-use alloy_primitives::U256;
+pub use alloy_primitives::{Address, U256};
+use alloy_primitives::{B256, keccak256};
 use alloy_provider::{Provider, ProviderBuilder};
+use alloy_rpc_types::EIP1186AccountProofResponse;
+use halo2_gadgets::poseidon::primitives::{self as poseidon, ConstantLength, P128Pow5T3};
+use halo2_proofs::pasta::Fp;
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 
 /// EVM execution trace
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EvmTrace {
     /// Opcodes executed (raw bytes from bytecode execution)
     pub opcodes: Vec<u8>,
-    /// Stack states at each step (top 3 values for circuit constraints)
-    pub stack_states: Vec<Vec<u64>>,
+    /// Stack states at each step (top 3 words for circuit constraints)
+    pub stack_states: Vec<Vec<U256>>,
     /// Program counter values
     pub pcs: Vec<u64>,
     /// Gas values at each step
     pub gas_values: Vec<u64>,
-    /// Memory snapshots (optional, for MLOAD/MSTORE ops)
+    /// Full stack length before each step (derived from stack effects when absent)
+    #[serde(default)]
+    pub stack_sizes: Option<Vec<u64>>,
+    /// Memory size in bytes before each step (derived from memory operands
+    /// when absent)
+    #[serde(default)]
+    pub memory_sizes: Option<Vec<u64>>,
+    /// Access set entry of every step with an access kind, in trace order
+    /// (derived from the stack when absent)
+    #[serde(default)]
+    pub accesses: Option<Vec<StateAccess>>,
+    /// Keys warm before the first step: sender, recipient, precompiles,
+    /// coinbase and the transaction's access list
+    #[serde(default)]
+    pub prewarmed: Option<Vec<AccessKey>>,
+    /// Original and current value of the slot of every SSTORE, in trace order
+    /// (derived from earlier reads and writes when absent)
+    #[serde(default)]
+    pub sstores: Option<Vec<SStoreRecord>>,
+    /// Refund counter before each step (derived from the SSTOREs when absent)
+    #[serde(default)]
+    pub refunds: Option<Vec<u64>>,
+    /// Gas charged before the first step: base cost, calldata and access list
+    #[serde(default)]
+    pub intrinsic_gas: Option<u64>,
+    /// Access of every MLOAD, MSTORE, MSTORE8 and KECCAK256, in trace order
+    /// (derived from the stack when absent)
     pub memory_ops: Option<Vec<MemoryOp>>,
+    /// Stack item read or written by every step, in trace order (derived
+    /// from the stack when absent)
+    #[serde(default)]
+    pub stack_ops: Option<Vec<StackOp>>,
     /// Storage operations (for SLOAD/SSTORE)
     pub storage_ops: Option<Vec<StorageOp>>,
     /// Transaction hash (if from network)
@@ -32,11 +74,54 @@ pub struct EvmTrace {
     pub bytecode: Option<Vec<u8>>,
 }
 
-/// Memory operation record
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Memory access of an MLOAD, MSTORE, MSTORE8 or KECCAK256 step
+///
+/// KECCAK256 reads the `SHA3_MAX_BYTES` bytes from its offset as word reads,
+/// one record per word, and hashes as many of them as its size operand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
 pub struct MemoryOp {
+    /// Index of the accessing step
+    pub step: usize,
+    /// Call frame whose memory is accessed (every frame has its own)
+    #[serde(default)]
+    pub call_id: usize,
+    /// Byte offset of the access
     pub offset: u64,
-    pub value: Vec<u8>,
+    /// Bytes accessed: 32, or 1 for MSTORE8
+    pub length: u64,
+    /// Word read or written (the byte MSTORE8 writes)
+    pub value: U256,
+    /// Whether the step writes memory
+    pub is_write: bool,
+}
+
+impl MemoryOp {
+    /// Bytes of the access at consecutive offsets, most significant first
+    pub fn bytes(&self) -> Vec<u8> {
+        let word = self.value.to_be_bytes::<32>();
+        word[32 - self.length.min(32) as usize..].to_vec()
+    }
+}
+
+/// Read or write of a stack item by one step
+///
+/// Items are addressed by their pointer, the number of items below them, so
+/// an item keeps its pointer while the stack grows and shrinks above it. The
+/// items a step touches are those of `OpCode::stack_slots`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct StackOp {
+    /// Index of the accessing step
+    pub step: usize,
+    /// Call frame whose stack is accessed (every frame has its own)
+    #[serde(default)]
+    pub call_id: usize,
+    /// Position of the access in trace order, counting from 1
+    pub rw_counter: u64,
+    /// Items below the accessed one
+    pub pointer: u64,
+    /// Word read or written
+    pub value: U256,
+    /// Whether the step writes the item
     pub is_write: bool,
 }
 
@@ -48,17 +133,65 @@ pub struct StorageOp {
     pub is_write: bool,
 }
 
+/// Key of the EIP-2929 access sets: an account, or one of its storage slots
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub struct AccessKey {
+    /// Account, or owner of the storage slot
+    pub address: Address,
+    /// Storage slot (`None` for the account itself)
+    #[serde(default)]
+    pub slot: Option<U256>,
+}
+
+/// Access set entry added by one step
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct StateAccess {
+    /// Index of the accessing step
+    pub step: usize,
+    /// Key accessed
+    pub key: AccessKey,
+    /// Whether the key was already in the access set
+    pub is_warm: bool,
+}
+
+/// Values of the slot an SSTORE writes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct SStoreRecord {
+    /// Index of the SSTORE step
+    pub step: usize,
+    /// Value of the slot at the start of the transaction
+    pub original: U256,
+    /// Value of the slot before the write
+    pub current: U256,
+}
+
 /// Circuit witness data extracted from trace
 #[derive(Debug, Clone)]
 pub struct CircuitWitness {
     /// Flattened opcode cells
     pub opcode_cells: Vec<u64>,
-    /// Flattened stack cells
-    pub stack_cells: Vec<u64>,
+    /// Flattened stack words
+    pub stack_cells: Vec<U256>,
     /// Gas consumption per step
     pub gas_cells: Vec<u64>,
+    /// Program counter per step
+    pub pc_cells: Vec<u64>,
+    /// Stack depth before each step
+    pub stack_depth_cells: Vec<u64>,
+    /// Memory size in bytes before each step
+    pub memory_size_cells: Vec<u64>,
+    /// Keys warm before the first step
+    pub prewarmed: Vec<AccessKey>,
+    /// Access set entry of every accessing step
+    pub accesses: Vec<StateAccess>,
+    /// Slot values of every SSTORE
+    pub sstores: Vec<SStoreRecord>,
+    /// Access of every MLOAD, MSTORE, MSTORE8 and KECCAK256
+    pub memory_ops: Vec<MemoryOp>,
+    /// Stack item read or written by every step
+    pub stack_ops: Vec<StackOp>,
     /// Public inputs (trace commitment)
-    pub public_inputs: Vec<u64>,
+    pub public_inputs: Vec<Fp>,
 }
 
 impl EvmTrace {
@@ -77,8 +210,938 @@ impl EvmTrace {
                 "Opcode and gas value count mismatch".to_string(),
             ));
         }
+        if let Some(sizes) = &self.stack_sizes {
+            if sizes.len() != self.opcodes.len() {
+                return Err(ProverError::InvalidInput(
+                    "Opcode and stack size count mismatch".to_string(),
+                ));
+            }
+        }
+        if let Some(sizes) = &self.memory_sizes {
+            if sizes.len() != self.opcodes.len() {
+                return Err(ProverError::InvalidInput(
+                    "Opcode and memory size count mismatch".to_string(),
+                ));
+            }
+        }
+        if let Some(refunds) = &self.refunds {
+            if refunds.len() != self.opcodes.len() {
+                return Err(ProverError::InvalidInput(
+                    "Opcode and refund count mismatch".to_string(),
+                ));
+            }
+        }
+        if let Some(accesses) = &self.accesses {
+            self.validate_accesses(accesses)?;
+        }
+        if let Some(sstores) = &self.sstores {
+            let steps: Vec<usize> = self
+                .opcodes
+                .iter()
+                .enumerate()
+                .filter(|&(_, &op)| op == OpCode::SStore as u8)
+                .map(|(i, _)| i)
+                .collect();
+            if !sstores.iter().map(|record| record.step).eq(steps) {
+                return Err(ProverError::InvalidInput(
+                    "SSTORE records do not match the SSTORE steps".to_string(),
+                ));
+            }
+        }
+        for (i, (&op, stack)) in self.opcodes.iter().zip(&self.stack_states).enumerate() {
+            let size = stack.get(1).copied().unwrap_or_default();
+            if op == OpCode::Keccak256 as u8 && size > U256::from(SHA3_MAX_BYTES) {
+                return Err(ProverError::InvalidInput(format!(
+                    "KECCAK256 at step {} hashes {} bytes, more than the {} supported",
+                    i, size, SHA3_MAX_BYTES
+                )));
+            }
+        }
+        if let Some(ops) = &self.memory_ops {
+            self.validate_memory_ops(ops)?;
+        }
+        if let Some(ops) = &self.stack_ops {
+            self.validate_stack_ops(ops)?;
+        }
         Ok(())
     }
+
+    /// Check memory records against the stack
+    ///
+    /// Every MLOAD, MSTORE and MSTORE8 needs one record, in order, whose offset
+    /// is the top of the stack. Writes must store the stack's value (its low
+    /// byte for MSTORE8) and reads must leave theirs on the next step's stack;
+    /// a read by the last step is taken as recorded. KECCAK256 needs a record
+    /// for every word of its window, whose hashed bytes must digest to the
+    /// next step's top of stack.
+    fn validate_memory_ops(&self, ops: &[MemoryOp]) -> Result<()> {
+        let derived = derive_memory_ops(
+            self.opcodes
+                .iter()
+                .zip(&self.stack_states)
+                .map(|(&op, stack)| (op, stack.as_slice())),
+        );
+        if derived.len() != ops.len() {
+            return Err(ProverError::InvalidInput(format!(
+                "Trace has {} memory accesses but {} memory records",
+                derived.len(),
+                ops.len()
+            )));
+        }
+
+        for (op, derived) in ops.iter().zip(&derived) {
+            let last = op.step + 1 == self.opcodes.len();
+            let hashed = self.opcodes.get(op.step) == Some(&(OpCode::Keccak256 as u8));
+            let value_matches = op.value == derived.value || ((last || hashed) && !op.is_write);
+            if op.step != derived.step
+                || op.offset != derived.offset
+                || op.length != derived.length
+                || op.is_write != derived.is_write
+                || !value_matches
+            {
+                return Err(ProverError::InvalidInput(format!(
+                    "Memory record for step {} does not match the access of step {}",
+                    op.step, derived.step
+                )));
+            }
+        }
+
+        // Hashed windows must digest to the next step's top of stack
+        let mut windows: HashMap<usize, Vec<u8>> = HashMap::new();
+        for op in ops {
+            if self.opcodes[op.step] == OpCode::Keccak256 as u8 {
+                windows.entry(op.step).or_default().extend(op.bytes());
+            }
+        }
+        for (step, window) in windows {
+            let Some(next) = self.stack_states.get(step + 1) else {
+                continue;
+            };
+            let size = self.stack_states[step].get(1).copied().unwrap_or_default();
+            let hashed = window
+                .get(..size.saturating_to::<usize>())
+                .unwrap_or(&window);
+            if next.first().copied().unwrap_or_default() != U256::from_be_bytes(keccak256(hashed).0)
+            {
+                return Err(ProverError::InvalidInput(format!(
+                    "KECCAK256 at step {} does not hash its memory",
+                    step
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check stack records against the trace
+    ///
+    /// Every step needs a record for each item of `OpCode::stack_slots`, in
+    /// order, at the pointer its stack depth puts the item, and counters must
+    /// increase. Reads must return the words the trace's stack shows; items
+    /// past it and written words are taken as recorded.
+    fn validate_stack_ops(&self, ops: &[StackOp]) -> Result<()> {
+        let depths = self.stack_depths();
+        let derived = derive_stack_ops(
+            self.opcodes
+                .iter()
+                .zip(depths.iter().copied())
+                .zip(&self.stack_states)
+                .map(|((&op, depth), stack)| (op, depth, stack.as_slice())),
+        );
+        if derived.len() != ops.len() {
+            return Err(ProverError::InvalidInput(format!(
+                "Trace has {} stack accesses but {} stack records",
+                derived.len(),
+                ops.len()
+            )));
+        }
+
+        let mut rw_counter = 0;
+        for (op, derived) in ops.iter().zip(&derived) {
+            // Reads of items the step's stack shows
+            let step = derived.step;
+            let shown = self.stack_states[step].len() as u64 + derived.pointer >= depths[step];
+            let value_matches = op.value == derived.value || op.is_write || !shown;
+            if op.step != derived.step
+                || op.pointer != derived.pointer
+                || op.is_write != derived.is_write
+                || op.rw_counter <= rw_counter
+                || !value_matches
+            {
+                return Err(ProverError::InvalidInput(format!(
+                    "Stack record for step {} does not match the access of step {}",
+                    op.step, derived.step
+                )));
+            }
+            rw_counter = op.rw_counter;
+        }
+        Ok(())
+    }
+
+    /// Check access records against the stack and the access set
+    ///
+    /// Every step with an access kind needs one record, in order. Account keys
+    /// must be the stack operand and storage keys the slot on top of the stack;
+    /// the owner of a slot and the address of a created contract are taken as
+    /// recorded. Warm flags must follow from the pre-warmed keys and the
+    /// earlier records.
+    fn validate_accesses(&self, accesses: &[StateAccess]) -> Result<()> {
+        let derived = derive_state_accesses(
+            self.opcodes
+                .iter()
+                .zip(&self.stack_states)
+                .map(|(&op, stack)| (op, stack.as_slice())),
+            &[],
+        );
+        if derived.len() != accesses.len() {
+            return Err(ProverError::InvalidInput(format!(
+                "Trace has {} accessing steps but {} access records",
+                derived.len(),
+                accesses.len()
+            )));
+        }
+
+        let mut expected = accesses.to_vec();
+        mark_warmth(&self.prewarmed_keys(), &mut expected);
+
+        for ((access, derived), expected) in accesses.iter().zip(&derived).zip(&expected) {
+            if access.step != derived.step {
+                return Err(ProverError::InvalidInput(format!(
+                    "Access record for step {} where step {} accesses state",
+                    access.step, derived.step
+                )));
+            }
+            let key_matches =
+                match OpCode::from_u8(self.opcodes[access.step]).and_then(|op| op.access_kind()) {
+                    Some(StateAccessKind::Storage) => {
+                        access.key.slot.is_some() && access.key.slot == derived.key.slot
+                    }
+                    Some(StateAccessKind::Account(_)) => access.key == derived.key,
+                    _ => access.key.slot.is_none(),
+                };
+            if !key_matches {
+                return Err(ProverError::InvalidInput(format!(
+                    "Access key at step {} does not match its stack operand",
+                    access.step
+                )));
+            }
+            if access.is_warm != expected.is_warm {
+                return Err(ProverError::InvalidInput(format!(
+                    "Access at step {} is recorded {} but the access set makes it {}",
+                    access.step,
+                    if access.is_warm { "warm" } else { "cold" },
+                    if expected.is_warm { "warm" } else { "cold" }
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Validate the trace against a fork's instruction set and gas schedule
+    ///
+    /// On top of `validate`, every opcode must be enabled in `hardfork` and every
+    /// step with a purely static cost must consume exactly that cost. SSTORE
+    /// must charge and refund what `SStoreSchedule` makes of its slot values,
+    /// the opcodes in `memory_gas_only` their memory expansion and data gas
+    /// and, from Berlin on, SLOAD, BALANCE, EXTCODESIZE and EXTCODEHASH must
+    /// add the cold surcharge exactly when their access is cold. Other steps
+    /// with dynamic gas are skipped. Within a frame, memory may only grow where
+    /// an opcode expands it, and exactly as far as its operands reach, and
+    /// every memory read must return what `check_memory_reads` expects.
+    pub fn validate_for(&self, hardfork: Hardfork) -> Result<()> {
+        self.validate()?;
+
+        for (i, &byte) in self.opcodes.iter().enumerate() {
+            if let Some(op) = OpCode::from_u8(byte) {
+                if !op.is_enabled(hardfork) {
+                    return Err(ProverError::InvalidInput(format!(
+                        "{} at step {} is not enabled in {}",
+                        op.name(),
+                        i,
+                        hardfork
+                    )));
+                }
+            }
+        }
+
+        let warmth: HashMap<usize, bool> = if hardfork >= Hardfork::Berlin {
+            self.state_accesses()
+                .iter()
+                .map(|access| (access.step, access.is_warm))
+                .collect()
+        } else {
+            HashMap::new()
+        };
+
+        let schedule = SStoreSchedule::of(hardfork);
+        let sstores: HashMap<usize, SStoreRecord> = self
+            .sstore_records()
+            .into_iter()
+            .map(|record| (record.step, record))
+            .collect();
+
+        let memory_sizes = self.memory_usage();
+        for (i, pair) in memory_sizes.windows(2).enumerate() {
+            let props = OpcodeProperties::of(self.opcodes[i], hardfork);
+            if !props.checked {
+                continue;
+            }
+            let expected = match OpCode::from_u8(self.opcodes[i]) {
+                Some(op) if op.memory_gas_only() => {
+                    op.memory_size_after(pair[0], &self.stack_states[i])
+                }
+                _ if props.memory => continue,
+                _ => pair[0],
+            };
+            if pair[1] != expected {
+                return Err(ProverError::InvalidInput(format!(
+                    "Memory size at step {} moves from {} to {}, expected {}",
+                    i, pair[0], pair[1], expected
+                )));
+            }
+        }
+
+        check_memory_reads(&self.memory_records(), memory_sizes[0])?;
+        check_stack_reads(&self.stack_records(), self.stack_depths()[0])?;
+
+        for (i, pair) in self.gas_values.windows(2).enumerate() {
+            let props = OpcodeProperties::of(self.opcodes[i], hardfork);
+            if !props.checked {
+                continue;
+            }
+            let priced_cost = match OpCode::from_u8(self.opcodes[i]) {
+                Some(OpCode::SStore) => sstores.get(&i).map(|record| {
+                    let is_warm = warmth.get(&i).copied().unwrap_or(true);
+                    let new = self.stack_states[i].get(1).copied().unwrap_or_default();
+                    schedule.dynamic_gas(record.original, record.current, new, is_warm)
+                }),
+                Some(op) if op.access_gas_only() => warmth
+                    .get(&i)
+                    .map(|is_warm| if *is_warm { 0 } else { op.cold_access_cost() }),
+                Some(op) if op.memory_gas_only() => {
+                    Some(op.memory_gas_cost(memory_sizes[i], &self.stack_states[i]))
+                }
+                _ => None,
+            };
+            let cost = match priced_cost {
+                Some(priced_cost) => props.gas + priced_cost,
+                None if props.dynamic => continue,
+                None => props.gas,
+            };
+            if pair[0].checked_sub(pair[1]) != Some(cost) {
+                return Err(ProverError::InvalidInput(format!(
+                    "Gas at step {} drops from {} to {}, expected a cost of {} in {}",
+                    i, pair[0], pair[1], cost, hardfork
+                )));
+            }
+        }
+
+        if let Some(refunds) = &self.refunds {
+            for (step, refund) in self.sstore_refunds(hardfork) {
+                let Some(&after) = refunds.get(step + 1) else {
+                    continue;
+                };
+                if after as i64 - refunds[step] as i64 != refund {
+                    return Err(ProverError::InvalidInput(format!(
+                        "Refund counter at step {} moves from {} to {}, expected a change of {} in {}",
+                        step, refunds[step], after, refund, hardfork
+                    )));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Slot values of every SSTORE, derived from earlier reads and writes if
+    /// none were recorded (see `derive_sstore_records`)
+    pub fn sstore_records(&self) -> Vec<SStoreRecord> {
+        if let Some(sstores) = &self.sstores {
+            return sstores.clone();
+        }
+
+        derive_sstore_records(
+            self.opcodes
+                .iter()
+                .zip(&self.stack_states)
+                .map(|(&op, stack)| (op, stack.as_slice())),
+            &self.state_accesses(),
+            &HashMap::new(),
+        )
+    }
+
+    /// Access of every MLOAD, MSTORE, MSTORE8 and KECCAK256, derived from the
+    /// stack if none were recorded (see `derive_memory_ops`)
+    pub fn memory_records(&self) -> Vec<MemoryOp> {
+        if let Some(ops) = &self.memory_ops {
+            return ops.clone();
+        }
+
+        derive_memory_ops(
+            self.opcodes
+                .iter()
+                .zip(&self.stack_states)
+                .map(|(&op, stack)| (op, stack.as_slice())),
+        )
+    }
+
+    /// Stack item read or written by every step, derived from the stack if
+    /// none were recorded (see `derive_stack_ops`)
+    pub fn stack_records(&self) -> Vec<StackOp> {
+        if let Some(ops) = &self.stack_ops {
+            return ops.clone();
+        }
+
+        derive_stack_ops(
+            self.opcodes
+                .iter()
+                .zip(self.stack_depths())
+                .zip(&self.stack_states)
+                .map(|((&op, depth), stack)| (op, depth, stack.as_slice())),
+        )
+    }
+
+    /// Change every SSTORE makes to the refund counter, by step
+    fn sstore_refunds(&self, hardfork: Hardfork) -> Vec<(usize, i64)> {
+        let schedule = SStoreSchedule::of(hardfork);
+        self.sstore_records()
+            .into_iter()
+            .map(|record| {
+                let new = self.stack_states[record.step]
+                    .get(1)
+                    .copied()
+                    .unwrap_or_default();
+                let refund = schedule.refund(record.original, record.current, new);
+                (record.step, refund)
+            })
+            .collect()
+    }
+
+    /// Refund counter before each step, derived from the SSTOREs if none was
+    /// recorded
+    pub fn refund_counters(&self, hardfork: Hardfork) -> Vec<u64> {
+        if let Some(refunds) = &self.refunds {
+            return refunds.clone();
+        }
+
+        let refunds: HashMap<usize, i64> = self.sstore_refunds(hardfork).into_iter().collect();
+        let mut counter = 0i64;
+        (0..self.opcodes.len())
+            .map(|i| {
+                let before = counter;
+                counter = (counter + refunds.get(&i).copied().unwrap_or(0)).max(0);
+                before as u64
+            })
+            .collect()
+    }
+
+    /// Gas the receipt reports
+    ///
+    /// The intrinsic gas plus the gas consumed from the first to the last step,
+    /// less the final refund counter capped at the fork's share of that total.
+    pub fn gas_used(&self, hardfork: Hardfork) -> u64 {
+        let consumed = (self.intrinsic_gas.unwrap_or(0)
+            + self.gas_values.first().copied().unwrap_or(0))
+        .saturating_sub(self.gas_values.last().copied().unwrap_or(0));
+
+        let last = self.opcodes.len().saturating_sub(1);
+        let last_refund = self
+            .sstore_refunds(hardfork)
+            .into_iter()
+            .find(|&(step, _)| step == last)
+            .map_or(0, |(_, refund)| refund);
+        let refund = self
+            .refund_counters(hardfork)
+            .last()
+            .map_or(0, |&counter| (counter as i64 + last_refund).max(0) as u64);
+
+        consumed - refund.min(consumed / hardfork.max_refund_quotient())
+    }
+
+    /// Keys warm before the first step (none unless recorded)
+    pub fn prewarmed_keys(&self) -> Vec<AccessKey> {
+        self.prewarmed.clone().unwrap_or_default()
+    }
+
+    /// Access set entry of every accessing step
+    ///
+    /// Traces from `debug_traceTransaction` carry their own records. Others get
+    /// records derived from the stack, see `derive_state_accesses`.
+    pub fn state_accesses(&self) -> Vec<StateAccess> {
+        if let Some(accesses) = &self.accesses {
+            return accesses.clone();
+        }
+
+        derive_state_accesses(
+            self.opcodes
+                .iter()
+                .zip(&self.stack_states)
+                .map(|(&op, stack)| (op, stack.as_slice())),
+            &self.prewarmed_keys(),
+        )
+    }
+
+    /// Memory size in bytes before each step
+    ///
+    /// Traces from `debug_traceTransaction` carry their own sizes. Others get
+    /// sizes derived from the stack, see `derive_memory_sizes`.
+    pub fn memory_usage(&self) -> Vec<u64> {
+        if let Some(sizes) = &self.memory_sizes {
+            return sizes.clone();
+        }
+
+        derive_memory_sizes(
+            self.opcodes
+                .iter()
+                .zip(&self.stack_states)
+                .map(|(&op, stack)| (op, stack.as_slice())),
+        )
+    }
+
+    /// Stack depth before each step
+    ///
+    /// Traces from `debug_traceTransaction` record the full stack length of
+    /// every step in `stack_sizes`, and those are used as-is. Hand-written
+    /// traces without them get depths reconstructed from each opcode's stack
+    /// effect, starting from the smallest initial depth that never underflows.
+    /// Stack effects never change between forks, so the latest fork's table is
+    /// used. Either way the depths are hashed into the trace commitment.
+    ///
+    /// # Returns
+    ///
+    /// One depth per opcode in the trace
+    pub fn stack_depths(&self) -> Vec<u64> {
+        if let Some(sizes) = &self.stack_sizes {
+            return sizes.clone();
+        }
+
+        let effects: Vec<(i64, i64)> = self
+            .opcodes
+            .iter()
+            .map(|&op| {
+                let props = OpcodeProperties::of(op, Hardfork::default());
+                (props.pops as i64, props.pushes as i64)
+            })
+            .collect();
+
+        let mut relative = 0i64;
+        let mut lowest = 0i64;
+        for &(pops, pushes) in &effects {
+            lowest = lowest.min(relative - pops);
+            relative += pushes - pops;
+        }
+
+        let mut depth = -lowest;
+        effects
+            .iter()
+            .map(|&(pops, pushes)| {
+                let current = depth as u64;
+                depth += pushes - pops;
+                current
+            })
+            .collect()
+    }
+}
+
+/// Address held in the low 160 bits of a stack word
+pub fn word_to_address(word: U256) -> Address {
+    Address::from_word(word.into())
+}
+
+/// Derive the access of every accessing step from its stack
+///
+/// Accounts are read from the stack operand and storage slots from the top of
+/// the stack. The stack does not say which contract owns a slot or which
+/// address a creation deploys to, so both are taken as `Address::ZERO`.
+///
+/// # Arguments
+///
+/// * `steps` - Opcode and stack (top first) of every step, in trace order
+/// * `prewarmed` - Keys warm before the first step
+pub fn derive_state_accesses<'a>(
+    steps: impl IntoIterator<Item = (u8, &'a [U256])>,
+    prewarmed: &[AccessKey],
+) -> Vec<StateAccess> {
+    let mut accesses: Vec<StateAccess> = steps
+        .into_iter()
+        .enumerate()
+        .filter_map(|(step, (opcode, stack))| {
+            let operand = |slot: usize| stack.get(slot).copied().unwrap_or_default();
+            let key = match OpCode::from_u8(opcode)?.access_kind()? {
+                StateAccessKind::Storage => AccessKey {
+                    address: Address::ZERO,
+                    slot: Some(operand(0)),
+                },
+                StateAccessKind::Account(slot) => AccessKey {
+                    address: word_to_address(operand(slot)),
+                    slot: None,
+                },
+                StateAccessKind::Created => AccessKey::default(),
+            };
+            Some(StateAccess {
+                step,
+                key,
+                is_warm: false,
+            })
+        })
+        .collect();
+
+    mark_warmth(prewarmed, &mut accesses);
+    accesses
+}
+
+/// Set the warm flag of every access from the keys accessed before it
+///
+/// Warmth gained in a call frame that later reverts is not rolled back, so
+/// such traces disagree with the EVM on later accesses to the same keys.
+pub fn mark_warmth(prewarmed: &[AccessKey], accesses: &mut [StateAccess]) {
+    let mut warm: HashSet<AccessKey> = prewarmed.iter().copied().collect();
+    for access in accesses {
+        access.is_warm = !warm.insert(access.key);
+    }
+}
+
+/// Memory size in bytes before each step of a single call frame
+///
+/// Memory starts empty and every step grows it to cover the ranges its stack
+/// operands (top first) address, see `OpCode::memory_size_after`. Steps are
+/// taken to run in one frame; `parse_debug_trace` follows the frames of
+/// traces that have several.
+pub fn derive_memory_sizes<'a>(steps: impl IntoIterator<Item = (u8, &'a [U256])>) -> Vec<u64> {
+    let mut size = 0;
+    steps
+        .into_iter()
+        .map(|(opcode, stack)| {
+            let before = size;
+            if let Some(op) = OpCode::from_u8(opcode) {
+                size = op.memory_size_after(size, stack);
+            }
+            before
+        })
+        .collect()
+}
+
+/// Memory access of every MLOAD, MSTORE, MSTORE8 and KECCAK256 of a single
+/// call frame
+///
+/// Offsets and written values come from the stack (top first). A load returns
+/// the top of the next step's stack; the last step has none, so its read is
+/// replayed from the earlier writes, as are the words KECCAK256 reads.
+pub fn derive_memory_ops<'a>(steps: impl IntoIterator<Item = (u8, &'a [U256])>) -> Vec<MemoryOp> {
+    let steps: Vec<(u8, &[U256])> = steps.into_iter().collect();
+    let operand = |stack: &[U256], i: usize| stack.get(i).copied().unwrap_or_default();
+    let mut ops: Vec<MemoryOp> = Vec::new();
+
+    for (i, &(opcode, stack)) in steps.iter().enumerate() {
+        let offset = operand(stack, 0).saturating_to::<u64>();
+        let op = match OpCode::from_u8(opcode) {
+            Some(OpCode::MLoad) => {
+                let value = match steps.get(i + 1) {
+                    Some(&(_, next)) => operand(next, 0),
+                    None => replay_memory_read(&ops, offset),
+                };
+                MemoryOp {
+                    step: i,
+                    call_id: 0,
+                    offset,
+                    length: 32,
+                    value,
+                    is_write: false,
+                }
+            }
+            Some(OpCode::MStore) => MemoryOp {
+                step: i,
+                call_id: 0,
+                offset,
+                length: 32,
+                value: operand(stack, 1),
+                is_write: true,
+            },
+            Some(OpCode::MStore8) => MemoryOp {
+                step: i,
+                call_id: 0,
+                offset,
+                length: 1,
+                value: operand(stack, 1) & U256::from(0xffu64),
+                is_write: true,
+            },
+            Some(OpCode::Keccak256) => {
+                for k in 0..(SHA3_MAX_BYTES / 32) as u64 {
+                    let offset = offset.saturating_add(32 * k);
+                    let value = replay_memory_read(&ops, offset);
+                    ops.push(MemoryOp {
+                        step: i,
+                        call_id: 0,
+                        offset,
+                        length: 32,
+                        value,
+                        is_write: false,
+                    });
+                }
+                continue;
+            }
+            _ => continue,
+        };
+        ops.push(op);
+    }
+
+    ops
+}
+
+/// Word at `offset` as left by the given accesses of one frame
+fn replay_memory_read(ops: &[MemoryOp], offset: u64) -> U256 {
+    let mut word = [0u8; 32];
+    for op in ops {
+        for (i, byte) in op.bytes().into_iter().enumerate() {
+            let address = op.offset.saturating_add(i as u64);
+            if (offset..offset.saturating_add(32)).contains(&address) {
+                word[(address - offset) as usize] = byte;
+            }
+        }
+    }
+    U256::from_be_bytes(word)
+}
+
+/// Check that every memory read returns the byte last written to its address
+///
+/// Bytes no access has touched yet read as zero, except below `initial_size`,
+/// the memory size the trace starts with: a trace that starts mid-execution
+/// reads memory written before it, so a first read there may return anything
+/// and later reads must agree with it.
+pub fn check_memory_reads(ops: &[MemoryOp], initial_size: u64) -> Result<()> {
+    let mut memory: HashMap<(usize, u64), u8> = HashMap::new();
+    for op in ops {
+        for (i, byte) in op.bytes().into_iter().enumerate() {
+            let address = op.offset.saturating_add(i as u64);
+            let key = (op.call_id, address);
+            if op.is_write {
+                memory.insert(key, byte);
+                continue;
+            }
+            let expected = match memory.get(&key) {
+                Some(&known) => known,
+                None if address < initial_size => *memory.entry(key).or_insert(byte),
+                None => 0,
+            };
+            if byte != expected {
+                return Err(ProverError::InvalidInput(format!(
+                    "Memory read at step {} returns {:#04x} at offset {}, expected {:#04x}",
+                    op.step, byte, address, expected
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Stack accesses of every step of a single call frame
+///
+/// Steps give their opcode, stack depth and stack (top first). Reads take
+/// their words from the step's stack and writes from the next step's; the
+/// last step has none, so its writes are zero. An item past the recorded
+/// stack comes from the other side of a DUP or SWAP, or else holds what the
+/// frame last left there.
+pub fn derive_stack_ops<'a>(
+    steps: impl IntoIterator<Item = (u8, u64, &'a [U256])>,
+) -> Vec<StackOp> {
+    let steps: Vec<(u8, u64, &[U256])> = steps.into_iter().collect();
+    let mut items = HashMap::new();
+    let mut ops = Vec::new();
+    for (i, &(opcode, depth, stack)) in steps.iter().enumerate() {
+        let after = steps.get(i + 1).map(|&(_, _, next)| next);
+        ops.extend(step_stack_ops(i, opcode, depth, stack, after, &mut items));
+    }
+    for (i, op) in ops.iter_mut().enumerate() {
+        op.rw_counter = i as u64 + 1;
+    }
+    ops
+}
+
+/// Stack accesses of one step, reads first, given the stack of the next step
+/// in its frame (none if the frame ends) and the words the frame's earlier
+/// accesses left by pointer, which the accesses update; counters are left at
+/// zero
+fn step_stack_ops(
+    step: usize,
+    opcode: u8,
+    depth: u64,
+    stack: &[U256],
+    after: Option<&[U256]>,
+    items: &mut HashMap<u64, U256>,
+) -> Vec<StackOp> {
+    let Some(op) = OpCode::from_u8(opcode) else {
+        return Vec::new();
+    };
+    let (reads, writes) = op.stack_slots();
+    let depth_after =
+        (depth + op.stack_produced() as u64).saturating_sub(op.stack_consumed() as u64);
+    let read_pointers: Vec<u64> = reads
+        .iter()
+        .map(|&k| depth.saturating_sub(1 + k as u64))
+        .collect();
+    let write_pointers: Vec<u64> = writes
+        .iter()
+        .map(|&k| depth_after.saturating_sub(1 + k as u64))
+        .collect();
+
+    let mut read_words: Vec<Option<U256>> = reads.iter().map(|&k| stack.get(k).copied()).collect();
+    let mut write_words: Vec<Option<U256>> = writes
+        .iter()
+        .map(|&k| after.and_then(|after| after.get(k).copied()))
+        .collect();
+    for (write, read) in op.stack_copies() {
+        read_words[read] = read_words[read].or(write_words[write]);
+    }
+    for (word, pointer) in read_words.iter_mut().zip(&read_pointers) {
+        *word = word.or_else(|| items.get(pointer).copied());
+    }
+    for (write, read) in op.stack_copies() {
+        write_words[write] = read_words[read];
+    }
+
+    let mut ops = Vec::with_capacity(reads.len() + writes.len());
+    let accesses = read_pointers
+        .into_iter()
+        .zip(read_words)
+        .map(|(pointer, word)| (false, pointer, word))
+        .chain(
+            write_pointers
+                .into_iter()
+                .zip(write_words)
+                .map(|(pointer, word)| (true, pointer, word)),
+        );
+    for (is_write, pointer, word) in accesses {
+        let value = word.unwrap_or_default();
+        if is_write {
+            items.insert(pointer, value);
+        } else {
+            items.entry(pointer).or_insert(value);
+        }
+        ops.push(StackOp {
+            step,
+            call_id: 0,
+            rw_counter: 0,
+            pointer,
+            value,
+            is_write,
+        });
+    }
+    ops
+}
+
+/// Check that every stack read returns the word last written to its item
+///
+/// A trace that starts mid-execution reads items pushed before it, so a
+/// first read below `initial_depth` may return anything and later reads must
+/// agree with it. Any other item must be written before it is read.
+pub fn check_stack_reads(ops: &[StackOp], initial_depth: u64) -> Result<()> {
+    let mut stack: HashMap<(usize, u64), U256> = HashMap::new();
+    for op in ops {
+        let key = (op.call_id, op.pointer);
+        if op.is_write {
+            stack.insert(key, op.value);
+            continue;
+        }
+        let expected = match stack.get(&key) {
+            Some(&known) => known,
+            None if op.pointer < initial_depth => *stack.entry(key).or_insert(op.value),
+            None => {
+                return Err(ProverError::InvalidInput(format!(
+                    "Stack read at step {} of item {}, which was never pushed",
+                    op.step, op.pointer
+                )));
+            }
+        };
+        if op.value != expected {
+            return Err(ProverError::InvalidInput(format!(
+                "Stack read at step {} returns {} at item {}, expected {}",
+                op.step, op.value, op.pointer, expected
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Derive the original and current value of the slot of every SSTORE
+///
+/// A slot's first access fixes its original value: `originals` when given,
+/// otherwise the value an SLOAD leaves on the next step's stack, otherwise
+/// zero. Each SSTORE then makes its value the slot's current one. Writes in
+/// reverted frames are not rolled back.
+///
+/// # Arguments
+///
+/// * `steps` - Opcode and stack (top first) of every step, in trace order
+/// * `accesses` - Access records of the steps, which key each slot
+/// * `originals` - Slot values at the start of the transaction, when known
+pub fn derive_sstore_records<'a>(
+    steps: impl IntoIterator<Item = (u8, &'a [U256])>,
+    accesses: &[StateAccess],
+    originals: &HashMap<AccessKey, U256>,
+) -> Vec<SStoreRecord> {
+    let steps: Vec<(u8, &[U256])> = steps.into_iter().collect();
+    let keys: HashMap<usize, AccessKey> = accesses
+        .iter()
+        .map(|access| (access.step, access.key))
+        .collect();
+
+    // Original and current value of every slot accessed so far
+    let mut slots: HashMap<AccessKey, (U256, U256)> = HashMap::new();
+    let mut records = Vec::new();
+    for (i, &(opcode, stack)) in steps.iter().enumerate() {
+        let Some(key) = keys.get(&i) else {
+            continue;
+        };
+        let read = (opcode == OpCode::SLoad as u8)
+            .then(|| steps.get(i + 1).and_then(|(_, next)| next.first().copied()))
+            .flatten();
+        let (original, current) = *slots.entry(*key).or_insert_with(|| {
+            let original = originals.get(key).copied().or(read).unwrap_or_default();
+            (original, read.unwrap_or(original))
+        });
+
+        if opcode == OpCode::SStore as u8 {
+            records.push(SStoreRecord {
+                step: i,
+                original,
+                current,
+            });
+            let new = stack.get(1).copied().unwrap_or_default();
+            slots.insert(*key, (original, new));
+        }
+    }
+    records
+}
+
+/// Storage values from a `prestateTracer` result, keyed like access records
+pub fn parse_prestate_storage(prestate: &serde_json::Value) -> HashMap<AccessKey, U256> {
+    let mut originals = HashMap::new();
+    let Some(accounts) = prestate.as_object() else {
+        return originals;
+    };
+
+    for (address, account) in accounts {
+        let Ok(address) = address.parse::<Address>() else {
+            continue;
+        };
+        let Some(storage) = account.get("storage").and_then(|v| v.as_object()) else {
+            continue;
+        };
+        for (slot, value) in storage {
+            let value = value.as_str().and_then(parse_word);
+            if let (Some(slot), Some(value)) = (parse_word(slot), value) {
+                originals.insert(
+                    AccessKey {
+                        address,
+                        slot: Some(slot),
+                    },
+                    value,
+                );
+            }
+        }
+    }
+    originals
 }
 
 /// Fetch and execute a transaction using debug_traceTransaction RPC
@@ -112,7 +1175,7 @@ pub async fn fetch_and_execute_tx(tx_hash: &str, rpc_url: &str) -> Result<(EvmTr
             return Err(ProverError::RpcConnectionError(format!(
                 "Failed to connect to RPC: {}",
                 e
-            )))
+            )));
         }
     };
 
@@ -136,6 +1199,12 @@ pub async fn fetch_and_execute_tx(tx_hash: &str, rpc_url: &str) -> Result<(EvmTr
 
     let gas_used = receipt.as_ref().map(|r| r.gas_used).unwrap_or(21000);
 
+    // Contract creations run in the frame of the deployed contract
+    let to = tx
+        .inner
+        .to()
+        .or_else(|| receipt.as_ref().and_then(|r| r.contract_address));
+
     // Try debug_traceTransaction first (requires archive node with debug namespace)
     let trace_result: std::result::Result<serde_json::Value, _> = provider
         .raw_request(
@@ -147,15 +1216,66 @@ pub async fn fetch_and_execute_tx(tx_hash: &str, rpc_url: &str) -> Result<(EvmTr
     match trace_result {
         Ok(trace_data) => {
             // Parse the debug trace result
-            let (opcodes, stack_states, pcs, gas_values, memory_ops, storage_ops, bytecode) =
-                parse_debug_trace(&trace_data, gas_used)?;
+            let (
+                opcodes,
+                stack_states,
+                stack_sizes,
+                pcs,
+                gas_values,
+                refunds,
+                memory_sizes,
+                mut accesses,
+                memory_ops,
+                stack_ops,
+                storage_ops,
+                bytecode,
+            ) = parse_debug_trace(&trace_data, gas_used, to)?;
+
+            // EIP-3651 warms the coinbase from Shanghai on
+            let coinbase = provider
+                .get_block_by_number(block_number.into())
+                .await
+                .ok()
+                .flatten()
+                .map(|block| block.header.beneficiary);
+            let prewarmed = transaction_prewarmed_keys(&tx, to, coinbase);
+            mark_warmth(&prewarmed, &mut accesses);
+
+            // Slot values before the transaction price its SSTOREs
+            let originals = provider
+                .raw_request::<_, serde_json::Value>(
+                    "debug_traceTransaction".into(),
+                    (tx_hash, serde_json::json!({"tracer": "prestateTracer"})),
+                )
+                .await
+                .map(|prestate| parse_prestate_storage(&prestate))
+                .unwrap_or_default();
+            let sstores = derive_sstore_records(
+                opcodes
+                    .iter()
+                    .zip(&stack_states)
+                    .map(|(&op, stack)| (op, stack.as_slice())),
+                &accesses,
+                &originals,
+            );
+            let intrinsic_gas = gas_values
+                .first()
+                .map(|&gas| tx.inner.gas_limit().saturating_sub(gas));
 
             let trace = EvmTrace {
                 opcodes,
                 stack_states,
                 pcs,
                 gas_values,
+                stack_sizes: Some(stack_sizes),
+                memory_sizes: Some(memory_sizes),
+                accesses: Some(accesses),
+                prewarmed: Some(prewarmed),
+                sstores: Some(sstores),
+                refunds: Some(refunds),
+                intrinsic_gas,
                 memory_ops,
+                stack_ops,
                 storage_ops,
                 tx_hash: Some(tx_hash.to_string()),
                 block_number: Some(block_number),
@@ -177,6 +1297,44 @@ pub async fn fetch_and_execute_tx(tx_hash: &str, rpc_url: &str) -> Result<(EvmTr
     }
 }
 
+/// Keys warm before a transaction's first step
+///
+/// EIP-2929 warms the sender, the recipient and the precompiles (here up to
+/// the point evaluation precompile at 0x0a), EIP-3651 the coinbase, and
+/// EIP-2930 every entry of the transaction's access list.
+fn transaction_prewarmed_keys(
+    tx: &alloy_rpc_types::Transaction,
+    to: Option<Address>,
+    coinbase: Option<Address>,
+) -> Vec<AccessKey> {
+    let account = |address: Address| AccessKey {
+        address,
+        slot: None,
+    };
+
+    let mut keys: Vec<AccessKey> = [Some(tx.inner.signer()), to, coinbase]
+        .into_iter()
+        .flatten()
+        .chain((1u8..=0x0a).map(Address::with_last_byte))
+        .map(account)
+        .collect();
+
+    for item in tx
+        .inner
+        .access_list()
+        .into_iter()
+        .flat_map(|list| list.iter())
+    {
+        keys.push(account(item.address));
+        keys.extend(item.storage_keys.iter().map(|slot| AccessKey {
+            address: item.address,
+            slot: Some(U256::from_be_bytes(slot.0)),
+        }));
+    }
+
+    keys
+}
+
 /// Construct a trace from transaction data when debug_traceTransaction is unavailable
 ///
 /// This fallback extracts opcodes from the transaction input data (for contract calls)
@@ -195,10 +1353,18 @@ fn construct_fallback_trace(
         // Simple transfer: just a single implicit STOP
         return Ok(EvmTrace {
             opcodes: vec![0x00], // STOP
-            stack_states: vec![vec![0, 0, 0]],
+            stack_states: vec![vec![U256::ZERO; 3]],
             pcs: vec![0],
             gas_values: vec![gas_used],
+            stack_sizes: None,
+            memory_sizes: None,
+            accesses: None,
+            prewarmed: None,
+            sstores: None,
+            refunds: None,
+            intrinsic_gas: None,
             memory_ops: None,
+            stack_ops: None,
             storage_ops: None,
             tx_hash: Some(tx_hash.to_string()),
             block_number: Some(block_number),
@@ -229,7 +1395,7 @@ fn construct_fallback_trace(
         gas_values.push(remaining_gas);
 
         // Simple stack simulation (placeholder values)
-        stack_states.push(vec![0, 0, 0]);
+        stack_states.push(vec![U256::ZERO; 3]);
 
         // Handle PUSH instructions (skip the immediate data)
         if (0x60..=0x7f).contains(&opcode) {
@@ -246,7 +1412,7 @@ fn construct_fallback_trace(
     // Ensure we have at least one opcode
     if opcodes.is_empty() {
         opcodes.push(0x00); // STOP
-        stack_states.push(vec![0, 0, 0]);
+        stack_states.push(vec![U256::ZERO; 3]);
         pcs.push(0);
         gas_values.push(gas_used);
     }
@@ -256,7 +1422,15 @@ fn construct_fallback_trace(
         stack_states,
         pcs,
         gas_values,
+        stack_sizes: None,
+        memory_sizes: None,
+        accesses: None,
+        prewarmed: None,
+        sstores: None,
+        refunds: None,
+        intrinsic_gas: None,
         memory_ops: None,
+        stack_ops: None,
         storage_ops: None,
         tx_hash: Some(tx_hash.to_string()),
         block_number: Some(block_number),
@@ -264,19 +1438,40 @@ fn construct_fallback_trace(
     })
 }
 
-/// Parse debug_traceTransaction response into trace components
-fn parse_debug_trace(
-    trace_result: &serde_json::Value,
-    total_gas: u64,
-) -> Result<(
+/// Trace components extracted from a debug_traceTransaction response:
+/// (opcodes, stack_states, stack_sizes, pcs, gas_values, refunds, memory_sizes,
+/// accesses, memory_ops, stack_ops, storage_ops, bytecode)
+type DebugTraceParts = (
     Vec<u8>,
-    Vec<Vec<u64>>,
+    Vec<Vec<U256>>,
+    Vec<u64>,
     Vec<u64>,
     Vec<u64>,
+    Vec<u64>,
+    Vec<u64>,
+    Vec<StateAccess>,
     Option<Vec<MemoryOp>>,
+    Option<Vec<StackOp>>,
     Option<Vec<StorageOp>>,
     Option<Vec<u8>>,
-)> {
+);
+
+/// Parse debug_traceTransaction response into trace components
+///
+/// Access records are keyed by the storage context of each call frame, which
+/// starts at `to` and follows the `depth` of the struct logs: CALL and
+/// STATICCALL switch to the callee, CALLCODE and DELEGATECALL keep the
+/// caller's storage, and a creation learns its address when it returns. Warm
+/// flags are left unset for `mark_warmth`.
+///
+/// Memory sizes are taken from `memSize`, or the length of `memory`, when the
+/// tracer reports them. Otherwise every frame starts with empty memory that
+/// grows with the memory operands of its steps.
+fn parse_debug_trace(
+    trace_result: &serde_json::Value,
+    total_gas: u64,
+    to: Option<Address>,
+) -> Result<DebugTraceParts> {
     // Extract structLogs array from the trace result
     let struct_logs = trace_result
         .get("structLogs")
@@ -293,10 +1488,31 @@ fn parse_debug_trace(
 
     let mut opcodes = Vec::with_capacity(struct_logs.len());
     let mut stack_states = Vec::with_capacity(struct_logs.len());
+    let mut stack_sizes = Vec::with_capacity(struct_logs.len());
     let mut pcs = Vec::with_capacity(struct_logs.len());
     let mut gas_values = Vec::with_capacity(struct_logs.len());
-    let mut memory_ops = Vec::new();
+    let mut refunds = Vec::with_capacity(struct_logs.len());
+    let mut memory_sizes = Vec::with_capacity(struct_logs.len());
+    let mut memory_ops: Vec<MemoryOp> = Vec::new();
     let mut storage_ops = Vec::new();
+    // Whole stack, depth and frame id of every step
+    let mut full_stacks: Vec<Vec<U256>> = Vec::with_capacity(struct_logs.len());
+    let mut step_frames: Vec<(usize, usize)> = Vec::with_capacity(struct_logs.len());
+
+    // Storage context of every frame by frame id, and the ids of the active
+    // frames, outermost first
+    let mut accesses: Vec<StateAccess> = Vec::new();
+    let mut frame_addresses: Vec<Option<Address>> = vec![to];
+    let mut frames: Vec<usize> = vec![0];
+    // Memory size of every active frame, outermost first
+    let mut frame_memory: Vec<u64> = vec![0];
+    // Storage accesses by frame, resolved once every creation has returned
+    let mut storage_frames: Vec<(usize, usize)> = Vec::new();
+    // Creations awaiting their result: (depth, access index, frame id)
+    let mut pending_creates: Vec<(usize, usize, Option<usize>)> = Vec::new();
+    // Load whose word the next step of its frame reveals: (op index, depth)
+    let mut pending_load: Option<(usize, usize)> = None;
+    let mut previous: Option<(u8, Vec<U256>)> = None;
 
     for (i, log) in struct_logs.iter().enumerate() {
         // Extract opcode name and convert to byte
@@ -319,50 +1535,164 @@ fn parse_debug_trace(
             .unwrap_or(total_gas.saturating_sub(i as u64 * 3));
         gas_values.push(gas);
 
-        // Extract stack (take top 3 values)
-        let stack = log
-            .get("stack")
-            .and_then(|v| v.as_array())
+        // Extract the refund counter (omitted while zero)
+        refunds.push(log.get("refund").and_then(|v| v.as_u64()).unwrap_or(0));
+
+        // Extract stack (record its full length and every word, top first,
+        // for the stack log and memory operands, and the top 3 for the circuit)
+        let stack_entries = log.get("stack").and_then(|v| v.as_array());
+        stack_sizes.push(stack_entries.map_or(0, |arr| arr.len() as u64));
+        let stack = stack_entries
             .map(|arr| {
                 arr.iter()
                     .rev()
-                    .take(3)
                     .map(|v| {
-                        v.as_str()
-                            .and_then(|s| u64::from_str_radix(s.trim_start_matches("0x"), 16).ok())
-                            .unwrap_or(0)
+                        let s = v.as_str().ok_or_else(|| {
+                            ProverError::ParseError(format!(
+                                "Non-string stack entry at index {}",
+                                i
+                            ))
+                        })?;
+                        parse_word(s).ok_or_else(|| {
+                            ProverError::ParseError(format!(
+                                "Invalid stack word {} at index {}",
+                                s, i
+                            ))
+                        })
                     })
-                    .collect::<Vec<u64>>()
+                    .collect::<Result<Vec<U256>>>()
             })
-            .unwrap_or_else(|| vec![0, 0, 0]);
+            .transpose()?
+            .unwrap_or_default();
 
         // Ensure stack has exactly 3 elements
-        let mut stack_3 = stack;
-        while stack_3.len() < 3 {
-            stack_3.push(0);
+        let mut stack_3 = stack.clone();
+        stack_3.resize(3, U256::ZERO);
+
+        // Follow the call frames and record this step's access
+        let depth = log
+            .get("depth")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(1)
+            .max(1) as usize;
+        if depth > frames.len() {
+            let caller = frame_addresses[frames[frames.len() - 1]];
+            let address = match &previous {
+                Some((op, stack))
+                    if *op == OpCode::Call as u8 || *op == OpCode::StaticCall as u8 =>
+                {
+                    Some(word_to_address(stack[1]))
+                }
+                Some((op, _)) if *op == OpCode::Create as u8 || *op == OpCode::Create2 as u8 => {
+                    None
+                }
+                _ => caller,
+            };
+            frame_addresses.push(address);
+            let id = frame_addresses.len() - 1;
+            if address.is_none() {
+                if let Some(pending) = pending_creates.last_mut() {
+                    pending.2 = Some(id);
+                }
+            }
+            frames.push(id);
+        }
+        frames.truncate(depth);
+
+        // Record the memory size of the frame and grow it by this step's operands
+        frame_memory.resize(frames.len(), 0);
+        let size = frame_memory
+            .last_mut()
+            .expect("the outermost frame stays active");
+        let reported = log.get("memSize").and_then(|v| v.as_u64()).or_else(|| {
+            log.get("memory")
+                .and_then(|v| v.as_array())
+                .map(|words| 32 * words.len() as u64)
+        });
+        if let Some(reported) = reported {
+            *size = reported;
+        }
+        memory_sizes.push(*size);
+        if let Some(op) = OpCode::from_u8(opcode_byte) {
+            *size = op.memory_size_after(*size, &stack);
         }
-        stack_states.push(stack_3);
 
-        // Extract memory operations (MLOAD/MSTORE)
-        if op_name == "MLOAD" || op_name == "MSTORE" {
-            if let Some(memory) = log.get("memory").and_then(|v| v.as_array()) {
-                let offset = pcs.last().copied().unwrap_or(0);
-                let value = memory
-                    .iter()
-                    .flat_map(|v| {
-                        v.as_str()
-                            .map(|s| hex::decode(s.trim_start_matches("0x")).unwrap_or_default())
-                            .unwrap_or_default()
-                    })
-                    .collect();
-                memory_ops.push(MemoryOp {
-                    offset,
-                    value,
-                    is_write: op_name == "MSTORE",
-                });
+        // A creation pushes its address for the next step back at its depth
+        pending_creates.retain(|&(create_depth, access, frame)| {
+            if create_depth != depth {
+                return true;
+            }
+            let address = word_to_address(stack_3[0]);
+            accesses[access].key.address = address;
+            if let Some(frame) = frame {
+                frame_addresses[frame] = Some(address);
             }
+            false
+        });
+
+        if let Some(kind) = OpCode::from_u8(opcode_byte).and_then(|op| op.access_kind()) {
+            let key = match kind {
+                StateAccessKind::Storage => {
+                    storage_frames.push((accesses.len(), frames[frames.len() - 1]));
+                    AccessKey {
+                        address: Address::ZERO,
+                        slot: Some(stack_3[0]),
+                    }
+                }
+                StateAccessKind::Account(slot) => AccessKey {
+                    address: word_to_address(stack_3[slot]),
+                    slot: None,
+                },
+                StateAccessKind::Created => {
+                    pending_creates.push((depth, accesses.len(), None));
+                    AccessKey::default()
+                }
+            };
+            accesses.push(StateAccess {
+                step: i,
+                key,
+                is_warm: false,
+            });
         }
 
+        previous = Some((opcode_byte, stack_3.clone()));
+        stack_states.push(stack_3);
+        step_frames.push((depth, frames[frames.len() - 1]));
+
+        // Record the memory accesses of MLOAD, MSTORE, MSTORE8 and KECCAK256
+        // in the active frame. A read returns the word in the memory snapshot,
+        // or else a load returns the top of the stack once its frame resumes
+        // and KECCAK256 the frame's earlier writes.
+        if let Some((index, load_depth)) = pending_load.take() {
+            if depth == load_depth {
+                memory_ops[index].value = stack.first().copied().unwrap_or_default();
+            }
+        }
+        let call_id = frames[frames.len() - 1];
+        for op in derive_memory_ops([(opcode_byte, stack.as_slice())]) {
+            let mut op = MemoryOp {
+                step: i,
+                call_id,
+                ..op
+            };
+            if !op.is_write {
+                match log.get("memory").and_then(|v| v.as_array()) {
+                    Some(words) => op.value = memory_word(words, op.offset),
+                    None if opcode_byte == OpCode::Keccak256 as u8 => {
+                        let frame: Vec<MemoryOp> = memory_ops
+                            .iter()
+                            .filter(|earlier| earlier.call_id == call_id)
+                            .copied()
+                            .collect();
+                        op.value = replay_memory_read(&frame, op.offset);
+                    }
+                    None => pending_load = Some((memory_ops.len(), depth)),
+                }
+            }
+            memory_ops.push(op);
+        }
+        full_stacks.push(stack);
+
         // Extract storage operations (SLOAD/SSTORE)
         if op_name == "SLOAD" || op_name == "SSTORE" {
             if let Some(storage) = log.get("storage").and_then(|v| v.as_object()) {
@@ -382,16 +1712,49 @@ fn parse_debug_trace(
         }
     }
 
+    for (access, frame) in storage_frames {
+        accesses[access].key.address = frame_addresses[frame].unwrap_or(Address::ZERO);
+    }
+
+    // A step's writes show on the next step of its frame, which for a call is
+    // the step it returns to; a frame that ends first leaves none
+    let mut resumes: Vec<Option<usize>> = vec![None; step_frames.len()];
+    let mut next_at_depth: Vec<Option<usize>> = Vec::new();
+    for (i, &(depth, _)) in step_frames.iter().enumerate().rev() {
+        next_at_depth.resize(depth + 1, None);
+        resumes[i] = next_at_depth[depth];
+        next_at_depth[depth] = Some(i);
+    }
+    let mut stack_ops: Vec<StackOp> = Vec::new();
+    let mut frame_items: HashMap<usize, HashMap<u64, U256>> = HashMap::new();
+    for (i, resume) in resumes.into_iter().enumerate() {
+        let call_id = step_frames[i].1;
+        let after = resume.map(|next| full_stacks[next].as_slice());
+        let items = frame_items.entry(call_id).or_default();
+        for op in step_stack_ops(i, opcodes[i], stack_sizes[i], &full_stacks[i], after, items) {
+            stack_ops.push(StackOp {
+                call_id,
+                rw_counter: stack_ops.len() as u64 + 1,
+                ..op
+            });
+        }
+    }
+
     Ok((
         opcodes,
         stack_states,
+        stack_sizes,
         pcs,
         gas_values,
+        refunds,
+        memory_sizes,
+        accesses,
         if memory_ops.is_empty() {
             None
         } else {
             Some(memory_ops)
         },
+        Some(stack_ops),
         if storage_ops.is_empty() {
             None
         } else {
@@ -401,155 +1764,41 @@ fn parse_debug_trace(
     ))
 }
 
+/// Word at `offset` of a memory snapshot of 32-byte hex words (zero past its end)
+fn memory_word(words: &[serde_json::Value], offset: u64) -> U256 {
+    let memory: Vec<u8> = words
+        .iter()
+        .flat_map(|word| {
+            let word = word.as_str().and_then(parse_word).unwrap_or_default();
+            word.to_be_bytes::<32>()
+        })
+        .collect();
+    let mut word = [0u8; 32];
+    for (i, byte) in word.iter_mut().enumerate() {
+        let address = offset.saturating_add(i as u64);
+        *byte = usize::try_from(address)
+            .ok()
+            .and_then(|address| memory.get(address))
+            .copied()
+            .unwrap_or(0);
+    }
+    U256::from_be_bytes(word)
+}
+
+/// Parse a hex-encoded 256-bit word ("0x" prefix optional)
+fn parse_word(s: &str) -> Option<U256> {
+    let digits = s.trim_start_matches("0x");
+    if digits.is_empty() {
+        return Some(U256::ZERO);
+    }
+    U256::from_str_radix(digits, 16).ok()
+}
+
 /// Convert EVM opcode name to byte value
+///
+/// Unknown names map to INVALID (0xfe).
 fn opcode_name_to_byte(name: &str) -> u8 {
-    match name {
-        "STOP" => 0x00,
-        "ADD" => 0x01,
-        "MUL" => 0x02,
-        "SUB" => 0x03,
-        "DIV" => 0x04,
-        "SDIV" => 0x05,
-        "MOD" => 0x06,
-        "SMOD" => 0x07,
-        "ADDMOD" => 0x08,
-        "MULMOD" => 0x09,
-        "EXP" => 0x0a,
-        "SIGNEXTEND" => 0x0b,
-        "LT" => 0x10,
-        "GT" => 0x11,
-        "SLT" => 0x12,
-        "SGT" => 0x13,
-        "EQ" => 0x14,
-        "ISZERO" => 0x15,
-        "AND" => 0x16,
-        "OR" => 0x17,
-        "XOR" => 0x18,
-        "NOT" => 0x19,
-        "BYTE" => 0x1a,
-        "SHL" => 0x1b,
-        "SHR" => 0x1c,
-        "SAR" => 0x1d,
-        "SHA3" | "KECCAK256" => 0x20,
-        "ADDRESS" => 0x30,
-        "BALANCE" => 0x31,
-        "ORIGIN" => 0x32,
-        "CALLER" => 0x33,
-        "CALLVALUE" => 0x34,
-        "CALLDATALOAD" => 0x35,
-        "CALLDATASIZE" => 0x36,
-        "CALLDATACOPY" => 0x37,
-        "CODESIZE" => 0x38,
-        "CODECOPY" => 0x39,
-        "GASPRICE" => 0x3a,
-        "EXTCODESIZE" => 0x3b,
-        "EXTCODECOPY" => 0x3c,
-        "RETURNDATASIZE" => 0x3d,
-        "RETURNDATACOPY" => 0x3e,
-        "EXTCODEHASH" => 0x3f,
-        "BLOCKHASH" => 0x40,
-        "COINBASE" => 0x41,
-        "TIMESTAMP" => 0x42,
-        "NUMBER" => 0x43,
-        "PREVRANDAO" | "DIFFICULTY" => 0x44,
-        "GASLIMIT" => 0x45,
-        "CHAINID" => 0x46,
-        "SELFBALANCE" => 0x47,
-        "BASEFEE" => 0x48,
-        "POP" => 0x50,
-        "MLOAD" => 0x51,
-        "MSTORE" => 0x52,
-        "MSTORE8" => 0x53,
-        "SLOAD" => 0x54,
-        "SSTORE" => 0x55,
-        "JUMP" => 0x56,
-        "JUMPI" => 0x57,
-        "PC" => 0x58,
-        "MSIZE" => 0x59,
-        "GAS" => 0x5a,
-        "JUMPDEST" => 0x5b,
-        "PUSH0" => 0x5f,
-        "PUSH1" => 0x60,
-        "PUSH2" => 0x61,
-        "PUSH3" => 0x62,
-        "PUSH4" => 0x63,
-        "PUSH5" => 0x64,
-        "PUSH6" => 0x65,
-        "PUSH7" => 0x66,
-        "PUSH8" => 0x67,
-        "PUSH9" => 0x68,
-        "PUSH10" => 0x69,
-        "PUSH11" => 0x6a,
-        "PUSH12" => 0x6b,
-        "PUSH13" => 0x6c,
-        "PUSH14" => 0x6d,
-        "PUSH15" => 0x6e,
-        "PUSH16" => 0x6f,
-        "PUSH17" => 0x70,
-        "PUSH18" => 0x71,
-        "PUSH19" => 0x72,
-        "PUSH20" => 0x73,
-        "PUSH21" => 0x74,
-        "PUSH22" => 0x75,
-        "PUSH23" => 0x76,
-        "PUSH24" => 0x77,
-        "PUSH25" => 0x78,
-        "PUSH26" => 0x79,
-        "PUSH27" => 0x7a,
-        "PUSH28" => 0x7b,
-        "PUSH29" => 0x7c,
-        "PUSH30" => 0x7d,
-        "PUSH31" => 0x7e,
-        "PUSH32" => 0x7f,
-        "DUP1" => 0x80,
-        "DUP2" => 0x81,
-        "DUP3" => 0x82,
-        "DUP4" => 0x83,
-        "DUP5" => 0x84,
-        "DUP6" => 0x85,
-        "DUP7" => 0x86,
-        "DUP8" => 0x87,
-        "DUP9" => 0x88,
-        "DUP10" => 0x89,
-        "DUP11" => 0x8a,
-        "DUP12" => 0x8b,
-        "DUP13" => 0x8c,
-        "DUP14" => 0x8d,
-        "DUP15" => 0x8e,
-        "DUP16" => 0x8f,
-        "SWAP1" => 0x90,
-        "SWAP2" => 0x91,
-        "SWAP3" => 0x92,
-        "SWAP4" => 0x93,
-        "SWAP5" => 0x94,
-        "SWAP6" => 0x95,
-        "SWAP7" => 0x96,
-        "SWAP8" => 0x97,
-        "SWAP9" => 0x98,
-        "SWAP10" => 0x99,
-        "SWAP11" => 0x9a,
-        "SWAP12" => 0x9b,
-        "SWAP13" => 0x9c,
-        "SWAP14" => 0x9d,
-        "SWAP15" => 0x9e,
-        "SWAP16" => 0x9f,
-        "LOG0" => 0xa0,
-        "LOG1" => 0xa1,
-        "LOG2" => 0xa2,
-        "LOG3" => 0xa3,
-        "LOG4" => 0xa4,
-        "CREATE" => 0xf0,
-        "CALL" => 0xf1,
-        "CALLCODE" => 0xf2,
-        "RETURN" => 0xf3,
-        "DELEGATECALL" => 0xf4,
-        "CREATE2" => 0xf5,
-        "STATICCALL" => 0xfa,
-        "REVERT" => 0xfd,
-        "INVALID" => 0xfe,
-        "SELFDESTRUCT" => 0xff,
-        _ => 0xfe, // Unknown opcode -> INVALID
-    }
+    OpCode::from_name(name).unwrap_or(OpCode::Invalid) as u8
 }
 
 /// Convert REVM execution data to circuit witness
@@ -578,20 +1827,31 @@ pub fn trace_to_witness(trace: &EvmTrace) -> Result<CircuitWitness> {
 
     let opcode_cells: Vec<u64> = trace.opcodes.iter().map(|&op| op as u64).collect();
 
-    let stack_cells: Vec<u64> = trace
+    let stack_cells: Vec<U256> = trace
         .stack_states
         .iter()
         .flat_map(|state| state.iter().take(3).copied())
         .collect();
 
     let gas_cells = trace.gas_values.clone();
+    let pc_cells = trace.pcs.clone();
+    let stack_depth_cells = trace.stack_depths();
+    let memory_size_cells = trace.memory_usage();
 
-    let public_inputs = compute_trace_commitment(trace);
+    let public_inputs = vec![compute_trace_commitment(trace)];
 
     Ok(CircuitWitness {
         opcode_cells,
         stack_cells,
         gas_cells,
+        pc_cells,
+        stack_depth_cells,
+        memory_size_cells,
+        prewarmed: trace.prewarmed_keys(),
+        accesses: trace.state_accesses(),
+        sstores: trace.sstore_records(),
+        memory_ops: trace.memory_records(),
+        stack_ops: trace.stack_records(),
         public_inputs,
     })
 }
@@ -611,7 +1871,7 @@ pub fn trace_to_witness(trace: &EvmTrace) -> Result<CircuitWitness> {
 /// ```json
 /// {
 ///   "opcodes": [96, 96, 1],
-///   "stack_states": [[1, 0, 0], [2, 1, 0], [3, 0, 0]],
+///   "stack_states": [[0, 0, 0], [1, 0, 0], [2, 1, 0]],
 ///   "pcs": [0, 2, 4],
 ///   "gas_values": [1000, 997, 994]
 /// }
@@ -648,6 +1908,122 @@ pub async fn fetch_trace_from_network(tx_hash: &str, rpc_url: &str) -> Result<Ev
     Ok(trace)
 }
 
+/// `eth_getProof` response together with the state root it opens against
+///
+/// Matches the saved-fixture format `{"stateRoot": ..., "proof": {...}}` so
+/// proofs fetched once can be replayed without a node.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StateProof {
+    /// State root of the block the proof was taken at
+    pub state_root: B256,
+    /// Account proof plus one storage proof per requested slot
+    pub proof: EIP1186AccountProofResponse,
+}
+
+impl StateProof {
+    /// Hashed trie key of the account
+    pub fn account_key(&self) -> B256 {
+        keccak256(self.proof.address)
+    }
+
+    /// Check the account proof against the state root and every storage
+    /// proof against the account's storage root
+    pub fn verify(&self) -> Result<()> {
+        let proof = &self.proof;
+        let path = verify_proof(self.state_root, self.account_key(), &proof.account_proof)?;
+        let account = path
+            .value
+            .map(|value| TrieAccount::decode(&value))
+            .transpose()?
+            .ok_or_else(|| {
+                ProverError::VerificationError("Account is absent from the state".to_string())
+            })?;
+        let claimed = TrieAccount {
+            nonce: proof.nonce,
+            balance: proof.balance,
+            storage_root: proof.storage_hash,
+            code_hash: proof.code_hash,
+        };
+        if account != claimed {
+            return Err(ProverError::VerificationError(
+                "Account fields do not match the account proof".to_string(),
+            ));
+        }
+
+        for storage in &proof.storage_proof {
+            let slot = storage.key.as_b256();
+            let path = verify_proof(proof.storage_hash, keccak256(slot), &storage.proof)?;
+            let value = path
+                .value
+                .map(|value| decode_storage_value(&value))
+                .transpose()?
+                .unwrap_or_default();
+            if value != storage.value {
+                return Err(ProverError::VerificationError(format!(
+                    "Storage proof for slot {} opens to {}, not {}",
+                    slot, value, storage.value
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parse a saved state proof
+pub fn parse_state_proof(json_str: &str) -> Result<StateProof> {
+    serde_json::from_str(json_str).map_err(|e| ProverError::ParseError(e.to_string()))
+}
+
+/// Load a saved state proof from a JSON file
+pub fn load_state_proof(path: impl AsRef<std::path::Path>) -> Result<StateProof> {
+    parse_state_proof(&std::fs::read_to_string(path)?)
+}
+
+/// Fetch an account's state proof via `eth_getProof`
+///
+/// # Arguments
+///
+/// * `rpc_url` - Ethereum RPC endpoint URL
+/// * `address` - Account to open
+/// * `slots` - Storage slots to open in the account's storage trie
+/// * `block_number` - Block whose post-state the proof opens
+///
+/// # Returns
+///
+/// The proof and the block's state root, checked against each other
+pub async fn fetch_state_proof(
+    rpc_url: &str,
+    address: Address,
+    slots: &[U256],
+    block_number: u64,
+) -> Result<StateProof> {
+    let provider = ProviderBuilder::new()
+        .connect(rpc_url)
+        .await
+        .map_err(|e| ProverError::RpcConnectionError(format!("Failed to connect to RPC: {}", e)))?;
+
+    let block = provider
+        .get_block_by_number(block_number.into())
+        .await
+        .map_err(|e| ProverError::NetworkError(format!("Failed to fetch block: {}", e)))?
+        .ok_or_else(|| ProverError::NetworkError(format!("Block {} not found", block_number)))?;
+
+    let keys = slots.iter().map(|slot| B256::from(*slot)).collect();
+    let proof = provider
+        .get_proof(address, keys)
+        .number(block_number)
+        .await
+        .map_err(|e| ProverError::NetworkError(format!("Failed to fetch proof: {}", e)))?;
+
+    let state_proof = StateProof {
+        state_root: block.header.state_root,
+        proof,
+    };
+    state_proof.verify()?;
+    Ok(state_proof)
+}
+
 /// Extract opcodes from bytecode (basic parser)
 ///
 /// This function parses EVM bytecode and extracts individual opcodes,
@@ -693,8 +2069,8 @@ pub fn parse_evm_data(trace: &EvmTrace) -> Result<CircuitWitness> {
     // Flatten opcodes to u64 cells
     let opcode_cells: Vec<u64> = trace.opcodes.iter().map(|&op| op as u64).collect();
 
-    // Flatten stack states (take top 3 values per step)
-    let stack_cells: Vec<u64> = trace
+    // Flatten stack states (take top 3 words per step)
+    let stack_cells: Vec<U256> = trace
         .stack_states
         .iter()
         .flat_map(|state| state.iter().take(3).copied())
@@ -703,58 +2079,207 @@ pub fn parse_evm_data(trace: &EvmTrace) -> Result<CircuitWitness> {
     // Gas consumption cells
     let gas_cells = trace.gas_values.clone();
 
+    // Program counter, stack depth and memory size cells
+    let pc_cells = trace.pcs.clone();
+    let stack_depth_cells = trace.stack_depths();
+    let memory_size_cells = trace.memory_usage();
+
     // Compute public inputs (hash of trace for commitment)
-    let public_inputs = compute_trace_commitment(trace);
+    let public_inputs = vec![compute_trace_commitment(trace)];
 
     Ok(CircuitWitness {
         opcode_cells,
         stack_cells,
         gas_cells,
+        pc_cells,
+        stack_depth_cells,
+        memory_size_cells,
+        prewarmed: trace.prewarmed_keys(),
+        accesses: trace.state_accesses(),
+        sstores: trace.sstore_records(),
+        memory_ops: trace.memory_records(),
+        stack_ops: trace.stack_records(),
         public_inputs,
     })
 }
 
-/// Compute trace commitment (hash for public input)
-fn compute_trace_commitment(trace: &EvmTrace) -> Vec<u64> {
-    use sha2::{Digest, Sha256};
+/// Native Poseidon sponge hashing an access key
+type AccessKeyHasher = poseidon::Hash<
+    Fp,
+    P128Pow5T3,
+    ConstantLength<ACCESS_KEY_INPUTS>,
+    POSEIDON_WIDTH,
+    POSEIDON_RATE,
+>;
+
+/// Native Poseidon sponge absorbing one key of the access commitment chain
+type AccessLinkHasher =
+    poseidon::Hash<Fp, P128Pow5T3, ConstantLength<2>, POSEIDON_WIDTH, POSEIDON_RATE>;
+
+/// Hash an access key as `AccessChip` does
+pub fn hash_access_key(key: &AccessKey) -> Fp {
+    AccessKeyHasher::init().hash(access_key_fields(key))
+}
 
-    let mut hasher = Sha256::new();
+/// Compute the access commitment that seeds the trace commitment
+///
+/// A Poseidon chain over the hash of every pre-warmed key, then every accessed
+/// key: `a_0 = 0` and `a_{i+1} = Poseidon(a_i, key_hash_i)`. Zero when there
+/// are no keys.
+pub fn compute_access_commitment(prewarmed: &[AccessKey], accesses: &[StateAccess]) -> Fp {
+    prewarmed
+        .iter()
+        .chain(accesses.iter().map(|access| &access.key))
+        .fold(Fp::zero(), |prev, key| {
+            AccessLinkHasher::init().hash([prev, hash_access_key(key)])
+        })
+}
 
-    // Hash opcodes
-    for &op in &trace.opcodes {
-        hasher.update([op]);
-    }
+/// Native Poseidon sponge hashing the slot values of an SSTORE
+type SStoreValuesHasher = poseidon::Hash<
+    Fp,
+    P128Pow5T3,
+    ConstantLength<SSTORE_VALUE_INPUTS>,
+    POSEIDON_WIDTH,
+    POSEIDON_RATE,
+>;
 
-    // Hash gas values
-    for &gas in &trace.gas_values {
-        hasher.update(gas.to_le_bytes());
-    }
+/// Compute the state commitment that seeds the trace commitment
+///
+/// The access commitment, continued by the hash of the original and current
+/// value of every SSTORE's slot: `s_{i+1} = Poseidon(s_i, values_hash_i)`.
+/// Equal to the access commitment without SSTOREs.
+pub fn compute_state_commitment(
+    prewarmed: &[AccessKey],
+    accesses: &[StateAccess],
+    sstores: &[SStoreRecord],
+) -> Fp {
+    sstores.iter().fold(
+        compute_access_commitment(prewarmed, accesses),
+        |prev, record| {
+            let values = SStoreValuesHasher::init().hash(sstore_value_fields(record));
+            AccessLinkHasher::init().hash([prev, values])
+        },
+    )
+}
 
-    let hash = hasher.finalize();
+/// Native Poseidon sponge absorbing one step of the trace commitment chain
+type StepHasher =
+    poseidon::Hash<Fp, P128Pow5T3, ConstantLength<STEP_HASH_INPUTS>, POSEIDON_WIDTH, POSEIDON_RATE>;
 
-    // Take first 4 u64s from hash (256 bits / 64 bits = 4)
-    hash.chunks(8)
-        .take(4)
-        .map(|chunk| {
-            let mut bytes = [0u8; 8];
-            bytes.copy_from_slice(chunk);
-            u64::from_le_bytes(bytes)
+/// Hash one link of the trace commitment chain
+///
+/// Native counterpart of `HashChip::hash_steps`: absorbs the previous link followed by
+/// the step's opcode, program counter, gas, stack depth, memory size and top three
+/// stack values.
+///
+/// # Arguments
+///
+/// * `prev` - Previous link (the state commitment for the first step)
+/// * `opcode` - Opcode byte
+/// * `pc` - Program counter
+/// * `gas` - Gas remaining before the step
+/// * `stack_depth` - Stack depth before the step
+/// * `memory_size` - Memory size in bytes before the step
+/// * `stack` - Top three stack words, absorbed as `(lo, hi)` limbs
+///
+/// # Returns
+///
+/// The next link of the chain
+pub fn hash_trace_step(
+    prev: Fp,
+    opcode: u8,
+    pc: u64,
+    gas: u64,
+    stack_depth: u64,
+    memory_size: u64,
+    stack: [U256; 3],
+) -> Fp {
+    let [s0_lo, s0_hi] = word_limbs::<Fp>(stack[0]);
+    let [s1_lo, s1_hi] = word_limbs::<Fp>(stack[1]);
+    let [s2_lo, s2_hi] = word_limbs::<Fp>(stack[2]);
+
+    StepHasher::init().hash([
+        prev,
+        Fp::from(opcode as u64),
+        Fp::from(pc),
+        Fp::from(gas),
+        Fp::from(stack_depth),
+        Fp::from(memory_size),
+        s0_lo,
+        s0_hi,
+        s1_lo,
+        s1_hi,
+        s2_lo,
+        s2_hi,
+    ])
+}
+
+/// Compute the trace commitment (public input) from a trace
+///
+/// Recomputes the same Poseidon hash chain the circuit constrains, so a verifier
+/// holding the trace can check the public input of a proof. The chain is seeded
+/// with the state commitment of the trace.
+///
+/// # Arguments
+///
+/// * `trace` - EVM execution trace
+///
+/// # Returns
+///
+/// Final link of the hash chain over every step
+pub fn compute_trace_commitment(trace: &EvmTrace) -> Fp {
+    let stack_depths = trace.stack_depths();
+    let memory_sizes = trace.memory_usage();
+    let seed = compute_state_commitment(
+        &trace.prewarmed_keys(),
+        &trace.state_accesses(),
+        &trace.sstore_records(),
+    );
+    trace
+        .opcodes
+        .iter()
+        .enumerate()
+        .fold(seed, |prev, (i, &opcode)| {
+            let state = trace.stack_states.get(i);
+            let stack_value = |j: usize| state.and_then(|s| s.get(j)).copied().unwrap_or_default();
+            hash_trace_step(
+                prev,
+                opcode,
+                trace.pcs.get(i).copied().unwrap_or(i as u64),
+                trace.gas_values.get(i).copied().unwrap_or(0),
+                stack_depths[i],
+                memory_sizes[i],
+                [stack_value(0), stack_value(1), stack_value(2)],
+            )
         })
-        .collect()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// Helper to lift small stack literals into words
+    fn words(values: &[u64]) -> Vec<U256> {
+        values.iter().map(|&v| U256::from(v)).collect()
+    }
+
     /// Helper to create a minimal valid trace for testing
     fn create_test_trace() -> EvmTrace {
         EvmTrace {
             opcodes: vec![0x60, 0x60, 0x01], // PUSH1, PUSH1, ADD
-            stack_states: vec![vec![1, 0, 0], vec![2, 1, 0], vec![3, 0, 0]],
+            stack_states: vec![words(&[0, 0, 0]), words(&[1, 0, 0]), words(&[2, 1, 0])],
             pcs: vec![0, 2, 4],
             gas_values: vec![1000, 997, 994],
+            stack_sizes: None,
+            memory_sizes: None,
+            accesses: None,
+            prewarmed: None,
+            sstores: None,
+            refunds: None,
+            intrinsic_gas: None,
             memory_ops: None,
+            stack_ops: None,
             storage_ops: None,
             tx_hash: None,
             block_number: None,
@@ -767,7 +2292,7 @@ mod tests {
         let trace = create_test_trace();
         assert_eq!(trace.opcodes.len(), 3);
         assert_eq!(trace.opcodes[2], 0x01); // ADD
-        assert_eq!(trace.stack_states[2][0], 3); // Result: 1 + 2 = 3
+        assert_eq!(trace.stack_states[2][..2], words(&[2, 1])); // Operands: 2 and 1
         assert!(trace.bytecode.is_some());
     }
 
@@ -786,10 +2311,27 @@ mod tests {
         assert!(matches!(result.unwrap_err(), ProverError::InvalidInput(_)));
     }
 
+    #[test]
+    fn test_trace_validation_for_hardfork() {
+        let mut trace = create_test_trace();
+        assert!(trace.validate_for(Hardfork::Petersburg).is_ok());
+
+        // PUSH0 only exists from Shanghai on
+        trace.opcodes[0] = 0x5f;
+        trace.pcs = vec![0, 1, 3];
+        trace.gas_values = vec![1000, 998, 995];
+        assert!(trace.validate_for(Hardfork::Shanghai).is_ok());
+        assert!(trace.validate_for(Hardfork::London).is_err());
+
+        // A static opcode must be charged exactly its cost
+        trace.gas_values[1] = 990;
+        assert!(trace.validate_for(Hardfork::Cancun).is_err());
+    }
+
     #[test]
     fn test_trace_validation_mismatch() {
         let mut trace = create_test_trace();
-        trace.stack_states.push(vec![0, 0, 0]);
+        trace.stack_states.push(words(&[0, 0, 0]));
         let result = trace.validate();
         assert!(result.is_err());
     }
@@ -801,7 +2343,7 @@ mod tests {
 
         assert_eq!(witness.opcode_cells.len(), 3);
         assert_eq!(witness.gas_cells.len(), 3);
-        assert_eq!(witness.public_inputs.len(), 4); // 4 u64s from SHA256
+        assert_eq!(witness.public_inputs.len(), 1); // Poseidon trace commitment
 
         // Verify opcodes are correctly converted
         assert_eq!(witness.opcode_cells[0], 0x60);
@@ -813,7 +2355,7 @@ mod tests {
         let trace = create_test_trace();
         let commitment = compute_trace_commitment(&trace);
 
-        assert_eq!(commitment.len(), 4);
+        assert_ne!(commitment, Fp::zero());
         // Commitment should be deterministic
         let commitment2 = compute_trace_commitment(&trace);
         assert_eq!(commitment, commitment2);
@@ -831,6 +2373,42 @@ mod tests {
         assert_ne!(commitment1, commitment2);
     }
 
+    #[test]
+    fn test_commitment_binds_stack_and_pc() {
+        let trace1 = create_test_trace();
+
+        let mut trace2 = create_test_trace();
+        trace2.stack_states[2][0] = U256::from(4u64);
+        assert_ne!(
+            compute_trace_commitment(&trace1),
+            compute_trace_commitment(&trace2)
+        );
+
+        let mut trace3 = create_test_trace();
+        trace3.pcs[1] = 1;
+        assert_ne!(
+            compute_trace_commitment(&trace1),
+            compute_trace_commitment(&trace3)
+        );
+    }
+
+    #[test]
+    fn test_commitment_binds_stack_depth() {
+        let trace1 = create_test_trace();
+
+        // Same top of stack, but a deeper stack underneath
+        let mut trace2 = create_test_trace();
+        trace2.stack_sizes = Some(vec![4, 5, 6]);
+        assert_eq!(trace2.stack_depths(), vec![4, 5, 6]);
+        assert_ne!(
+            compute_trace_commitment(&trace1),
+            compute_trace_commitment(&trace2)
+        );
+
+        trace2.stack_sizes = Some(vec![4, 5]);
+        assert!(trace2.validate().is_err());
+    }
+
     #[test]
     fn test_parse_trace_json_valid() {
         let json = r#"{
@@ -850,7 +2428,504 @@ mod tests {
 
         let trace = result.unwrap();
         assert_eq!(trace.opcodes.len(), 3);
-        assert_eq!(trace.stack_states[2][0], 3);
+        assert_eq!(trace.stack_states[2][0], U256::from(3u64));
+    }
+
+    #[test]
+    fn test_parse_debug_trace_full_width_stack() {
+        let max = format!("0x{}", "f".repeat(64));
+        let response = serde_json::json!({
+            "structLogs": [
+                { "op": "PUSH1", "pc": 0, "gas": 1000, "stack": [] },
+                { "op": "PUSH32", "pc": 2, "gas": 997, "stack": ["0x1"] },
+                { "op": "ADD", "pc": 35, "gas": 994, "stack": ["0x1", max] },
+            ]
+        });
+
+        let (_, stack_states, stack_sizes, ..) = parse_debug_trace(&response, 9, None).unwrap();
+        assert_eq!(stack_sizes, vec![0, 1, 2]);
+        assert_eq!(stack_states[0], vec![U256::ZERO; 3]);
+        assert_eq!(
+            stack_states[2],
+            vec![U256::MAX, U256::from(1u64), U256::ZERO]
+        );
+
+        let bad = serde_json::json!({
+            "structLogs": [{ "op": "ADD", "pc": 0, "gas": 3, "stack": ["0xnothex"] }]
+        });
+        assert!(parse_debug_trace(&bad, 3, None).is_err());
+    }
+
+    #[test]
+    fn test_parse_debug_trace_frame_addresses() {
+        let to = Address::with_last_byte(0xaa);
+        // SLOAD 1 in `to`, CALL 0xbb, SLOAD 2 in 0xbb, then back in `to`
+        let response = serde_json::json!({
+            "structLogs": [
+                { "op": "SLOAD", "pc": 0, "gas": 10000, "depth": 1, "stack": ["0x1"] },
+                { "op": "CALL", "pc": 1, "gas": 7900, "depth": 1,
+                  "stack": ["0x0", "0x0", "0x0", "0x0", "0x0", "0xbb", "0x1000"] },
+                { "op": "SLOAD", "pc": 0, "gas": 4000, "depth": 2, "stack": ["0x2"] },
+                { "op": "POP", "pc": 2, "gas": 2000, "depth": 1, "stack": ["0x1"] },
+            ]
+        });
+
+        let (.., accesses, _, _, _, _) = parse_debug_trace(&response, 10000, Some(to)).unwrap();
+        let callee = Address::with_last_byte(0xbb);
+        let keys: Vec<_> = accesses.iter().map(|a| (a.step, a.key)).collect();
+        assert_eq!(
+            keys,
+            vec![
+                (
+                    0,
+                    AccessKey {
+                        address: to,
+                        slot: Some(U256::from(1u64))
+                    }
+                ),
+                (
+                    1,
+                    AccessKey {
+                        address: callee,
+                        slot: None
+                    }
+                ),
+                (
+                    2,
+                    AccessKey {
+                        address: callee,
+                        slot: Some(U256::from(2u64))
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_state_accesses_warmth() {
+        // PUSH1 5, SLOAD, PUSH1 5, SLOAD, POP
+        let mut trace = EvmTrace {
+            opcodes: vec![0x60, 0x54, 0x60, 0x54, 0x50],
+            stack_states: vec![
+                words(&[0, 0, 0]),
+                words(&[5, 0, 0]),
+                words(&[7, 0, 0]),
+                words(&[5, 7, 0]),
+                words(&[7, 7, 0]),
+            ],
+            pcs: vec![0, 2, 3, 5, 6],
+            gas_values: vec![10_000, 9_997, 7_897, 7_894, 7_794],
+            ..create_test_trace()
+        };
+        trace.bytecode = None;
+
+        let accesses = trace.state_accesses();
+        assert_eq!(accesses.len(), 2);
+        assert_eq!((accesses[0].step, accesses[0].is_warm), (1, false));
+        assert_eq!((accesses[1].step, accesses[1].is_warm), (3, true));
+        assert!(trace.validate_for(Hardfork::Cancun).is_ok());
+
+        // The first read is cold, so charging it the warm cost is rejected
+        trace.gas_values[2] = 9_897;
+        assert!(trace.validate_for(Hardfork::Berlin).is_err());
+
+        // A pre-warmed slot makes both reads warm
+        trace.prewarmed = Some(vec![accesses[0].key]);
+        trace.gas_values = vec![10_000, 9_997, 9_897, 9_894, 9_794];
+        assert!(trace.state_accesses().iter().all(|access| access.is_warm));
+        assert!(trace.validate_for(Hardfork::Berlin).is_ok());
+
+        // Recorded warm flags must follow from the access set
+        let mut recorded = trace.state_accesses();
+        recorded[0].is_warm = false;
+        trace.accesses = Some(recorded);
+        assert!(trace.validate().is_err());
+
+        // Every accessing step needs a record
+        trace.accesses = Some(Vec::new());
+        assert!(trace.validate().is_err());
+    }
+
+    /// PUSH1 1, PUSH1 0, SSTORE, PUSH1 0, PUSH1 0, SSTORE, STOP: sets slot 0
+    /// and clears it again
+    fn create_sstore_trace() -> EvmTrace {
+        EvmTrace {
+            opcodes: vec![0x60, 0x60, 0x55, 0x60, 0x60, 0x55, 0x00],
+            stack_states: vec![
+                words(&[0, 0, 0]),
+                words(&[1, 0, 0]),
+                words(&[0, 1, 0]),
+                words(&[0, 0, 0]),
+                words(&[0, 0, 0]),
+                words(&[0, 0, 0]),
+                words(&[0, 0, 0]),
+            ],
+            pcs: vec![0, 2, 4, 5, 7, 9, 10],
+            // Cold set, then a warm write back to the original value
+            gas_values: vec![30_000, 29_997, 29_994, 7_894, 7_891, 7_888, 7_788],
+            bytecode: None,
+            ..create_test_trace()
+        }
+    }
+
+    #[test]
+    fn test_sstore_metering() {
+        let mut trace = create_sstore_trace();
+        let records = trace.sstore_records();
+        assert_eq!(
+            records,
+            vec![
+                SStoreRecord {
+                    step: 2,
+                    original: U256::ZERO,
+                    current: U256::ZERO,
+                },
+                SStoreRecord {
+                    step: 5,
+                    original: U256::ZERO,
+                    current: U256::from(1u64),
+                },
+            ]
+        );
+        assert!(trace.validate_for(Hardfork::Cancun).is_ok());
+
+        // Restoring the zero original refunds the set, capped at a fifth of the gas
+        let refunds = trace.refund_counters(Hardfork::Cancun);
+        assert_eq!(refunds, vec![0, 0, 0, 0, 0, 0, 19_900]);
+        assert_eq!(trace.gas_used(Hardfork::Cancun), 22_212 - 22_212 / 5);
+        trace.intrinsic_gas = Some(21_000);
+        assert_eq!(trace.gas_used(Hardfork::Cancun), 43_212 - 43_212 / 5);
+
+        // Recorded refunds must move by what each SSTORE refunds
+        trace.refunds = Some(refunds);
+        assert!(trace.validate_for(Hardfork::Cancun).is_ok());
+        trace.refunds.as_mut().unwrap()[6] = 4_800;
+        assert!(trace.validate_for(Hardfork::Cancun).is_err());
+        trace.refunds = None;
+
+        // A non-zero original makes the first write a reset
+        trace.sstores = Some(
+            records
+                .iter()
+                .map(|record| SStoreRecord {
+                    original: U256::from(1u64),
+                    ..*record
+                })
+                .collect(),
+        );
+        assert!(trace.validate_for(Hardfork::Cancun).is_err());
+
+        // Records must match the SSTORE steps
+        trace.sstores = Some(records[..1].to_vec());
+        assert!(trace.validate().is_err());
+    }
+
+    #[test]
+    fn test_memory_sizes() {
+        // PUSH1 0x2a, PUSH1 0x40, MSTORE, MSIZE, STOP
+        let mut trace = EvmTrace {
+            opcodes: vec![0x60, 0x60, 0x52, 0x59, 0x00],
+            stack_states: vec![
+                words(&[0, 0, 0]),
+                words(&[0x2a, 0, 0]),
+                words(&[0x40, 0x2a, 0]),
+                words(&[0, 0, 0]),
+                words(&[96, 0, 0]),
+            ],
+            pcs: vec![0, 2, 4, 5, 6],
+            gas_values: vec![1000, 997, 994, 982, 980],
+            bytecode: None,
+            ..create_test_trace()
+        };
+        assert_eq!(trace.memory_usage(), vec![0, 0, 0, 96, 96]);
+        assert!(trace.validate_for(Hardfork::Cancun).is_ok());
+
+        // The MSTORE pays for the two words it adds past the stored one
+        trace.gas_values[3] = 991;
+        trace.gas_values[4] = 989;
+        assert!(trace.validate_for(Hardfork::Cancun).is_err());
+        trace.gas_values[3] = 982;
+        trace.gas_values[4] = 980;
+
+        // Recorded sizes must follow the memory operands
+        trace.memory_sizes = Some(vec![0, 0, 0, 128, 128]);
+        assert!(trace.validate_for(Hardfork::Cancun).is_err());
+        trace.memory_sizes = Some(vec![0, 0, 0, 96]);
+        assert!(trace.validate().is_err());
+    }
+
+    #[test]
+    fn test_parse_debug_trace_memory_sizes() {
+        // Reported sizes win; the callee starts with empty memory of its own
+        let response = serde_json::json!({
+            "structLogs": [
+                { "op": "MSTORE", "pc": 0, "gas": 10000, "depth": 1,
+                  "stack": ["0x1", "0x20"], "memSize": 0 },
+                { "op": "CALL", "pc": 1, "gas": 9990, "depth": 1,
+                  "stack": ["0x0", "0x0", "0x0", "0x0", "0x0", "0xbb", "0x1000"] },
+                { "op": "MSTORE8", "pc": 0, "gas": 4000, "depth": 2, "stack": ["0x1", "0x0"] },
+                { "op": "MSIZE", "pc": 1, "gas": 3997, "depth": 2, "stack": [] },
+                { "op": "POP", "pc": 2, "gas": 2000, "depth": 1, "stack": ["0x1"],
+                  "memory": ["0x0", "0x0"] },
+            ]
+        });
+
+        let (.., memory_sizes, _, _, _, _, _) = parse_debug_trace(&response, 10000, None).unwrap();
+        assert_eq!(memory_sizes, vec![0, 64, 0, 32, 64]);
+    }
+
+    #[test]
+    fn test_memory_ops() {
+        // PUSH1 0x2a, PUSH1 0x40, MSTORE, PUSH1 0x41, MLOAD, STOP
+        let mut trace = EvmTrace {
+            opcodes: vec![0x60, 0x60, 0x52, 0x60, 0x51, 0x00],
+            stack_states: vec![
+                words(&[0, 0, 0]),
+                words(&[0x2a, 0, 0]),
+                words(&[0x40, 0x2a, 0]),
+                words(&[0, 0, 0]),
+                words(&[0x41, 0, 0]),
+                words(&[0x2a00, 0, 0]),
+            ],
+            pcs: vec![0, 2, 4, 5, 7, 8],
+            gas_values: vec![1000, 997, 994, 982, 979, 973],
+            bytecode: None,
+            ..create_test_trace()
+        };
+        let ops = trace.memory_records();
+        assert_eq!(ops.len(), 2);
+        assert_eq!(
+            (ops[1].step, ops[1].offset, ops[1].is_write),
+            (4, 0x41, false)
+        );
+        assert_eq!(ops[1].value, U256::from(0x2a00u64));
+        assert!(trace.validate_for(Hardfork::Cancun).is_ok());
+
+        // The load must return the stored bytes
+        trace.stack_states[5] = words(&[0x2a, 0, 0]);
+        assert!(trace.validate_for(Hardfork::Cancun).is_err());
+
+        // Bytes written before the trace may be read below its initial size
+        let read = MemoryOp {
+            length: 32,
+            value: U256::from(7u64),
+            ..MemoryOp::default()
+        };
+        assert!(check_memory_reads(&[read], 0).is_err());
+        assert!(check_memory_reads(&[read, read], 32).is_ok());
+        let zero = MemoryOp {
+            value: U256::ZERO,
+            ..read
+        };
+        assert!(check_memory_reads(&[read, zero], 32).is_err());
+
+        // MSTORE8 writes a single byte, in the frame of the access
+        let byte = derive_memory_ops([(0x53, words(&[0x3f, 0x1234, 0]).as_slice())]);
+        assert_eq!(byte[0].bytes(), vec![0x34]);
+        let other_frame = MemoryOp {
+            call_id: 1,
+            offset: 0x20,
+            ..read
+        };
+        assert!(check_memory_reads(&[byte[0], other_frame], 0).is_err());
+    }
+
+    #[test]
+    fn test_stack_ops() {
+        // PUSH1 1, PUSH1 2, ADD: the last step has no successor to push to
+        let mut trace = create_test_trace();
+        let ops = trace.stack_records();
+        let summary: Vec<_> = ops
+            .iter()
+            .map(|op| (op.step, op.pointer, op.is_write, op.value))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (0, 0, true, U256::from(1u64)),
+                (1, 1, true, U256::from(2u64)),
+                (2, 1, false, U256::from(2u64)),
+                (2, 0, false, U256::from(1u64)),
+                (2, 0, true, U256::ZERO),
+            ]
+        );
+        assert!(trace.validate_for(Hardfork::Cancun).is_ok());
+
+        // Records must match the stack the trace shows
+        let mut wrong = ops.clone();
+        wrong[3].value = U256::from(5u64);
+        trace.stack_ops = Some(wrong);
+        assert!(trace.validate().is_err());
+        trace.stack_ops = Some(ops[1..].to_vec());
+        assert!(trace.validate().is_err());
+
+        // ADD must pop what the PUSH1s pushed
+        trace.stack_ops = None;
+        trace.stack_states[2] = words(&[2, 7, 0]);
+        assert!(trace.validate_for(Hardfork::Cancun).is_err());
+
+        // Items pushed before the trace may be read below its initial depth
+        let read = StackOp {
+            pointer: 3,
+            value: U256::from(7u64),
+            ..StackOp::default()
+        };
+        assert!(check_stack_reads(&[read], 3).is_err());
+        assert!(check_stack_reads(&[read, read], 4).is_ok());
+        let zero = StackOp {
+            value: U256::ZERO,
+            ..read
+        };
+        assert!(check_stack_reads(&[read, zero], 4).is_err());
+    }
+
+    #[test]
+    fn test_parse_debug_trace_stack_ops() {
+        // SWAP3 reaches the fourth item, past the top three words it shows
+        let response = serde_json::json!({
+            "structLogs": [
+                { "op": "SWAP3", "pc": 0, "gas": 1000, "depth": 1,
+                  "stack": ["0x1", "0x2", "0x3", "0x4"] },
+                { "op": "STOP", "pc": 1, "gas": 997, "depth": 1,
+                  "stack": ["0x4", "0x2", "0x3", "0x1"] },
+            ]
+        });
+
+        let (.., stack_ops, _, _) = parse_debug_trace(&response, 1000, None).unwrap();
+        let ops = stack_ops.unwrap();
+        let summary: Vec<_> = ops
+            .iter()
+            .map(|op| (op.rw_counter, op.pointer, op.is_write, op.value))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (1, 3, false, U256::from(4u64)),
+                (2, 0, false, U256::from(1u64)),
+                (3, 3, true, U256::from(1u64)),
+                (4, 0, true, U256::from(4u64)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sha3_memory_ops() {
+        // PUSH1 0x2a, PUSH1 0, MSTORE, PUSH1 0x20, PUSH1 0, KECCAK256, STOP
+        let digest = U256::from_be_bytes(keccak256(U256::from(0x2a).to_be_bytes::<32>()).0);
+        let mut trace = EvmTrace {
+            opcodes: vec![0x60, 0x60, 0x52, 0x60, 0x60, 0x20, 0x00],
+            stack_states: vec![
+                words(&[0, 0, 0]),
+                words(&[0x2a, 0, 0]),
+                words(&[0, 0x2a, 0]),
+                words(&[0, 0, 0]),
+                words(&[0x20, 0, 0]),
+                words(&[0, 0x20, 0]),
+                vec![digest],
+            ],
+            pcs: vec![0, 2, 4, 5, 7, 9, 10],
+            gas_values: vec![1000, 997, 994, 988, 985, 982, 946],
+            bytecode: None,
+            ..create_test_trace()
+        };
+
+        // KECCAK256 reads its whole window, word by word
+        let ops = trace.memory_records();
+        let words_read = SHA3_MAX_BYTES / 32;
+        assert_eq!(ops.len(), 1 + words_read);
+        assert!(ops[1..].iter().all(|op| op.step == 5 && !op.is_write));
+        assert_eq!(ops[1].value, U256::from(0x2a));
+        assert_eq!(ops[words_read].offset, 32 * (words_read as u64 - 1));
+        trace.memory_ops = Some(ops.clone());
+        assert!(trace.validate_for(Hardfork::Cancun).is_ok());
+
+        // The next step must hold the digest of the hashed bytes
+        trace.stack_states[6] = vec![digest + U256::from(1)];
+        assert!(trace.validate_for(Hardfork::Cancun).is_err());
+
+        // Longer messages than the window are rejected
+        trace.stack_states[6] = vec![digest];
+        trace.stack_states[5][1] = U256::from(SHA3_MAX_BYTES + 1);
+        trace.memory_ops = None;
+        assert!(trace.validate_for(Hardfork::Cancun).is_err());
+    }
+
+    #[test]
+    fn test_parse_debug_trace_memory_ops() {
+        // Loads read the snapshot, or the top of stack after the step
+        let response = serde_json::json!({
+            "structLogs": [
+                { "op": "MSTORE", "pc": 0, "gas": 1000, "depth": 1, "stack": ["0x2a", "0x40"] },
+                { "op": "MLOAD", "pc": 1, "gas": 991, "depth": 1, "stack": ["0x40"],
+                  "memory": ["0x0", "0x0", "0x2a"] },
+                { "op": "MLOAD", "pc": 2, "gas": 988, "depth": 1, "stack": ["0x2a", "0x5f"] },
+                { "op": "STOP", "pc": 3, "gas": 985, "depth": 1, "stack": ["0x2a", "0x2a00"] },
+            ]
+        });
+
+        let (.., memory_ops, _, _, _) = parse_debug_trace(&response, 1000, None).unwrap();
+        let ops = memory_ops.unwrap();
+        let summary: Vec<_> = ops
+            .iter()
+            .map(|op| (op.step, op.offset, op.is_write, op.value))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (0, 0x40, true, U256::from(0x2au64)),
+                (1, 0x40, false, U256::from(0x2au64)),
+                (2, 0x5f, false, U256::from(0x2a00u64)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_derive_sstore_records_from_originals() {
+        let trace = create_sstore_trace();
+        let accesses = trace.state_accesses();
+        let originals = HashMap::from([(accesses[0].key, U256::from(5u64))]);
+        let records = derive_sstore_records(
+            trace
+                .opcodes
+                .iter()
+                .zip(&trace.stack_states)
+                .map(|(&op, stack)| (op, stack.as_slice())),
+            &accesses,
+            &originals,
+        );
+        assert_eq!(records[0].original, U256::from(5u64));
+        assert_eq!(records[0].current, U256::from(5u64));
+        assert_eq!(records[1].original, U256::from(5u64));
+        assert_eq!(records[1].current, U256::from(1u64));
+
+        let prestate = serde_json::json!({
+            "0x00000000000000000000000000000000000000aa": {
+                "balance": "0x0",
+                "storage": {
+                    "0x0000000000000000000000000000000000000000000000000000000000000001": "0x2a"
+                }
+            },
+            "0x00000000000000000000000000000000000000bb": { "balance": "0x1" }
+        });
+        let storage = parse_prestate_storage(&prestate);
+        let key = AccessKey {
+            address: Address::with_last_byte(0xaa),
+            slot: Some(U256::from(1u64)),
+        };
+        assert_eq!(storage.len(), 1);
+        assert_eq!(storage[&key], U256::from(0x2au64));
+    }
+
+    #[test]
+    fn test_opcode_name_to_byte() {
+        assert_eq!(opcode_name_to_byte("PUSH0"), 0x5f);
+        assert_eq!(opcode_name_to_byte("DUP16"), 0x8f);
+        assert_eq!(opcode_name_to_byte("TSTORE"), 0x5d);
+        assert_eq!(opcode_name_to_byte("BLOBBASEFEE"), 0x4a);
+        assert_eq!(opcode_name_to_byte("SHA3"), 0x20);
+        assert_eq!(opcode_name_to_byte("KECCAK256"), 0x20);
+        assert_eq!(opcode_name_to_byte("DIFFICULTY"), 0x44);
+        assert_eq!(opcode_name_to_byte("NOT_AN_OPCODE"), 0xfe);
     }
 
     #[test]
@@ -890,10 +2965,18 @@ mod tests {
     fn test_trace_with_storage_ops() {
         let trace = EvmTrace {
             opcodes: vec![0x54, 0x55], // SLOAD, SSTORE
-            stack_states: vec![vec![1, 0, 0], vec![2, 1, 0]],
+            stack_states: vec![words(&[1, 0, 0]), words(&[2, 1, 0])],
             pcs: vec![0, 1],
             gas_values: vec![1000, 800],
+            stack_sizes: None,
+            memory_sizes: None,
+            accesses: None,
+            prewarmed: None,
+            sstores: None,
+            refunds: None,
+            intrinsic_gas: None,
             memory_ops: None,
+            stack_ops: None,
             storage_ops: Some(vec![StorageOp {
                 key: U256::from(1),
                 value: U256::from(100),
@@ -911,7 +2994,7 @@ mod tests {
     #[test]
     fn test_large_trace() {
         let opcodes = vec![0x60; 100]; // 100 PUSH1 operations
-        let stack_states = vec![vec![1, 0, 0]; 100];
+        let stack_states = vec![words(&[1, 0, 0]); 100];
         let pcs: Vec<u64> = (0..100).map(|i| i * 2).collect();
         let gas_values: Vec<u64> = (0..100).map(|i| 1000 - i * 3).collect();
 
@@ -920,7 +3003,15 @@ mod tests {
             stack_states,
             pcs,
             gas_values,
+            stack_sizes: None,
+            memory_sizes: None,
+            accesses: None,
+            prewarmed: None,
+            sstores: None,
+            refunds: None,
+            intrinsic_gas: None,
             memory_ops: None,
+            stack_ops: None,
             storage_ops: None,
             tx_hash: None,
             block_number: None,
@@ -957,7 +3048,7 @@ mod tests {
 
         assert_eq!(witness.opcode_cells.len(), 3);
         assert_eq!(witness.gas_cells.len(), 3);
-        assert_eq!(witness.public_inputs.len(), 4);
+        assert_eq!(witness.public_inputs.len(), 1);
     }
 
     #[test]
@@ -965,9 +3056,57 @@ mod tests {
         let trace = create_test_trace();
         let witness = trace_to_witness(&trace).unwrap();
 
-        assert!(witness.public_inputs.len() > 0);
+        assert!(!witness.public_inputs.is_empty());
 
         let witness2 = trace_to_witness(&trace).unwrap();
         assert_eq!(witness.public_inputs, witness2.public_inputs);
     }
+
+    #[test]
+    fn test_stack_depths() {
+        let trace = create_test_trace();
+        assert_eq!(trace.stack_depths(), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_stack_depths_mid_execution() {
+        // ADD first needs two items already on the stack
+        let trace = EvmTrace {
+            opcodes: vec![0x01, 0x60, 0x01],
+            stack_states: vec![vec![U256::ZERO; 3]; 3],
+            pcs: vec![0, 1, 3],
+            gas_values: vec![1000, 997, 994],
+            stack_sizes: None,
+            memory_sizes: None,
+            accesses: None,
+            prewarmed: None,
+            sstores: None,
+            refunds: None,
+            intrinsic_gas: None,
+            memory_ops: None,
+            stack_ops: None,
+            storage_ops: None,
+            tx_hash: None,
+            block_number: None,
+            bytecode: None,
+        };
+        assert_eq!(trace.stack_depths(), vec![2, 1, 2]);
+    }
+
+    #[test]
+    fn test_state_proof_fixture() {
+        let state_proof = load_state_proof("tests/fixtures/eth_get_proof.json").unwrap();
+        assert_eq!(state_proof.proof.storage_proof.len(), 3);
+        state_proof.verify().unwrap();
+
+        // A tampered slot value no longer matches its storage proof
+        let mut tampered = state_proof.clone();
+        tampered.proof.storage_proof[0].value += U256::from(1);
+        assert!(tampered.verify().is_err());
+
+        // So does a different state root
+        let mut tampered = state_proof;
+        tampered.state_root = keccak256([0u8]);
+        assert!(tampered.verify().is_err());
+    }
 }
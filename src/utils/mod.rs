@@ -5,6 +5,10 @@
 
 pub mod benchmarks;
 pub mod evm_parser;
+pub mod mpt;
+pub mod smt;
 
 pub use benchmarks::*;
 pub use evm_parser::*;
+pub use mpt::*;
+pub use smt::*;
@@ -0,0 +1,368 @@
+//! Ethereum Merkle-Patricia trie proofs
+//!
+//! Decodes the RLP nodes of an `eth_getProof` account or storage proof and
+//! walks them along the hashed key, checking that every node hashes to the
+//! reference its parent holds. Keys are the Keccak-256 hash of the address or
+//! storage slot, so every leaf sits 64 nibbles deep.
+
+use alloy_primitives::{B256, Bytes, U256, keccak256};
+
+use crate::errors::{ProverError, Result};
+
+/// Decoded RLP item: a byte string or the payload of a list
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RlpItem<'a> {
+    /// Payload bytes
+    pub payload: &'a [u8],
+    /// Whether the item is a list
+    pub list: bool,
+}
+
+fn malformed(what: &str) -> ProverError {
+    ProverError::ParseError(format!("Malformed trie node: {}", what))
+}
+
+/// Decode the RLP item at the start of `bytes`, returning it and its encoded length
+pub fn rlp_item(bytes: &[u8]) -> Result<(RlpItem<'_>, usize)> {
+    let prefix = *bytes.first().ok_or_else(|| malformed("empty item"))?;
+    let (list, header, len) = match prefix {
+        0x00..=0x7f => {
+            return Ok((
+                RlpItem {
+                    payload: &bytes[..1],
+                    list: false,
+                },
+                1,
+            ));
+        }
+        0x80..=0xb7 => (false, 1, (prefix - 0x80) as usize),
+        0xc0..=0xf7 => (true, 1, (prefix - 0xc0) as usize),
+        _ => {
+            let list = prefix >= 0xf8;
+            let size = (prefix - if list { 0xf7 } else { 0xb7 }) as usize;
+            let len_bytes = bytes
+                .get(1..1 + size)
+                .ok_or_else(|| malformed("truncated length"))?;
+            let len = len_bytes
+                .iter()
+                .try_fold(0usize, |acc, byte| {
+                    acc.checked_mul(256).map(|acc| acc + *byte as usize)
+                })
+                .ok_or_else(|| malformed("oversized length"))?;
+            (list, 1 + size, len)
+        }
+    };
+    let payload = bytes
+        .get(header..header + len)
+        .ok_or_else(|| malformed("truncated payload"))?;
+    Ok((RlpItem { payload, list }, header + len))
+}
+
+/// Decode the items of an RLP list that fills `bytes`
+pub fn rlp_list(bytes: &[u8]) -> Result<Vec<RlpItem<'_>>> {
+    let (item, len) = rlp_item(bytes)?;
+    if !item.list || len != bytes.len() {
+        return Err(malformed("expected a list"));
+    }
+    let mut items = Vec::new();
+    let mut rest = item.payload;
+    while !rest.is_empty() {
+        let (item, len) = rlp_item(rest)?;
+        items.push(item);
+        rest = &rest[len..];
+    }
+    Ok(items)
+}
+
+/// Nibbles of a key, most significant first
+pub fn key_nibbles(key: B256) -> Vec<u8> {
+    key.iter()
+        .flat_map(|byte| [byte >> 4, byte & 0x0f])
+        .collect()
+}
+
+/// Trie node
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MptNode {
+    /// Sixteen child references, one per nibble
+    Branch {
+        /// Hash of every non-empty child
+        children: Box<[Option<B256>; 16]>,
+    },
+    /// Shared path nibbles leading to a single child
+    Extension {
+        /// Path nibbles
+        path: Vec<u8>,
+        /// Hash of the child
+        child: B256,
+    },
+    /// Remaining path nibbles and the value stored at the key
+    Leaf {
+        /// Path nibbles
+        path: Vec<u8>,
+        /// Stored value (RLP encoded by the trie's user)
+        value: Vec<u8>,
+    },
+}
+
+impl MptNode {
+    /// Decode a node, rejecting children embedded in their parent
+    ///
+    /// Nodes shorter than 32 bytes are inlined instead of hashed; with 32-byte
+    /// hashed keys they only occur in tiny tries, which are not supported.
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let items = rlp_list(bytes)?;
+        let reference = |item: &RlpItem<'_>| match (item.list, item.payload.len()) {
+            (false, 0) => Ok(None),
+            (false, 32) => Ok(Some(B256::from_slice(item.payload))),
+            _ => Err(ProverError::InvalidInput(
+                "Embedded trie nodes are not supported".to_string(),
+            )),
+        };
+
+        match items.len() {
+            17 => {
+                if !items[16].payload.is_empty() {
+                    return Err(malformed("branch with a value"));
+                }
+                let mut children = [None; 16];
+                for (child, item) in children.iter_mut().zip(&items) {
+                    *child = reference(item)?;
+                }
+                Ok(MptNode::Branch {
+                    children: Box::new(children),
+                })
+            }
+            2 => {
+                let encoded = items[0].payload;
+                let flag = *encoded.first().ok_or_else(|| malformed("empty path"))?;
+                let mut path = Vec::with_capacity(2 * encoded.len());
+                if flag & 0x10 != 0 {
+                    path.push(flag & 0x0f);
+                }
+                path.extend(
+                    encoded[1..]
+                        .iter()
+                        .flat_map(|byte| [byte >> 4, byte & 0x0f]),
+                );
+                match flag >> 4 {
+                    0 | 1 => Ok(MptNode::Extension {
+                        path,
+                        child: reference(&items[1])?.ok_or_else(|| malformed("empty child"))?,
+                    }),
+                    2 | 3 if !items[1].list => Ok(MptNode::Leaf {
+                        path,
+                        value: items[1].payload.to_vec(),
+                    }),
+                    _ => Err(malformed("bad path flag")),
+                }
+            }
+            _ => Err(malformed("unexpected item count")),
+        }
+    }
+}
+
+/// Result of walking a key through a proof
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProofPath {
+    /// Index of the item holding the next node's hash, for every node but the last
+    pub children: Vec<usize>,
+    /// Value at the key, or `None` when the proof shows the key is absent
+    pub value: Option<Vec<u8>>,
+}
+
+/// Walk `key` through `proof` from `root`
+///
+/// Fails when a node does not hash to its parent's reference or the proof
+/// does not end where the key's path does.
+pub fn verify_proof(root: B256, key: B256, proof: &[Bytes]) -> Result<ProofPath> {
+    let nibbles = key_nibbles(key);
+    let mut expected = root;
+    let mut depth = 0;
+    let mut children = Vec::with_capacity(proof.len());
+
+    for (index, node) in proof.iter().enumerate() {
+        if keccak256(node) != expected {
+            return Err(ProverError::VerificationError(format!(
+                "Trie node {} does not match its reference",
+                index
+            )));
+        }
+        let last = index + 1 == proof.len();
+        let rest = &nibbles[depth..];
+        let next = match MptNode::decode(node)? {
+            MptNode::Branch { children: refs } => {
+                let nibble = *rest
+                    .first()
+                    .ok_or_else(|| malformed("branch below a leaf"))?;
+                children.push(nibble as usize);
+                depth += 1;
+                refs[nibble as usize]
+            }
+            MptNode::Extension { path, child } => {
+                children.push(1);
+                depth += path.len();
+                rest.starts_with(&path).then_some(child)
+            }
+            MptNode::Leaf { path, value } => {
+                if !last {
+                    return Err(malformed("leaf before the end of the proof"));
+                }
+                let value = (path.as_slice() == rest).then_some(value);
+                return Ok(ProofPath { children, value });
+            }
+        };
+        match (next, last) {
+            (Some(child), false) => expected = child,
+            (None, true) => {
+                children.pop();
+                return Ok(ProofPath {
+                    children,
+                    value: None,
+                });
+            }
+            (Some(_), true) => return Err(malformed("proof ends above the leaf")),
+            (None, false) => return Err(malformed("proof continues past the key")),
+        }
+    }
+    Err(malformed("empty proof"))
+}
+
+/// Fields of an account leaf
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TrieAccount {
+    /// Transaction count
+    pub nonce: u64,
+    /// Balance in wei
+    pub balance: U256,
+    /// Root of the account's storage trie
+    pub storage_root: B256,
+    /// Hash of the account's code
+    pub code_hash: B256,
+}
+
+impl TrieAccount {
+    /// Decode an account leaf value
+    pub fn decode(value: &[u8]) -> Result<Self> {
+        let items = rlp_list(value)?;
+        let [nonce, balance, storage_root, code_hash] = items.as_slice() else {
+            return Err(malformed("account with unexpected fields"));
+        };
+        if nonce.payload.len() > 8 || balance.payload.len() > 32 {
+            return Err(malformed("oversized account field"));
+        }
+        if storage_root.payload.len() != 32 || code_hash.payload.len() != 32 {
+            return Err(malformed("account hash field"));
+        }
+        Ok(Self {
+            nonce: nonce
+                .payload
+                .iter()
+                .fold(0, |acc, byte| acc << 8 | *byte as u64),
+            balance: U256::from_be_slice(balance.payload),
+            storage_root: B256::from_slice(storage_root.payload),
+            code_hash: B256::from_slice(code_hash.payload),
+        })
+    }
+}
+
+/// Decode a storage leaf value: the RLP encoding of the slot's value
+pub fn decode_storage_value(value: &[u8]) -> Result<U256> {
+    let (item, len) = rlp_item(value)?;
+    if item.list || len != value.len() || item.payload.len() > 32 {
+        return Err(malformed("storage value"));
+    }
+    Ok(U256::from_be_slice(item.payload))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::hex;
+
+    /// Leaf holding the encoded `value` under the whole 64-nibble path of `key`
+    fn single_leaf(key: B256, value: &[u8]) -> Vec<u8> {
+        let mut path = vec![0x81 + 32, 0x20];
+        path.extend_from_slice(key.as_slice());
+        let mut encoded_value = match value {
+            [byte] if *byte < 0x80 => vec![],
+            _ => vec![0x80 + value.len() as u8],
+        };
+        encoded_value.extend_from_slice(value);
+        let payload: Vec<u8> = path.into_iter().chain(encoded_value).collect();
+        let mut node = vec![0xf8, payload.len() as u8];
+        node.extend(payload);
+        node
+    }
+
+    #[test]
+    fn test_rlp_items() {
+        let (item, len) = rlp_item(&[0x05]).unwrap();
+        assert_eq!((item.payload, item.list, len), (&[0x05][..], false, 1));
+        let (item, len) = rlp_item(&[0x82, 0x12, 0x34, 0xff]).unwrap();
+        assert_eq!((item.payload, len), (&[0x12, 0x34][..], 3));
+        let items = rlp_list(&hex!("c88363617483646f67")).unwrap();
+        assert_eq!(items[0].payload, b"cat");
+        assert_eq!(items[1].payload, b"dog");
+        assert!(rlp_item(&[0x83, 0x01]).is_err());
+        assert!(rlp_list(&[0x82, 0x12, 0x34]).is_err());
+    }
+
+    #[test]
+    fn test_verify_single_leaf() {
+        let key = keccak256([1u8]);
+        let node = single_leaf(key, &[0x2a]);
+        let root = keccak256(&node);
+        let proof = vec![Bytes::from(node)];
+
+        let path = verify_proof(root, key, &proof).unwrap();
+        assert!(path.children.is_empty());
+        assert_eq!(path.value.as_deref(), Some(&[0x2a][..]));
+        assert_eq!(
+            decode_storage_value(&path.value.unwrap()).unwrap(),
+            U256::from(0x2a)
+        );
+
+        // Another key diverges at the leaf, proving its absence
+        let other = verify_proof(root, keccak256([2u8]), &proof).unwrap();
+        assert_eq!(other.value, None);
+
+        // A different root fails
+        assert!(verify_proof(keccak256([0u8]), key, &proof).is_err());
+    }
+
+    #[test]
+    fn test_decode_nodes() {
+        let mut branch = vec![0xf8, 0x00];
+        let child = keccak256([7u8]);
+        for nibble in 0..17 {
+            if nibble == 3 {
+                branch.push(0xa0);
+                branch.extend_from_slice(child.as_slice());
+            } else {
+                branch.push(0x80);
+            }
+        }
+        branch[1] = (branch.len() - 2) as u8;
+        let MptNode::Branch { children } = MptNode::decode(&branch).unwrap() else {
+            panic!("expected a branch");
+        };
+        assert_eq!(children[3], Some(child));
+        assert_eq!(children.iter().flatten().count(), 1);
+
+        // One-nibble extension: its path is a single byte
+        let mut extension = vec![0xe2, 0x13, 0xa0];
+        extension.extend_from_slice(child.as_slice());
+        extension[0] = 0xc0 + (extension.len() - 1) as u8;
+        assert_eq!(
+            MptNode::decode(&extension).unwrap(),
+            MptNode::Extension {
+                path: vec![3],
+                child
+            }
+        );
+
+        // Embedded children are rejected
+        assert!(MptNode::decode(&hex!("c4823012c0")).is_err());
+    }
+}
@@ -0,0 +1,283 @@
+//! Poseidon sparse Merkle tree over storage slots
+//!
+//! A slot's key is the Poseidon hash of the limbs of its address and slot, and
+//! its leaf sits at the low `SMT_DEPTH` bits of the key. An empty leaf is zero
+//! and stands for a zero-valued slot; any other leaf is
+//! `Poseidon(key, value_lo, value_hi)`, committing to the full key as well as
+//! the value. A node is `Poseidon(left, right)`. Keys sharing their low bits
+//! collide, and a tree holding one of them cannot hold the other.
+
+use alloy_primitives::{Address, U256};
+use halo2_gadgets::poseidon::primitives::{self as poseidon, ConstantLength, P128Pow5T3, Spec};
+use halo2_proofs::pasta::group::ff::PrimeField;
+use std::collections::HashMap;
+
+use crate::chips::hash_chip::{POSEIDON_RATE, POSEIDON_WIDTH};
+use crate::chips::word_chip::{limb_value, word_limbs};
+use crate::errors::{ProverError, Result};
+
+/// Levels between a leaf and the root
+///
+/// The rest of a key above its path, 192 bits, is range checked in whole
+/// bytes, so every key below 2^254 has a single decomposition.
+pub const SMT_DEPTH: usize = 62;
+
+/// Native Poseidon hash of a fixed number of field elements
+fn hash<F, const L: usize>(message: [F; L]) -> F
+where
+    F: PrimeField,
+    P128Pow5T3: Spec<F, POSEIDON_WIDTH, POSEIDON_RATE>,
+{
+    poseidon::Hash::<F, P128Pow5T3, ConstantLength<L>, POSEIDON_WIDTH, POSEIDON_RATE>::init()
+        .hash(message)
+}
+
+/// Key of a storage slot: Poseidon over the `(lo, hi)` limbs of its address and slot
+pub fn slot_key<F>(address: Address, slot: U256) -> F
+where
+    F: PrimeField,
+    P128Pow5T3: Spec<F, POSEIDON_WIDTH, POSEIDON_RATE>,
+{
+    let [address_lo, address_hi] = word_limbs::<F>(U256::from_be_slice(address.as_slice()));
+    let [slot_lo, slot_hi] = word_limbs::<F>(slot);
+    hash([address_lo, address_hi, slot_lo, slot_hi])
+}
+
+/// Leaf of a slot holding `value` (zero for a zero value)
+pub fn leaf_hash<F>(key: F, value: U256) -> F
+where
+    F: PrimeField,
+    P128Pow5T3: Spec<F, POSEIDON_WIDTH, POSEIDON_RATE>,
+{
+    if value.is_zero() {
+        return F::ZERO;
+    }
+    let [lo, hi] = word_limbs::<F>(value);
+    hash([key, lo, hi])
+}
+
+/// Parent of two nodes
+pub fn node_hash<F>(left: F, right: F) -> F
+where
+    F: PrimeField,
+    P128Pow5T3: Spec<F, POSEIDON_WIDTH, POSEIDON_RATE>,
+{
+    hash([left, right])
+}
+
+/// Index of a key's leaf: the low `SMT_DEPTH` bits of the key
+pub fn path_index<F: PrimeField>(key: F) -> u64 {
+    limb_value(key) as u64 & ((1 << SMT_DEPTH) - 1)
+}
+
+/// Root of the tree holding `value` at `key`, given the siblings of its path
+/// from the leaf up
+pub fn compute_root<F>(key: F, value: U256, siblings: &[F]) -> F
+where
+    F: PrimeField,
+    P128Pow5T3: Spec<F, POSEIDON_WIDTH, POSEIDON_RATE>,
+{
+    let index = path_index(key);
+    siblings
+        .iter()
+        .enumerate()
+        .fold(leaf_hash(key, value), |node, (level, &sibling)| {
+            if index >> level & 1 == 0 {
+                node_hash(node, sibling)
+            } else {
+                node_hash(sibling, node)
+            }
+        })
+}
+
+/// Update of one leaf, with the siblings that prove both roots
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MerkleUpdate<F> {
+    /// Key of the slot
+    pub key: F,
+    /// Siblings of the leaf's path, from the leaf up
+    pub siblings: Vec<F>,
+    /// Value before the update
+    pub old_value: U256,
+    /// Value after the update
+    pub new_value: U256,
+    /// Root before the update
+    pub old_root: F,
+    /// Root after the update
+    pub new_root: F,
+}
+
+/// Sparse Merkle tree of storage slot values
+#[derive(Debug, Clone)]
+pub struct SparseMerkleTree<F> {
+    /// Non-empty nodes by level (0 for leaves) and index
+    nodes: HashMap<(usize, u64), F>,
+    /// Key and value of every non-empty leaf
+    leaves: HashMap<u64, (F, U256)>,
+    /// Root of an empty subtree of every height
+    empty: Vec<F>,
+}
+
+impl<F> Default for SparseMerkleTree<F>
+where
+    F: PrimeField,
+    P128Pow5T3: Spec<F, POSEIDON_WIDTH, POSEIDON_RATE>,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F> SparseMerkleTree<F>
+where
+    F: PrimeField,
+    P128Pow5T3: Spec<F, POSEIDON_WIDTH, POSEIDON_RATE>,
+{
+    /// Create an empty tree
+    pub fn new() -> Self {
+        let mut empty = vec![F::ZERO];
+        for level in 0..SMT_DEPTH {
+            empty.push(node_hash(empty[level], empty[level]));
+        }
+        Self {
+            nodes: HashMap::new(),
+            leaves: HashMap::new(),
+            empty,
+        }
+    }
+
+    /// Root of the tree
+    pub fn root(&self) -> F {
+        self.node(SMT_DEPTH, 0)
+    }
+
+    /// Value of the slot with `key` (zero when absent)
+    pub fn get(&self, key: F) -> U256 {
+        match self.leaves.get(&path_index(key)) {
+            Some(&(leaf_key, value)) if leaf_key == key => value,
+            _ => U256::ZERO,
+        }
+    }
+
+    fn node(&self, level: usize, index: u64) -> F {
+        self.nodes
+            .get(&(level, index))
+            .copied()
+            .unwrap_or(self.empty[level])
+    }
+
+    /// Set the slot with `key` to `value` and return the proof of the update
+    ///
+    /// Fails when another slot holds the key's leaf.
+    pub fn update(&mut self, key: F, value: U256) -> Result<MerkleUpdate<F>> {
+        let index = path_index(key);
+        if let Some(&(other, _)) = self.leaves.get(&index) {
+            if other != key {
+                return Err(ProverError::InvalidInput(format!(
+                    "Storage key collides with another slot at leaf {:#x}",
+                    index
+                )));
+            }
+        }
+
+        let siblings: Vec<F> = (0..SMT_DEPTH)
+            .map(|level| self.node(level, (index >> level) ^ 1))
+            .collect();
+        let old_value = self.get(key);
+        let old_root = self.root();
+
+        if value.is_zero() {
+            self.leaves.remove(&index);
+        } else {
+            self.leaves.insert(index, (key, value));
+        }
+        let mut node = leaf_hash(key, value);
+        for (level, sibling) in siblings.iter().enumerate() {
+            let position = index >> level;
+            if node == self.empty[level] {
+                self.nodes.remove(&(level, position));
+            } else {
+                self.nodes.insert((level, position), node);
+            }
+            node = if position & 1 == 0 {
+                node_hash(node, *sibling)
+            } else {
+                node_hash(*sibling, node)
+            };
+        }
+        self.nodes.insert((SMT_DEPTH, 0), node);
+
+        Ok(MerkleUpdate {
+            key,
+            siblings,
+            old_value,
+            new_value: value,
+            old_root,
+            new_root: node,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2_proofs::pasta::Fp;
+
+    fn key(slot: u64) -> Fp {
+        slot_key(Address::with_last_byte(0xaa), U256::from(slot))
+    }
+
+    #[test]
+    fn test_smt_updates_and_proofs() {
+        let mut tree = SparseMerkleTree::<Fp>::new();
+        let empty_root = tree.root();
+
+        let first = tree.update(key(1), U256::from(5u64)).unwrap();
+        assert_eq!(first.old_root, empty_root);
+        assert_eq!(first.old_value, U256::ZERO);
+        assert_eq!(
+            compute_root(first.key, U256::ZERO, &first.siblings),
+            first.old_root
+        );
+        assert_eq!(
+            compute_root(first.key, U256::from(5u64), &first.siblings),
+            first.new_root
+        );
+
+        // A second slot's proof passes through the first one's leaf
+        let second = tree.update(key(2), U256::MAX).unwrap();
+        assert_eq!(second.old_root, first.new_root);
+        assert_eq!(
+            compute_root(second.key, U256::MAX, &second.siblings),
+            tree.root()
+        );
+        assert_eq!(tree.get(key(1)), U256::from(5u64));
+        assert_eq!(tree.get(key(3)), U256::ZERO);
+
+        // The root only depends on the values held, and zero empties a leaf
+        let mut other = SparseMerkleTree::<Fp>::new();
+        other.update(key(2), U256::MAX).unwrap();
+        other.update(key(1), U256::from(5u64)).unwrap();
+        assert_eq!(other.root(), tree.root());
+        tree.update(key(1), U256::ZERO).unwrap();
+        tree.update(key(2), U256::ZERO).unwrap();
+        assert_eq!(tree.root(), empty_root);
+    }
+
+    #[test]
+    fn test_smt_key_binding() {
+        assert_ne!(key(1), key(2));
+        assert_ne!(
+            key(1),
+            slot_key::<Fp>(Address::with_last_byte(0xbb), U256::from(1u64))
+        );
+        // The leaf commits to the key, not only to the path
+        assert_ne!(
+            leaf_hash(key(1), U256::from(1u64)),
+            leaf_hash(
+                key(1) + Fp::from(1 << 32).square().square(),
+                U256::from(1u64)
+            )
+        );
+    }
+}
@@ -2,16 +2,32 @@
 //!
 //! Tests that compare different implementations or approaches
 
-use zephyr_proof::{generate_proof, utils::evm_parser::EvmTrace, ProverConfig};
+use zephyr_proof::{
+    Hardfork, ProverConfig, generate_proof,
+    utils::evm_parser::{EvmTrace, U256},
+};
+
+/// Lift small stack literals into 256-bit words
+fn words(values: &[u64]) -> Vec<U256> {
+    values.iter().map(|&v| U256::from(v)).collect()
+}
 
 /// Helper to create a test trace
 fn create_test_trace() -> EvmTrace {
     EvmTrace {
         opcodes: vec![0x60, 0x60, 0x01], // PUSH1, PUSH1, ADD
-        stack_states: vec![vec![1, 0, 0], vec![2, 1, 0], vec![3, 0, 0]],
+        stack_states: vec![words(&[0, 0, 0]), words(&[1, 0, 0]), words(&[2, 1, 0])],
         pcs: vec![0, 2, 4],
         gas_values: vec![1000, 997, 994],
+        stack_sizes: None,
+        memory_sizes: None,
+        accesses: None,
+        prewarmed: None,
+        sstores: None,
+        refunds: None,
+        intrinsic_gas: None,
         memory_ops: None,
+        stack_ops: None,
         storage_ops: None,
         tx_hash: None,
         block_number: None,
@@ -30,6 +46,7 @@ async fn test_parallel_sequential_equivalence() {
         k: 17,
         num_threads: None,
         rpc_url: None,
+        hardfork: Hardfork::Cancun,
     };
     let proof_parallel = generate_proof(&trace_json, &config_parallel).await.unwrap();
 
@@ -39,6 +56,7 @@ async fn test_parallel_sequential_equivalence() {
         k: 17,
         num_threads: None,
         rpc_url: None,
+        hardfork: Hardfork::Cancun,
     };
     let proof_sequential = generate_proof(&trace_json, &config_sequential)
         .await
@@ -117,10 +135,18 @@ async fn test_different_traces_produce_different_commitments() {
     // Trace 1: ADD operation
     let trace1 = EvmTrace {
         opcodes: vec![0x60, 0x60, 0x01],
-        stack_states: vec![vec![1, 0, 0], vec![2, 1, 0], vec![3, 0, 0]],
+        stack_states: vec![words(&[0, 0, 0]), words(&[1, 0, 0]), words(&[2, 1, 0])],
         pcs: vec![0, 2, 4],
         gas_values: vec![1000, 997, 994],
+        stack_sizes: None,
+        memory_sizes: None,
+        accesses: None,
+        prewarmed: None,
+        sstores: None,
+        refunds: None,
+        intrinsic_gas: None,
         memory_ops: None,
+        stack_ops: None,
         storage_ops: None,
         tx_hash: None,
         block_number: None,
@@ -130,10 +156,18 @@ async fn test_different_traces_produce_different_commitments() {
     // Trace 2: Different opcodes - use PUSH1, PUSH1, SUB
     let trace2 = EvmTrace {
         opcodes: vec![0x60, 0x60, 0x03],
-        stack_states: vec![vec![5, 0, 0], vec![3, 5, 0], vec![2, 0, 0]],
+        stack_states: vec![words(&[0, 0, 0]), words(&[5, 0, 0]), words(&[3, 5, 0])],
         pcs: vec![0, 2, 4],
         gas_values: vec![1000, 997, 994],
+        stack_sizes: None,
+        memory_sizes: None,
+        accesses: None,
+        prewarmed: None,
+        sstores: None,
+        refunds: None,
+        intrinsic_gas: None,
         memory_ops: None,
+        stack_ops: None,
         storage_ops: None,
         tx_hash: None,
         block_number: None,
@@ -157,10 +191,18 @@ async fn test_trace_length_affects_gas() {
     // Short trace
     let trace_short = EvmTrace {
         opcodes: vec![0x60, 0x60, 0x01],
-        stack_states: vec![vec![1, 0, 0], vec![2, 1, 0], vec![3, 0, 0]],
+        stack_states: vec![words(&[0, 0, 0]), words(&[1, 0, 0]), words(&[2, 1, 0])],
         pcs: vec![0, 2, 4],
         gas_values: vec![1000, 997, 994],
+        stack_sizes: None,
+        memory_sizes: None,
+        accesses: None,
+        prewarmed: None,
+        sstores: None,
+        refunds: None,
+        intrinsic_gas: None,
         memory_ops: None,
+        stack_ops: None,
         storage_ops: None,
         tx_hash: None,
         block_number: None,
@@ -169,7 +211,7 @@ async fn test_trace_length_affects_gas() {
 
     // Long trace
     let opcodes_long = vec![0x60; 20];
-    let stack_states_long = vec![vec![1, 0, 0]; 20];
+    let stack_states_long = vec![words(&[1, 0, 0]); 20];
     let pcs_long: Vec<u64> = (0..20).map(|i| i * 2).collect();
     let gas_values_long: Vec<u64> = (0..20).map(|i| 1000 - i * 3).collect();
 
@@ -178,7 +220,15 @@ async fn test_trace_length_affects_gas() {
         stack_states: stack_states_long,
         pcs: pcs_long,
         gas_values: gas_values_long,
+        stack_sizes: None,
+        memory_sizes: None,
+        accesses: None,
+        prewarmed: None,
+        sstores: None,
+        refunds: None,
+        intrinsic_gas: None,
         memory_ops: None,
+        stack_ops: None,
         storage_ops: None,
         tx_hash: None,
         block_number: None,
@@ -243,10 +293,18 @@ fn test_commitment_collision_resistance() {
     for i in 0..100 {
         let trace = EvmTrace {
             opcodes: vec![0x60, (i % 256) as u8],
-            stack_states: vec![vec![i as u64, 0, 0], vec![(i + 1) as u64, 0, 0]],
+            stack_states: vec![words(&[i as u64, 0, 0]), words(&[(i + 1) as u64, 0, 0])],
             pcs: vec![0, 2],
             gas_values: vec![1000, 997],
+            stack_sizes: None,
+            memory_sizes: None,
+            accesses: None,
+            prewarmed: None,
+            sstores: None,
+            refunds: None,
+            intrinsic_gas: None,
             memory_ops: None,
+            stack_ops: None,
             storage_ops: None,
             tx_hash: None,
             block_number: None,
@@ -3,19 +3,32 @@
 //! Tests end-to-end workflows including trace parsing, proof generation, and verification
 
 use zephyr_proof::{
-    generate_proof,
-    utils::evm_parser::{parse_evm_data, parse_trace_json, EvmTrace},
-    verify_proof, ProverConfig,
+    ProverConfig, generate_proof,
+    utils::evm_parser::{EvmTrace, U256, parse_evm_data, parse_trace_json},
+    verify_proof,
 };
 
+/// Lift small stack literals into 256-bit words
+fn words(values: &[u64]) -> Vec<U256> {
+    values.iter().map(|&v| U256::from(v)).collect()
+}
+
 /// Helper to create a test trace
 fn create_test_trace() -> EvmTrace {
     EvmTrace {
         opcodes: vec![0x60, 0x60, 0x01], // PUSH1, PUSH1, ADD
-        stack_states: vec![vec![1, 0, 0], vec![2, 1, 0], vec![3, 0, 0]],
+        stack_states: vec![words(&[0, 0, 0]), words(&[1, 0, 0]), words(&[2, 1, 0])],
         pcs: vec![0, 2, 4],
         gas_values: vec![1000, 997, 994],
+        stack_sizes: None,
+        memory_sizes: None,
+        accesses: None,
+        prewarmed: None,
+        sstores: None,
+        refunds: None,
+        intrinsic_gas: None,
         memory_ops: None,
+        stack_ops: None,
         storage_ops: None,
         tx_hash: None,
         block_number: None,
@@ -39,7 +52,7 @@ async fn test_end_to_end_proof_generation_and_verification() {
     // Verify proof metadata
     assert_eq!(proof.metadata.opcode_count, 3);
     assert_eq!(proof.metadata.gas_used, 6);
-    assert!(proof.proof.len() > 0);
+    assert!(!proof.proof.is_empty());
 
     // Verify proof
     let verify_result = verify_proof(&proof, &config).await;
@@ -109,12 +122,16 @@ async fn test_parallel_vs_sequential_proof_generation() {
     );
 
     // Both should verify
-    assert!(verify_proof(&parallel_proof, &parallel_config)
-        .await
-        .unwrap());
-    assert!(verify_proof(&sequential_proof, &sequential_config)
-        .await
-        .unwrap());
+    assert!(
+        verify_proof(&parallel_proof, &parallel_config)
+            .await
+            .unwrap()
+    );
+    assert!(
+        verify_proof(&sequential_proof, &sequential_config)
+            .await
+            .unwrap()
+    );
 }
 
 #[tokio::test]
@@ -124,7 +141,15 @@ async fn test_invalid_trace_rejected() {
         stack_states: vec![],
         pcs: vec![],
         gas_values: vec![],
+        stack_sizes: None,
+        memory_sizes: None,
+        accesses: None,
+        prewarmed: None,
+        sstores: None,
+        refunds: None,
+        intrinsic_gas: None,
         memory_ops: None,
+        stack_ops: None,
         storage_ops: None,
         tx_hash: None,
         block_number: None,
@@ -142,10 +167,18 @@ async fn test_invalid_trace_rejected() {
 async fn test_trace_with_metadata() {
     let trace = EvmTrace {
         opcodes: vec![0x60, 0x01],
-        stack_states: vec![vec![1, 0, 0], vec![2, 0, 0]],
+        stack_states: vec![words(&[1, 0, 0]), words(&[2, 0, 0])],
         pcs: vec![0, 2],
         gas_values: vec![1000, 997],
+        stack_sizes: None,
+        memory_sizes: None,
+        accesses: None,
+        prewarmed: None,
+        sstores: None,
+        refunds: None,
+        intrinsic_gas: None,
         memory_ops: None,
+        stack_ops: None,
         storage_ops: None,
         tx_hash: Some("0xabcdef1234567890".to_string()),
         block_number: Some(15000000),
@@ -185,7 +218,7 @@ fn test_parse_trace_from_json() {
     let trace = result.unwrap();
     assert_eq!(trace.opcodes.len(), 3);
     assert_eq!(trace.opcodes[2], 1); // ADD opcode
-    assert_eq!(trace.stack_states[2][0], 3); // Result of 1 + 2
+    assert_eq!(trace.stack_states[2][0], U256::from(3u64)); // Result of 1 + 2
 }
 
 #[test]
@@ -196,7 +229,7 @@ fn test_witness_generation() {
     // Verify witness structure
     assert_eq!(witness.opcode_cells.len(), trace.opcodes.len());
     assert_eq!(witness.gas_cells.len(), trace.gas_values.len());
-    assert_eq!(witness.public_inputs.len(), 4); // SHA256 hash split into 4 u64s
+    assert_eq!(witness.public_inputs.len(), 1); // Poseidon trace commitment
 
     // Verify opcode conversion
     for (i, &opcode) in trace.opcodes.iter().enumerate() {
@@ -208,7 +241,7 @@ fn test_witness_generation() {
 async fn test_large_trace() {
     // Create a larger trace
     let opcodes = vec![0x60; 50]; // 50 PUSH1 operations
-    let stack_states = vec![vec![1, 0, 0]; 50];
+    let stack_states = vec![words(&[1, 0, 0]); 50];
     let pcs: Vec<u64> = (0..50).map(|i| i * 2).collect();
     let gas_values: Vec<u64> = (0..50).map(|i| 1000 - i * 3).collect();
 
@@ -217,7 +250,15 @@ async fn test_large_trace() {
         stack_states,
         pcs,
         gas_values,
+        stack_sizes: None,
+        memory_sizes: None,
+        accesses: None,
+        prewarmed: None,
+        sstores: None,
+        refunds: None,
+        intrinsic_gas: None,
         memory_ops: None,
+        stack_ops: None,
         storage_ops: None,
         tx_hash: None,
         block_number: None,
@@ -269,7 +310,7 @@ async fn test_proof_serialization() {
 
     // Serialize to JSON
     let proof_json = serde_json::to_string(&proof).unwrap();
-    assert!(proof_json.len() > 0);
+    assert!(!proof_json.is_empty());
 
     // Deserialize back
     let deserialized: zephyr_proof::ProofOutput = serde_json::from_str(&proof_json).unwrap();
@@ -289,15 +330,23 @@ async fn test_multiple_arithmetic_operations() {
     let trace = EvmTrace {
         opcodes: vec![0x60, 0x60, 0x01, 0x60, 0x03], // PUSH1, PUSH1, ADD, PUSH1, SUB
         stack_states: vec![
-            vec![1, 0, 0],
-            vec![2, 1, 0],
-            vec![3, 0, 0],
-            vec![4, 3, 0],
-            vec![1, 0, 0], // 4 - 3 = 1
+            words(&[0, 0, 0]),
+            words(&[1, 0, 0]),
+            words(&[2, 1, 0]),
+            words(&[3, 0, 0]),
+            words(&[4, 3, 0]), // SUB computes 4 - 3
         ],
-        pcs: vec![0, 2, 4, 6, 8],
+        pcs: vec![0, 2, 4, 5, 7],
         gas_values: vec![1000, 997, 994, 991, 988],
+        stack_sizes: None,
+        memory_sizes: None,
+        accesses: None,
+        prewarmed: None,
+        sstores: None,
+        refunds: None,
+        intrinsic_gas: None,
         memory_ops: None,
+        stack_ops: None,
         storage_ops: None,
         tx_hash: None,
         block_number: None,
@@ -311,3 +360,46 @@ async fn test_multiple_arithmetic_operations() {
     assert_eq!(proof.metadata.opcode_count, 5);
     assert!(verify_proof(&proof, &config).await.unwrap());
 }
+
+#[tokio::test]
+async fn test_bitwise_proof_roundtrip() {
+    // PUSH1 0x0c, PUSH1 0x0a, AND, STOP: the AND result is the top of the STOP step's stack
+    let trace = EvmTrace {
+        opcodes: vec![0x60, 0x60, 0x16, 0x00],
+        stack_states: vec![
+            words(&[0, 0, 0]),
+            words(&[0x0c, 0, 0]),
+            words(&[0x0a, 0x0c, 0]),
+            words(&[0x08, 0, 0]),
+        ],
+        pcs: vec![0, 2, 4, 5],
+        gas_values: vec![1000, 997, 994, 991],
+        stack_sizes: None,
+        memory_sizes: None,
+        accesses: None,
+        prewarmed: None,
+        sstores: None,
+        refunds: None,
+        intrinsic_gas: None,
+        memory_ops: None,
+        stack_ops: None,
+        storage_ops: None,
+        tx_hash: None,
+        block_number: None,
+        bytecode: Some(vec![0x60, 0x0c, 0x60, 0x0a, 0x16, 0x00]),
+    };
+
+    let trace_json = serde_json::to_string(&trace).unwrap();
+    let config = ProverConfig {
+        k: 12,
+        ..Default::default()
+    };
+
+    let mut proof = generate_proof(&trace_json, &config).await.unwrap();
+    assert_eq!(proof.opcodes, trace.opcodes);
+    assert!(verify_proof(&proof, &config).await.unwrap());
+
+    // The verifier lays the circuit out from the claimed opcodes
+    proof.opcodes = vec![0x00; 4];
+    assert!(!verify_proof(&proof, &config).await.unwrap());
+}